@@ -1,20 +1,275 @@
-use crate::core::models::{Packet, IpReputation};
-
-#[allow(dead_code)]
-pub fn detect_suspicious(packet: &Packet, rep: Option<&IpReputation>) -> bool {
-    
-    if let Some(r) = rep {
-        if r.score > 70 {
-            return true;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, TimeZone, Utc};
+
+use crate::core::flow;
+use crate::core::models::{Alert, Packet, Severity, TlsFingerprints, TransportProtocol, X509Certificate};
+use crate::core::stream::{Direction, StreamKey};
+use crate::core::x509;
+
+/// Builds an alert on `packet` (capture number `packet_id`).
+pub fn packet_alert(packet: &Packet, packet_id: u64, rule_id: &str, severity: Severity, message: String) -> Alert {
+    let (src_port, dst_port) = match &packet.transport {
+        Some(TransportProtocol::Tcp(t)) => (Some(t.src_port), Some(t.dst_port)),
+        Some(TransportProtocol::Udp(u)) => (Some(u.src_port), Some(u.dst_port)),
+        _ => (None, None),
+    };
+    Alert {
+        rule_id: rule_id.to_string(),
+        rule_rev: None,
+        severity,
+        message,
+        classtype: None,
+        timestamp: packet.timestamp,
+        packet_id: Some(packet_id),
+        protocol: packet.ip.as_ref().map(|ip| ip.protocol),
+        src_ip: packet.ip.as_ref().map(|ip| ip.src_ip.clone()),
+        src_port,
+        dst_ip: packet.ip.as_ref().map(|ip| ip.dst_ip.clone()),
+        dst_port,
+        related_packets: Vec::new(),
+    }
+}
+
+/// Builds an alert on data sent in `direction` on the TCP connection `key`,
+/// completed by packet `packet_id`.
+pub fn stream_alert(
+    key: &StreamKey,
+    direction: Direction,
+    timestamp: u128,
+    packet_id: u64,
+    rule_id: &str,
+    severity: Severity,
+    message: String,
+) -> Alert {
+    let split = |endpoint: &str| match endpoint.rsplit_once(':') {
+        Some((ip, port)) => (Some(ip.to_string()), port.parse().ok()),
+        None => (Some(endpoint.to_string()), None),
+    };
+    let (src, dst) = match direction {
+        Direction::ToServer => (split(&key.client), split(&key.server)),
+        Direction::ToClient => (split(&key.server), split(&key.client)),
+    };
+    Alert {
+        rule_id: rule_id.to_string(),
+        rule_rev: None,
+        severity,
+        message,
+        classtype: None,
+        timestamp,
+        packet_id: Some(packet_id),
+        protocol: Some(6),
+        src_ip: src.0,
+        src_port: src.1,
+        dst_ip: dst.0,
+        dst_port: dst.1,
+        related_packets: Vec::new(),
+    }
+}
+
+pub fn severity_label(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "info",
+        Severity::Low => "faible",
+        Severity::Medium => "moyenne",
+        Severity::High => "haute",
+        Severity::Critical => "critique",
+    }
+}
+
+pub fn parse_severity(s: &str) -> Option<Severity> {
+    match s.to_ascii_lowercase().as_str() {
+        "info" => Some(Severity::Info),
+        "low" | "faible" => Some(Severity::Low),
+        "medium" | "moyenne" => Some(Severity::Medium),
+        "high" | "haute" => Some(Severity::High),
+        "critical" | "critique" => Some(Severity::Critical),
+        _ => None,
+    }
+}
+
+fn alert_endpoint(ip: &Option<String>, port: Option<u16>) -> String {
+    ip.as_deref().map_or_else(|| "?".to_string(), |ip| flow::endpoint_label(ip, port))
+}
+
+/// One-line description of an alert for the live table.
+pub fn alert_line(alert: &Alert) -> String {
+    let packet = alert.packet_id.map(|id| format!("#{id} ")).unwrap_or_default();
+    format!(
+        " ⚠ {packet}[{}] {} ({} → {}, {})",
+        severity_label(alert.severity),
+        alert.message,
+        alert_endpoint(&alert.src_ip, alert.src_port),
+        alert_endpoint(&alert.dst_ip, alert.dst_port),
+        alert.rule_id
+    )
+}
+
+pub fn print_report(alerts: &[Alert]) {
+    println!();
+    println!("══════════════════════════════════════════════════════════════════════");
+    println!(" WireFish - Alertes ({})", alerts.len());
+    println!("══════════════════════════════════════════════════════════════════════");
+    println!(
+        "{:<8} {:<12} {:<9} {:<24} {:<28} {:<28} Message",
+        "Paquet", "Heure", "Gravité", "Règle", "Source", "Destination"
+    );
+
+    for a in alerts {
+        let time = Utc
+            .timestamp_millis_opt(a.timestamp as i64)
+            .single()
+            .map(|t| t.format("%H:%M:%S").to_string())
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "{:<8} {:<12} {:<9} {:<24} {:<28} {:<28} {}",
+            a.packet_id.map(|id| format!("#{id}")).unwrap_or_else(|| "-".to_string()),
+            time,
+            severity_label(a.severity),
+            a.rule_id,
+            alert_endpoint(&a.src_ip, a.src_port),
+            alert_endpoint(&a.dst_ip, a.dst_port),
+            a.message
+        );
+        if !a.related_packets.is_empty() {
+            let ids: Vec<String> = a.related_packets.iter().map(|id| format!("#{id}")).collect();
+            println!("         Paquets liés : {}", ids.join(" "));
+        }
+    }
+}
+
+/// Known TLS fingerprints (JA3/JA3S MD5, JA4/JA4S) loaded from a local file.
+///
+/// One entry per line: the fingerprint, then an optional label separated by
+/// a comma, a tab or spaces. Empty lines and lines starting with `#` are
+/// ignored, so abuse.ch style CSV exports can be used as-is.
+#[derive(Debug, Default)]
+pub struct FingerprintList {
+    entries: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FingerprintMatch {
+    pub kind: &'static str,
+    pub fingerprint: String,
+    pub label: String,
+}
+
+impl FingerprintList {
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let mut entries = HashMap::new();
+
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (fp, label) = match line.find([',', '\t', ' ']) {
+                Some(idx) => (&line[..idx], line[idx + 1..].trim()),
+                None => (line, ""),
+            };
+            let label = label.trim_matches(|c| c == ',' || c == '"');
+            let label = if label.is_empty() { "known fingerprint" } else { label };
+
+            entries.insert(fp.trim_matches('"').to_ascii_lowercase(), label.to_string());
+        }
+
+        Ok(FingerprintList { entries })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn check(&self, fp: &TlsFingerprints) -> Vec<FingerprintMatch> {
+        let candidates = [
+            ("JA3", &fp.ja3_hash),
+            ("JA3S", &fp.ja3s_hash),
+            ("JA4", &fp.ja4),
+            ("JA4S", &fp.ja4s),
+        ];
+
+        candidates
+            .into_iter()
+            .filter_map(|(kind, value)| {
+                let value = value.as_ref()?;
+                let label = self.entries.get(&value.to_ascii_lowercase())?;
+                Some(FingerprintMatch {
+                    kind,
+                    fingerprint: value.clone(),
+                    label: label.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CertificateIssue {
+    pub kind: &'static str,
+    pub detail: String,
+}
+
+fn format_date(ts: i64) -> String {
+    DateTime::from_timestamp(ts, 0)
+        .map(|d| d.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| ts.to_string())
+}
+
+/// Sanity checks on a server certificate chain: validity period of every
+/// certificate, and self-signature / host name for the leaf.
+pub fn check_certificates(
+    chain: &[X509Certificate],
+    sni: Option<&str>,
+    now: i64,
+) -> Vec<CertificateIssue> {
+    let mut issues = Vec::new();
+    let Some(leaf) = chain.first() else {
+        return issues;
+    };
+
+    let leaf_name = leaf.subject_cn.as_deref().unwrap_or(&leaf.subject);
+
+    if leaf.subject == leaf.issuer {
+        issues.push(CertificateIssue {
+            kind: "self-signed",
+            detail: format!("certificat auto-signé ({leaf_name})"),
+        });
+    }
+
+    for (i, cert) in chain.iter().enumerate() {
+        let name = cert.subject_cn.as_deref().unwrap_or(&cert.subject);
+        if cert.not_after != 0 && cert.not_after < now {
+            issues.push(CertificateIssue {
+                kind: "expired",
+                detail: format!("certificat #{i} expiré le {} ({name})", format_date(cert.not_after)),
+            });
+        }
+        if cert.not_before > now {
+            issues.push(CertificateIssue {
+                kind: "not-yet-valid",
+                detail: format!("certificat #{i} valide à partir du {} ({name})", format_date(cert.not_before)),
+            });
         }
     }
 
-    if let Some(ip) = &packet.ip {
-        // Flag simple FTP control traffic
-        if ip.protocol == 21 {
-            return true;
+    if let Some(host) = sni {
+        let names = x509::certificate_names(leaf);
+        if !names.iter().any(|n| x509::hostname_matches(n, host)) {
+            issues.push(CertificateIssue {
+                kind: "hostname-mismatch",
+                detail: format!("{host} absent du certificat ({})", names.join(", ")),
+            });
         }
     }
 
-    false
+    issues
 }
@@ -0,0 +1,278 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+use std::net::Ipv4Addr;
+
+use chrono::{TimeZone, Utc};
+
+use crate::core::dhcp;
+use crate::core::models::{Alert, ArpBinding, ArpMessage, Packet, Severity};
+use crate::core::parser;
+
+/// A reply must follow its request within this delay.
+const REQUEST_TIMEOUT_MS: u128 = 5_000;
+const GRATUITOUS_WINDOW_MS: u128 = 10_000;
+/// Gratuitous ARPs from one MAC within the window that make a flood.
+const GRATUITOUS_FLOOD: usize = 10;
+/// Distinct IPs one MAC may claim before it looks like a spoofer.
+const MAC_IP_LIMIT: usize = 8;
+const SWEEP_INTERVAL_MS: u128 = 10_000;
+/// Bindings, pending requests, MACs followed for floods and reported
+/// findings kept at most each. The least recently seen binding makes room
+/// for a new one; the reported findings are forgotten all at once.
+const MAX_ENTRIES: usize = 4096;
+
+const CLASSTYPE: &str = "bad-unknown";
+const BROADCAST_MAC: &str = "ff:ff:ff:ff:ff:ff";
+const ZERO_MAC: &str = "00:00:00:00:00:00";
+
+/// Learns IP ↔ MAC bindings from ARP and DHCP acknowledgements, and flags
+/// ARP spoofing symptoms.
+#[derive(Default)]
+pub struct ArpWatch {
+    bindings: HashMap<String, ArpBinding>,
+    /// Requests waiting for their reply, by (requester IP, requested IP).
+    pending: HashMap<(String, String), u128>,
+    gratuitous: HashMap<String, VecDeque<u128>>,
+    flooding: HashSet<String>,
+    /// Binding changes already reported, as (IP, lower MAC, higher MAC), so
+    /// that a flip-flopping IP is reported once.
+    reported_changes: HashSet<(String, String, String)>,
+    reported_macs: HashSet<String>,
+    reported_replies: HashSet<(String, String)>,
+    last_sweep: u128,
+}
+
+impl ArpWatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts from the bindings of a previous run, the most recently seen
+    /// ones if there are too many.
+    pub fn with_bindings(mut bindings: Vec<ArpBinding>) -> Self {
+        bindings.sort_by_key(|b| std::cmp::Reverse(b.last_seen));
+        ArpWatch {
+            bindings: bindings.into_iter().take(MAX_ENTRIES).map(|b| (b.ip.clone(), b)).collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Bindings sorted by IP address.
+    pub fn bindings(&self) -> Vec<ArpBinding> {
+        let mut list: Vec<ArpBinding> = self.bindings.values().cloned().collect();
+        list.sort_by_key(|b| b.ip.parse::<Ipv4Addr>().ok());
+        list
+    }
+
+    /// Checks `packet` (number `packet_id`).
+    pub fn observe(&mut self, packet: &Packet, packet_id: u64) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        let now = packet.timestamp;
+
+        if let Some(arp) = parser::parse_arp(&packet.payload) {
+            self.observe_arp(&arp, now, packet_id, &mut alerts);
+        } else if let Some((ip, mac)) = dhcp_ack(packet) {
+            // The DHCP server is authoritative: a new lease moves the IP silently.
+            self.make_room(&ip);
+            let binding = self.bindings.entry(ip.clone()).or_insert_with(|| ArpBinding {
+                ip,
+                mac: mac.clone(),
+                learned_from: "dhcp".to_string(),
+                first_seen: now,
+                last_seen: now,
+                changes: 0,
+            });
+            if binding.mac != mac {
+                binding.mac = mac;
+                binding.first_seen = now;
+                binding.changes += 1;
+            }
+            binding.learned_from = "dhcp".to_string();
+            binding.last_seen = now;
+        }
+
+        if now >= self.last_sweep + SWEEP_INTERVAL_MS {
+            self.last_sweep = now;
+            self.pending.retain(|_, at| now.saturating_sub(*at) < REQUEST_TIMEOUT_MS);
+            self.gratuitous.retain(|_, times| {
+                times.back().is_some_and(|at| now.saturating_sub(*at) < GRATUITOUS_WINDOW_MS)
+            });
+            let gratuitous = &self.gratuitous;
+            self.flooding.retain(|mac| gratuitous.contains_key(mac));
+        }
+        alerts
+    }
+
+    fn observe_arp(&mut self, arp: &ArpMessage, now: u128, packet_id: u64, alerts: &mut Vec<Alert>) {
+        let alert = |rule_id: &str, severity: Severity, message: String| Alert {
+            rule_id: rule_id.to_string(),
+            rule_rev: None,
+            severity,
+            message,
+            classtype: Some(CLASSTYPE.to_string()),
+            timestamp: now,
+            packet_id: Some(packet_id),
+            protocol: None,
+            src_ip: Some(arp.sender_ip.clone()),
+            src_port: None,
+            dst_ip: Some(arp.target_ip.clone()),
+            dst_port: None,
+            related_packets: Vec::new(),
+        };
+        // Address conflict probes announce nothing.
+        if arp.sender_ip == "0.0.0.0" || arp.sender_mac == ZERO_MAC {
+            return;
+        }
+
+        let gratuitous =
+            arp.sender_ip == arp.target_ip || (arp.operation == 2 && arp.target_mac == BROADCAST_MAC);
+        if gratuitous && (self.gratuitous.len() < MAX_ENTRIES || self.gratuitous.contains_key(&arp.sender_mac)) {
+            let times = self.gratuitous.entry(arp.sender_mac.clone()).or_default();
+            times.push_back(now);
+            while times.front().is_some_and(|at| now.saturating_sub(*at) >= GRATUITOUS_WINDOW_MS) {
+                times.pop_front();
+            }
+            if times.len() >= GRATUITOUS_FLOOD && remember(&mut self.flooding, arp.sender_mac.clone()) {
+                let message = format!(
+                    "Rafale d'ARP gratuits : {} en {} s depuis {}",
+                    times.len(),
+                    GRATUITOUS_WINDOW_MS / 1000,
+                    arp.sender_mac
+                );
+                alerts.push(alert("arp-gratuitous-flood", Severity::Medium, message));
+            }
+        } else if arp.operation == 1 {
+            if self.pending.len() < MAX_ENTRIES {
+                self.pending.insert((arp.sender_ip.clone(), arp.target_ip.clone()), now);
+            }
+        } else if arp.operation == 2 {
+            let request = (arp.target_ip.clone(), arp.sender_ip.clone());
+            // A request is answered once: a second reply is not expected either.
+            let solicited = self
+                .pending
+                .remove(&request)
+                .is_some_and(|at| now.saturating_sub(at) < REQUEST_TIMEOUT_MS);
+            let reply = (arp.sender_ip.clone(), arp.sender_mac.clone());
+            if !solicited && remember(&mut self.reported_replies, reply) {
+                let message = format!(
+                    "Réponse ARP non sollicitée : {} est à {} (pour {})",
+                    arp.sender_ip, arp.sender_mac, arp.target_ip
+                );
+                alerts.push(alert("arp-unsolicited-reply", Severity::Medium, message));
+            }
+        }
+
+        let mut claimed = false;
+        match self.bindings.get_mut(&arp.sender_ip) {
+            Some(binding) if binding.mac == arp.sender_mac => binding.last_seen = now,
+            Some(binding) => {
+                let (low, high) = if binding.mac < arp.sender_mac {
+                    (binding.mac.clone(), arp.sender_mac.clone())
+                } else {
+                    (arp.sender_mac.clone(), binding.mac.clone())
+                };
+                if remember(&mut self.reported_changes, (arp.sender_ip.clone(), low, high)) {
+                    let message = format!(
+                        "{} passe de {} à {} (connue depuis {})",
+                        arp.sender_ip,
+                        binding.mac,
+                        arp.sender_mac,
+                        time_label(binding.first_seen)
+                    );
+                    alerts.push(alert("arp-binding-change", Severity::High, message));
+                }
+                binding.mac = arp.sender_mac.clone();
+                binding.learned_from = "arp".to_string();
+                binding.first_seen = now;
+                binding.last_seen = now;
+                binding.changes += 1;
+                claimed = true;
+            }
+            None => {
+                self.make_room(&arp.sender_ip);
+                self.bindings.insert(
+                    arp.sender_ip.clone(),
+                    ArpBinding {
+                        ip: arp.sender_ip.clone(),
+                        mac: arp.sender_mac.clone(),
+                        learned_from: "arp".to_string(),
+                        first_seen: now,
+                        last_seen: now,
+                        changes: 0,
+                    },
+                );
+                claimed = true;
+            }
+        }
+
+        if claimed && !self.reported_macs.contains(&arp.sender_mac) {
+            let ips = self.bindings.values().filter(|b| b.mac == arp.sender_mac).count();
+            if ips >= MAC_IP_LIMIT {
+                remember(&mut self.reported_macs, arp.sender_mac.clone());
+                let message = format!("{} revendique {ips} adresses IP", arp.sender_mac);
+                alerts.push(alert("arp-mac-many-ips", Severity::Medium, message));
+            }
+        }
+    }
+
+    /// Evicts the least recently seen binding when the table is full and
+    /// `ip` is not in it.
+    fn make_room(&mut self, ip: &str) {
+        if self.bindings.len() < MAX_ENTRIES || self.bindings.contains_key(ip) {
+            return;
+        }
+        let oldest = self
+            .bindings
+            .values()
+            .min_by_key(|b| b.last_seen)
+            .map(|b| b.ip.clone());
+        if let Some(oldest) = oldest {
+            self.bindings.remove(&oldest);
+        }
+    }
+}
+
+/// Inserts `value` into a set of reported findings, forgetting them all
+/// when it is full; false if it was already there.
+fn remember<T: Eq + Hash>(set: &mut HashSet<T>, value: T) -> bool {
+    if set.len() >= MAX_ENTRIES && !set.contains(&value) {
+        set.clear();
+    }
+    set.insert(value)
+}
+
+/// Address and client MAC of a DHCPACK.
+fn dhcp_ack(packet: &Packet) -> Option<(String, String)> {
+    let msg = packet.dhcp.as_ref().filter(|m| m.version == 4 && m.message_type == dhcp::ACK)?;
+    Some((msg.assigned_ip.clone()?, msg.client_mac.clone()?))
+}
+
+fn time_label(ms: u128) -> String {
+    Utc.timestamp_millis_opt(ms as i64)
+        .single()
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+pub fn print_report(bindings: &[ArpBinding]) {
+    println!();
+    println!("══════════════════════════════════════════════════════════════════════");
+    println!(" WireFish - Table ARP ({})", bindings.len());
+    println!("══════════════════════════════════════════════════════════════════════");
+    println!(
+        "{:<16} {:<18} {:<6} {:<20} {:<20} Changements",
+        "IP", "MAC", "Source", "Depuis", "Vu le"
+    );
+
+    for b in bindings {
+        println!(
+            "{:<16} {:<18} {:<6} {:<20} {:<20} {}",
+            b.ip,
+            b.mac,
+            b.learned_from,
+            time_label(b.first_seen),
+            time_label(b.last_seen),
+            b.changes
+        );
+    }
+}
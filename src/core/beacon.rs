@@ -0,0 +1,236 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::core::flow::{self, FlowTable};
+use crate::core::models::{Alert, Flow, Packet, Severity};
+
+const FLOW_IDLE_MS: u128 = 60_000;
+const SWEEP_INTERVAL_MS: u128 = 5_000;
+/// Connections older than this no longer count.
+const WINDOW_MS: u128 = 4 * 3_600_000;
+const MAX_CONNECTIONS: usize = 200;
+const MIN_CONNECTIONS: usize = 6;
+/// (client, destination) series kept at most; the least recently active one
+/// makes room for a new one.
+const MAX_SERIES: usize = 16_384;
+/// Faster callbacks are left to keepalive and polling traffic.
+const MIN_INTERVAL_MS: u128 = 5_000;
+const SCORE_THRESHOLD: u32 = 75;
+/// An interval counts as periodic within this share of a multiple of the
+/// median interval (missed callbacks).
+const PERIOD_TOLERANCE: f64 = 0.1;
+
+/// Infrastructure protocols that run on timers by design.
+const IGNORED_PORTS: &[u16] = &[53, 67, 68, 123, 137, 138, 1900, 5353, 5355];
+
+/// A finished conversation.
+#[derive(Debug, Clone)]
+struct Connection {
+    start: u128,
+    bytes: u64,
+    duration_ms: u128,
+    packet_id: u64,
+}
+
+/// Conversations of one client to one destination (SNI or IP, and port).
+#[derive(Default)]
+struct Series {
+    protocol: u8,
+    server_ip: String,
+    server_port: Option<u16>,
+    connections: VecDeque<Connection>,
+    alerted: bool,
+}
+
+/// What a flow needs at expiry that the flow table does not keep.
+struct Opening {
+    packet_id: u64,
+    sni: Option<String>,
+}
+
+/// Regularity of a series of connections.
+struct BeaconScore {
+    connections: usize,
+    median_interval_ms: u128,
+    /// Coefficient of variation of the intervals.
+    jitter: f64,
+    /// Share of intervals close to a multiple of the median.
+    periodicity: f64,
+    mean_bytes: f64,
+    /// Coefficient of variation of the sizes.
+    size_spread: f64,
+    mean_duration_ms: f64,
+    /// 0 to 100.
+    score: u32,
+}
+
+fn mean_and_cv(values: &[f64]) -> (f64, f64) {
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    if mean == 0.0 {
+        return (0.0, 0.0);
+    }
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+    (mean, variance.sqrt() / mean)
+}
+
+/// Scores connection start times (sorted), sizes and durations.
+fn score(connections: &VecDeque<Connection>) -> Option<BeaconScore> {
+    if connections.len() < MIN_CONNECTIONS {
+        return None;
+    }
+    let starts: Vec<u128> = connections.iter().map(|c| c.start).collect();
+    let mut intervals: Vec<u128> = starts.windows(2).map(|w| w[1] - w[0]).collect();
+    let as_f64: Vec<f64> = intervals.iter().map(|&i| i as f64).collect();
+    intervals.sort_unstable();
+    let median = intervals[intervals.len() / 2];
+    if median < MIN_INTERVAL_MS {
+        return None;
+    }
+
+    let (_, jitter) = mean_and_cv(&as_f64);
+    let periodic = as_f64
+        .iter()
+        .filter(|&&i| {
+            let ratio = i / median as f64;
+            let multiple = ratio.round();
+            multiple >= 1.0 && (ratio - multiple).abs() <= PERIOD_TOLERANCE * multiple
+        })
+        .count();
+    let periodicity = periodic as f64 / as_f64.len() as f64;
+    let sizes: Vec<f64> = connections.iter().map(|c| c.bytes as f64).collect();
+    let (mean_bytes, size_spread) = mean_and_cv(&sizes);
+    let durations: Vec<f64> = connections.iter().map(|c| c.duration_ms as f64).collect();
+    let (mean_duration_ms, duration_spread) = mean_and_cv(&durations);
+
+    let regular = |cv: f64| 1.0 - cv.min(1.0);
+    let score = 100.0
+        * (0.35 * regular(jitter)
+            + 0.25 * periodicity
+            + 0.25 * regular(size_spread)
+            + 0.15 * regular(duration_spread));
+    Some(BeaconScore {
+        connections: connections.len(),
+        median_interval_ms: median,
+        jitter,
+        periodicity,
+        mean_bytes,
+        size_spread,
+        mean_duration_ms,
+        score: score.round() as u32,
+    })
+}
+
+/// Looks for clients calling the same destination on a schedule: regular
+/// connection start times, similar sizes and durations.
+#[derive(Default)]
+pub struct BeaconDetector {
+    flows: FlowTable,
+    openings: HashMap<u64, Opening>,
+    series: HashMap<(String, String), Series>,
+    last_sweep: u128,
+}
+
+impl BeaconDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accounts `packet` (number `packet_id`); connections are scored once
+    /// they end.
+    pub fn observe(&mut self, packet: &Packet, packet_id: u64) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        let now = packet.timestamp;
+
+        if let Some(flow) = self.flows.observe(packet, None, "") {
+            let opening = self.openings.entry(flow.id).or_insert(Opening { packet_id, sni: None });
+            if let Some(sni) = packet.tls.as_ref().and_then(|t| t.client_hello.as_ref()).and_then(|h| h.sni.as_ref()) {
+                opening.sni = Some(sni.to_ascii_lowercase());
+            }
+        }
+
+        if now >= self.last_sweep + SWEEP_INTERVAL_MS {
+            self.last_sweep = now;
+            for record in self.flows.expire(now, FLOW_IDLE_MS, u128::MAX) {
+                if let Some(opening) = self.openings.remove(&record.flow.id) {
+                    alerts.extend(self.finished(&record.flow, opening, now));
+                }
+            }
+            self.series.retain(|_, s| s.connections.back().is_some_and(|c| now.saturating_sub(c.start) < WINDOW_MS));
+        }
+        alerts
+    }
+
+    fn finished(&mut self, flow: &Flow, opening: Opening, now: u128) -> Option<Alert> {
+        if !matches!(flow.protocol, 6 | 17) || flow.server_port.is_some_and(|p| IGNORED_PORTS.contains(&p)) {
+            return None;
+        }
+        let port = flow.server_port.map(|p| format!(":{p}")).unwrap_or_default();
+        let target = match &opening.sni {
+            Some(sni) => format!("{sni}{port}"),
+            None => flow::endpoint_label(&flow.server_ip, flow.server_port),
+        };
+        let key = (flow.client_ip.clone(), target.clone());
+        if self.series.len() >= MAX_SERIES && !self.series.contains_key(&key) {
+            let oldest = self
+                .series
+                .iter()
+                .min_by_key(|(_, s)| s.connections.back().map_or(0, |c| c.start))
+                .map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                self.series.remove(&oldest);
+            }
+        }
+        let series = self.series.entry(key).or_default();
+        series.protocol = flow.protocol;
+        series.server_ip = flow.server_ip.clone();
+        series.server_port = flow.server_port;
+
+        let connection = Connection {
+            start: flow.first_seen,
+            bytes: flow.bytes(),
+            duration_ms: flow.duration_ms(),
+            packet_id: opening.packet_id,
+        };
+        let at = series.connections.partition_point(|c| c.start <= connection.start);
+        series.connections.insert(at, connection);
+        while series.connections.len() > MAX_CONNECTIONS
+            || series.connections.front().is_some_and(|c| now.saturating_sub(c.start) >= WINDOW_MS)
+        {
+            series.connections.pop_front();
+        }
+
+        if series.alerted {
+            return None;
+        }
+        let s = score(&series.connections)?;
+        if s.score < SCORE_THRESHOLD {
+            return None;
+        }
+        series.alerted = true;
+        let message = format!(
+            "Beacon possible vers {target} : {} connexions toutes les {:.0} s (gigue {:.0} %, périodicité {:.0} %, {:.0} o ± {:.0} %, durée {:.1} s) — score {}",
+            s.connections,
+            s.median_interval_ms as f64 / 1000.0,
+            s.jitter * 100.0,
+            s.periodicity * 100.0,
+            s.mean_bytes,
+            s.size_spread * 100.0,
+            s.mean_duration_ms / 1000.0,
+            s.score
+        );
+        Some(Alert {
+            rule_id: "beacon".to_string(),
+            rule_rev: None,
+            severity: if s.score >= 90 { Severity::High } else { Severity::Medium },
+            message,
+            classtype: Some("trojan-activity".to_string()),
+            timestamp: now,
+            packet_id: series.connections.back().map(|c| c.packet_id),
+            protocol: Some(series.protocol),
+            src_ip: Some(flow.client_ip.clone()),
+            src_port: None,
+            dst_ip: Some(series.server_ip.clone()),
+            dst_port: series.server_port,
+            related_packets: series.connections.iter().map(|c| c.packet_id).collect(),
+        })
+    }
+}
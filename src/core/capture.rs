@@ -1,10 +1,11 @@
 use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::Path;
 use std::time::{Duration, Instant};
 
 use pcap::{Capture, Device, Error};
 use crossbeam::channel::Sender;
 
-use crate::core::parser::parse_packet;
+use crate::core::parser::{parse_packet, parse_packet_at};
 use crate::core::models::{Packet, IpHeader};
 
 pub fn list_devices() -> Vec<String> {
@@ -43,6 +44,7 @@ pub fn quick_peek(device_name: &str, duration_ms: u64) -> usize {
     count
 }
 
+#[allow(clippy::collapsible_match)]
 fn fill_ip_from_l3(packet: &mut Packet, ether_type: u16, data: &[u8]) {
     match ether_type {
         0x0800 => {
@@ -171,3 +173,33 @@ pub fn capture_on(device_name: &str, sender: Sender<Packet>, debug: bool) {
         }
     }
 }
+
+/// Reads a capture file (pcap/pcapng) and hands every parsed packet, with
+/// its frame number (from 1) and recorded timestamp, to `on_packet`.
+/// Returns the number of frames read.
+pub fn read_file(path: &Path, mut on_packet: impl FnMut(u64, Packet)) -> Result<u64, String> {
+    let mut cap = Capture::from_file(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let mut frames = 0;
+
+    loop {
+        match cap.next_packet() {
+            Ok(packet) => {
+                frames += 1;
+                let data = packet.data;
+                let ts = packet.header.ts.tv_sec as u128 * 1000 + packet.header.ts.tv_usec as u128 / 1000;
+
+                if let Some(mut parsed) = parse_packet_at(data, ts) {
+                    if data.len() >= 14 {
+                        let ether_type = u16::from_be_bytes([data[12], data[13]]);
+                        fill_ip_from_l3(&mut parsed, ether_type, data);
+                    }
+                    on_packet(frames, parsed);
+                }
+            }
+            Err(Error::NoMorePackets) => break,
+            Err(e) => return Err(format!("{}: {e}", path.display())),
+        }
+    }
+
+    Ok(frames)
+}
@@ -1,24 +1,12 @@
 use crate::core::models::{Packet, TransportProtocol};
+use crate::core::parser;
+use crate::core::tls;
 
 fn looks_like_tls(payload: &[u8]) -> bool {
     if payload.len() < 5 {
         return false;
     }
-    payload[0] == 0x16 && payload[1] == 0x03
-}
-
-fn is_tls12(payload: &[u8]) -> bool {
-    if payload.len() < 3 {
-        return false;
-    }
-    payload[0] == 0x16 && payload[1] == 0x03 && payload[2] == 0x03
-}
-
-fn is_tls13(payload: &[u8]) -> bool {
-    if payload.len() < 3 {
-        return false;
-    }
-    payload[0] == 0x16 && payload[1] == 0x03 && payload[2] == 0x04
+    (0x14..=0x17).contains(&payload[0]) && payload[1] == 0x03
 }
 
 pub fn classify(packet: &Packet) -> &'static str {
@@ -45,6 +33,19 @@ pub fn classify(packet: &Packet) -> &'static str {
             58 => return "ICMPv6",
 
             6 => {
+                if let Some(info) = &packet.tls {
+                    return tls::version_label(tls::effective_version(info));
+                }
+                if !packet.grpc.is_empty() {
+                    return "gRPC";
+                }
+                if packet.http2.is_some() {
+                    return "HTTP2";
+                }
+                if !packet.http.is_empty() {
+                    return "HTTP";
+                }
+
                 if let (Some(sport), Some(dport)) = (src_port, dst_port) {
                     let (minp, maxp) = if sport < dport {
                         (sport, dport)
@@ -59,13 +60,7 @@ pub fn classify(packet: &Packet) -> &'static str {
                         return "HTTP";
                     }
                     if minp == 443 || maxp == 443 {
-                        if is_tls13(&packet.payload) {
-                            return "TLSv1.3";
-                        }
-                        if is_tls12(&packet.payload) {
-                            return "TLSv1.2";
-                        }
-                        if looks_like_tls(&packet.payload) {
+                        if looks_like_tls(parser::transport_payload(&packet.payload)) {
                             return "TLS";
                         }
                         return "HTTPS";
@@ -87,8 +82,10 @@ pub fn classify(packet: &Packet) -> &'static str {
                         67 | 68 => "DHCP",
                         123 => "NTP",
                         137 => "NBNS",
+                        546 | 547 => "DHCPv6",
                         1900 => "SSDP",
                         5353 => "mDNS",
+                        5355 => "LLMNR",
                         443 => "QUIC",
                         _ => "UDP",
                     };
@@ -0,0 +1,653 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::core::alerts;
+use crate::core::http;
+use crate::core::models::{Alert, Packet, Severity, TransportProtocol};
+use crate::core::parser;
+use crate::core::stream::{Direction, StreamEvent, StreamKey};
+
+/// Longest client line kept while waiting for its end of line.
+const MAX_LINE: usize = 1024;
+/// Bytes of server output kept to spot Telnet prompts.
+const PROMPT_TAIL: usize = 64;
+const MAX_SESSIONS: usize = 4096;
+const MAX_COMMUNITIES: usize = 4096;
+
+const CLASSTYPE: &str = "policy-violation";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Service {
+    Ftp,
+    Telnet,
+    Pop3,
+    Imap,
+    Smtp,
+}
+
+impl Service {
+    fn from_port(port: u16) -> Option<Self> {
+        match port {
+            21 => Some(Service::Ftp),
+            23 => Some(Service::Telnet),
+            110 => Some(Service::Pop3),
+            143 => Some(Service::Imap),
+            25 | 587 => Some(Service::Smtp),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Service::Ftp => "FTP",
+            Service::Telnet => "Telnet",
+            Service::Pop3 => "POP3",
+            Service::Imap => "IMAP",
+            Service::Smtp => "SMTP",
+        }
+    }
+
+    fn rule_id(self) -> &'static str {
+        match self {
+            Service::Ftp => "cleartext-ftp",
+            Service::Telnet => "cleartext-telnet",
+            Service::Pop3 => "cleartext-pop3",
+            Service::Imap => "cleartext-imap",
+            Service::Smtp => "cleartext-smtp",
+        }
+    }
+}
+
+/// What the next client line carries in a SASL exchange.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sasl {
+    None,
+    /// `AUTH PLAIN` without initial response.
+    Plain,
+    /// `AUTH LOGIN`: user name, then password, each in base64.
+    LoginUser,
+    LoginPassword,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Prompt {
+    Login,
+    Password,
+}
+
+struct Session {
+    service: Service,
+    line: Vec<u8>,
+    user: Option<String>,
+    sasl: Sasl,
+    /// Telnet: lowercased end of the server output, and the prompt being answered.
+    server_tail: Vec<u8>,
+    prompt: Option<Prompt>,
+}
+
+/// Found credentials, before they are turned into an alert.
+struct Login {
+    user: Option<String>,
+    secret: String,
+    method: &'static str,
+}
+
+/// Hides a secret while keeping a hint of it for the analyst.
+pub fn mask(secret: &str) -> String {
+    let chars: Vec<char> = secret.chars().collect();
+    match chars.len() {
+        0 => String::new(),
+        1..=3 => "***".to_string(),
+        4..=7 => format!("{}***", chars[0]),
+        n => format!("{}***{}", chars[0], chars[n - 1]),
+    }
+}
+
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for c in text.trim().bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            _ => return None,
+        };
+        acc = (acc << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
+
+fn base64_text(text: &str) -> Option<String> {
+    base64_decode(text).map(|b| String::from_utf8_lossy(&b).into_owned())
+}
+
+/// `authzid NUL authcid NUL password` of SASL PLAIN.
+fn sasl_plain(text: &str) -> Option<Login> {
+    let decoded = base64_decode(text)?;
+    let mut parts = decoded.split(|b| *b == 0);
+    let _authzid = parts.next()?;
+    let user = String::from_utf8_lossy(parts.next()?).into_owned();
+    let secret = String::from_utf8_lossy(parts.next()?).into_owned();
+    Some(Login {
+        user: Some(user),
+        secret,
+        method: "AUTH PLAIN",
+    })
+}
+
+/// Splits an IMAP command argument list, honouring quoted strings.
+fn imap_args(text: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut arg = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => arg.extend(chars.next()),
+                    '"' => break,
+                    _ => arg.push(c),
+                }
+            }
+            args.push(arg);
+        } else {
+            let mut arg = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                arg.push(c);
+                chars.next();
+            }
+            args.push(arg);
+        }
+    }
+    args
+}
+
+/// Drops Telnet option negotiation (IAC sequences) from a chunk.
+fn strip_telnet(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] != 0xFF {
+            out.push(data[i]);
+            i += 1;
+            continue;
+        }
+        match data.get(i + 1) {
+            Some(0xFF) => {
+                out.push(0xFF);
+                i += 2;
+            }
+            Some(251..=254) => i += 3,
+            // Subnegotiation: skip to IAC SE.
+            Some(250) => {
+                i += 2;
+                while i < data.len() && !(data[i] == 0xFF && data.get(i + 1) == Some(&240)) {
+                    i += 1;
+                }
+                i += 2;
+            }
+            _ => i += 2,
+        }
+    }
+    out
+}
+
+impl Session {
+    fn new(service: Service) -> Self {
+        Session {
+            service,
+            line: Vec::new(),
+            user: None,
+            sasl: Sasl::None,
+            server_tail: Vec::new(),
+            prompt: None,
+        }
+    }
+
+    /// Starts a SASL exchange from `AUTH <mech> [initial response]`.
+    fn sasl_start(&mut self, mechanism: &str, initial: Option<&str>) -> Option<Login> {
+        match (mechanism.to_ascii_uppercase().as_str(), initial) {
+            ("PLAIN", Some(resp)) if resp != "=" => sasl_plain(resp),
+            ("PLAIN", _) => {
+                self.sasl = Sasl::Plain;
+                None
+            }
+            ("LOGIN", Some(user)) => {
+                self.user = base64_text(user);
+                self.sasl = Sasl::LoginPassword;
+                None
+            }
+            ("LOGIN", None) => {
+                self.sasl = Sasl::LoginUser;
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn sasl_continue(&mut self, line: &str) -> Option<Login> {
+        let state = std::mem::replace(&mut self.sasl, Sasl::None);
+        // `*` aborts the exchange.
+        if line == "*" {
+            return None;
+        }
+        match state {
+            Sasl::None => None,
+            Sasl::Plain => sasl_plain(line),
+            Sasl::LoginUser => {
+                self.user = base64_text(line);
+                self.sasl = Sasl::LoginPassword;
+                None
+            }
+            Sasl::LoginPassword => Some(Login {
+                user: self.user.clone(),
+                secret: base64_text(line)?,
+                method: "AUTH LOGIN",
+            }),
+        }
+    }
+
+    fn client_line(&mut self, line: &str) -> Option<Login> {
+        if self.sasl != Sasl::None {
+            return self.sasl_continue(line);
+        }
+        let mut words = line.splitn(2, ' ');
+        let first = words.next().unwrap_or_default();
+        let rest = words.next().unwrap_or_default().trim();
+        match self.service {
+            Service::Ftp | Service::Pop3 => match first.to_ascii_uppercase().as_str() {
+                "USER" => {
+                    self.user = Some(rest.to_string());
+                    None
+                }
+                "PASS" => Some(Login {
+                    user: self.user.clone(),
+                    secret: rest.to_string(),
+                    method: "USER/PASS",
+                }),
+                "AUTH" if self.service == Service::Pop3 => {
+                    let mut args = rest.split_whitespace();
+                    let mechanism = args.next().unwrap_or_default();
+                    self.sasl_start(mechanism, args.next())
+                }
+                _ => None,
+            },
+            Service::Smtp => {
+                if !first.eq_ignore_ascii_case("AUTH") {
+                    return None;
+                }
+                let mut args = rest.split_whitespace();
+                let mechanism = args.next().unwrap_or_default();
+                self.sasl_start(mechanism, args.next())
+            }
+            Service::Imap => {
+                // `tag COMMAND args`
+                let mut words = rest.splitn(2, ' ');
+                let command = words.next().unwrap_or_default().to_ascii_uppercase();
+                let args = imap_args(words.next().unwrap_or_default());
+                match command.as_str() {
+                    "LOGIN" if args.len() >= 2 => Some(Login {
+                        user: Some(args[0].clone()),
+                        secret: args[1].clone(),
+                        method: "LOGIN",
+                    }),
+                    "AUTHENTICATE" if !args.is_empty() => {
+                        self.sasl_start(&args[0], args.get(1).map(String::as_str))
+                    }
+                    _ => None,
+                }
+            }
+            Service::Telnet => None,
+        }
+    }
+
+    fn client_data(&mut self, data: &[u8]) -> Vec<Login> {
+        let mut logins = Vec::new();
+        if self.service == Service::Telnet {
+            for byte in strip_telnet(data) {
+                let Some(prompt) = self.prompt else {
+                    continue;
+                };
+                match byte {
+                    b'\r' | b'\n' | 0 => {
+                        if self.line.is_empty() {
+                            continue;
+                        }
+                        let text = String::from_utf8_lossy(&std::mem::take(&mut self.line)).into_owned();
+                        self.prompt = None;
+                        match prompt {
+                            Prompt::Login => self.user = Some(text),
+                            Prompt::Password => logins.push(Login {
+                                user: self.user.clone(),
+                                secret: text,
+                                method: "login",
+                            }),
+                        }
+                    }
+                    0x08 | 0x7F => {
+                        self.line.pop();
+                    }
+                    _ if self.line.len() < MAX_LINE => self.line.push(byte),
+                    _ => {}
+                }
+            }
+            return logins;
+        }
+
+        for &byte in data {
+            if byte == b'\n' {
+                let line = std::mem::take(&mut self.line);
+                let text = String::from_utf8_lossy(&line);
+                if let Some(login) = self.client_line(text.trim_end_matches('\r').trim()) {
+                    logins.push(login);
+                }
+            } else if self.line.len() < MAX_LINE {
+                self.line.push(byte);
+            }
+        }
+        logins
+    }
+
+    /// Telnet: notices the login and password prompts in the server output.
+    fn server_data(&mut self, data: &[u8]) {
+        if self.service != Service::Telnet {
+            return;
+        }
+        self.server_tail.extend(strip_telnet(data).iter().map(u8::to_ascii_lowercase));
+        if self.server_tail.len() > PROMPT_TAIL {
+            self.server_tail.drain(..self.server_tail.len() - PROMPT_TAIL);
+        }
+        let tail = String::from_utf8_lossy(&self.server_tail);
+        let tail = tail.trim_end();
+        if tail.ends_with("password:") {
+            self.prompt = Some(Prompt::Password);
+            self.line.clear();
+        } else if tail.ends_with("login:") || tail.ends_with("username:") {
+            self.prompt = Some(Prompt::Login);
+            self.line.clear();
+        }
+    }
+}
+
+/// SNMPv1/v2c message: `SEQUENCE { INTEGER version, OCTET STRING community, PDU }`.
+fn snmp_community(payload: &[u8]) -> Option<(&'static str, String)> {
+    // Definite BER length starting at `i`: (length, first content byte).
+    fn length(data: &[u8], i: usize) -> Option<(usize, usize)> {
+        let first = *data.get(i)?;
+        if first < 0x80 {
+            return Some((first as usize, i + 1));
+        }
+        let n = (first & 0x7F) as usize;
+        if n == 0 || n > 4 {
+            return None;
+        }
+        let bytes = data.get(i + 1..i + 1 + n)?;
+        let len = bytes.iter().fold(0usize, |acc, b| (acc << 8) | *b as usize);
+        Some((len, i + 1 + n))
+    }
+
+    if payload.first() != Some(&0x30) {
+        return None;
+    }
+    let (_, i) = length(payload, 1)?;
+    if payload.get(i) != Some(&0x02) {
+        return None;
+    }
+    let (len, i) = length(payload, i + 1)?;
+    if len != 1 {
+        return None;
+    }
+    let version = match *payload.get(i)? {
+        0 => "v1",
+        1 => "v2c",
+        _ => return None,
+    };
+    let i = i + 1;
+    if payload.get(i) != Some(&0x04) {
+        return None;
+    }
+    let (len, i) = length(payload, i + 1)?;
+    let community = payload.get(i..i + len)?;
+    Some((version, String::from_utf8_lossy(community).into_owned()))
+}
+
+/// Spots credentials sent in clear: FTP and POP3 `USER`/`PASS`, IMAP
+/// `LOGIN`, SASL `AUTH PLAIN`/`LOGIN` (SMTP, IMAP, POP3), Telnet logins,
+/// HTTP Basic authentication and SNMPv1/v2c communities.
+#[derive(Default)]
+pub struct CredentialDetector {
+    sessions: HashMap<StreamKey, Session>,
+    communities: HashSet<(String, String, String)>,
+}
+
+impl CredentialDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checks `packet` (number `packet_id`) after the HTTP decoders ran, and
+    /// the stream `events` it produced.
+    pub fn observe(&mut self, packet: &Packet, packet_id: u64, events: &[StreamEvent]) -> Vec<Alert> {
+        let mut found = Vec::new();
+
+        for event in events {
+            match event {
+                StreamEvent::Data { key, direction, data } => {
+                    if !self.sessions.contains_key(key) {
+                        let port = key.server.rsplit_once(':').and_then(|(_, p)| p.parse().ok());
+                        let Some(service) = port.and_then(Service::from_port) else {
+                            continue;
+                        };
+                        if self.sessions.len() >= MAX_SESSIONS {
+                            self.sessions.clear();
+                        }
+                        self.sessions.insert(key.clone(), Session::new(service));
+                    }
+                    let Some(session) = self.sessions.get_mut(key) else {
+                        continue;
+                    };
+                    let service = session.service;
+                    match direction {
+                        Direction::ToServer => {
+                            for login in session.client_data(data) {
+                                found.push(alerts::stream_alert(
+                                    key,
+                                    Direction::ToServer,
+                                    packet.timestamp,
+                                    packet_id,
+                                    service.rule_id(),
+                                    Severity::High,
+                                    login_message(service.name(), &login),
+                                ));
+                            }
+                        }
+                        Direction::ToClient => session.server_data(data),
+                    }
+                }
+                StreamEvent::Gap { key, .. } | StreamEvent::Closed { key, .. } => {
+                    self.sessions.remove(key);
+                }
+            }
+        }
+
+        // Requests are reported once, when complete, without their response.
+        for tx in packet.http.iter().filter(|tx| tx.response.is_none()) {
+            let Some(req) = &tx.request else {
+                continue;
+            };
+            for name in ["authorization", "proxy-authorization"] {
+                let Some(value) = http::header(&req.headers, name) else {
+                    continue;
+                };
+                let Some((scheme, token)) = value.trim().split_once(' ') else {
+                    continue;
+                };
+                if !scheme.eq_ignore_ascii_case("basic") {
+                    continue;
+                }
+                let Some(decoded) = base64_text(token) else {
+                    continue;
+                };
+                let (user, secret) = decoded.split_once(':').unwrap_or((&decoded, ""));
+                let login = Login {
+                    user: Some(user.to_string()),
+                    secret: secret.to_string(),
+                    method: "Basic",
+                };
+                let host = req.host.as_deref().unwrap_or("");
+                found.push(alerts::packet_alert(
+                    packet,
+                    packet_id,
+                    "cleartext-http-basic",
+                    Severity::High,
+                    format!("{} ({host}{})", login_message("HTTP", &login), req.uri),
+                ));
+            }
+        }
+
+        if let (Some(ip), Some(TransportProtocol::Udp(udp))) = (&packet.ip, &packet.transport) {
+            let snmp = [161, 162].contains(&udp.dst_port) || [161, 162].contains(&udp.src_port);
+            if let Some((version, community)) = snmp
+                .then(|| snmp_community(parser::transport_payload(&packet.payload)))
+                .flatten()
+            {
+                if self.communities.len() >= MAX_COMMUNITIES {
+                    self.communities.clear();
+                }
+                if self
+                    .communities
+                    .insert((ip.src_ip.clone(), ip.dst_ip.clone(), community.clone()))
+                {
+                    found.push(alerts::packet_alert(
+                        packet,
+                        packet_id,
+                        "cleartext-snmp",
+                        Severity::Medium,
+                        format!("SNMP{version} : communauté en clair « {} »", mask(&community)),
+                    ));
+                }
+            }
+        }
+
+        for alert in &mut found {
+            alert.classtype = Some(CLASSTYPE.to_string());
+        }
+        found
+    }
+}
+
+fn login_message(protocol: &str, login: &Login) -> String {
+    format!(
+        "{protocol} : identifiants en clair ({}, utilisateur {}, mot de passe {})",
+        login.method,
+        login.user.as_deref().unwrap_or("?"),
+        mask(&login.secret)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Replays `chunks` through a session, returning (user, secret, method)
+    /// of each login found.
+    fn logins(service: Service, chunks: &[(Direction, &[u8])]) -> Vec<(Option<String>, String, &'static str)> {
+        let mut session = Session::new(service);
+        let mut found = Vec::new();
+        for (direction, data) in chunks {
+            match direction {
+                Direction::ToServer => found.extend(session.client_data(data)),
+                Direction::ToClient => session.server_data(data),
+            }
+        }
+        found.into_iter().map(|l| (l.user, l.secret, l.method)).collect()
+    }
+
+    fn login(user: &str, secret: &str, method: &'static str) -> (Option<String>, String, &'static str) {
+        (Some(user.to_string()), secret.to_string(), method)
+    }
+
+    #[test]
+    fn mask_keeps_a_hint() {
+        assert_eq!(mask(""), "");
+        assert_eq!(mask("abc"), "***");
+        assert_eq!(mask("pass"), "p***");
+        assert_eq!(mask("password"), "p***d");
+        assert_eq!(mask("éléphant"), "é***t");
+    }
+
+    #[test]
+    fn ftp_user_pass_across_chunks() {
+        let chunks: [(Direction, &[u8]); 4] = [
+            (Direction::ToClient, b"220 ready\r\n"),
+            (Direction::ToServer, b"USER al"),
+            (Direction::ToServer, b"ice\r\nPA"),
+            (Direction::ToServer, b"SS s3cret\r\nQUIT\r\n"),
+        ];
+        assert_eq!(logins(Service::Ftp, &chunks), [login("alice", "s3cret", "USER/PASS")]);
+    }
+
+    #[test]
+    fn imap_login_and_authenticate() {
+        let chunks: [(Direction, &[u8]); 4] = [
+            (Direction::ToServer, b"a1 LOGIN \"bob\" \"p\\\"w d\"\r\n"),
+            (Direction::ToServer, b"a2 AUTHENTICATE PLAIN\r\n"),
+            (Direction::ToClient, b"+ \r\n"),
+            (Direction::ToServer, b"AGJvYgBodW50ZXIy\r\na3 AUTHENTICATE PLAIN YWRtaW4AY2Fyb2wAcHc=\r\n"),
+        ];
+        assert_eq!(
+            logins(Service::Imap, &chunks),
+            [login("bob", "p\"w d", "LOGIN"), login("bob", "hunter2", "AUTH PLAIN"), login("carol", "pw", "AUTH PLAIN")]
+        );
+    }
+
+    #[test]
+    fn smtp_auth_login() {
+        let chunks: [(Direction, &[u8]); 3] = [
+            (Direction::ToServer, b"EHLO client\r\nAUTH LOGIN\r\nYWxpY2U=\r\nc2VjcmV0\r\n"),
+            // An aborted exchange carries nothing.
+            (Direction::ToServer, b"AUTH LOGIN YWxpY2U=\r\n*\r\n"),
+            (Direction::ToServer, b"AUTH PLAIN\r\n*\r\n"),
+        ];
+        assert_eq!(logins(Service::Smtp, &chunks), [login("alice", "secret", "AUTH LOGIN")]);
+    }
+
+    #[test]
+    fn pop3_auth_plain_with_initial_response() {
+        let chunks: [(Direction, &[u8]); 1] = [(Direction::ToServer, b"AUTH PLAIN AGJvYgBodW50ZXIy\r\n")];
+        assert_eq!(logins(Service::Pop3, &chunks), [login("bob", "hunter2", "AUTH PLAIN")]);
+    }
+
+    #[test]
+    fn telnet_prompts_and_echo() {
+        let chunks: [(Direction, &[u8]); 9] = [
+            (Direction::ToClient, b"\xff\xfb\x01\xff\xfb\x03Welcome\r\n"),
+            // Typed before any prompt: not a user name.
+            (Direction::ToServer, b"\xff\xfd\x01ls\r\n"),
+            (Direction::ToClient, b"router login: "),
+            (Direction::ToServer, b"r"),
+            (Direction::ToServer, b"ooo\x7ft"),
+            (Direction::ToServer, b"\r\0"),
+            (Direction::ToClient, b"root\r\nPassword: "),
+            (Direction::ToServer, b"to"),
+            (Direction::ToServer, b"or\r\n"),
+        ];
+        assert_eq!(logins(Service::Telnet, &chunks), [login("root", "toor", "login")]);
+    }
+}
@@ -0,0 +1,586 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use chrono::{TimeZone, Utc};
+
+use crate::core::dns;
+use crate::core::models::{Alert, DhcpLease, DhcpMessage, DhcpServer, Packet, Severity, TransportProtocol};
+
+pub const DISCOVER: u8 = 1;
+pub const OFFER: u8 = 2;
+pub const REQUEST: u8 = 3;
+pub const DECLINE: u8 = 4;
+pub const ACK: u8 = 5;
+pub const NAK: u8 = 6;
+pub const RELEASE: u8 = 7;
+
+pub const V6_SOLICIT: u8 = 1;
+pub const V6_ADVERTISE: u8 = 2;
+pub const V6_REPLY: u8 = 7;
+pub const V6_RELEASE: u8 = 8;
+pub const V6_DECLINE: u8 = 9;
+pub const V6_RECONFIGURE: u8 = 10;
+const V6_RELAY_FORW: u8 = 12;
+const V6_RELAY_REPL: u8 = 13;
+const MAX_RELAY_DEPTH: u8 = 8;
+
+const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+
+/// Distinct clients asking for an address within the window that make a
+/// starvation attempt.
+const STARVATION_CLIENTS: usize = 50;
+const STARVATION_WINDOW_MS: u128 = 60_000;
+/// Servers kept at most; past it, the least recently seen untrusted one
+/// makes room.
+const MAX_SERVERS: usize = 256;
+/// Leases and client host names kept at most.
+const MAX_CLIENTS: usize = 4096;
+
+fn read_u16(data: &[u8], off: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*data.get(off)?, *data.get(off + 1)?]))
+}
+
+fn read_u32(data: &[u8], off: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(off..off + 4)?.try_into().ok()?))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn mac(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect::<Vec<_>>().join(":")
+}
+
+fn text(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).trim_end_matches('\0').to_string()
+}
+
+/// A set IPv4 address; 0.0.0.0 means none.
+fn ipv4(bytes: &[u8]) -> Option<String> {
+    let addr = Ipv4Addr::from(<[u8; 4]>::try_from(bytes).ok()?);
+    (!addr.is_unspecified()).then(|| addr.to_string())
+}
+
+fn ipv6(bytes: &[u8]) -> Option<String> {
+    Some(Ipv6Addr::from(<[u8; 16]>::try_from(bytes).ok()?).to_string())
+}
+
+/// Decodes a DHCPv4 message (BOOTP with the magic cookie and option 53).
+pub fn parse_v4(data: &[u8]) -> Option<DhcpMessage> {
+    if data.len() < 240 || !matches!(data[0], 1 | 2) || data[236..240] != MAGIC_COOKIE {
+        return None;
+    }
+    let mut m = DhcpMessage {
+        version: 4,
+        transaction_id: read_u32(data, 4)?,
+        client_ip: ipv4(&data[12..16]),
+        assigned_ip: ipv4(&data[16..20]),
+        ..Default::default()
+    };
+    // Ethernet hardware address
+    if data[1] == 1 && data[2] == 6 {
+        m.client_mac = Some(mac(&data[28..34]));
+    }
+
+    let mut options = &data[240..];
+    while let Some(&code) = options.first() {
+        match code {
+            0 => {
+                options = &options[1..];
+                continue;
+            }
+            255 => break,
+            _ => {}
+        }
+        let Some(value) = options.get(1).and_then(|&len| options.get(2..2 + len as usize)) else {
+            break;
+        };
+        match code {
+            53 => m.message_type = value.first().copied().unwrap_or_default(),
+            3 => m.routers = value.chunks_exact(4).filter_map(ipv4).collect(),
+            6 => m.dns_servers = value.chunks_exact(4).filter_map(ipv4).collect(),
+            12 => m.hostname = Some(text(value)),
+            50 => m.requested_ip = ipv4(value),
+            51 => m.lease_time = read_u32(value, 0),
+            54 => m.server_id = ipv4(value),
+            60 => m.vendor_class = Some(text(value)),
+            61 => m.client_id = Some(hex(value)),
+            _ => {}
+        }
+        options = &options[2 + value.len()..];
+    }
+
+    // Plain BOOTP carries no message type.
+    (m.message_type != 0).then_some(m)
+}
+
+/// DHCPv6 options as (code, value), in order.
+fn options_v6(mut data: &[u8]) -> Vec<(u16, &[u8])> {
+    let mut options = Vec::new();
+    while let (Some(code), Some(len)) = (read_u16(data, 0), read_u16(data, 2)) {
+        let Some(value) = data.get(4..4 + len as usize) else {
+            break;
+        };
+        options.push((code, value));
+        data = &data[4 + len as usize..];
+    }
+    options
+}
+
+/// Link-layer address of a DUID-LLT (1) or DUID-LL (3) over Ethernet.
+fn duid_mac(duid: &[u8]) -> Option<String> {
+    let link = match read_u16(duid, 0)? {
+        1 => duid.get(8..)?,
+        3 => duid.get(4..)?,
+        _ => return None,
+    };
+    (read_u16(duid, 2)? == 1 && link.len() == 6).then(|| mac(link))
+}
+
+/// Decodes a DHCPv6 message, unwrapping relay messages.
+pub fn parse_v6(data: &[u8]) -> Option<DhcpMessage> {
+    parse_v6_at(data, 0)
+}
+
+fn parse_v6_at(data: &[u8], depth: u8) -> Option<DhcpMessage> {
+    let &kind = data.first()?;
+    if matches!(kind, V6_RELAY_FORW | V6_RELAY_REPL) {
+        // hop count, link address, peer address, then options
+        if depth >= MAX_RELAY_DEPTH {
+            return None;
+        }
+        let (_, inner) = options_v6(data.get(34..)?).into_iter().find(|(code, _)| *code == 9)?;
+        return parse_v6_at(inner, depth + 1);
+    }
+    if data.len() < 4 || !(1..=11).contains(&kind) {
+        return None;
+    }
+
+    let mut m = DhcpMessage {
+        version: 6,
+        message_type: kind,
+        transaction_id: u32::from_be_bytes([0, data[1], data[2], data[3]]),
+        ..Default::default()
+    };
+    let from_server = matches!(kind, V6_ADVERTISE | V6_REPLY | V6_RECONFIGURE);
+    for (code, value) in options_v6(&data[4..]) {
+        match code {
+            1 => {
+                m.client_id = Some(hex(value));
+                m.client_mac = duid_mac(value);
+            }
+            2 => m.server_id = Some(hex(value)),
+            // IA_NA: IAID, T1, T2, then IA address options
+            3 => {
+                let Some(inner) = value.get(12..) else {
+                    continue;
+                };
+                for (_, addr) in options_v6(inner).into_iter().filter(|(code, _)| *code == 5) {
+                    let ip = addr.get(..16).and_then(ipv6);
+                    if from_server {
+                        m.assigned_ip = ip;
+                        m.lease_time = read_u32(addr, 20);
+                    } else {
+                        m.requested_ip = ip;
+                    }
+                }
+            }
+            16 => {
+                // enterprise number, then length-prefixed strings
+                let first = read_u16(value, 4).and_then(|len| value.get(6..6 + len as usize));
+                m.vendor_class = first.map(text);
+            }
+            23 => m.dns_servers = value.chunks_exact(16).filter_map(ipv6).collect(),
+            39 => m.hostname = dns::read_name(value, 1).map(|(name, _)| name),
+            _ => {}
+        }
+    }
+    Some(m)
+}
+
+pub fn from_packet(packet: &Packet, payload: &[u8]) -> Option<DhcpMessage> {
+    let Some(TransportProtocol::Udp(u)) = &packet.transport else {
+        return None;
+    };
+    match (u.src_port, u.dst_port) {
+        (67 | 68, 67 | 68) => parse_v4(payload),
+        (546 | 547, 546 | 547) => parse_v6(payload),
+        _ => None,
+    }
+}
+
+pub fn message_type_name(version: u8, message_type: u8) -> &'static str {
+    match (version, message_type) {
+        (4, DISCOVER) => "DISCOVER",
+        (4, OFFER) => "OFFER",
+        (4, REQUEST) => "REQUEST",
+        (4, DECLINE) => "DECLINE",
+        (4, ACK) => "ACK",
+        (4, NAK) => "NAK",
+        (4, RELEASE) => "RELEASE",
+        (4, 8) => "INFORM",
+        (6, V6_SOLICIT) => "SOLICIT",
+        (6, V6_ADVERTISE) => "ADVERTISE",
+        (6, 3) => "REQUEST",
+        (6, 4) => "CONFIRM",
+        (6, 5) => "RENEW",
+        (6, 6) => "REBIND",
+        (6, V6_REPLY) => "REPLY",
+        (6, V6_RELEASE) => "RELEASE",
+        (6, V6_DECLINE) => "DECLINE",
+        (6, V6_RECONFIGURE) => "RECONFIGURE",
+        (6, 11) => "INFORMATION-REQUEST",
+        _ => "?",
+    }
+}
+
+/// Short description used in the live table next to the protocol label.
+pub fn summary(msg: &DhcpMessage) -> String {
+    let kind = message_type_name(msg.version, msg.message_type);
+    let client = msg.client_mac.as_deref().or(msg.hostname.as_deref()).unwrap_or_default();
+    match (&msg.assigned_ip, &msg.requested_ip) {
+        (Some(ip), _) => format!("{kind} {ip} → {client}"),
+        (None, Some(ip)) => format!("{kind} {client} {ip}"),
+        (None, None) => format!("{kind} {client}").trim_end().to_string(),
+    }
+}
+
+fn is_server_message(msg: &DhcpMessage) -> bool {
+    match msg.version {
+        4 => matches!(msg.message_type, OFFER | ACK | NAK),
+        _ => matches!(msg.message_type, V6_ADVERTISE | V6_REPLY | V6_RECONFIGURE),
+    }
+}
+
+/// Client MAC, or client identifier when the MAC is unknown.
+fn client_key(msg: &DhcpMessage) -> Option<String> {
+    msg.client_mac.clone().or_else(|| msg.client_id.clone())
+}
+
+impl DhcpLease {
+    fn active(&self, now: u128) -> bool {
+        !self.released && self.lease_time.is_none_or(|t| self.granted + t as u128 * 1000 > now)
+    }
+}
+
+/// Builds the tables of DHCP servers and leases, and flags rogue servers,
+/// starvation attempts and address conflicts.
+#[derive(Default)]
+pub struct DhcpMonitor {
+    /// Server identifiers or addresses given with `--dhcp-server`. When
+    /// empty, the first server of each IP version is trusted.
+    trusted: Vec<String>,
+    servers: HashMap<(u8, String), DhcpServer>,
+    leases: HashMap<String, DhcpLease>,
+    hostnames: HashMap<String, String>,
+    /// DISCOVER/SOLICIT as (time, client, whether the client address differs
+    /// from the frame source).
+    discovers: VecDeque<(u128, String, bool)>,
+    last_discover: HashMap<String, u128>,
+    starving: bool,
+}
+
+impl DhcpMonitor {
+    pub fn new(trusted: Vec<String>) -> Self {
+        DhcpMonitor {
+            trusted,
+            ..Self::default()
+        }
+    }
+
+    /// Servers sorted by IP version and address.
+    pub fn servers(&self) -> Vec<DhcpServer> {
+        let mut list: Vec<DhcpServer> = self.servers.values().cloned().collect();
+        list.sort_by_key(|s| (s.version, s.ip.parse::<IpAddr>().ok()));
+        list
+    }
+
+    /// Leases sorted by address.
+    pub fn leases(&self) -> Vec<DhcpLease> {
+        let mut list: Vec<DhcpLease> = self.leases.values().cloned().collect();
+        list.sort_by_key(|l| l.ip.parse::<IpAddr>().ok());
+        list
+    }
+
+    /// Checks `packet` (number `packet_id`).
+    pub fn observe(&mut self, packet: &Packet, packet_id: u64) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        let Some(msg) = &packet.dhcp else {
+            return alerts;
+        };
+        let now = packet.timestamp;
+        let alert = |rule_id: &str, severity: Severity, classtype: &str, message: String| Alert {
+            rule_id: rule_id.to_string(),
+            rule_rev: None,
+            severity,
+            message,
+            classtype: Some(classtype.to_string()),
+            timestamp: now,
+            packet_id: Some(packet_id),
+            protocol: Some(17),
+            src_ip: packet.ip.as_ref().map(|ip| ip.src_ip.clone()),
+            src_port: match &packet.transport {
+                Some(TransportProtocol::Udp(u)) => Some(u.src_port),
+                _ => None,
+            },
+            dst_ip: packet.ip.as_ref().map(|ip| ip.dst_ip.clone()),
+            dst_port: match &packet.transport {
+                Some(TransportProtocol::Udp(u)) => Some(u.dst_port),
+                _ => None,
+            },
+            related_packets: Vec::new(),
+        };
+        let client = client_key(msg);
+        if let (Some(client), Some(name)) = (&client, &msg.hostname) {
+            if self.hostnames.len() < MAX_CLIENTS || self.hostnames.contains_key(client) {
+                self.hostnames.insert(client.clone(), name.clone());
+            }
+        }
+        let kind = message_type_name(msg.version, msg.message_type);
+
+        if is_server_message(msg) {
+            let ip = packet.ip.as_ref().map(|ip| ip.src_ip.clone()).unwrap_or_default();
+            let id = msg.server_id.clone().unwrap_or_else(|| ip.clone());
+            let known = self.servers.contains_key(&(msg.version, id.clone()));
+            if !known {
+                let trusted = if self.trusted.is_empty() {
+                    !self.servers.values().any(|s| s.version == msg.version)
+                } else {
+                    self.trusted.iter().any(|t| *t == id || *t == ip)
+                };
+                if !trusted {
+                    let mut message = format!(
+                        "Serveur DHCP inattendu {ip}{} : {kind} pour {}",
+                        packet.eth.as_ref().map(|e| format!(" ({})", e.src_mac)).unwrap_or_default(),
+                        client.as_deref().unwrap_or("?")
+                    );
+                    if !msg.routers.is_empty() {
+                        message.push_str(&format!(", passerelle {}", msg.routers.join(", ")));
+                    }
+                    if !msg.dns_servers.is_empty() {
+                        message.push_str(&format!(", DNS {}", msg.dns_servers.join(", ")));
+                    }
+                    alerts.push(alert("dhcp-rogue-server", Severity::High, "bad-unknown", message));
+                }
+                if self.servers.len() >= MAX_SERVERS {
+                    let oldest = self
+                        .servers
+                        .iter()
+                        .filter(|(_, s)| !s.trusted)
+                        .min_by_key(|(_, s)| s.last_seen)
+                        .map(|(key, _)| key.clone());
+                    match oldest {
+                        Some(key) => {
+                            self.servers.remove(&key);
+                        }
+                        None => return alerts,
+                    }
+                }
+                self.servers.insert(
+                    (msg.version, id.clone()),
+                    DhcpServer {
+                        version: msg.version,
+                        id: id.clone(),
+                        ip,
+                        mac: packet.eth.as_ref().map(|e| e.src_mac.clone()),
+                        first_seen: now,
+                        trusted,
+                        ..Default::default()
+                    },
+                );
+            }
+            let server = self.servers.get_mut(&(msg.version, id.clone())).unwrap();
+            server.last_seen = now;
+            match (msg.version, msg.message_type) {
+                (4, OFFER) | (6, V6_ADVERTISE) => server.offers += 1,
+                (4, ACK) | (6, V6_REPLY) => server.acks += 1,
+                (4, NAK) => server.naks += 1,
+                _ => {}
+            }
+            if !msg.routers.is_empty() {
+                server.routers = msg.routers.clone();
+            }
+            if !msg.dns_servers.is_empty() {
+                server.dns_servers = msg.dns_servers.clone();
+            }
+
+            let granted = matches!((msg.version, msg.message_type), (4, ACK) | (6, V6_REPLY));
+            if let (true, Some(ip), Some(client)) = (granted, &msg.assigned_ip, &client) {
+                alerts.extend(self.grant(msg, ip, client, &id, now).map(|m| {
+                    alert("dhcp-lease-conflict", Severity::Medium, "bad-unknown", m)
+                }));
+            }
+            return alerts;
+        }
+
+        match (msg.version, msg.message_type) {
+            (4, DISCOVER) | (6, V6_SOLICIT) => {
+                if let Some(client) = client {
+                    // A DUID may name another interface; chaddr names the sender.
+                    let spoofed = msg.version == 4
+                        && packet.eth.as_ref().is_some_and(|e| msg.client_mac.as_ref().is_some_and(|m| *m != e.src_mac));
+                    alerts.extend(self.discover(client, spoofed, now).map(|m| {
+                        alert("dhcp-starvation", Severity::High, "attempted-dos", m)
+                    }));
+                }
+            }
+            (4, DECLINE) | (6, V6_DECLINE) => {
+                let message = format!(
+                    "{} refuse {} : adresse déjà utilisée sur le réseau (DECLINE)",
+                    client.as_deref().unwrap_or("?"),
+                    msg.requested_ip.as_deref().unwrap_or("?")
+                );
+                alerts.push(alert("dhcp-lease-conflict", Severity::Medium, "bad-unknown", message));
+            }
+            (4, RELEASE) | (6, V6_RELEASE) => {
+                let released = msg.client_ip.as_ref().or(msg.requested_ip.as_ref());
+                if let Some(lease) = released.and_then(|ip| self.leases.get_mut(ip)) {
+                    if Some(&lease.client) == client.as_ref() {
+                        lease.released = true;
+                    }
+                }
+            }
+            _ => {}
+        }
+        alerts
+    }
+
+    /// Records the lease of `ip` to `client`, and describes the conflict if
+    /// another client still holds it.
+    fn grant(&mut self, msg: &DhcpMessage, ip: &str, client: &str, server: &str, now: u128) -> Option<String> {
+        let conflict = self
+            .leases
+            .get(ip)
+            .filter(|l| l.client != client && l.active(now))
+            .map(|l| {
+                format!(
+                    "{ip} attribuée à {client} par {server} alors que le bail de {} (par {}, depuis {}) court encore",
+                    l.client,
+                    l.server,
+                    time_label(l.granted)
+                )
+            });
+        if self.leases.len() >= MAX_CLIENTS && !self.leases.contains_key(ip) {
+            self.leases.retain(|_, l| l.active(now));
+            if self.leases.len() >= MAX_CLIENTS {
+                return conflict;
+            }
+        }
+        self.leases.insert(
+            ip.to_string(),
+            DhcpLease {
+                version: msg.version,
+                ip: ip.to_string(),
+                client: client.to_string(),
+                hostname: msg.hostname.clone().or_else(|| self.hostnames.get(client).cloned()),
+                server: server.to_string(),
+                granted: now,
+                lease_time: msg.lease_time,
+                released: false,
+            },
+        );
+        conflict
+    }
+
+    /// Records a request for an address and describes the starvation attempt
+    /// the first time the distinct clients of the window reach the threshold.
+    fn discover(&mut self, client: String, spoofed: bool, now: u128) -> Option<String> {
+        if self.starving && self.discovers.back().is_some_and(|(at, _, _)| now.saturating_sub(*at) >= STARVATION_WINDOW_MS) {
+            self.starving = false;
+        }
+        self.last_discover.insert(client.clone(), now);
+        self.discovers.push_back((now, client, spoofed));
+        while let Some((at, _, _)) = self.discovers.front() {
+            if now.saturating_sub(*at) < STARVATION_WINDOW_MS {
+                break;
+            }
+            let (at, client, _) = self.discovers.pop_front().unwrap();
+            if self.last_discover.get(&client) == Some(&at) {
+                self.last_discover.remove(&client);
+            }
+        }
+
+        let clients = self.last_discover.len();
+        if self.starving || clients < STARVATION_CLIENTS {
+            return None;
+        }
+        self.starving = true;
+        let spoofed: HashSet<&String> = self.discovers.iter().filter(|d| d.2).map(|d| &d.1).collect();
+        let mut message = format!(
+            "Épuisement DHCP possible : {clients} clients différents en {} s",
+            STARVATION_WINDOW_MS / 1000
+        );
+        if !spoofed.is_empty() {
+            message.push_str(&format!(
+                " ({} adresses MAC différentes de l’expéditeur de la trame)",
+                spoofed.len()
+            ));
+        }
+        Some(message)
+    }
+}
+
+fn time_label(ms: u128) -> String {
+    Utc.timestamp_millis_opt(ms as i64)
+        .single()
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+pub fn print_report(servers: &[DhcpServer], leases: &[DhcpLease]) {
+    println!();
+    println!("══════════════════════════════════════════════════════════════════════");
+    println!(" WireFish - Serveurs DHCP ({})", servers.len());
+    println!("══════════════════════════════════════════════════════════════════════");
+    for s in servers {
+        println!();
+        println!(
+            "DHCPv{} {}{}{}",
+            s.version,
+            s.ip,
+            s.mac.as_ref().map(|m| format!("  [{m}]")).unwrap_or_default(),
+            if s.trusted { "" } else { "  ⚠️ inattendu" }
+        );
+        if s.id != s.ip {
+            println!("  Identifiant : {}", s.id);
+        }
+        println!(
+            "  Réponses    : {} offres, {} ACK, {} NAK ({} → {})",
+            s.offers,
+            s.acks,
+            s.naks,
+            time_label(s.first_seen),
+            time_label(s.last_seen)
+        );
+        if !s.routers.is_empty() {
+            println!("  Passerelle  : {}", s.routers.join(", "));
+        }
+        if !s.dns_servers.is_empty() {
+            println!("  DNS         : {}", s.dns_servers.join(", "));
+        }
+    }
+
+    println!();
+    println!(" Baux ({})", leases.len());
+    println!(
+        "{:<26} {:<18} {:<20} {:<20} {:<10} Nom",
+        "IP", "Client", "Serveur", "Attribué le", "Durée"
+    );
+    for l in leases {
+        let duration = match (l.released, l.lease_time) {
+            (true, _) => "libéré".to_string(),
+            (false, Some(t)) => format!("{t} s"),
+            (false, None) => "-".to_string(),
+        };
+        println!(
+            "{:<26} {:<18} {:<20} {:<20} {:<10} {}",
+            l.ip,
+            l.client,
+            l.server,
+            time_label(l.granted),
+            duration,
+            l.hostname.as_deref().unwrap_or("-")
+        );
+    }
+}
@@ -0,0 +1,657 @@
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use crate::core::models::{DnsMessage, DnsQuestion, DnsRecord, Edns, Packet, TransportProtocol};
+use crate::core::stream::{Direction, StreamEvent, StreamKey};
+
+pub const TYPE_A: u16 = 1;
+pub const TYPE_NS: u16 = 2;
+pub const TYPE_CNAME: u16 = 5;
+pub const TYPE_SOA: u16 = 6;
+pub const TYPE_PTR: u16 = 12;
+pub const TYPE_MX: u16 = 15;
+pub const TYPE_TXT: u16 = 16;
+pub const TYPE_AAAA: u16 = 28;
+pub const TYPE_SRV: u16 = 33;
+pub const TYPE_OPT: u16 = 41;
+pub const TYPE_SVCB: u16 = 64;
+pub const TYPE_HTTPS: u16 = 65;
+
+pub const RCODE_NXDOMAIN: u16 = 3;
+
+/// Pointer hops allowed while expanding a compressed name.
+const MAX_POINTER_HOPS: usize = 32;
+/// Queries kept waiting for their response before being counted as lost.
+const MAX_PENDING_QUERIES: usize = 10_000;
+const QUERY_TIMEOUT_MS: u128 = 30_000;
+/// TCP stream directions with a partial message kept at the same time.
+const MAX_TCP_STREAMS: usize = 4096;
+
+fn read_u16(data: &[u8], off: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*data.get(off)?, *data.get(off + 1)?]))
+}
+
+fn read_u32(data: &[u8], off: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(off..off + 4)?.try_into().ok()?))
+}
+
+/// Reads a possibly compressed domain name starting at `off` within the
+/// whole message. Returns the dotted name and the offset right after it.
+pub fn read_name(msg: &[u8], mut off: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut end = None;
+    let mut hops = 0;
+
+    loop {
+        let len = *msg.get(off)? as usize;
+        match len & 0xc0 {
+            0x00 => {
+                if len == 0 {
+                    off += 1;
+                    break;
+                }
+                let label = msg.get(off + 1..off + 1 + len)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                off += 1 + len;
+            }
+            0xc0 => {
+                hops += 1;
+                if hops > MAX_POINTER_HOPS {
+                    return None;
+                }
+                let ptr = ((len & 0x3f) << 8) | *msg.get(off + 1)? as usize;
+                if end.is_none() {
+                    end = Some(off + 2);
+                }
+                off = ptr;
+            }
+            _ => return None,
+        }
+    }
+
+    let name = if labels.is_empty() { ".".to_string() } else { labels.join(".") };
+    Some((name, end.unwrap_or(off)))
+}
+
+fn character_strings(mut data: &[u8]) -> Vec<String> {
+    let mut out = Vec::new();
+    while let Some((&len, rest)) = data.split_first() {
+        let len = (len as usize).min(rest.len());
+        out.push(String::from_utf8_lossy(&rest[..len]).into_owned());
+        data = &rest[len..];
+    }
+    out
+}
+
+fn svc_params(msg: &[u8], start: usize, end: usize) -> Option<String> {
+    let priority = read_u16(msg, start)?;
+    let (target, mut off) = read_name(msg, start + 2)?;
+    let mut parts = vec![priority.to_string(), target];
+
+    while off + 4 <= end {
+        let key = read_u16(msg, off)?;
+        let len = read_u16(msg, off + 2)? as usize;
+        let value = msg.get(off + 4..off + 4 + len)?;
+        off += 4 + len;
+
+        let rendered = match key {
+            1 => format!("alpn={}", character_strings(value).join(",")),
+            2 => "no-default-alpn".to_string(),
+            3 if value.len() == 2 => format!("port={}", u16::from_be_bytes([value[0], value[1]])),
+            4 => format!(
+                "ipv4hint={}",
+                value
+                    .chunks_exact(4)
+                    .map(|c| Ipv4Addr::new(c[0], c[1], c[2], c[3]).to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            5 => format!("ech=<{} octets>", value.len()),
+            6 => format!(
+                "ipv6hint={}",
+                value
+                    .chunks_exact(16)
+                    .map(|c| Ipv6Addr::from(<[u8; 16]>::try_from(c).unwrap()).to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            _ => format!("key{key}=<{} octets>", value.len()),
+        };
+        parts.push(rendered);
+    }
+
+    Some(parts.join(" "))
+}
+
+/// Human readable RDATA. Unknown types are rendered as hex.
+fn render_rdata(msg: &[u8], rtype: u16, start: usize, len: usize) -> Option<String> {
+    let rdata = msg.get(start..start + len)?;
+    let text = match rtype {
+        TYPE_A if len == 4 => Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3]).to_string(),
+        TYPE_AAAA if len == 16 => Ipv6Addr::from(<[u8; 16]>::try_from(rdata).unwrap()).to_string(),
+        TYPE_NS | TYPE_CNAME | TYPE_PTR => read_name(msg, start)?.0,
+        TYPE_MX => format!("{} {}", read_u16(msg, start)?, read_name(msg, start + 2)?.0),
+        TYPE_TXT => character_strings(rdata)
+            .iter()
+            .map(|s| format!("\"{s}\""))
+            .collect::<Vec<_>>()
+            .join(" "),
+        TYPE_SRV => format!(
+            "{} {} {} {}",
+            read_u16(msg, start)?,
+            read_u16(msg, start + 2)?,
+            read_u16(msg, start + 4)?,
+            read_name(msg, start + 6)?.0
+        ),
+        TYPE_SOA => {
+            let (mname, off) = read_name(msg, start)?;
+            let (rname, off) = read_name(msg, off)?;
+            format!(
+                "{mname} {rname} {} {} {} {} {}",
+                read_u32(msg, off)?,
+                read_u32(msg, off + 4)?,
+                read_u32(msg, off + 8)?,
+                read_u32(msg, off + 12)?,
+                read_u32(msg, off + 16)?
+            )
+        }
+        TYPE_SVCB | TYPE_HTTPS => svc_params(msg, start, start + len)?,
+        _ => rdata.iter().map(|b| format!("{b:02x}")).collect(),
+    };
+    Some(text)
+}
+
+/// Reads one resource record; returns it with the RDATA bounds and the
+/// offset of the next record.
+fn read_record(msg: &[u8], off: usize) -> Option<(DnsRecord, &[u8], usize)> {
+    let (name, off) = read_name(msg, off)?;
+    let rtype = read_u16(msg, off)?;
+    let class = read_u16(msg, off + 2)?;
+    let ttl = read_u32(msg, off + 4)?;
+    let rdlen = read_u16(msg, off + 8)? as usize;
+    let start = off + 10;
+    let rdata = msg.get(start..start + rdlen)?;
+
+    let data = if rtype == TYPE_OPT {
+        String::new()
+    } else {
+        render_rdata(msg, rtype, start, rdlen).unwrap_or_default()
+    };
+
+    Some((
+        DnsRecord {
+            name,
+            rtype,
+            class,
+            ttl,
+            data,
+        },
+        rdata,
+        start + rdlen,
+    ))
+}
+
+/// OPT pseudo-record: the class holds the UDP payload size and the TTL
+/// the extended RCODE, version and DO bit.
+fn parse_edns(rec: &DnsRecord, rdata: &[u8]) -> Edns {
+    let mut options = Vec::new();
+    let mut off = 0;
+    while off + 4 <= rdata.len() {
+        let code = u16::from_be_bytes([rdata[off], rdata[off + 1]]);
+        let len = u16::from_be_bytes([rdata[off + 2], rdata[off + 3]]) as usize;
+        options.push(code);
+        off += 4 + len;
+    }
+
+    Edns {
+        udp_payload_size: rec.class,
+        version: ((rec.ttl >> 16) & 0xff) as u8,
+        dnssec_ok: rec.ttl & 0x8000 != 0,
+        options,
+    }
+}
+
+/// Decodes a DNS message (UDP payload or one TCP length-prefixed frame).
+pub fn parse_message(msg: &[u8]) -> Option<DnsMessage> {
+    if msg.len() < 12 {
+        return None;
+    }
+
+    let flags = read_u16(msg, 2)?;
+    let counts = [
+        read_u16(msg, 4)? as usize,
+        read_u16(msg, 6)? as usize,
+        read_u16(msg, 8)? as usize,
+        read_u16(msg, 10)? as usize,
+    ];
+
+    let mut m = DnsMessage {
+        id: read_u16(msg, 0)?,
+        is_response: flags & 0x8000 != 0,
+        opcode: ((flags >> 11) & 0x0f) as u8,
+        authoritative: flags & 0x0400 != 0,
+        truncated: flags & 0x0200 != 0,
+        recursion_desired: flags & 0x0100 != 0,
+        recursion_available: flags & 0x0080 != 0,
+        authentic_data: flags & 0x0020 != 0,
+        checking_disabled: flags & 0x0010 != 0,
+        rcode: flags & 0x000f,
+        ..Default::default()
+    };
+
+    // Bound the question count: it rejects most non-DNS traffic that
+    // happens to use the port.
+    if counts[0] > 32 {
+        return None;
+    }
+
+    let mut off = 12;
+    for _ in 0..counts[0] {
+        let (name, next) = read_name(msg, off)?;
+        m.questions.push(DnsQuestion {
+            name,
+            qtype: read_u16(msg, next)?,
+            qclass: read_u16(msg, next + 2)?,
+        });
+        off = next + 4;
+    }
+
+    // A truncated or malformed record ends the decoding but keeps what
+    // was already read.
+    'sections: for (section, &count) in counts[1..].iter().enumerate() {
+        for _ in 0..count {
+            let Some((rec, rdata, next)) = read_record(msg, off) else {
+                break 'sections;
+            };
+            off = next;
+
+            if rec.rtype == TYPE_OPT {
+                m.rcode |= ((rec.ttl >> 24) as u16) << 4;
+                m.edns = Some(parse_edns(&rec, rdata));
+                continue;
+            }
+            match section {
+                0 => m.answers.push(rec),
+                1 => m.authority.push(rec),
+                _ => m.additional.push(rec),
+            }
+        }
+    }
+
+    Some(m)
+}
+
+/// DNS over TCP: every message is preceded by a 16-bit length. Decodes the
+/// complete messages at the start of `buf` and removes them, leaving the
+/// start of a message split across segments for the next call.
+pub fn parse_tcp(buf: &mut Vec<u8>) -> Vec<DnsMessage> {
+    let mut messages = Vec::new();
+    let mut off = 0;
+    while let Some(len) = read_u16(buf, off) {
+        let Some(msg) = buf.get(off + 2..off + 2 + len as usize) else {
+            break;
+        };
+        messages.extend(parse_message(msg));
+        off += 2 + len as usize;
+    }
+    buf.drain(..off);
+    messages
+}
+
+/// Ports sharing the DNS wire format: DNS, mDNS and LLMNR.
+pub const DNS_PORTS: [u16; 3] = [53, 5353, 5355];
+/// Multicast name resolution: queries are not answered by one server.
+const MULTICAST_PORTS: [u16; 2] = [5353, 5355];
+
+/// Decodes the DNS message of a UDP datagram on a DNS-like port, if any.
+/// DNS over TCP is decoded from the reassembled stream by `DnsTracker`.
+pub fn from_packet(packet: &Packet, payload: &[u8]) -> Option<DnsMessage> {
+    match &packet.transport {
+        Some(TransportProtocol::Udp(u))
+            if DNS_PORTS.contains(&u.src_port) || DNS_PORTS.contains(&u.dst_port) =>
+        {
+            parse_message(payload)
+        }
+        _ => None,
+    }
+}
+
+pub fn type_name(rtype: u16) -> String {
+    let name = match rtype {
+        TYPE_A => "A",
+        TYPE_NS => "NS",
+        TYPE_CNAME => "CNAME",
+        TYPE_SOA => "SOA",
+        10 => "NULL",
+        TYPE_PTR => "PTR",
+        13 => "HINFO",
+        TYPE_MX => "MX",
+        TYPE_TXT => "TXT",
+        TYPE_AAAA => "AAAA",
+        TYPE_SRV => "SRV",
+        35 => "NAPTR",
+        TYPE_OPT => "OPT",
+        43 => "DS",
+        46 => "RRSIG",
+        47 => "NSEC",
+        48 => "DNSKEY",
+        TYPE_SVCB => "SVCB",
+        TYPE_HTTPS => "HTTPS",
+        252 => "AXFR",
+        255 => "ANY",
+        257 => "CAA",
+        _ => return format!("TYPE{rtype}"),
+    };
+    name.to_string()
+}
+
+pub fn rcode_name(rcode: u16) -> String {
+    let name = match rcode {
+        0 => "NOERROR",
+        1 => "FORMERR",
+        2 => "SERVFAIL",
+        RCODE_NXDOMAIN => "NXDOMAIN",
+        4 => "NOTIMP",
+        5 => "REFUSED",
+        6 => "YXDOMAIN",
+        7 => "YXRRSET",
+        8 => "NXRRSET",
+        9 => "NOTAUTH",
+        10 => "NOTZONE",
+        16 => "BADVERS",
+        _ => return format!("RCODE{rcode}"),
+    };
+    name.to_string()
+}
+
+/// Short description used in the live table next to the protocol label.
+pub fn summary(msg: &DnsMessage) -> String {
+    let q = msg.questions.first();
+    let qtype = q.map(|q| type_name(q.qtype)).unwrap_or_default();
+    let qname = q.map(|q| q.name.as_str()).unwrap_or("");
+
+    if !msg.is_response {
+        return format!("Q {qtype} {qname}");
+    }
+    if msg.rcode != 0 {
+        return format!("R {} {qname}", rcode_name(msg.rcode));
+    }
+    match msg.answers.iter().find(|a| a.rtype != TYPE_CNAME).or(msg.answers.first()) {
+        Some(a) => format!("R {} {}", type_name(a.rtype), a.data),
+        None => format!("R {qtype} {qname} (vide)"),
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DnsStats {
+    pub queries: u64,
+    pub responses: u64,
+    pub matched: u64,
+    pub nxdomain: u64,
+    pub total_latency_ms: u64,
+}
+
+impl DnsStats {
+    pub fn nxdomain_rate(&self) -> f64 {
+        if self.responses == 0 {
+            return 0.0;
+        }
+        self.nxdomain as f64 / self.responses as f64
+    }
+
+    pub fn avg_latency_ms(&self) -> Option<f64> {
+        if self.matched == 0 {
+            return None;
+        }
+        Some(self.total_latency_ms as f64 / self.matched as f64)
+    }
+}
+
+type QueryKey = (u16, String, String);
+
+/// Port of an `ip:port` endpoint.
+fn endpoint_port(endpoint: &str) -> Option<u16> {
+    endpoint.rsplit_once(':')?.1.parse().ok()
+}
+
+/// Decodes DNS over TCP from the reassembled streams, then pairs responses
+/// with their query (same ID, reversed endpoints) to fill
+/// `DnsMessage::latency_ms` and keep global DNS statistics.
+#[derive(Default)]
+pub struct DnsTracker {
+    pending: HashMap<QueryKey, u128>,
+    /// Bytes of a TCP message not complete yet, per stream direction.
+    tcp: HashMap<(StreamKey, Direction), Vec<u8>>,
+    stats: DnsStats,
+}
+
+impl DnsTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stats(&self) -> &DnsStats {
+        &self.stats
+    }
+
+    /// Queries still waiting for an answer.
+    pub fn unanswered(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Attaches the DNS messages completed by this packet's stream data,
+    /// then matches every message of the packet.
+    pub fn observe(&mut self, packet: &mut Packet, events: &[StreamEvent]) {
+        for event in events {
+            match event {
+                StreamEvent::Data { key, direction, data } => {
+                    let dns = [&key.client, &key.server].iter().any(|e| endpoint_port(e) == Some(53));
+                    if !dns {
+                        continue;
+                    }
+                    let slot = (key.clone(), *direction);
+                    if !self.tcp.contains_key(&slot) && self.tcp.len() >= MAX_TCP_STREAMS {
+                        self.tcp.clear();
+                    }
+                    let buf = self.tcp.entry(slot).or_default();
+                    buf.extend_from_slice(data);
+                    packet.dns.extend(parse_tcp(buf));
+                }
+                // Lost bytes break the length framing.
+                StreamEvent::Gap { key, direction, .. } => {
+                    self.tcp.remove(&(key.clone(), *direction));
+                }
+                StreamEvent::Closed { key, .. } => {
+                    self.tcp.remove(&(key.clone(), Direction::ToServer));
+                    self.tcp.remove(&(key.clone(), Direction::ToClient));
+                }
+            }
+        }
+
+        let Some(ip) = &packet.ip else {
+            return;
+        };
+        let (sport, dport) = match &packet.transport {
+            Some(TransportProtocol::Udp(u)) => (u.src_port, u.dst_port),
+            Some(TransportProtocol::Tcp(t)) => (t.src_port, t.dst_port),
+            _ => return,
+        };
+        let multicast = MULTICAST_PORTS.contains(&sport) || MULTICAST_PORTS.contains(&dport);
+        let src = format!("{}:{sport}", ip.src_ip);
+        let dst = format!("{}:{dport}", ip.dst_ip);
+        let now = packet.timestamp;
+
+        for msg in &mut packet.dns {
+            if !msg.is_response {
+                self.stats.queries += 1;
+                if multicast {
+                    continue;
+                }
+                if self.pending.len() >= MAX_PENDING_QUERIES {
+                    self.pending
+                        .retain(|_, ts| now.saturating_sub(*ts) < QUERY_TIMEOUT_MS);
+                }
+                self.pending.entry((msg.id, src.clone(), dst.clone())).or_insert(now);
+                continue;
+            }
+
+            self.stats.responses += 1;
+            if msg.rcode == RCODE_NXDOMAIN {
+                self.stats.nxdomain += 1;
+            }
+            if let Some(sent) = self.pending.remove(&(msg.id, dst.clone(), src.clone())) {
+                let latency = now.saturating_sub(sent) as u64;
+                msg.latency_ms = Some(latency);
+                self.stats.matched += 1;
+                self.stats.total_latency_ms += latency;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::parser;
+
+    /// Query for www.example.com A, ID 0x1234, recursion desired.
+    const QUERY: &[u8] = &[
+        0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+        3, b'w', b'w', b'w', 7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 3, b'c', b'o', b'm', 0, //
+        0x00, 0x01, 0x00, 0x01,
+    ];
+
+    /// Its answer, 93.184.216.34 with a TTL of one hour, the owner name
+    /// compressed as a pointer to the question.
+    const RESPONSE: &[u8] = &[
+        0x12, 0x34, 0x81, 0x80, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, //
+        3, b'w', b'w', b'w', 7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 3, b'c', b'o', b'm', 0, //
+        0x00, 0x01, 0x00, 0x01, //
+        0xc0, 0x0c, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x0e, 0x10, 0x00, 0x04, 93, 184, 216, 34,
+    ];
+
+    fn framed(msgs: &[&[u8]]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for msg in msgs {
+            out.extend_from_slice(&(msg.len() as u16).to_be_bytes());
+            out.extend_from_slice(msg);
+        }
+        out
+    }
+
+    /// Ethernet + IPv4 + UDP (17) or TCP (6) frame.
+    fn frame(proto: u8, src: [u8; 4], sport: u16, dst: [u8; 4], dport: u16, payload: &[u8]) -> Vec<u8> {
+        let l4_len = if proto == 6 { 20 } else { 8 };
+        let mut f = vec![0u8; 12];
+        f.extend_from_slice(&[0x08, 0x00, 0x45, 0]);
+        f.extend_from_slice(&((20 + l4_len + payload.len()) as u16).to_be_bytes());
+        f.extend_from_slice(&[0, 0, 0, 0, 64, proto, 0, 0]);
+        f.extend_from_slice(&src);
+        f.extend_from_slice(&dst);
+        f.extend_from_slice(&sport.to_be_bytes());
+        f.extend_from_slice(&dport.to_be_bytes());
+        if proto == 6 {
+            f.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 1, 0x50, 0x18, 0xff, 0xff, 0, 0, 0, 0]);
+        } else {
+            f.extend_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+            f.extend_from_slice(&[0, 0]);
+        }
+        f.extend_from_slice(payload);
+        f
+    }
+
+    fn data(client: &str, server: &str, direction: Direction, bytes: &[u8]) -> StreamEvent {
+        StreamEvent::Data {
+            key: StreamKey {
+                client: client.to_string(),
+                server: server.to_string(),
+            },
+            direction,
+            data: bytes.to_vec(),
+        }
+    }
+
+    #[test]
+    fn decodes_query_and_compressed_answer() {
+        let q = parse_message(QUERY).unwrap();
+        assert!(!q.is_response && q.recursion_desired);
+        assert_eq!(q.questions[0].name, "www.example.com");
+        assert_eq!(q.questions[0].qtype, TYPE_A);
+
+        let r = parse_message(RESPONSE).unwrap();
+        assert!(r.is_response && r.recursion_available);
+        assert_eq!(r.id, 0x1234);
+        assert_eq!(r.answers.len(), 1);
+        assert_eq!(r.answers[0].name, "www.example.com");
+        assert_eq!(r.answers[0].ttl, 3600);
+        assert_eq!(r.answers[0].data, "93.184.216.34");
+        assert_eq!(summary(&r), "R A 93.184.216.34");
+    }
+
+    #[test]
+    fn rejects_pointer_loops_and_short_messages() {
+        let mut looped = QUERY[..12].to_vec();
+        looped.extend_from_slice(&[0xc0, 0x0c]);
+        assert_eq!(read_name(&looped, 12), None);
+        assert!(parse_message(&QUERY[..11]).is_none());
+    }
+
+    #[test]
+    fn tcp_decodes_pipelined_and_split_messages() {
+        let stream = framed(&[QUERY, QUERY, RESPONSE]);
+        let split = stream.len() - 10;
+
+        let mut buf = stream[..split].to_vec();
+        assert_eq!(parse_tcp(&mut buf).len(), 2);
+        assert_eq!(buf.len(), 2 + RESPONSE.len() - 10);
+
+        buf.extend_from_slice(&stream[split..]);
+        let rest = parse_tcp(&mut buf);
+        assert_eq!(rest.len(), 1);
+        assert!(rest[0].is_response);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn tracker_matches_tcp_messages_from_stream_data() {
+        let (client, server) = ("10.0.0.1:40000", "10.0.0.2:53");
+        let segment = |to_server: bool, bytes: &[u8], ts: u128| {
+            let f = if to_server {
+                frame(6, [10, 0, 0, 1], 40000, [10, 0, 0, 2], 53, bytes)
+            } else {
+                frame(6, [10, 0, 0, 2], 53, [10, 0, 0, 1], 40000, bytes)
+            };
+            parser::parse_packet_at(&f, ts).unwrap()
+        };
+        let mut tracker = DnsTracker::new();
+        let query = framed(&[QUERY]);
+
+        let mut p = segment(true, &query[..5], 1000);
+        tracker.observe(&mut p, &[data(client, server, Direction::ToServer, &query[..5])]);
+        assert!(p.dns.is_empty());
+
+        let mut p = segment(true, &query[5..], 1010);
+        tracker.observe(&mut p, &[data(client, server, Direction::ToServer, &query[5..])]);
+        assert_eq!(p.dns.len(), 1);
+        assert_eq!(tracker.unanswered(), 1);
+
+        let response = framed(&[RESPONSE]);
+        let mut p = segment(false, &response, 1050);
+        tracker.observe(&mut p, &[data(client, server, Direction::ToClient, &response)]);
+        assert_eq!(p.dns[0].latency_ms, Some(40));
+        assert_eq!(tracker.stats().matched, 1);
+        assert_eq!(tracker.unanswered(), 0);
+    }
+
+    #[test]
+    fn multicast_queries_are_not_left_unanswered() {
+        let mut tracker = DnsTracker::new();
+        for (dst, port) in [([224, 0, 0, 251], 5353), ([224, 0, 0, 252], 5355), ([10, 0, 0, 2], 53)] {
+            let mut p = parser::parse_packet_at(&frame(17, [10, 0, 0, 1], port, dst, port, QUERY), 0).unwrap();
+            assert_eq!(p.dns.len(), 1);
+            tracker.observe(&mut p, &[]);
+        }
+        assert_eq!(tracker.stats().queries, 3);
+        assert_eq!(tracker.unanswered(), 1);
+    }
+}
@@ -0,0 +1,301 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::core::alerts;
+use crate::core::models::{Alert, DnsMessage, Packet, Severity};
+
+const WINDOW_MS: u128 = 120_000;
+const SWEEP_INTERVAL_MS: u128 = 10_000;
+const RCODE_NXDOMAIN: u16 = 3;
+const TYPE_NULL: u16 = 10;
+const TYPE_TXT: u16 = 16;
+
+/// Unique subdomains of one parent within the window that make a tunnel,
+/// provided enough of them look encoded.
+const TUNNEL_UNIQUE: usize = 20;
+const TUNNEL_SUSPICIOUS: usize = 10;
+/// TXT/NULL queries to one parent within the window that make a tunnel.
+const TUNNEL_TXT_NULL: usize = 20;
+/// Distinct algorithmic-looking domains one client may look up in the window.
+const DGA_DOMAINS: usize = 10;
+const MAX_UNIQUE: usize = 10_000;
+
+/// Second-level labels under which registrations happen one level deeper
+/// (`example.co.uk`).
+const SECOND_LEVELS: &[&str] = &["co", "com", "net", "org", "gov", "edu", "ac", "ne", "or", "go"];
+
+/// Frequent English bigrams: readable labels are mostly made of them,
+/// random ones are not.
+const COMMON_BIGRAMS: &[&str] = &[
+    "th", "he", "in", "er", "an", "re", "on", "at", "en", "nd", "ti", "es", "or", "te", "of", "ed", "is", "it",
+    "al", "ar", "st", "to", "nt", "ng", "se", "ha", "as", "ou", "io", "le", "ve", "co", "me", "de", "hi", "ri",
+    "ro", "ic", "ne", "ea", "ra", "ce", "li", "ch", "ll", "be", "ma", "si", "om", "ur", "ge", "ta", "la", "ca",
+    "el", "pe", "po", "ol", "il", "ec", "ac", "ct", "us", "et", "ho", "ow", "am", "ai", "na", "wo", "oo", "ee",
+    "ss", "ot", "no", "mo", "tr", "wa", "ad", "lo", "ie", "ap", "pl", "sh", "id", "im", "ig", "go", "ag",
+    "un", "em", "ut", "ck", "ok", "oc", "bo", "fa", "fi", "fo", "gr", "gl", "ke", "ki", "mi", "mp", "nc", "ni",
+    "op", "os", "pa", "pr", "qu", "rs", "sa", "sc", "sp", "su", "tu", "ub", "ue", "ul", "up", "vi",
+    "we", "wi", "yo", "ab", "ak", "az", "ba", "bl", "br", "cl", "cr", "da", "di", "do", "ev", "ex", "ff", "ga",
+    "gi", "gu", "ht", "ia", "if", "ir", "iv", "lu", "ly", "mb", "mu", "nk", "oa", "od", "og", "oi", "ph", "ps",
+    "rt", "ru", "sk", "sm", "sn", "sw", "tw", "ua", "ud", "ug", "vo", "xt", "ze",
+];
+
+/// Shannon entropy of the characters of `s`, in bits per character.
+fn entropy(s: &str) -> f64 {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    let mut total = 0usize;
+    for c in s.chars() {
+        *counts.entry(c).or_default() += 1;
+        total += 1;
+    }
+    if total == 0 {
+        return 0.0;
+    }
+    counts
+        .values()
+        .map(|&n| {
+            let p = n as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Splits `name` into the subdomain part and the registered parent domain.
+fn split_parent(name: &str) -> (&str, &str) {
+    let name = name.trim_end_matches('.');
+    let labels: Vec<&str> = name.split('.').collect();
+    let mut keep = 2;
+    if labels.len() >= 3 && labels[labels.len() - 1].len() == 2 && SECOND_LEVELS.contains(&labels[labels.len() - 2]) {
+        keep = 3;
+    }
+    if labels.len() <= keep {
+        return ("", name);
+    }
+    let parent_len: usize = labels[labels.len() - keep..].iter().map(|l| l.len() + 1).sum::<usize>() - 1;
+    let split = name.len() - parent_len;
+    (&name[..split - 1], &name[split..])
+}
+
+/// How readable a label is: the share of its letter bigrams that are
+/// common in English.
+fn bigram_score(label: &str) -> f64 {
+    let chars: Vec<char> = label.chars().collect();
+    let mut total = 0usize;
+    let mut common = 0usize;
+    for pair in chars.windows(2) {
+        if !pair[0].is_ascii_alphabetic() || !pair[1].is_ascii_alphabetic() {
+            continue;
+        }
+        total += 1;
+        let bigram: String = pair.iter().collect();
+        if COMMON_BIGRAMS.contains(&bigram.as_str()) {
+            common += 1;
+        }
+    }
+    if total == 0 {
+        return 0.0;
+    }
+    common as f64 / total as f64
+}
+
+/// Whether the registered label of a domain looks algorithmically
+/// generated: long, high character entropy, few common bigrams.
+pub fn looks_generated(parent: &str) -> bool {
+    let label = parent.split('.').next().unwrap_or("").to_ascii_lowercase();
+    if label.len() < 8 || label.starts_with("xn--") {
+        return false;
+    }
+    let digits = label.chars().filter(|c| c.is_ascii_digit()).count();
+    let letters = label.chars().filter(|c| c.is_ascii_alphabetic()).count();
+    if letters == 0 {
+        return false;
+    }
+    let h = entropy(&label);
+    let readable = bigram_score(&label);
+    let digit_ratio = digits as f64 / label.len() as f64;
+    (h >= 2.8 && readable < 0.35) || (label.len() >= 12 && h >= 3.3 && digit_ratio >= 0.25)
+}
+
+/// Encoding-like traits of the subdomain part of a query: very long labels,
+/// high entropy.
+fn suspicious_subdomain(subdomain: &str) -> bool {
+    let longest = subdomain.split('.').map(str::len).max().unwrap_or(0);
+    let compact: String = subdomain.chars().filter(|&c| c != '.').collect();
+    longest >= 40 || (compact.len() >= 24 && entropy(&compact) >= 3.8)
+}
+
+/// Queries seen for one (client, parent domain).
+#[derive(Default)]
+struct ParentStats {
+    started: u128,
+    last_seen: u128,
+    subdomains: HashSet<String>,
+    queries: usize,
+    suspicious: usize,
+    txt_null: usize,
+    longest_label: usize,
+    entropy_sum: f64,
+    alerted: bool,
+}
+
+/// Algorithmic-looking domains looked up by one client.
+#[derive(Default)]
+struct ClientStats {
+    started: u128,
+    last_seen: u128,
+    domains: HashSet<String>,
+    nxdomain: usize,
+    alerted: bool,
+}
+
+impl ParentStats {
+    /// Starts a new window once the current one is over, forgetting the
+    /// alert only when the queries had stopped for a whole window.
+    fn roll(&mut self, now: u128) {
+        let alerted = self.alerted && now.saturating_sub(self.last_seen) < WINDOW_MS;
+        if now.saturating_sub(self.started) >= WINDOW_MS {
+            *self = ParentStats { started: now, alerted, ..Default::default() };
+        }
+        self.last_seen = now;
+    }
+}
+
+impl ClientStats {
+    fn roll(&mut self, now: u128) {
+        let alerted = self.alerted && now.saturating_sub(self.last_seen) < WINDOW_MS;
+        if now.saturating_sub(self.started) >= WINDOW_MS {
+            *self = ClientStats { started: now, alerted, ..Default::default() };
+        }
+        self.last_seen = now;
+    }
+}
+
+/// Scores DNS queries for tunneling (long, high-entropy, many unique
+/// subdomains of one parent, TXT/NULL volume) and algorithmically generated
+/// domains.
+#[derive(Default)]
+pub struct DnsAnomalyDetector {
+    parents: HashMap<(String, String), ParentStats>,
+    clients: HashMap<String, ClientStats>,
+    last_sweep: u128,
+}
+
+impl DnsAnomalyDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checks the DNS messages of `packet` (number `packet_id`).
+    pub fn observe(&mut self, packet: &Packet, packet_id: u64) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        let Some(ip) = &packet.ip else {
+            return alerts;
+        };
+        let now = packet.timestamp;
+
+        for msg in &packet.dns {
+            if msg.is_response {
+                if msg.rcode == RCODE_NXDOMAIN && asks_generated(msg) {
+                    if let Some(stats) = self.clients.get_mut(&ip.dst_ip) {
+                        stats.nxdomain += 1;
+                    }
+                }
+            } else {
+                for q in &msg.questions {
+                    let name = q.name.trim_end_matches('.').to_ascii_lowercase();
+                    if ignored(&name) {
+                        continue;
+                    }
+                    let (subdomain, parent) = split_parent(&name);
+                    alerts.extend(self.tunnel_query(packet, packet_id, &ip.src_ip, subdomain, parent, q.qtype));
+                    if looks_generated(parent) {
+                        let stats = self.clients.entry(ip.src_ip.clone()).or_default();
+                        stats.roll(now);
+                        if stats.domains.len() < MAX_UNIQUE {
+                            stats.domains.insert(parent.to_string());
+                        }
+                        if !stats.alerted && stats.domains.len() >= DGA_DOMAINS {
+                            stats.alerted = true;
+                            let mut examples: Vec<&str> = stats.domains.iter().map(String::as_str).collect();
+                            examples.sort_unstable();
+                            examples.truncate(3);
+                            let message = format!(
+                                "Domaines générés (DGA) : {} domaines aléatoires en {} s, {} NXDOMAIN (ex. {})",
+                                stats.domains.len(),
+                                WINDOW_MS / 1000,
+                                stats.nxdomain,
+                                examples.join(", ")
+                            );
+                            let mut alert = alerts::packet_alert(packet, packet_id, "dns-dga", Severity::Medium, message);
+                            alert.classtype = Some("trojan-activity".to_string());
+                            alerts.push(alert);
+                        }
+                    }
+                }
+            }
+        }
+
+        if now >= self.last_sweep + SWEEP_INTERVAL_MS {
+            self.last_sweep = now;
+            self.parents.retain(|_, s| now.saturating_sub(s.last_seen) < WINDOW_MS);
+            self.clients.retain(|_, s| now.saturating_sub(s.last_seen) < WINDOW_MS);
+        }
+        alerts
+    }
+
+    fn tunnel_query(
+        &mut self,
+        packet: &Packet,
+        packet_id: u64,
+        client: &str,
+        subdomain: &str,
+        parent: &str,
+        qtype: u16,
+    ) -> Option<Alert> {
+        let now = packet.timestamp;
+        let stats = self.parents.entry((client.to_string(), parent.to_string())).or_default();
+        stats.roll(now);
+        stats.queries += 1;
+        if qtype == TYPE_TXT || qtype == TYPE_NULL {
+            stats.txt_null += 1;
+        }
+        if !subdomain.is_empty() && stats.subdomains.len() < MAX_UNIQUE && stats.subdomains.insert(subdomain.to_string()) {
+            let compact: String = subdomain.chars().filter(|&c| c != '.').collect();
+            stats.entropy_sum += entropy(&compact);
+            stats.longest_label = stats.longest_label.max(subdomain.split('.').map(str::len).max().unwrap_or(0));
+            if suspicious_subdomain(subdomain) {
+                stats.suspicious += 1;
+            }
+        }
+
+        let unique = stats.subdomains.len();
+        let tunnel = (unique >= TUNNEL_UNIQUE && stats.suspicious >= TUNNEL_SUSPICIOUS)
+            || (stats.txt_null >= TUNNEL_TXT_NULL && unique >= TUNNEL_UNIQUE / 2);
+        if stats.alerted || !tunnel {
+            return None;
+        }
+        stats.alerted = true;
+        let message = format!(
+            "Tunnel DNS probable vers {parent} : {unique} sous-domaines uniques sur {} requêtes en {} s, label max {}, entropie moy. {:.1}, {} TXT/NULL",
+            stats.queries,
+            WINDOW_MS / 1000,
+            stats.longest_label,
+            stats.entropy_sum / unique.max(1) as f64,
+            stats.txt_null
+        );
+        let mut alert = alerts::packet_alert(packet, packet_id, "dns-tunnel", Severity::High, message);
+        alert.classtype = Some("bad-unknown".to_string());
+        Some(alert)
+    }
+}
+
+/// Local and reverse lookups are neither tunnels nor generated domains.
+fn ignored(name: &str) -> bool {
+    !name.contains('.') || name.ends_with(".arpa") || name.ends_with(".local") || name.ends_with(".lan")
+}
+
+/// Whether a question of `msg` is for a domain that looks generated.
+fn asks_generated(msg: &DnsMessage) -> bool {
+    msg.questions.iter().any(|q| {
+        let name = q.name.trim_end_matches('.').to_ascii_lowercase();
+        !ignored(&name) && looks_generated(split_parent(&name).1)
+    })
+}
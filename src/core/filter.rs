@@ -0,0 +1,278 @@
+use crate::core::classifier;
+use crate::core::dns;
+use crate::core::grpc;
+use crate::core::http;
+use crate::core::http2;
+use crate::core::models::{Packet, TransportProtocol};
+use crate::core::tcpanalysis;
+use crate::core::tls;
+
+/// Fields accepted on the left-hand side of a filter clause.
+pub const FIELDS: &[&str] = &[
+    "proto",
+    "ip",
+    "src",
+    "dst",
+    "port",
+    "sport",
+    "dport",
+    "tls.version",
+    "tls.sni",
+    "tls.alpn",
+    "tls.cipher",
+    "tls.group",
+    "tls.sigalg",
+    "tls.ja3",
+    "tls.ja3s",
+    "tls.ja4",
+    "tls.ja4s",
+    "tls.cert.subject",
+    "tls.cert.issuer",
+    "tls.cert.san",
+    "dns.id",
+    "dns.qname",
+    "dns.qtype",
+    "dns.rcode",
+    "dns.answer",
+    "http.method",
+    "http.uri",
+    "http.host",
+    "http.status",
+    "http.user_agent",
+    "http.content_type",
+    "http2.frame",
+    "http2.method",
+    "http2.path",
+    "http2.authority",
+    "http2.status",
+    "grpc.service",
+    "grpc.method",
+    "grpc.status",
+    "tcp.analysis",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Exists,
+    Eq,
+    Ne,
+    Contains,
+}
+
+#[derive(Debug, Clone)]
+struct Clause {
+    field: String,
+    op: Op,
+    value: String,
+}
+
+/// Display filter built from `--filter` expressions.
+///
+/// Syntax: `field`, `field==value`, `field!=value` or `field~value`
+/// (case-insensitive substring). Clauses can be chained with `&&`
+/// and every clause must match.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    clauses: Vec<Clause>,
+}
+
+impl Filter {
+    pub fn is_empty(&self) -> bool {
+        self.clauses.is_empty()
+    }
+
+    pub fn add(&mut self, expr: &str) -> Result<(), String> {
+        for part in expr.split("&&") {
+            let part = part.trim();
+            if part.is_empty() {
+                continue;
+            }
+            self.clauses.push(parse_clause(part)?);
+        }
+        Ok(())
+    }
+
+    pub fn matches(&self, packet: &Packet) -> bool {
+        self.clauses.iter().all(|c| {
+            let values = field_values(packet, &c.field);
+            match c.op {
+                Op::Exists => !values.is_empty(),
+                Op::Eq => values.iter().any(|v| v.eq_ignore_ascii_case(&c.value)),
+                Op::Ne => !values.iter().any(|v| v.eq_ignore_ascii_case(&c.value)),
+                Op::Contains => {
+                    let needle = c.value.to_ascii_lowercase();
+                    values
+                        .iter()
+                        .any(|v| v.to_ascii_lowercase().contains(&needle))
+                }
+            }
+        })
+    }
+}
+
+fn parse_clause(part: &str) -> Result<Clause, String> {
+    let (field, op, value) = if let Some((f, v)) = part.split_once("==") {
+        (f, Op::Eq, v)
+    } else if let Some((f, v)) = part.split_once("!=") {
+        (f, Op::Ne, v)
+    } else if let Some((f, v)) = part.split_once('~') {
+        (f, Op::Contains, v)
+    } else {
+        (part, Op::Exists, "")
+    };
+
+    let field = field.trim().to_ascii_lowercase();
+    if !FIELDS.contains(&field.as_str()) {
+        return Err(format!(
+            "Champ de filtre inconnu : {field} (champs : {})",
+            FIELDS.join(", ")
+        ));
+    }
+
+    let value = value.trim().trim_matches('"').to_string();
+    if op != Op::Exists && value.is_empty() {
+        return Err(format!("Valeur manquante dans le filtre : {part}"));
+    }
+
+    Ok(Clause { field, op, value })
+}
+
+fn field_values(packet: &Packet, field: &str) -> Vec<String> {
+    let ports = match &packet.transport {
+        Some(TransportProtocol::Tcp(t)) => Some((t.src_port, t.dst_port)),
+        Some(TransportProtocol::Udp(u)) => Some((u.src_port, u.dst_port)),
+        _ => None,
+    };
+    // `tls.*` fields match every packet of the connection, not only the hellos.
+    let info = packet.connection_tls.as_ref().or(packet.tls.as_ref());
+    let fp = info.map(|t| &t.fingerprints);
+    let certs = info.map(|t| t.certificates.as_slice()).unwrap_or_default();
+    let ch = info.and_then(|t| t.client_hello.as_ref());
+    let sh = info.and_then(|t| t.server_hello.as_ref());
+    let requests = packet.http.iter().filter_map(|t| t.request.as_ref());
+    let responses = packet.http.iter().filter_map(|t| t.response.as_ref());
+    let h2 = packet.http2.as_ref();
+
+    match field {
+        "proto" => vec![classifier::classify(packet).to_string()],
+        "ip" => packet
+            .ip
+            .iter()
+            .flat_map(|ip| [ip.src_ip.clone(), ip.dst_ip.clone()])
+            .collect(),
+        "src" => packet.ip.iter().map(|ip| ip.src_ip.clone()).collect(),
+        "dst" => packet.ip.iter().map(|ip| ip.dst_ip.clone()).collect(),
+        "port" => ports
+            .iter()
+            .flat_map(|(s, d)| [s.to_string(), d.to_string()])
+            .collect(),
+        "sport" => ports.iter().map(|(s, _)| s.to_string()).collect(),
+        "dport" => ports.iter().map(|(_, d)| d.to_string()).collect(),
+        "tls.version" => info
+            .iter()
+            .map(|t| tls::version_label(tls::effective_version(t)).to_string())
+            .collect(),
+        "tls.sni" => ch.and_then(|c| c.sni.clone()).into_iter().collect(),
+        "tls.alpn" => {
+            let mut v: Vec<String> = ch.map(|c| c.alpn.clone()).unwrap_or_default();
+            v.extend(sh.and_then(|s| s.alpn.clone()));
+            v
+        }
+        "tls.cipher" => {
+            let mut ids: Vec<u16> = ch.map(|c| c.cipher_suites.clone()).unwrap_or_default();
+            ids.extend(sh.map(|s| s.cipher_suite));
+            ids.into_iter().map(tls::cipher_suite_name).collect()
+        }
+        "tls.group" => {
+            let mut ids: Vec<u16> = ch
+                .map(|c| c.supported_groups.clone())
+                .unwrap_or_default();
+            ids.extend(sh.and_then(|s| s.key_share_group));
+            ids.into_iter().map(tls::group_name).collect()
+        }
+        "tls.sigalg" => ch
+            .map(|c| c.signature_algorithms.clone())
+            .unwrap_or_default()
+            .into_iter()
+            .map(tls::signature_algorithm_name)
+            .collect(),
+        "tls.ja3" => fp.and_then(|f| f.ja3_hash.clone()).into_iter().collect(),
+        "tls.ja3s" => fp.and_then(|f| f.ja3s_hash.clone()).into_iter().collect(),
+        "tls.ja4" => fp.and_then(|f| f.ja4.clone()).into_iter().collect(),
+        "tls.ja4s" => fp.and_then(|f| f.ja4s.clone()).into_iter().collect(),
+        "tls.cert.subject" => certs.iter().map(|c| c.subject.clone()).collect(),
+        "tls.cert.issuer" => certs.iter().map(|c| c.issuer.clone()).collect(),
+        "tls.cert.san" => certs
+            .iter()
+            .flat_map(|c| c.san_dns.iter().chain(c.san_ip.iter()).cloned())
+            .collect(),
+        "dns.id" => packet.dns.iter().map(|d| d.id.to_string()).collect(),
+        "dns.qname" => packet
+            .dns
+            .iter()
+            .flat_map(|d| d.questions.iter().map(|q| q.name.clone()))
+            .collect(),
+        "dns.qtype" => packet
+            .dns
+            .iter()
+            .flat_map(|d| d.questions.iter().map(|q| dns::type_name(q.qtype)))
+            .collect(),
+        "dns.rcode" => packet
+            .dns
+            .iter()
+            .filter(|d| d.is_response)
+            .map(|d| dns::rcode_name(d.rcode))
+            .collect(),
+        "dns.answer" => packet
+            .dns
+            .iter()
+            .flat_map(|d| d.answers.iter().map(|a| a.data.clone()))
+            .collect(),
+        "http.method" => requests.map(|r| r.method.clone()).collect(),
+        "http.uri" => requests.map(|r| r.uri.clone()).collect(),
+        "http.host" => requests.filter_map(|r| r.host.clone()).collect(),
+        "http.status" => responses.map(|r| r.status.to_string()).collect(),
+        "http.user_agent" => requests
+            .filter_map(|r| http::header(&r.headers, "User-Agent").map(str::to_string))
+            .collect(),
+        "http.content_type" => requests
+            .map(|r| &r.headers)
+            .chain(responses.map(|r| &r.headers))
+            .filter_map(|h| http::header(h, "Content-Type").map(str::to_string))
+            .collect(),
+        "http2.frame" => h2
+            .iter()
+            .flat_map(|i| i.frames.iter().map(|f| http2::frame_type_name(f.frame_type).to_string()))
+            .collect(),
+        "http2.method" => h2_header(packet, ":method", true),
+        "http2.path" => h2_header(packet, ":path", true),
+        "http2.authority" => h2_header(packet, ":authority", true),
+        "http2.status" => h2_header(packet, ":status", false),
+        "grpc.service" => packet.grpc.iter().map(|c| c.service.clone()).collect(),
+        "grpc.method" => packet.grpc.iter().map(|c| c.method.clone()).collect(),
+        "grpc.status" => packet
+            .grpc
+            .iter()
+            .filter_map(|c| c.status)
+            .flat_map(|s| [s.to_string(), grpc::status_name(s).to_string()])
+            .collect(),
+        "tcp.analysis" => packet
+            .tcp_analysis
+            .iter()
+            .flat_map(|a| a.flags.iter().map(|&f| tcpanalysis::expert_code(f).to_string()))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn h2_header(packet: &Packet, name: &str, request: bool) -> Vec<String> {
+    packet
+        .http2
+        .iter()
+        .flat_map(|i| i.streams.iter())
+        .filter_map(|s| {
+            let headers = if request { &s.request_headers } else { &s.response_headers };
+            http::header(headers, name).map(str::to_string)
+        })
+        .collect()
+}
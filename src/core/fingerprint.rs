@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+
+use md5::{Digest as _, Md5};
+use sha2::Sha256;
+
+use crate::core::models::{ClientHello, Packet, ServerHello, TlsFingerprints, TlsInfo, TransportProtocol};
+use crate::core::tls::is_grease;
+
+/// Upper bound on the number of connections remembered by the tracker.
+const MAX_TRACKED_CONNECTIONS: usize = 4096;
+
+pub fn compute(info: &TlsInfo) -> TlsFingerprints {
+    let mut fp = TlsFingerprints::default();
+
+    if let Some(ch) = &info.client_hello {
+        let ja3 = ja3_string(ch);
+        fp.ja3_hash = Some(md5_hex(&ja3));
+        fp.ja3 = Some(ja3);
+        fp.ja4 = Some(ja4(ch));
+    }
+
+    if let Some(sh) = &info.server_hello {
+        let ja3s = ja3s_string(sh);
+        fp.ja3s_hash = Some(md5_hex(&ja3s));
+        fp.ja3s = Some(ja3s);
+        fp.ja4s = Some(ja4s(sh));
+    }
+
+    fp
+}
+
+fn dec_list<T: ToString>(values: impl Iterator<Item = T>) -> String {
+    values.map(|v| v.to_string()).collect::<Vec<_>>().join("-")
+}
+
+fn no_grease(values: &[u16]) -> impl Iterator<Item = u16> + '_ {
+    values.iter().copied().filter(|v| !is_grease(*v))
+}
+
+/// `SSLVersion,Ciphers,Extensions,EllipticCurves,EllipticCurvePointFormats`
+pub fn ja3_string(ch: &ClientHello) -> String {
+    format!(
+        "{},{},{},{},{}",
+        ch.legacy_version,
+        dec_list(no_grease(&ch.cipher_suites)),
+        dec_list(no_grease(&ch.extensions)),
+        dec_list(no_grease(&ch.supported_groups)),
+        dec_list(ch.ec_point_formats.iter()),
+    )
+}
+
+/// `SSLVersion,Cipher,Extensions`
+pub fn ja3s_string(sh: &ServerHello) -> String {
+    format!(
+        "{},{},{}",
+        sh.legacy_version,
+        sh.cipher_suite,
+        dec_list(no_grease(&sh.extensions)),
+    )
+}
+
+fn md5_hex(s: &str) -> String {
+    to_hex(&Md5::digest(s.as_bytes()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// First 12 hex characters of the SHA-256 of `s`, or zeros for an empty list.
+fn sha256_12(s: &str) -> String {
+    if s.is_empty() {
+        return "000000000000".to_string();
+    }
+    to_hex(&Sha256::digest(s.as_bytes()))[..12].to_string()
+}
+
+fn hex_list(values: impl Iterator<Item = u16>) -> String {
+    values.map(|v| format!("{v:04x}")).collect::<Vec<_>>().join(",")
+}
+
+fn ja4_version(version: u16) -> &'static str {
+    match version {
+        0x0304 => "13",
+        0x0303 => "12",
+        0x0302 => "11",
+        0x0301 => "10",
+        0x0300 => "s3",
+        0x0002 => "s2",
+        0xfeff => "d1",
+        0xfefd => "d2",
+        0xfefc => "d3",
+        _ => "00",
+    }
+}
+
+/// First and last character of the ALPN value, falling back to the hex
+/// representation when either end is not alphanumeric.
+fn ja4_alpn(alpn: Option<&str>) -> String {
+    let Some(value) = alpn.filter(|a| !a.is_empty()) else {
+        return "00".to_string();
+    };
+    let bytes = value.as_bytes();
+    let (first, last) = (bytes[0], bytes[bytes.len() - 1]);
+    if first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric() {
+        return format!("{}{}", first as char, last as char);
+    }
+    let hex = to_hex(bytes);
+    format!("{}{}", &hex[..1], &hex[hex.len() - 1..])
+}
+
+fn count2(n: usize) -> String {
+    format!("{:02}", n.min(99))
+}
+
+/// JA4 (TCP only): `t<ver><d|i><#ciphers><#exts><alpn>_<ciphers>_<exts+sigalgs>`.
+pub fn ja4(ch: &ClientHello) -> String {
+    let version = no_grease(&ch.supported_versions)
+        .max()
+        .unwrap_or(ch.legacy_version);
+    let sni = if ch.sni.is_some() { "d" } else { "i" };
+
+    let mut ciphers: Vec<u16> = no_grease(&ch.cipher_suites).collect();
+    let exts: Vec<u16> = no_grease(&ch.extensions).collect();
+
+    let a = format!(
+        "t{}{}{}{}{}",
+        ja4_version(version),
+        sni,
+        count2(ciphers.len()),
+        count2(exts.len()),
+        ja4_alpn(ch.alpn.first().map(String::as_str)),
+    );
+
+    ciphers.sort_unstable();
+    let b = sha256_12(&hex_list(ciphers.into_iter()));
+
+    // SNI and ALPN are already represented in part `a`.
+    let mut hashed_exts: Vec<u16> = exts.into_iter().filter(|e| *e != 0x0000 && *e != 0x0010).collect();
+    hashed_exts.sort_unstable();
+    let mut c_input = hex_list(hashed_exts.into_iter());
+    let sigalgs = hex_list(no_grease(&ch.signature_algorithms));
+    if !sigalgs.is_empty() {
+        c_input.push('_');
+        c_input.push_str(&sigalgs);
+    }
+    let c = sha256_12(&c_input);
+
+    format!("{a}_{b}_{c}")
+}
+
+/// JA4S (TCP only): `t<ver><#exts><alpn>_<cipher>_<exts in order>`.
+pub fn ja4s(sh: &ServerHello) -> String {
+    let version = sh.selected_version.unwrap_or(sh.legacy_version);
+    let exts: Vec<u16> = no_grease(&sh.extensions).collect();
+
+    format!(
+        "t{}{}{}_{:04x}_{}",
+        ja4_version(version),
+        count2(exts.len()),
+        ja4_alpn(sh.alpn.as_deref()),
+        sh.cipher_suite,
+        sha256_12(&hex_list(exts.into_iter())),
+    )
+}
+
+fn merge(into: &mut TlsFingerprints, from: &TlsFingerprints) {
+    into.ja3 = into.ja3.take().or_else(|| from.ja3.clone());
+    into.ja3_hash = into.ja3_hash.take().or_else(|| from.ja3_hash.clone());
+    into.ja3s = into.ja3s.take().or_else(|| from.ja3s.clone());
+    into.ja3s_hash = into.ja3s_hash.take().or_else(|| from.ja3s_hash.clone());
+    into.ja4 = into.ja4.take().or_else(|| from.ja4.clone());
+    into.ja4s = into.ja4s.take().or_else(|| from.ja4s.clone());
+}
+
+/// Joins the TLS details of the same TCP connection: the ServerHello packet
+/// also carries the JA3/JA4 of the client that opened it, and every packet
+/// of the connection gets its hellos, fingerprints and certificates in
+/// `connection_tls`.
+#[derive(Default)]
+pub struct FingerprintTracker {
+    by_conn: HashMap<(String, String), TlsInfo>,
+}
+
+impl FingerprintTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn observe(&mut self, packet: &mut Packet) {
+        let (Some(ip), Some(TransportProtocol::Tcp(tcp))) = (&packet.ip, &packet.transport) else {
+            return;
+        };
+
+        let a = format!("{}:{}", ip.src_ip, tcp.src_port);
+        let b = format!("{}:{}", ip.dst_ip, tcp.dst_port);
+        let key = if a < b { (a, b) } else { (b, a) };
+
+        if let Some(info) = packet.tls.as_mut() {
+            if self.by_conn.len() >= MAX_TRACKED_CONNECTIONS && !self.by_conn.contains_key(&key) {
+                self.by_conn.clear();
+            }
+
+            let known = self.by_conn.entry(key.clone()).or_default();
+            merge(&mut info.fingerprints, &known.fingerprints);
+            known.fingerprints = info.fingerprints.clone();
+            known.record_version = info.record_version;
+            if info.client_hello.is_some() {
+                known.client_hello = info.client_hello.clone();
+            }
+            if info.server_hello.is_some() {
+                known.server_hello = info.server_hello.clone();
+            }
+            if !info.certificates.is_empty() {
+                known.certificates = info.certificates.clone();
+            }
+        }
+        packet.connection_tls = self.by_conn.get(&key).cloned();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Example of the JA3 README (github.com/salesforce/ja3).
+    #[test]
+    fn ja3_known_answer() {
+        let ch = ClientHello {
+            legacy_version: 769,
+            cipher_suites: vec![47, 53, 5, 10, 49161, 49162, 49171, 49172, 50, 56, 19, 4],
+            extensions: vec![0, 10, 11],
+            supported_groups: vec![23, 24, 25],
+            ec_point_formats: vec![0],
+            ..Default::default()
+        };
+        let fp = compute(&TlsInfo {
+            client_hello: Some(ch),
+            ..Default::default()
+        });
+        assert_eq!(fp.ja3.as_deref(), Some("769,47-53-5-10-49161-49162-49171-49172-50-56-19-4,0-10-11,23-24-25,0"));
+        assert_eq!(fp.ja3_hash.as_deref(), Some("ada70206e40642a3e4461f35503241d5"));
+    }
+
+    // Chrome example of the JA4 specification (github.com/FoxIO-LLC/ja4),
+    // with the GREASE values a real hello carries.
+    #[test]
+    fn ja4_known_answer() {
+        let ch = ClientHello {
+            legacy_version: 0x0303,
+            cipher_suites: vec![
+                0x2a2a, 0x1301, 0x1302, 0x1303, 0xc02b, 0xc02f, 0xc02c, 0xc030, 0xcca9, 0xcca8, 0xc013, 0xc014, 0x009c,
+                0x009d, 0x002f, 0x0035,
+            ],
+            extensions: vec![
+                0x8a8a, 0x001b, 0x0000, 0x0033, 0x0010, 0x4469, 0x0017, 0x002d, 0x000d, 0x0005, 0x0023, 0x0012, 0x002b,
+                0xff01, 0x000b, 0x000a, 0x0015, 0x3a3a,
+            ],
+            sni: Some("www.cloudflare.com".to_string()),
+            alpn: vec!["h2".to_string(), "http/1.1".to_string()],
+            signature_algorithms: vec![0x0403, 0x0804, 0x0401, 0x0503, 0x0805, 0x0501, 0x0806, 0x0601],
+            supported_versions: vec![0x7a7a, 0x0304, 0x0303],
+            ..Default::default()
+        };
+        assert_eq!(ja4(&ch), "t13d1516h2_8daaf6152771_e5627efa2ab1");
+    }
+
+    #[test]
+    fn ja4_without_sni_or_alpn() {
+        let ch = ClientHello {
+            legacy_version: 0x0303,
+            cipher_suites: vec![0x002f],
+            ..Default::default()
+        };
+        assert!(ja4(&ch).starts_with("t12i010000_"));
+        assert!(ja4(&ch).ends_with("_000000000000"));
+    }
+}
@@ -0,0 +1,388 @@
+use std::collections::HashMap;
+
+use crate::core::models::{Flow, FlowState, Packet, TransportProtocol};
+use crate::core::parser;
+use crate::core::stream::{TCP_ACK, TCP_FIN, TCP_RST, TCP_SYN};
+use crate::core::tcpanalysis;
+
+/// Ended TCP connections are kept this long so their last ACKs do not
+/// start a new conversation.
+const CLOSE_LINGER_MS: u128 = 2_000;
+
+/// Labels that only say which transport was used.
+const GENERIC_LABELS: &[&str] = &["TCP", "UDP", "OTHER"];
+
+type Endpoint = (String, Option<u16>);
+
+/// Bidirectional key: endpoints are stored in a fixed order.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct FlowKey {
+    iface: Option<String>,
+    vlan: Option<u16>,
+    protocol: u8,
+    low: Endpoint,
+    high: Endpoint,
+}
+
+struct FlowEntry {
+    flow: Flow,
+    /// How reliable `flow.application` is (see `label_rank`).
+    rank: u8,
+    fin_to_server: bool,
+    fin_to_client: bool,
+    /// Counters and start time at the last export (active timeout).
+    exported: Flow,
+}
+
+/// Why a flow was handed to an exporter (IPFIX flowEndReason values).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndReason {
+    Idle = 1,
+    Active = 2,
+    EndOfFlow = 3,
+    Forced = 4,
+}
+
+/// A flow ready for export. Its counters and `first_seen` only cover the
+/// packets since the previous export of the same conversation.
+#[derive(Debug, Clone)]
+pub struct FlowRecord {
+    pub flow: Flow,
+    pub reason: EndReason,
+}
+
+impl FlowEntry {
+    fn record(&mut self, reason: EndReason) -> FlowRecord {
+        let mut flow = self.flow.clone();
+        flow.first_seen = self.exported.last_seen.max(flow.first_seen);
+        flow.packets_to_server -= self.exported.packets_to_server;
+        flow.bytes_to_server -= self.exported.bytes_to_server;
+        flow.packets_to_client -= self.exported.packets_to_client;
+        flow.bytes_to_client -= self.exported.bytes_to_client;
+        self.exported = self.flow.clone();
+        FlowRecord { flow, reason }
+    }
+
+    fn has_news(&self) -> bool {
+        self.flow.packets() > self.exported.packets()
+    }
+}
+
+fn endpoints(packet: &Packet) -> Option<(u8, Endpoint, Endpoint, u8)> {
+    let ip = packet.ip.as_ref()?;
+    let (sport, dport, flags) = match &packet.transport {
+        Some(TransportProtocol::Tcp(t)) => (Some(t.src_port), Some(t.dst_port), t.flags),
+        Some(TransportProtocol::Udp(u)) => (Some(u.src_port), Some(u.dst_port), 0),
+        _ => (None, None, 0),
+    };
+    Some((
+        ip.protocol,
+        (ip.src_ip.clone(), sport),
+        (ip.dst_ip.clone(), dport),
+        flags,
+    ))
+}
+
+/// Labels backed by a decoded payload beat port-based guesses, which beat
+/// the bare transport name.
+fn label_rank(packet: &Packet, label: &str) -> u8 {
+    let decoded = packet.tls.is_some()
+        || !packet.dns.is_empty()
+        || packet.nbns.is_some()
+        || !packet.http.is_empty()
+        || packet.http2.is_some()
+        || !packet.grpc.is_empty();
+    if decoded {
+        2
+    } else if GENERIC_LABELS.contains(&label) {
+        0
+    } else {
+        1
+    }
+}
+
+/// Aggregates packets into conversations with per-direction counters,
+/// TCP flags and state, and the most specific application label seen.
+#[derive(Default)]
+pub struct FlowTable {
+    active: HashMap<FlowKey, FlowEntry>,
+    finished: Vec<FlowEntry>,
+    next_id: u64,
+}
+
+impl FlowTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accounts `packet` (seen on `iface`, classified as `application`)
+    /// and returns its flow. Packets without an IP layer are ignored.
+    pub fn observe(&mut self, packet: &Packet, iface: Option<&str>, application: &str) -> Option<&Flow> {
+        let (protocol, src, dst, flags) = endpoints(packet)?;
+        let (low, high) = if src <= dst {
+            (src.clone(), dst.clone())
+        } else {
+            (dst.clone(), src.clone())
+        };
+        let key = FlowKey {
+            iface: iface.map(str::to_string),
+            vlan: packet.eth.as_ref().and_then(|e| e.vlan),
+            protocol,
+            low,
+            high,
+        };
+
+        // A new SYN on a finished TCP conversation starts another one.
+        let syn_only = flags & TCP_SYN != 0 && flags & TCP_ACK == 0;
+        if syn_only
+            && self
+                .active
+                .get(&key)
+                .is_some_and(|e| matches!(e.flow.state, FlowState::Closed | FlowState::Reset))
+        {
+            if let Some(entry) = self.active.remove(&key) {
+                self.finished.push(entry);
+            }
+        }
+
+        let next_id = &mut self.next_id;
+        let entry = self.active.entry(key.clone()).or_insert_with(|| {
+            *next_id += 1;
+            // The SYN sender is the client; otherwise guess the server is
+            // the lower port, as for TCP reassembly.
+            let src_is_client = if flags & TCP_SYN != 0 {
+                flags & TCP_ACK == 0
+            } else {
+                match (src.1, dst.1) {
+                    (Some(sport), Some(dport)) => sport >= dport,
+                    _ => true,
+                }
+            };
+            let (client, server) = if src_is_client { (&src, &dst) } else { (&dst, &src) };
+            FlowEntry {
+                flow: Flow {
+                    id: *next_id,
+                    iface: key.iface.clone(),
+                    vlan: key.vlan,
+                    protocol,
+                    client_ip: client.0.clone(),
+                    client_port: client.1,
+                    server_ip: server.0.clone(),
+                    server_port: server.1,
+                    first_seen: packet.timestamp,
+                    application: application.to_string(),
+                    ..Default::default()
+                },
+                rank: label_rank(packet, application),
+                fin_to_server: false,
+                fin_to_client: false,
+                exported: Flow::default(),
+            }
+        });
+
+        let flow = &mut entry.flow;
+        let to_server = flow.client_ip == src.0 && flow.client_port == src.1;
+        let size = parser::ip_length(&packet.payload) as u64;
+        flow.last_seen = flow.last_seen.max(packet.timestamp);
+        if to_server {
+            flow.packets_to_server += 1;
+            flow.bytes_to_server += size;
+            flow.tcp_flags_to_server |= flags;
+            entry.fin_to_server |= flags & TCP_FIN != 0;
+        } else {
+            flow.packets_to_client += 1;
+            flow.bytes_to_client += size;
+            flow.tcp_flags_to_client |= flags;
+            entry.fin_to_client |= flags & TCP_FIN != 0;
+        }
+
+        if let Some(analysis) = &packet.tcp_analysis {
+            flow.tcp_perf.record(analysis);
+        }
+        if let Some(ch) = packet.tls.as_ref().and_then(|t| t.client_hello.as_ref()) {
+            flow.sni = flow.sni.take().or_else(|| ch.sni.clone());
+            flow.alpn = flow.alpn.take().or_else(|| ch.alpn.first().cloned());
+        }
+        if let Some(alpn) = packet.tls.as_ref().and_then(|t| t.server_hello.as_ref()?.alpn.clone()) {
+            flow.alpn = Some(alpn);
+        }
+
+        let rank = label_rank(packet, application);
+        if rank > entry.rank {
+            entry.rank = rank;
+            flow.application = application.to_string();
+        }
+
+        flow.state = if flags & TCP_RST != 0 || flow.state == FlowState::Reset {
+            FlowState::Reset
+        } else if entry.fin_to_server && entry.fin_to_client {
+            FlowState::Closed
+        } else if entry.fin_to_server || entry.fin_to_client {
+            FlowState::Closing
+        } else if flow.packets_to_server > 0 && flow.packets_to_client > 0 && flags & TCP_SYN == 0 {
+            FlowState::Established
+        } else {
+            flow.state
+        };
+
+        Some(&entry.flow)
+    }
+
+    /// Every conversation seen so far, in order of appearance.
+    pub fn flows(&self) -> Vec<Flow> {
+        let mut flows: Vec<Flow> = self
+            .finished
+            .iter()
+            .chain(self.active.values())
+            .map(|e| e.flow.clone())
+            .collect();
+        flows.sort_by_key(|f| f.id);
+        flows
+    }
+
+    /// Hands over the conversations to export and forgets the ended ones:
+    /// TCP connections closed or reset (after a short linger), flows idle for `idle_timeout_ms`,
+    /// and a slice of flows active for longer than `active_timeout_ms`.
+    pub fn expire(&mut self, now: u128, idle_timeout_ms: u128, active_timeout_ms: u128) -> Vec<FlowRecord> {
+        let mut records: Vec<FlowRecord> = self
+            .finished
+            .drain(..)
+            .filter(|e| e.has_news())
+            .map(|mut e| e.record(EndReason::EndOfFlow))
+            .collect();
+
+        self.active.retain(|_, entry| {
+            let idle = now.saturating_sub(entry.flow.last_seen);
+            let ended = matches!(entry.flow.state, FlowState::Closed | FlowState::Reset) && idle >= CLOSE_LINGER_MS;
+            if ended || idle > idle_timeout_ms {
+                if entry.has_news() {
+                    records.push(entry.record(if ended { EndReason::EndOfFlow } else { EndReason::Idle }));
+                }
+                return false;
+            }
+            let slice_start = entry.exported.last_seen.max(entry.flow.first_seen);
+            if entry.has_news() && now.saturating_sub(slice_start) > active_timeout_ms {
+                records.push(entry.record(EndReason::Active));
+            }
+            true
+        });
+        records.sort_by_key(|r| r.flow.id);
+        records
+    }
+
+    /// End of the capture: every conversation with unexported packets.
+    pub fn drain(&mut self) -> Vec<FlowRecord> {
+        let mut records = self.expire(u128::MAX, u128::MAX, u128::MAX);
+        records.extend(
+            self.active
+                .drain()
+                .map(|(_, e)| e)
+                .filter(|e| e.has_news())
+                .map(|mut e| e.record(EndReason::Forced)),
+        );
+        records.sort_by_key(|r| r.flow.id);
+        records
+    }
+}
+
+pub fn protocol_name(protocol: u8) -> String {
+    match protocol {
+        1 => "ICMP".to_string(),
+        6 => "TCP".to_string(),
+        17 => "UDP".to_string(),
+        58 => "ICMPv6".to_string(),
+        n => format!("IP/{n}"),
+    }
+}
+
+/// `SYN,ACK,FIN`-style rendering of a TCP flags union.
+pub fn flags_label(flags: u8) -> String {
+    const NAMES: [(u8, &str); 6] = [
+        (TCP_SYN, "SYN"),
+        (TCP_ACK, "ACK"),
+        (0x08, "PSH"),
+        (0x20, "URG"),
+        (TCP_FIN, "FIN"),
+        (TCP_RST, "RST"),
+    ];
+    let names: Vec<&str> = NAMES.iter().filter(|(f, _)| flags & f != 0).map(|(_, n)| *n).collect();
+    if names.is_empty() {
+        "-".to_string()
+    } else {
+        names.join(",")
+    }
+}
+
+pub fn state_label(state: FlowState) -> &'static str {
+    match state {
+        FlowState::New => "Nouveau",
+        FlowState::Established => "Établi",
+        FlowState::Closing => "Fermeture",
+        FlowState::Closed => "Fermé",
+        FlowState::Reset => "RST",
+    }
+}
+
+pub fn endpoint_label(ip: &str, port: Option<u16>) -> String {
+    match port {
+        Some(p) if ip.contains(':') => format!("[{ip}]:{p}"),
+        Some(p) => format!("{ip}:{p}"),
+        None => ip.to_string(),
+    }
+}
+
+fn human_bytes(n: u64) -> String {
+    match n {
+        0..=9_999 => format!("{n} o"),
+        10_000..=9_999_999 => format!("{:.1} Ko", n as f64 / 1024.0),
+        _ => format!("{:.1} Mo", n as f64 / (1024.0 * 1024.0)),
+    }
+}
+
+pub fn print_report(flows: &[Flow]) {
+    println!();
+    println!("══════════════════════════════════════════════════════════════════════");
+    println!(" WireFish - Conversations ({})", flows.len());
+    println!("══════════════════════════════════════════════════════════════════════");
+    println!(
+        "{:<6} {:<28} {:<28} {:<10} {:>8} {:>10} {:>10} {:>9} {:<11} Flags",
+        "Proto", "Client", "Serveur", "Appli", "Paquets", "→ Serveur", "→ Client", "Durée", "État"
+    );
+
+    for f in flows {
+        let flags = if f.protocol == 6 {
+            format!(
+                "{} / {}",
+                flags_label(f.tcp_flags_to_server),
+                flags_label(f.tcp_flags_to_client)
+            )
+        } else {
+            String::new()
+        };
+        println!(
+            "{:<6} {:<28} {:<28} {:<10} {:>8} {:>10} {:>10} {:>7.1} s {:<11} {}",
+            protocol_name(f.protocol),
+            endpoint_label(&f.client_ip, f.client_port),
+            endpoint_label(&f.server_ip, f.server_port),
+            f.application,
+            f.packets(),
+            human_bytes(f.bytes_to_server),
+            human_bytes(f.bytes_to_client),
+            f.duration_ms() as f64 / 1000.0,
+            state_label(f.state),
+            flags
+        );
+        if let Some(vlan) = f.vlan {
+            println!("       VLAN {vlan}");
+        }
+        match (&f.sni, &f.alpn) {
+            (Some(sni), Some(alpn)) => println!("       SNI {sni} (ALPN {alpn})"),
+            (Some(sni), None) => println!("       SNI {sni}"),
+            (None, Some(alpn)) => println!("       ALPN {alpn}"),
+            (None, None) => {}
+        }
+        if let Some(line) = tcpanalysis::perf_line(&f.tcp_perf) {
+            println!("       {line}");
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+use std::io::IsTerminal;
+
+use crossterm::style::{Color, Stylize};
+
+use crate::core::models::{Packet, TransportProtocol};
+use crate::core::parser;
+use crate::core::stream::{CloseReason, Direction, StreamEvent, TcpReassembler};
+
+const HEX_WIDTH: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FollowFormat {
+    Ascii,
+    Hex,
+}
+
+#[derive(Debug, Clone)]
+pub enum Segment {
+    Data { direction: Direction, data: Vec<u8> },
+    /// Bytes of the stream that were not captured.
+    Gap { direction: Direction, missing: u64 },
+}
+
+/// Both directions of one TCP connection or UDP conversation, in the
+/// order they were seen.
+#[derive(Debug, Clone)]
+pub struct FollowedStream {
+    pub transport: &'static str,
+    pub client: String,
+    pub server: String,
+    pub packets: usize,
+    pub segments: Vec<Segment>,
+}
+
+impl FollowedStream {
+    pub fn bytes(&self, direction: Direction) -> usize {
+        self.segments
+            .iter()
+            .map(|s| match s {
+                Segment::Data { direction: d, data } if *d == direction => data.len(),
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// Payload of one direction, or of both interleaved.
+    pub fn raw(&self, side: Option<Direction>) -> Vec<u8> {
+        let mut out = Vec::new();
+        for s in &self.segments {
+            if let Segment::Data { direction, data } = s {
+                if side.is_none_or(|d| d == *direction) {
+                    out.extend_from_slice(data);
+                }
+            }
+        }
+        out
+    }
+}
+
+fn endpoints(packet: &Packet) -> Option<(&'static str, String, String)> {
+    let ip = packet.ip.as_ref()?;
+    let (transport, sport, dport) = match packet.transport.as_ref()? {
+        TransportProtocol::Tcp(t) => ("TCP", t.src_port, t.dst_port),
+        TransportProtocol::Udp(u) => ("UDP", u.src_port, u.dst_port),
+        _ => return None,
+    };
+    Some((
+        transport,
+        format!("{}:{}", ip.src_ip, sport),
+        format!("{}:{}", ip.dst_ip, dport),
+    ))
+}
+
+/// Rebuilds the stream that packet `id` belongs to from the stored packets.
+/// For TCP, only the connection containing that packet is kept when the
+/// endpoints were reused.
+pub fn follow_stream(packets: &[(u64, Packet)], id: u64) -> Result<FollowedStream, String> {
+    let target = packets
+        .iter()
+        .find(|(pid, _)| *pid == id)
+        .ok_or_else(|| format!("Paquet #{id} introuvable"))?;
+    let (transport, src, dst) =
+        endpoints(&target.1).ok_or_else(|| format!("Le paquet #{id} n'est ni TCP ni UDP"))?;
+
+    let members = packets.iter().filter(|(_, p)| {
+        endpoints(p).is_some_and(|(t, s, d)| t == transport && ((s == src && d == dst) || (s == dst && d == src)))
+    });
+
+    let mut stream = FollowedStream {
+        transport,
+        client: src.clone(),
+        server: dst.clone(),
+        packets: 0,
+        segments: Vec::new(),
+    };
+
+    if transport == "UDP" {
+        for (_, packet) in members {
+            let Some((_, s, _)) = endpoints(packet) else {
+                continue;
+            };
+            if stream.packets == 0 {
+                stream.client = s.clone();
+                stream.server = if s == src { dst.clone() } else { src.clone() };
+            }
+            stream.packets += 1;
+            let data = parser::transport_payload(&packet.payload);
+            if !data.is_empty() {
+                let direction = if s == stream.client { Direction::ToServer } else { Direction::ToClient };
+                stream.segments.push(Segment::Data {
+                    direction,
+                    data: data.to_vec(),
+                });
+            }
+        }
+        return Ok(stream);
+    }
+
+    // Each close starts a new generation; keep the one holding `id`.
+    let mut streams = TcpReassembler::new();
+    let mut generation = 0;
+    let mut target_generation = 0;
+    let mut packet_counts: HashMap<usize, usize> = HashMap::new();
+    let mut collected: Vec<(usize, StreamEvent)> = Vec::new();
+    for (pid, packet) in members {
+        let events = streams.observe(packet);
+        // A SYN reusing the endpoints closes the previous connection first.
+        let reused = events
+            .iter()
+            .take_while(|e| matches!(e, StreamEvent::Closed { reason: CloseReason::Reused, .. }))
+            .count();
+        *packet_counts.entry(generation + reused).or_default() += 1;
+        if *pid == id {
+            target_generation = generation + reused;
+        }
+        for event in events {
+            let closed = matches!(event, StreamEvent::Closed { .. });
+            collected.push((generation, event));
+            if closed {
+                generation += 1;
+            }
+        }
+    }
+    collected.extend(streams.finish().into_iter().map(|e| (generation, e)));
+
+    stream.packets = packet_counts.get(&target_generation).copied().unwrap_or(0);
+    for (gen, event) in collected {
+        if gen != target_generation {
+            continue;
+        }
+        match event {
+            StreamEvent::Data { key, direction, data } => {
+                stream.client = key.client;
+                stream.server = key.server;
+                stream.segments.push(Segment::Data { direction, data });
+            }
+            StreamEvent::Gap { direction, missing, .. } => stream.segments.push(Segment::Gap { direction, missing }),
+            StreamEvent::Closed { .. } => {}
+        }
+    }
+    Ok(stream)
+}
+
+fn printable(data: &[u8]) -> String {
+    data.iter()
+        .filter(|&&b| b != b'\r')
+        .map(|&b| match b {
+            b'\n' | b'\t' | 0x20..=0x7e => b as char,
+            _ => '.',
+        })
+        .collect()
+}
+
+fn hex_lines(data: &[u8], offset: usize) -> Vec<String> {
+    data.chunks(HEX_WIDTH)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{b:02x}")).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| if (0x20..=0x7e).contains(&b) { b as char } else { '.' })
+                .collect();
+            format!(
+                "{:08X}  {:<width$}  {ascii}",
+                offset + i * HEX_WIDTH,
+                hex.join(" "),
+                width = HEX_WIDTH * 3 - 1
+            )
+        })
+        .collect()
+}
+
+/// Prints the stream like Wireshark's Follow Stream: client bytes in red,
+/// server bytes in blue (colors only on a terminal).
+pub fn print_stream(stream: &FollowedStream, format: FollowFormat) {
+    let color = std::io::stdout().is_terminal();
+    let paint = |text: &str, c: Color| {
+        if color {
+            text.with(c).to_string()
+        } else {
+            text.to_string()
+        }
+    };
+    let color_of = |direction: Direction| match direction {
+        Direction::ToServer => Color::Red,
+        Direction::ToClient => Color::Blue,
+    };
+
+    println!("══════════════════════════════════════════════════════════════════════");
+    println!(" Flux {} {} ⇄ {}", stream.transport, stream.client, stream.server);
+    println!("══════════════════════════════════════════════════════════════════════");
+
+    let mut offsets = [0usize; 2];
+    for segment in &stream.segments {
+        match segment {
+            Segment::Data { direction, data } => match format {
+                FollowFormat::Ascii => {
+                    let text = printable(data);
+                    print!("{}", paint(&text, color_of(*direction)));
+                    if !text.ends_with('\n') {
+                        println!();
+                    }
+                }
+                FollowFormat::Hex => {
+                    let side = *direction as usize;
+                    // Server side indented, as in Wireshark.
+                    let indent = if *direction == Direction::ToClient { "    " } else { "" };
+                    for line in hex_lines(data, offsets[side]) {
+                        println!("{indent}{}", paint(&line, color_of(*direction)));
+                    }
+                    offsets[side] += data.len();
+                }
+            },
+            Segment::Gap { direction, missing } => {
+                let text = format!("[… {missing} octets manquants …]");
+                println!("{}", paint(&text, color_of(*direction)));
+            }
+        }
+    }
+
+    println!("──────────────────────────────────────────────────────────────────────");
+    println!(
+        " {} → {} : {} octets",
+        stream.client,
+        stream.server,
+        stream.bytes(Direction::ToServer)
+    );
+    println!(
+        " {} → {} : {} octets",
+        stream.server,
+        stream.client,
+        stream.bytes(Direction::ToClient)
+    );
+    println!(" {} paquet(s)", stream.packets);
+}
@@ -0,0 +1,169 @@
+use std::io::Read;
+
+use crate::core::http;
+use crate::core::models::{GrpcCall, GrpcMessage, Http2Stream, Packet};
+use crate::core::protobuf::{self, DescriptorPool};
+
+/// Decompressed size limit per message.
+const MAX_DECODED: u64 = 16 * 1024 * 1024;
+
+pub fn is_grpc(headers: &[(String, String)]) -> bool {
+    http::header(headers, "content-type")
+        .map(|ct| ct.starts_with("application/grpc"))
+        .unwrap_or(false)
+}
+
+pub fn status_name(code: u32) -> &'static str {
+    match code {
+        0 => "OK",
+        1 => "CANCELLED",
+        2 => "UNKNOWN",
+        3 => "INVALID_ARGUMENT",
+        4 => "DEADLINE_EXCEEDED",
+        5 => "NOT_FOUND",
+        6 => "ALREADY_EXISTS",
+        7 => "PERMISSION_DENIED",
+        8 => "RESOURCE_EXHAUSTED",
+        9 => "FAILED_PRECONDITION",
+        10 => "ABORTED",
+        11 => "OUT_OF_RANGE",
+        12 => "UNIMPLEMENTED",
+        13 => "INTERNAL",
+        14 => "UNAVAILABLE",
+        15 => "DATA_LOSS",
+        16 => "UNAUTHENTICATED",
+        _ => "?",
+    }
+}
+
+/// Splits a gRPC body into its length-prefixed messages
+/// (1-byte compressed flag, 4-byte big-endian length, payload).
+/// A truncated last message is returned with what is available.
+pub fn split_messages(body: &[u8]) -> Vec<(bool, usize, &[u8])> {
+    let mut out = Vec::new();
+    let mut rest = body;
+    while rest.len() >= 5 {
+        let compressed = rest[0] == 1;
+        let len = u32::from_be_bytes([rest[1], rest[2], rest[3], rest[4]]) as usize;
+        let end = (5 + len).min(rest.len());
+        out.push((compressed, len, &rest[5..end]));
+        rest = &rest[end..];
+    }
+    out
+}
+
+/// Fails rather than cutting the message at `MAX_DECODED`: a truncated
+/// protobuf would decode into wrong fields.
+fn decompress(data: &[u8], encoding: Option<&str>) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    let reader: Box<dyn Read + '_> = match encoding {
+        Some("gzip") => Box::new(flate2::read::GzDecoder::new(data)),
+        Some("deflate") => Box::new(flate2::read::ZlibDecoder::new(data)),
+        Some(other) => return Err(format!("encodage {other} non pris en charge")),
+        None => return Err("message compressé sans grpc-encoding".to_string()),
+    };
+    reader
+        .take(MAX_DECODED + 1)
+        .read_to_end(&mut out)
+        .map_err(|e| format!("décompression : {e}"))?;
+    if out.len() as u64 > MAX_DECODED {
+        return Err(format!("message décompressé de plus de {} Mio", MAX_DECODED / (1024 * 1024)));
+    }
+    Ok(out)
+}
+
+/// Turns finished HTTP/2 streams carrying `application/grpc` into calls,
+/// decoding protobuf messages with the descriptor set when one is loaded.
+#[derive(Default)]
+pub struct GrpcDecoder {
+    pool: Option<DescriptorPool>,
+}
+
+impl GrpcDecoder {
+    pub fn new(pool: Option<DescriptorPool>) -> Self {
+        GrpcDecoder { pool }
+    }
+
+    fn messages(&self, body: &[u8], encoding: Option<&str>, type_name: Option<&str>) -> Vec<GrpcMessage> {
+        split_messages(body)
+            .into_iter()
+            .map(|(compressed, length, payload)| {
+                let data = if compressed {
+                    decompress(payload, encoding)
+                } else {
+                    Ok(payload.to_vec())
+                };
+                let (data, error) = match data {
+                    Ok(d) => (Some(d), None),
+                    Err(e) => (None, Some(e)),
+                };
+                let value = data.filter(|d| compressed || d.len() == length).and_then(|d| {
+                    match (&self.pool, type_name) {
+                        (Some(pool), Some(t)) => pool.decode(t, &d),
+                        _ => protobuf::decode_raw(&d),
+                    }
+                });
+                GrpcMessage {
+                    compressed,
+                    length,
+                    json: value.map(|v| v.to_string()),
+                    error,
+                }
+            })
+            .collect()
+    }
+
+    pub fn call(&self, stream: &Http2Stream) -> Option<GrpcCall> {
+        if !is_grpc(&stream.request_headers) && !is_grpc(&stream.response_headers) {
+            return None;
+        }
+
+        let path = http::header(&stream.request_headers, ":path").unwrap_or("");
+        let (service, method) = path
+            .trim_start_matches('/')
+            .split_once('/')
+            .unwrap_or((path, ""));
+        let desc = self.pool.as_ref().and_then(|p| p.method(path));
+
+        // Trailers-only responses carry the status in the headers.
+        let status_headers = if stream.trailers.is_empty() {
+            &stream.response_headers
+        } else {
+            &stream.trailers
+        };
+
+        Some(GrpcCall {
+            stream_id: stream.stream_id,
+            service: service.to_string(),
+            method: method.to_string(),
+            requests: self.messages(
+                &stream.request_body,
+                http::header(&stream.request_headers, "grpc-encoding"),
+                desc.map(|d| d.input_type.as_str()),
+            ),
+            responses: self.messages(
+                &stream.response_body,
+                http::header(&stream.response_headers, "grpc-encoding"),
+                desc.map(|d| d.output_type.as_str()),
+            ),
+            status: http::header(status_headers, "grpc-status").and_then(|s| s.parse().ok()),
+            status_message: http::header(status_headers, "grpc-message").map(http::percent_decode),
+            trailers: stream.trailers.clone(),
+            latency_ms: stream.latency_ms,
+        })
+    }
+
+    pub fn observe(&self, packet: &mut Packet) {
+        let Some(info) = &packet.http2 else {
+            return;
+        };
+        let calls: Vec<GrpcCall> = info.streams.iter().filter_map(|s| self.call(s)).collect();
+        packet.grpc.extend(calls);
+    }
+}
+
+/// Short description used in the live table next to the protocol label.
+pub fn summary(call: &GrpcCall) -> String {
+    let status = call.status.map(status_name).unwrap_or("-");
+    format!("{} {status}", call.method)
+}
@@ -0,0 +1,367 @@
+use std::collections::HashMap;
+
+use crate::core::models::{Packet, TransportProtocol, X509Certificate};
+use crate::core::stream::{Direction, StreamEvent, StreamKey};
+use crate::core::tls;
+use crate::core::x509;
+
+const CONTENT_HANDSHAKE: u8 = 0x16;
+const HS_CERTIFICATE: u8 = 11;
+const HS_SERVER_HELLO_DONE: u8 = 14;
+
+/// Handshakes followed at the same time; beyond that the table is reset.
+const MAX_STREAMS: usize = 1024;
+/// Bytes kept per handshake before giving up on it.
+const MAX_BUFFERED: usize = 256 * 1024;
+/// Client bytes kept while waiting for the end of its hello.
+const MAX_HELLO: usize = 64 * 1024;
+
+/// Certificate chain recovered from a server's handshake flight.
+pub struct CertificateChain {
+    pub version: u16,
+    pub sni: Option<String>,
+    pub certificates: Vec<X509Certificate>,
+}
+
+#[derive(Default)]
+struct ServerFlight {
+    version: u16,
+    records: Vec<u8>,
+    handshake: Vec<u8>,
+    buffered: usize,
+}
+
+enum Progress {
+    NeedMore,
+    Done,
+    Chain(Vec<X509Certificate>),
+}
+
+impl ServerFlight {
+    fn push(&mut self, data: &[u8]) -> Progress {
+        self.records.extend_from_slice(data);
+        self.buffered += data.len();
+        if self.buffered > MAX_BUFFERED {
+            return Progress::Done;
+        }
+        self.drain()
+    }
+
+    fn drain(&mut self) -> Progress {
+        while self.records.len() >= 5 {
+            let len = u16::from_be_bytes([self.records[3], self.records[4]]) as usize;
+            if self.records[0] != CONTENT_HANDSHAKE {
+                // ChangeCipherSpec/Alert/AppData: the cleartext handshake is over.
+                return Progress::Done;
+            }
+            if self.records.len() < 5 + len {
+                break;
+            }
+            self.handshake.extend_from_slice(&self.records[5..5 + len]);
+            self.records.drain(..5 + len);
+        }
+
+        while self.handshake.len() >= 4 {
+            let msg_type = self.handshake[0];
+            let len = ((self.handshake[1] as usize) << 16)
+                | ((self.handshake[2] as usize) << 8)
+                | self.handshake[3] as usize;
+            if self.handshake.len() < 4 + len {
+                break;
+            }
+
+            match msg_type {
+                HS_CERTIFICATE => {
+                    return Progress::Chain(x509::parse_certificate_list(&self.handshake[4..4 + len]));
+                }
+                HS_SERVER_HELLO_DONE => return Progress::Done,
+                _ => {}
+            }
+            self.handshake.drain(..4 + len);
+        }
+
+        Progress::NeedMore
+    }
+}
+
+/// Follows the server side of TLS ≤ 1.2 handshakes in the reassembled
+/// TCP stream until the Certificate message is complete. TLS 1.3
+/// handshakes are ignored since their certificates are encrypted.
+#[derive(Default)]
+pub struct HandshakeReassembler {
+    flights: HashMap<StreamKey, ServerFlight>,
+    sni: HashMap<StreamKey, String>,
+}
+
+impl HandshakeReassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn observe(&mut self, packet: &Packet, events: &[StreamEvent]) -> Option<CertificateChain> {
+        let (Some(ip), Some(TransportProtocol::Tcp(tcp))) = (&packet.ip, &packet.transport) else {
+            return None;
+        };
+        let src = format!("{}:{}", ip.src_ip, tcp.src_port);
+        let dst = format!("{}:{}", ip.dst_ip, tcp.dst_port);
+
+        if let Some(ch) = packet.tls.as_ref().and_then(|t| t.client_hello.as_ref()) {
+            if let Some(sni) = &ch.sni {
+                self.reserve();
+                self.sni.insert(StreamKey { client: src, server: dst }, sni.clone());
+            }
+            return None;
+        }
+
+        if let Some(sh) = packet.tls.as_ref().and_then(|t| t.server_hello.as_ref()) {
+            let key = StreamKey { client: dst, server: src };
+            let version = sh.selected_version.unwrap_or(sh.legacy_version);
+            if version >= 0x0304 {
+                self.sni.remove(&key);
+                return None;
+            }
+            self.reserve();
+            self.flights.insert(key, ServerFlight { version, ..Default::default() });
+        }
+
+        let mut chain = None;
+        for event in events {
+            match event {
+                StreamEvent::Data {
+                    key,
+                    direction: Direction::ToClient,
+                    data,
+                } => {
+                    let Some(flight) = self.flights.get_mut(key) else {
+                        continue;
+                    };
+                    match flight.push(data) {
+                        Progress::NeedMore => {}
+                        Progress::Done => {
+                            self.flights.remove(key);
+                            self.sni.remove(key);
+                        }
+                        Progress::Chain(certificates) => {
+                            let version = flight.version;
+                            self.flights.remove(key);
+                            chain = Some(CertificateChain {
+                                version,
+                                sni: self.sni.remove(key),
+                                certificates,
+                            });
+                        }
+                    }
+                }
+                StreamEvent::Data { .. } => {}
+                StreamEvent::Gap { key, .. } | StreamEvent::Closed { key, .. } => {
+                    self.flights.remove(key);
+                    self.sni.remove(key);
+                }
+            }
+        }
+        chain
+    }
+
+    fn reserve(&mut self) {
+        if self.flights.len() >= MAX_STREAMS {
+            self.flights.clear();
+        }
+        if self.sni.len() >= MAX_STREAMS {
+            self.sni.clear();
+        }
+    }
+}
+
+/// Whether the first handshake message of a client flight is complete;
+/// `None` when the flight is not a TLS handshake.
+fn hello_complete(records: &[u8]) -> Option<bool> {
+    let mut handshake = Vec::new();
+    let mut off = 0;
+    while off + 5 <= records.len() {
+        if records[off] != CONTENT_HANDSHAKE || records[off + 1] != 0x03 {
+            return None;
+        }
+        let len = u16::from_be_bytes([records[off + 3], records[off + 4]]) as usize;
+        let end = (off + 5 + len).min(records.len());
+        handshake.extend_from_slice(&records[off + 5..end]);
+        off += 5 + len;
+    }
+    if handshake.len() < 4 {
+        return Some(false);
+    }
+    let len = ((handshake[1] as usize) << 16) | ((handshake[2] as usize) << 8) | handshake[3] as usize;
+    Some(handshake.len() >= 4 + len)
+}
+
+/// Decodes ClientHellos spanning several TCP segments (post-quantum key
+/// shares make them over 1.5 KB) from the reassembled client stream, on
+/// the packet that completes them.
+#[derive(Default)]
+pub struct HelloReassembler {
+    /// Client bytes of connections whose hello is not complete yet, `None`
+    /// once decoded or given up.
+    flights: HashMap<StreamKey, Option<Vec<u8>>>,
+}
+
+impl HelloReassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn observe(&mut self, packet: &mut Packet, events: &[StreamEvent]) {
+        for event in events {
+            match event {
+                StreamEvent::Data {
+                    key,
+                    direction: Direction::ToServer,
+                    data,
+                } => {
+                    if !self.flights.contains_key(key) {
+                        if self.flights.len() >= MAX_STREAMS {
+                            self.flights.clear();
+                        }
+                        let handshake = data.len() >= 2 && data[0] == CONTENT_HANDSHAKE && data[1] == 0x03;
+                        self.flights.insert(key.clone(), handshake.then(Vec::new));
+                    }
+                    let Some(Some(flight)) = self.flights.get_mut(key) else {
+                        continue;
+                    };
+                    flight.extend_from_slice(data);
+                    match hello_complete(flight) {
+                        Some(false) if flight.len() <= MAX_HELLO => continue,
+                        Some(true) => {
+                            let decoded = tls::parse(flight).filter(|t| t.client_hello.is_some());
+                            if packet.tls.as_ref().is_none_or(|t| t.client_hello.is_none()) && decoded.is_some() {
+                                packet.tls = decoded;
+                            }
+                        }
+                        _ => {}
+                    }
+                    self.flights.insert(key.clone(), None);
+                }
+                StreamEvent::Data { .. } => {}
+                StreamEvent::Gap { key, .. } | StreamEvent::Closed { key, .. } => {
+                    self.flights.remove(key);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::parser;
+    use crate::core::stream::{TcpReassembler, TCP_ACK, TCP_SYN};
+
+    /// Ethernet + IPv4 + TCP segment between 10.0.0.1:40000 and 10.0.0.2:443.
+    fn segment(to_server: bool, seq: u32, flags: u8, payload: &[u8]) -> Packet {
+        let (src, sport, dst, dport) = if to_server {
+            ([10, 0, 0, 1], 40000u16, [10, 0, 0, 2], 443u16)
+        } else {
+            ([10, 0, 0, 2], 443, [10, 0, 0, 1], 40000)
+        };
+        let mut f = vec![0u8; 12];
+        f.extend_from_slice(&[0x08, 0x00, 0x45, 0]);
+        f.extend_from_slice(&((40 + payload.len()) as u16).to_be_bytes());
+        f.extend_from_slice(&[0, 0, 0, 0, 64, 6, 0, 0]);
+        f.extend_from_slice(&src);
+        f.extend_from_slice(&dst);
+        f.extend_from_slice(&sport.to_be_bytes());
+        f.extend_from_slice(&dport.to_be_bytes());
+        f.extend_from_slice(&seq.to_be_bytes());
+        f.extend_from_slice(&[0, 0, 0, 0, 0x50, flags, 0xff, 0xff, 0, 0, 0, 0]);
+        f.extend_from_slice(payload);
+        parser::parse_packet_at(&f, 1000).unwrap()
+    }
+
+    fn vec16(body: &[u8]) -> Vec<u8> {
+        let mut out = (body.len() as u16).to_be_bytes().to_vec();
+        out.extend_from_slice(body);
+        out
+    }
+
+    /// TLS 1.3 ClientHello for example.org with an X25519MLKEM768 key share.
+    fn client_hello() -> Vec<u8> {
+        let mut exts = Vec::new();
+        let mut ext = |id: u16, data: Vec<u8>| {
+            exts.extend_from_slice(&id.to_be_bytes());
+            exts.extend(vec16(&data));
+        };
+        let mut name = vec![0];
+        name.extend(vec16(b"example.org"));
+        ext(0, vec16(&name));
+        ext(10, vec16(&[0x11, 0xec, 0, 0x1d]));
+        ext(16, vec16(b"\x02h2"));
+        ext(43, vec![2, 3, 4]);
+        let mut shares = vec![0x11, 0xec];
+        shares.extend(vec16(&[0x42; 1216]));
+        ext(51, vec16(&shares));
+
+        let mut body = vec![3, 3];
+        body.extend([7; 32]);
+        body.push(0);
+        body.extend(vec16(&[0x13, 0x01, 0x13, 0x02]));
+        body.extend([1, 0]);
+        body.extend(vec16(&exts));
+        let mut handshake = vec![1, 0];
+        handshake.extend((body.len() as u16).to_be_bytes());
+        handshake.extend(body);
+        let mut record = vec![CONTENT_HANDSHAKE, 3, 1];
+        record.extend(vec16(&handshake));
+        record
+    }
+
+    #[test]
+    fn hello_split_across_segments() {
+        let hello = client_hello();
+        let (first, second) = hello.split_at(1000);
+        // Neither half is decoded on its own.
+        assert!(tls::parse(first).is_none());
+
+        let mut streams = TcpReassembler::new();
+        let mut hellos = HelloReassembler::new();
+        let packets = [
+            segment(true, 100, TCP_SYN, &[]),
+            segment(false, 500, TCP_SYN | TCP_ACK, &[]),
+            segment(true, 101, TCP_ACK, first),
+            segment(true, 1101, TCP_ACK, second),
+        ];
+        let mut decoded = Vec::new();
+        for mut packet in packets {
+            let events = streams.observe(&packet);
+            hellos.observe(&mut packet, &events);
+            decoded.push(packet.tls);
+        }
+        assert!(decoded[..3].iter().all(Option::is_none));
+        let info = decoded[3].as_ref().expect("hello décodé sur le second segment");
+        let ch = info.client_hello.as_ref().unwrap();
+        assert_eq!(ch.sni.as_deref(), Some("example.org"));
+        assert_eq!(ch.alpn, ["h2"]);
+        assert_eq!(ch.key_share_groups, [0x11ec]);
+        assert_eq!(ch.extensions, [0, 10, 16, 43, 51]);
+        assert!(info.fingerprints.ja4.as_deref().unwrap().starts_with("t13d0205h2_"));
+    }
+
+    #[test]
+    fn later_packets_match_the_connection_sni() {
+        use crate::core::filter::Filter;
+        use crate::core::fingerprint::FingerprintTracker;
+
+        let mut filter = Filter::default();
+        filter.add("tls.sni~example.org").unwrap();
+        let mut tracker = FingerprintTracker::new();
+        let hello = client_hello();
+        let packets = [
+            segment(true, 100, TCP_SYN, &[]),
+            segment(true, 101, TCP_ACK, &hello),
+            segment(false, 501, TCP_ACK, b"\x17\x03\x03\x00\x01x"),
+        ];
+        let mut matched = Vec::new();
+        for mut packet in packets {
+            tracker.observe(&mut packet);
+            matched.push(filter.matches(&packet));
+        }
+        assert_eq!(matched, [false, true, true]);
+    }
+}
@@ -0,0 +1,489 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::OnceLock;
+
+/// RFC 7541 Appendix A.
+const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+/// RFC 7541 Appendix B: (code, bit length) of every symbol, 256 = EOS.
+const HUFFMAN_CODES: [(u32, u8); 257] = [
+    (0x1ff8, 13), (0x7fffd8, 23), (0xfffffe2, 28), (0xfffffe3, 28),
+    (0xfffffe4, 28), (0xfffffe5, 28), (0xfffffe6, 28), (0xfffffe7, 28),
+    (0xfffffe8, 28), (0xffffea, 24), (0x3ffffffc, 30), (0xfffffe9, 28),
+    (0xfffffea, 28), (0x3ffffffd, 30), (0xfffffeb, 28), (0xfffffec, 28),
+    (0xfffffed, 28), (0xfffffee, 28), (0xfffffef, 28), (0xffffff0, 28),
+    (0xffffff1, 28), (0xffffff2, 28), (0x3ffffffe, 30), (0xffffff3, 28),
+    (0xffffff4, 28), (0xffffff5, 28), (0xffffff6, 28), (0xffffff7, 28),
+    (0xffffff8, 28), (0xffffff9, 28), (0xffffffa, 28), (0xffffffb, 28),
+    (0x14, 6), (0x3f8, 10), (0x3f9, 10), (0xffa, 12),
+    (0x1ff9, 13), (0x15, 6), (0xf8, 8), (0x7fa, 11),
+    (0x3fa, 10), (0x3fb, 10), (0xf9, 8), (0x7fb, 11),
+    (0xfa, 8), (0x16, 6), (0x17, 6), (0x18, 6),
+    (0x0, 5), (0x1, 5), (0x2, 5), (0x19, 6),
+    (0x1a, 6), (0x1b, 6), (0x1c, 6), (0x1d, 6),
+    (0x1e, 6), (0x1f, 6), (0x5c, 7), (0xfb, 8),
+    (0x7ffc, 15), (0x20, 6), (0xffb, 12), (0x3fc, 10),
+    (0x1ffa, 13), (0x21, 6), (0x5d, 7), (0x5e, 7),
+    (0x5f, 7), (0x60, 7), (0x61, 7), (0x62, 7),
+    (0x63, 7), (0x64, 7), (0x65, 7), (0x66, 7),
+    (0x67, 7), (0x68, 7), (0x69, 7), (0x6a, 7),
+    (0x6b, 7), (0x6c, 7), (0x6d, 7), (0x6e, 7),
+    (0x6f, 7), (0x70, 7), (0x71, 7), (0x72, 7),
+    (0xfc, 8), (0x73, 7), (0xfd, 8), (0x1ffb, 13),
+    (0x7fff0, 19), (0x1ffc, 13), (0x3ffc, 14), (0x22, 6),
+    (0x7ffd, 15), (0x3, 5), (0x23, 6), (0x4, 5),
+    (0x24, 6), (0x5, 5), (0x25, 6), (0x26, 6),
+    (0x27, 6), (0x6, 5), (0x74, 7), (0x75, 7),
+    (0x28, 6), (0x29, 6), (0x2a, 6), (0x7, 5),
+    (0x2b, 6), (0x76, 7), (0x2c, 6), (0x8, 5),
+    (0x9, 5), (0x2d, 6), (0x77, 7), (0x78, 7),
+    (0x79, 7), (0x7a, 7), (0x7b, 7), (0x7ffe, 15),
+    (0x7fc, 11), (0x3ffd, 14), (0x1ffd, 13), (0xffffffc, 28),
+    (0xfffe6, 20), (0x3fffd2, 22), (0xfffe7, 20), (0xfffe8, 20),
+    (0x3fffd3, 22), (0x3fffd4, 22), (0x3fffd5, 22), (0x7fffd9, 23),
+    (0x3fffd6, 22), (0x7fffda, 23), (0x7fffdb, 23), (0x7fffdc, 23),
+    (0x7fffdd, 23), (0x7fffde, 23), (0xffffeb, 24), (0x7fffdf, 23),
+    (0xffffec, 24), (0xffffed, 24), (0x3fffd7, 22), (0x7fffe0, 23),
+    (0xffffee, 24), (0x7fffe1, 23), (0x7fffe2, 23), (0x7fffe3, 23),
+    (0x7fffe4, 23), (0x1fffdc, 21), (0x3fffd8, 22), (0x7fffe5, 23),
+    (0x3fffd9, 22), (0x7fffe6, 23), (0x7fffe7, 23), (0xffffef, 24),
+    (0x3fffda, 22), (0x1fffdd, 21), (0xfffe9, 20), (0x3fffdb, 22),
+    (0x3fffdc, 22), (0x7fffe8, 23), (0x7fffe9, 23), (0x1fffde, 21),
+    (0x7fffea, 23), (0x3fffdd, 22), (0x3fffde, 22), (0xfffff0, 24),
+    (0x1fffdf, 21), (0x3fffdf, 22), (0x7fffeb, 23), (0x7fffec, 23),
+    (0x1fffe0, 21), (0x1fffe1, 21), (0x3fffe0, 22), (0x1fffe2, 21),
+    (0x7fffed, 23), (0x3fffe1, 22), (0x7fffee, 23), (0x7fffef, 23),
+    (0xfffea, 20), (0x3fffe2, 22), (0x3fffe3, 22), (0x3fffe4, 22),
+    (0x7ffff0, 23), (0x3fffe5, 22), (0x3fffe6, 22), (0x7ffff1, 23),
+    (0x3ffffe0, 26), (0x3ffffe1, 26), (0xfffeb, 20), (0x7fff1, 19),
+    (0x3fffe7, 22), (0x7ffff2, 23), (0x3fffe8, 22), (0x1ffffec, 25),
+    (0x3ffffe2, 26), (0x3ffffe3, 26), (0x3ffffe4, 26), (0x7ffffde, 27),
+    (0x7ffffdf, 27), (0x3ffffe5, 26), (0xfffff1, 24), (0x1ffffed, 25),
+    (0x7fff2, 19), (0x1fffe3, 21), (0x3ffffe6, 26), (0x7ffffe0, 27),
+    (0x7ffffe1, 27), (0x3ffffe7, 26), (0x7ffffe2, 27), (0xfffff2, 24),
+    (0x1fffe4, 21), (0x1fffe5, 21), (0x3ffffe8, 26), (0x3ffffe9, 26),
+    (0xffffffd, 28), (0x7ffffe3, 27), (0x7ffffe4, 27), (0x7ffffe5, 27),
+    (0xfffec, 20), (0xfffff3, 24), (0xfffed, 20), (0x1fffe6, 21),
+    (0x3fffe9, 22), (0x1fffe7, 21), (0x1fffe8, 21), (0x7ffff3, 23),
+    (0x3fffea, 22), (0x3fffeb, 22), (0x1ffffee, 25), (0x1ffffef, 25),
+    (0xfffff4, 24), (0xfffff5, 24), (0x3ffffea, 26), (0x7ffff4, 23),
+    (0x3ffffeb, 26), (0x7ffffe6, 27), (0x3ffffec, 26), (0x3ffffed, 26),
+    (0x7ffffe7, 27), (0x7ffffe8, 27), (0x7ffffe9, 27), (0x7ffffea, 27),
+    (0x7ffffeb, 27), (0xffffffe, 28), (0x7ffffec, 27), (0x7ffffed, 27),
+    (0x7ffffee, 27), (0x7ffffef, 27), (0x7fffff0, 27), (0x3ffffee, 26),
+    (0x3fffffff, 30),
+];
+
+/// Upper bound on a dynamic table, whatever the peer announces.
+const MAX_TABLE_SIZE: usize = 64 * 1024;
+
+fn huffman_lookup() -> &'static HashMap<(u8, u32), u16> {
+    static LOOKUP: OnceLock<HashMap<(u8, u32), u16>> = OnceLock::new();
+    LOOKUP.get_or_init(|| {
+        HUFFMAN_CODES
+            .iter()
+            .enumerate()
+            .map(|(sym, &(code, bits))| ((bits, code), sym as u16))
+            .collect()
+    })
+}
+
+pub fn huffman_decode(data: &[u8]) -> Option<Vec<u8>> {
+    let lookup = huffman_lookup();
+    let mut out = Vec::with_capacity(data.len() * 8 / 5);
+    let mut code: u32 = 0;
+    let mut bits: u8 = 0;
+
+    for byte in data {
+        for shift in (0..8).rev() {
+            code = (code << 1) | ((byte >> shift) & 1) as u32;
+            bits += 1;
+            if bits < 5 {
+                continue;
+            }
+            if let Some(&sym) = lookup.get(&(bits, code)) {
+                if sym == 256 {
+                    return None;
+                }
+                out.push(sym as u8);
+                code = 0;
+                bits = 0;
+            } else if bits >= 30 {
+                return None;
+            }
+        }
+    }
+
+    // Padding: at most 7 bits, all set (prefix of EOS).
+    if bits > 7 || code != (1 << bits) - 1 {
+        return None;
+    }
+    Some(out)
+}
+
+/// Integer with an N-bit prefix (RFC 7541 §5.1). Returns (value, next offset).
+fn decode_int(data: &[u8], off: usize, prefix_bits: u8) -> Option<(usize, usize)> {
+    let mask = (1usize << prefix_bits) - 1;
+    let mut value = (*data.get(off)? as usize) & mask;
+    let mut pos = off + 1;
+    if value < mask {
+        return Some((value, pos));
+    }
+
+    let mut shift = 0;
+    loop {
+        let b = *data.get(pos)?;
+        pos += 1;
+        value = value.checked_add(((b & 0x7f) as usize).checked_shl(shift)?)?;
+        if b & 0x80 == 0 {
+            return Some((value, pos));
+        }
+        shift += 7;
+        if shift > 28 {
+            return None;
+        }
+    }
+}
+
+fn decode_string(data: &[u8], off: usize) -> Option<(String, usize)> {
+    let huffman = *data.get(off)? & 0x80 != 0;
+    let (len, start) = decode_int(data, off, 7)?;
+    let raw = data.get(start..start.checked_add(len)?)?;
+    let bytes = if huffman { huffman_decode(raw)? } else { raw.to_vec() };
+    Some((String::from_utf8_lossy(&bytes).to_string(), start + len))
+}
+
+fn invalid(what: &str, off: usize) -> String {
+    format!("HPACK : {what} invalide à l'octet {off}")
+}
+
+/// HPACK decoder for one direction of an HTTP/2 connection: the dynamic
+/// table persists from one header block to the next.
+#[derive(Debug)]
+pub struct HpackDecoder {
+    dynamic: VecDeque<(String, String)>,
+    size: usize,
+    max_size: usize,
+}
+
+impl Default for HpackDecoder {
+    fn default() -> Self {
+        HpackDecoder {
+            dynamic: VecDeque::new(),
+            size: 0,
+            max_size: 4096,
+        }
+    }
+}
+
+impl HpackDecoder {
+    fn entry(&self, index: usize) -> Option<(String, String)> {
+        match index {
+            0 => None,
+            1..=61 => STATIC_TABLE.get(index - 1).map(|(n, v)| (n.to_string(), v.to_string())),
+            _ => self.dynamic.get(index - 62).cloned(),
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.size > self.max_size {
+            match self.dynamic.pop_back() {
+                Some((n, v)) => self.size -= n.len() + v.len() + 32,
+                None => break,
+            }
+        }
+    }
+
+    fn insert(&mut self, name: String, value: String) {
+        let entry_size = name.len() + value.len() + 32;
+        self.size += entry_size;
+        self.dynamic.push_front((name, value));
+        self.evict();
+    }
+
+    /// Decodes a complete header block (HEADERS/PUSH_PROMISE + CONTINUATIONs).
+    pub fn decode(&mut self, block: &[u8]) -> Result<Vec<(String, String)>, String> {
+        let mut headers = Vec::new();
+        let mut off = 0;
+
+        while off < block.len() {
+            let b = block[off];
+            if b & 0x80 != 0 {
+                // Indexed header field
+                let (index, next) = decode_int(block, off, 7).ok_or_else(|| invalid("index", off))?;
+                headers.push(self.entry(index).ok_or_else(|| invalid("index", off))?);
+                off = next;
+            } else if b & 0xe0 == 0x20 {
+                // Dynamic table size update
+                let (size, next) = decode_int(block, off, 5).ok_or_else(|| invalid("taille de table", off))?;
+                self.max_size = size.min(MAX_TABLE_SIZE);
+                self.evict();
+                off = next;
+            } else {
+                // Literal: with incremental indexing (01), without (0000) or never indexed (0001)
+                let indexing = b & 0xc0 == 0x40;
+                let prefix = if indexing { 6 } else { 4 };
+                let (index, next) = decode_int(block, off, prefix).ok_or_else(|| invalid("littéral", off))?;
+                let (name, next) = if index == 0 {
+                    decode_string(block, next).ok_or_else(|| invalid("nom", off))?
+                } else {
+                    (self.entry(index).ok_or_else(|| invalid("index", off))?.0, next)
+                };
+                let (value, next) = decode_string(block, next).ok_or_else(|| invalid("valeur", off))?;
+                if indexing {
+                    self.insert(name.clone(), value.clone());
+                }
+                headers.push((name, value));
+                off = next;
+            }
+        }
+
+        Ok(headers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(hex: &str) -> Vec<u8> {
+        let digits: Vec<u8> = hex.bytes().filter(u8::is_ascii_hexdigit).collect();
+        digits
+            .chunks(2)
+            .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16).unwrap())
+            .collect()
+    }
+
+    type Headers = &'static [(&'static str, &'static str)];
+
+    /// Decodes consecutive header blocks with one decoder, checking the
+    /// headers and the dynamic table size after each.
+    fn check(mut decoder: HpackDecoder, blocks: &[(&str, Headers, usize)]) {
+        for (i, (hex, expected, size)) in blocks.iter().enumerate() {
+            let headers = decoder.decode(&bytes(hex)).unwrap();
+            let expected: Vec<(String, String)> =
+                expected.iter().map(|(n, v)| (n.to_string(), v.to_string())).collect();
+            assert_eq!(headers, expected, "bloc {}", i + 1);
+            assert_eq!(decoder.size, *size, "bloc {}", i + 1);
+        }
+    }
+
+    const REQUEST_1: Headers =
+        &[(":method", "GET"), (":scheme", "http"), (":path", "/"), (":authority", "www.example.com")];
+    const REQUEST_2: Headers = &[
+        (":method", "GET"),
+        (":scheme", "http"),
+        (":path", "/"),
+        (":authority", "www.example.com"),
+        ("cache-control", "no-cache"),
+    ];
+    const REQUEST_3: Headers = &[
+        (":method", "GET"),
+        (":scheme", "https"),
+        (":path", "/index.html"),
+        (":authority", "www.example.com"),
+        ("custom-key", "custom-value"),
+    ];
+
+    const RESPONSE_1: Headers = &[
+        (":status", "302"),
+        ("cache-control", "private"),
+        ("date", "Mon, 21 Oct 2013 20:13:21 GMT"),
+        ("location", "https://www.example.com"),
+    ];
+    const RESPONSE_2: Headers = &[
+        (":status", "307"),
+        ("cache-control", "private"),
+        ("date", "Mon, 21 Oct 2013 20:13:21 GMT"),
+        ("location", "https://www.example.com"),
+    ];
+    const RESPONSE_3: Headers = &[
+        (":status", "200"),
+        ("cache-control", "private"),
+        ("date", "Mon, 21 Oct 2013 20:13:22 GMT"),
+        ("location", "https://www.example.com"),
+        ("content-encoding", "gzip"),
+        ("set-cookie", "foo=ASDJKHQKBZXOQWEOPIUAXQWEOIU; max-age=3600; version=1"),
+    ];
+
+    fn small_table() -> HpackDecoder {
+        HpackDecoder {
+            max_size: 256,
+            ..Default::default()
+        }
+    }
+
+    /// RFC 7541 C.1.
+    #[test]
+    fn integers() {
+        assert_eq!(decode_int(&[0x0a], 0, 5), Some((10, 1)));
+        assert_eq!(decode_int(&[0x1f, 0x9a, 0x0a], 0, 5), Some((1337, 3)));
+        assert_eq!(decode_int(&[0x2a], 0, 8), Some((42, 1)));
+        assert_eq!(decode_int(&[0x1f, 0x9a], 0, 5), None);
+        assert_eq!(decode_int(&[0x1f, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01], 0, 5), None);
+    }
+
+    /// RFC 7541 C.3.
+    #[test]
+    fn requests_without_huffman() {
+        check(
+            HpackDecoder::default(),
+            &[
+                ("8286 8441 0f77 7777 2e65 7861 6d70 6c65 2e63 6f6d", REQUEST_1, 57),
+                ("8286 84be 5808 6e6f 2d63 6163 6865", REQUEST_2, 110),
+                (
+                    "8287 85bf 400a 6375 7374 6f6d 2d6b 6579 0c63 7573 746f 6d2d 7661 6c75 65",
+                    REQUEST_3,
+                    164,
+                ),
+            ],
+        );
+    }
+
+    /// RFC 7541 C.4.
+    #[test]
+    fn requests_with_huffman() {
+        check(
+            HpackDecoder::default(),
+            &[
+                ("8286 8441 8cf1 e3c2 e5f2 3a6b a0ab 90f4 ff", REQUEST_1, 57),
+                ("8286 84be 5886 a8eb 1064 9cbf", REQUEST_2, 110),
+                ("8287 85bf 4088 25a8 49e9 5ba9 7d7f 8925 a849 e95b b8e8 b4bf", REQUEST_3, 164),
+            ],
+        );
+    }
+
+    /// RFC 7541 C.5: a 256-byte table, so entries get evicted.
+    #[test]
+    fn responses_without_huffman() {
+        check(
+            small_table(),
+            &[
+                (
+                    "4803 3330 3258 0770 7269 7661 7465 611d 4d6f 6e2c 2032 3120 4f63 7420 3230 3133 \
+                     2032 303a 3133 3a32 3120 474d 546e 1768 7474 7073 3a2f 2f77 7777 2e65 7861 6d70 \
+                     6c65 2e63 6f6d",
+                    RESPONSE_1,
+                    222,
+                ),
+                ("4803 3330 37c1 c0bf", RESPONSE_2, 222),
+                (
+                    "88c1 611d 4d6f 6e2c 2032 3120 4f63 7420 3230 3133 2032 303a 3133 3a32 3220 474d \
+                     54c0 5a04 677a 6970 7738 666f 6f3d 4153 444a 4b48 514b 425a 584f 5157 454f 5049 \
+                     5541 5851 5745 4f49 553b 206d 6178 2d61 6765 3d33 3630 303b 2076 6572 7369 6f6e \
+                     3d31",
+                    RESPONSE_3,
+                    215,
+                ),
+            ],
+        );
+    }
+
+    /// RFC 7541 C.6.
+    #[test]
+    fn responses_with_huffman() {
+        check(
+            small_table(),
+            &[
+                (
+                    "4882 6402 5885 aec3 771a 4b61 96d0 7abe 9410 54d4 44a8 2005 9504 0b81 66e0 82a6 \
+                     2d1b ff6e 919d 29ad 1718 63c7 8f0b 97c8 e9ae 82ae 43d3",
+                    RESPONSE_1,
+                    222,
+                ),
+                ("4883 640e ffc1 c0bf", RESPONSE_2, 222),
+                (
+                    "88c1 6196 d07a be94 1054 d444 a820 0595 040b 8166 e084 a62d 1bff c05a 839b d9ab \
+                     77ad 94e7 821d d7f2 e6c7 b335 dfdf cd5b 3960 d5af 2708 7f36 72c1 ab27 0fb5 291f \
+                     9587 3160 65c0 03ed 4ee5 b106 3d50 07",
+                    RESPONSE_3,
+                    215,
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn size_update_evicts_and_is_capped() {
+        let mut decoder = HpackDecoder::default();
+        decoder.decode(&bytes("8286 8441 0f77 7777 2e65 7861 6d70 6c65 2e63 6f6d")).unwrap();
+        assert_eq!(decoder.dynamic.len(), 1);
+        // Size update to 0, then a reference to the evicted entry.
+        assert!(decoder.decode(&bytes("20 be")).is_err());
+        assert!(decoder.dynamic.is_empty());
+        // 2^21 - 1 + 31 announced, capped.
+        decoder.decode(&bytes("3f e1 ff 7f")).unwrap();
+        assert_eq!(decoder.max_size, MAX_TABLE_SIZE);
+    }
+
+    #[test]
+    fn rejects_bad_huffman_padding_and_indexes() {
+        // "www.example.com" (C.4.1), then with zero padding, then an EOS.
+        assert!(huffman_decode(&bytes("f1e3 c2e5 f23a 6ba0 ab90 f4ff")).is_some());
+        assert!(huffman_decode(&bytes("f1e3 c2e5 f23a 6ba0 ab90 f400")).is_none());
+        assert!(huffman_decode(&bytes("ffff ffff")).is_none());
+        assert!(HpackDecoder::default().decode(&[0x80]).is_err());
+        assert!(HpackDecoder::default().decode(&[0xbe]).is_err());
+    }
+}
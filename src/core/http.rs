@@ -0,0 +1,436 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::core::models::{HttpRequest, HttpResponse, HttpTransaction, Packet};
+use crate::core::stream::{Direction, StreamEvent, StreamKey};
+
+const METHODS: &[&str] = &[
+    "GET", "POST", "PUT", "DELETE", "HEAD", "OPTIONS", "PATCH", "CONNECT", "TRACE",
+    "PROPFIND", "PROPPATCH", "MKCOL", "COPY", "MOVE", "LOCK", "UNLOCK", "REPORT",
+];
+
+/// Largest accepted request/status line + headers block.
+const MAX_HEAD: usize = 64 * 1024;
+/// Body bytes kept in memory per message (the length is still counted).
+const MAX_BODY: usize = 16 * 1024 * 1024;
+/// Connections followed at the same time; beyond that the table is reset.
+const MAX_CONNECTIONS: usize = 4096;
+/// Requests waiting for their response on one connection.
+const MAX_PENDING: usize = 64;
+
+pub fn looks_like_request(data: &[u8]) -> bool {
+    METHODS
+        .iter()
+        .any(|m| data.starts_with(m.as_bytes()) && data.get(m.len()) == Some(&b' '))
+}
+
+pub fn looks_like_response(data: &[u8]) -> bool {
+    data.starts_with(b"HTTP/1.")
+}
+
+/// Case-insensitive header lookup (first occurrence).
+pub fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// Decodes `%XX` escapes (URIs, `filename*`, `grpc-message`).
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let digits = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(b) = u8::from_str_radix(digits, 16) {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+fn is_chunked(headers: &[(String, String)]) -> bool {
+    header(headers, "Transfer-Encoding")
+        .map(|v| v.to_ascii_lowercase().contains("chunked"))
+        .unwrap_or(false)
+}
+
+fn content_length(headers: &[(String, String)]) -> Option<usize> {
+    header(headers, "Content-Length").and_then(|v| v.trim().parse().ok())
+}
+
+enum Message {
+    Request(HttpRequest),
+    Response(HttpResponse),
+}
+
+impl Message {
+    fn body_mut(&mut self) -> (&mut Vec<u8>, &mut usize) {
+        match self {
+            Message::Request(r) => (&mut r.body, &mut r.body_len),
+            Message::Response(r) => (&mut r.body, &mut r.body_len),
+        }
+    }
+}
+
+enum Stage {
+    Head,
+    Body(usize),
+    ChunkSize,
+    ChunkData(usize),
+    ChunkEnd,
+    Trailers,
+    UntilClose,
+}
+
+enum Step {
+    Continue,
+    NeedMore,
+    Done(Message, u128),
+    Error,
+}
+
+fn find_crlf(buf: &[u8]) -> Option<usize> {
+    buf.windows(2).position(|w| w == b"\r\n")
+}
+
+fn parse_head(head: &[u8]) -> Option<Message> {
+    let text = String::from_utf8_lossy(head);
+    let mut lines = text.split("\r\n");
+    let start = lines.next()?;
+
+    let mut headers = Vec::new();
+    for line in lines {
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let mut parts = start.splitn(3, ' ');
+    let first = parts.next()?;
+    let second = parts.next()?;
+    let third = parts.next().unwrap_or("");
+
+    if first.starts_with("HTTP/1.") {
+        return Some(Message::Response(HttpResponse {
+            version: first.to_string(),
+            status: second.parse().ok()?,
+            reason: third.to_string(),
+            headers,
+            ..Default::default()
+        }));
+    }
+    if !METHODS.contains(&first) || !third.starts_with("HTTP/") {
+        return None;
+    }
+    Some(Message::Request(HttpRequest {
+        method: first.to_string(),
+        uri: second.to_string(),
+        version: third.to_string(),
+        host: header(&headers, "Host").map(str::to_string),
+        headers,
+        ..Default::default()
+    }))
+}
+
+/// Incremental parser for the messages of one direction of a connection.
+struct MessageParser {
+    buf: Vec<u8>,
+    stage: Stage,
+    current: Option<Message>,
+    head_ts: u128,
+}
+
+impl Default for MessageParser {
+    fn default() -> Self {
+        MessageParser {
+            buf: Vec::new(),
+            stage: Stage::Head,
+            current: None,
+            head_ts: 0,
+        }
+    }
+}
+
+impl MessageParser {
+    fn keep_body(&mut self, len: usize) {
+        let Some(msg) = self.current.as_mut() else {
+            return;
+        };
+        let (body, body_len) = msg.body_mut();
+        let room = MAX_BODY.saturating_sub(body.len()).min(len);
+        body.extend_from_slice(&self.buf[..room]);
+        *body_len += len;
+        self.buf.drain(..len);
+    }
+
+    fn finish(&mut self) -> Step {
+        self.stage = Stage::Head;
+        match self.current.take() {
+            Some(msg) => Step::Done(msg, self.head_ts),
+            None => Step::Error,
+        }
+    }
+
+    /// `request_method` is the method of the request a response answers,
+    /// needed to know whether the response carries a body.
+    fn step(&mut self, request_method: Option<&str>, ts: u128) -> Step {
+        match self.stage {
+            Stage::Head => {
+                let Some(end) = self.buf.windows(4).position(|w| w == b"\r\n\r\n") else {
+                    return if self.buf.len() > MAX_HEAD { Step::Error } else { Step::NeedMore };
+                };
+                let Some(msg) = parse_head(&self.buf[..end]) else {
+                    return Step::Error;
+                };
+                self.buf.drain(..end + 4);
+                self.head_ts = ts;
+
+                self.stage = match &msg {
+                    Message::Request(r) if is_chunked(&r.headers) => Stage::ChunkSize,
+                    Message::Request(r) => Stage::Body(content_length(&r.headers).unwrap_or(0)),
+                    Message::Response(r)
+                        if request_method == Some("HEAD")
+                            || (100..200).contains(&r.status)
+                            || r.status == 204
+                            || r.status == 304 =>
+                    {
+                        Stage::Body(0)
+                    }
+                    Message::Response(r) if is_chunked(&r.headers) => Stage::ChunkSize,
+                    Message::Response(r) => match content_length(&r.headers) {
+                        Some(len) => Stage::Body(len),
+                        None => Stage::UntilClose,
+                    },
+                };
+                self.current = Some(msg);
+                Step::Continue
+            }
+            Stage::Body(remaining) => {
+                let take = remaining.min(self.buf.len());
+                self.keep_body(take);
+                if take == remaining {
+                    return self.finish();
+                }
+                self.stage = Stage::Body(remaining - take);
+                Step::NeedMore
+            }
+            Stage::ChunkSize => {
+                let Some(end) = find_crlf(&self.buf) else {
+                    return if self.buf.len() > MAX_HEAD { Step::Error } else { Step::NeedMore };
+                };
+                let line = String::from_utf8_lossy(&self.buf[..end]).to_string();
+                let size = line.split(';').next().unwrap_or("").trim();
+                let Ok(size) = usize::from_str_radix(size, 16) else {
+                    return Step::Error;
+                };
+                self.buf.drain(..end + 2);
+                self.stage = if size == 0 { Stage::Trailers } else { Stage::ChunkData(size) };
+                Step::Continue
+            }
+            Stage::ChunkData(remaining) => {
+                let take = remaining.min(self.buf.len());
+                self.keep_body(take);
+                if take < remaining {
+                    self.stage = Stage::ChunkData(remaining - take);
+                    return Step::NeedMore;
+                }
+                self.stage = Stage::ChunkEnd;
+                Step::Continue
+            }
+            Stage::ChunkEnd => {
+                if self.buf.len() < 2 {
+                    return Step::NeedMore;
+                }
+                self.buf.drain(..2);
+                self.stage = Stage::ChunkSize;
+                Step::Continue
+            }
+            Stage::Trailers => {
+                let Some(end) = find_crlf(&self.buf) else {
+                    return Step::NeedMore;
+                };
+                self.buf.drain(..end + 2);
+                if end == 0 {
+                    return self.finish();
+                }
+                Step::Continue
+            }
+            Stage::UntilClose => {
+                let len = self.buf.len();
+                self.keep_body(len);
+                Step::NeedMore
+            }
+        }
+    }
+}
+
+#[derive(Default, PartialEq, Eq)]
+enum Detection {
+    #[default]
+    Unknown,
+    Http,
+    NotHttp,
+}
+
+#[derive(Default)]
+struct HttpConnection {
+    detection: Detection,
+    requests: MessageParser,
+    responses: MessageParser,
+    pending: VecDeque<(HttpRequest, u128)>,
+}
+
+impl HttpConnection {
+    fn feed(&mut self, direction: Direction, data: &[u8], ts: u128) -> Vec<HttpTransaction> {
+        if self.detection == Detection::Unknown {
+            let http = match direction {
+                Direction::ToServer => looks_like_request(data),
+                Direction::ToClient => looks_like_response(data),
+            };
+            self.detection = if http { Detection::Http } else { Detection::NotHttp };
+        }
+        if self.detection != Detection::Http {
+            return Vec::new();
+        }
+
+        let mut out = Vec::new();
+        match direction {
+            Direction::ToServer => self.requests.buf.extend_from_slice(data),
+            Direction::ToClient => self.responses.buf.extend_from_slice(data),
+        }
+
+        while self.detection == Detection::Http {
+            let method = self.pending.front().map(|(r, _)| r.method.clone());
+            let parser = match direction {
+                Direction::ToServer => &mut self.requests,
+                Direction::ToClient => &mut self.responses,
+            };
+            match parser.step(method.as_deref(), ts) {
+                Step::Continue => {}
+                Step::NeedMore => break,
+                Step::Done(msg, head_ts) => self.complete(msg, head_ts, &mut out),
+                // Lost sync: stop parsing this connection.
+                Step::Error => self.detection = Detection::NotHttp,
+            }
+        }
+        out
+    }
+
+    fn complete(&mut self, msg: Message, head_ts: u128, out: &mut Vec<HttpTransaction>) {
+        match msg {
+            Message::Request(req) => {
+                if self.pending.len() >= MAX_PENDING {
+                    self.pending.pop_front();
+                }
+                self.pending.push_back((req.clone(), head_ts));
+                out.push(HttpTransaction {
+                    request: Some(req),
+                    ..Default::default()
+                });
+            }
+            // Interim response (100 Continue...): the final one follows.
+            Message::Response(resp) if (100..200).contains(&resp.status) && resp.status != 101 => {}
+            Message::Response(resp) => {
+                let (request, latency_ms) = match self.pending.pop_front() {
+                    Some((req, req_ts)) => (Some(req), Some(head_ts.saturating_sub(req_ts) as u64)),
+                    None => (None, None),
+                };
+                let upgraded = resp.status == 101
+                    || (resp.status / 100 == 2
+                        && request.as_ref().map(|r| r.method == "CONNECT").unwrap_or(false));
+                out.push(HttpTransaction {
+                    request,
+                    response: Some(resp),
+                    latency_ms,
+                });
+                if upgraded {
+                    self.detection = Detection::NotHttp;
+                }
+            }
+        }
+    }
+
+    /// Connection closed: a response delimited by the close is complete.
+    fn close(&mut self) -> Vec<HttpTransaction> {
+        let mut out = Vec::new();
+        if self.detection == Detection::Http && matches!(self.responses.stage, Stage::UntilClose) {
+            if let Step::Done(msg, head_ts) = self.responses.finish() {
+                self.complete(msg, head_ts, &mut out);
+            }
+        }
+        out
+    }
+}
+
+/// Follows HTTP/1.x conversations over reassembled TCP streams, on any
+/// port, and pairs responses with their (possibly pipelined) requests.
+#[derive(Default)]
+pub struct HttpTracker {
+    conns: HashMap<StreamKey, HttpConnection>,
+}
+
+impl HttpTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn on_event(&mut self, event: &StreamEvent, ts: u128) -> Vec<HttpTransaction> {
+        match event {
+            StreamEvent::Data { key, direction, data } => {
+                if !self.conns.contains_key(key) && self.conns.len() >= MAX_CONNECTIONS {
+                    self.conns.clear();
+                }
+                self.conns.entry(key.clone()).or_default().feed(*direction, data, ts)
+            }
+            // Lost bytes: the parser cannot resync mid-message, restart
+            // detection on the next data.
+            StreamEvent::Gap { key, .. } => {
+                self.conns.remove(key);
+                Vec::new()
+            }
+            StreamEvent::Closed { key, .. } => self
+                .conns
+                .remove(key)
+                .map(|mut c| c.close())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// End of the capture: completes the responses waiting for a close.
+    pub fn finish(&mut self) -> Vec<HttpTransaction> {
+        self.conns.drain().flat_map(|(_, mut c)| c.close()).collect()
+    }
+
+    /// Attaches the transactions completed by this packet's stream data.
+    pub fn observe(&mut self, packet: &mut Packet, events: &[StreamEvent]) {
+        for event in events {
+            let done = self.on_event(event, packet.timestamp);
+            packet.http.extend(done);
+        }
+    }
+}
+
+/// Short description used in the live table next to the protocol label.
+pub fn summary(transactions: &[HttpTransaction]) -> Option<String> {
+    let tx = transactions.last()?;
+    match (&tx.request, &tx.response) {
+        (_, Some(resp)) => {
+            let uri = tx.request.as_ref().map(|r| r.uri.as_str()).unwrap_or("");
+            Some(format!("{} {}", resp.status, uri).trim_end().to_string())
+        }
+        (Some(req), None) => Some(format!(
+            "{} {}{}",
+            req.method,
+            req.host.as_deref().unwrap_or(""),
+            req.uri
+        )),
+        (None, None) => None,
+    }
+}
@@ -0,0 +1,537 @@
+use std::collections::HashMap;
+
+use crate::core::hpack::HpackDecoder;
+use crate::core::http;
+use crate::core::models::{Http2Frame, Http2Info, Http2Stream, Packet};
+use crate::core::stream::{Direction, StreamEvent, StreamKey};
+
+pub const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+pub const FRAME_DATA: u8 = 0x0;
+pub const FRAME_HEADERS: u8 = 0x1;
+pub const FRAME_PRIORITY: u8 = 0x2;
+pub const FRAME_RST_STREAM: u8 = 0x3;
+pub const FRAME_SETTINGS: u8 = 0x4;
+pub const FRAME_PUSH_PROMISE: u8 = 0x5;
+pub const FRAME_PING: u8 = 0x6;
+pub const FRAME_GOAWAY: u8 = 0x7;
+pub const FRAME_WINDOW_UPDATE: u8 = 0x8;
+pub const FRAME_CONTINUATION: u8 = 0x9;
+
+const FLAG_END_STREAM: u8 = 0x01;
+const FLAG_ACK: u8 = 0x01;
+const FLAG_END_HEADERS: u8 = 0x04;
+const FLAG_PADDED: u8 = 0x08;
+const FLAG_PRIORITY: u8 = 0x20;
+
+/// Frame payloads are at most 2^24-1 bytes.
+const MAX_FRAME: usize = (1 << 24) - 1;
+/// Body bytes kept in memory per stream and direction.
+const MAX_BODY: usize = 16 * 1024 * 1024;
+/// Connections followed at the same time; beyond that the table is reset.
+const MAX_CONNECTIONS: usize = 4096;
+/// Open streams per connection; beyond that the stream table is reset.
+const MAX_STREAMS: usize = 1024;
+
+pub fn frame_type_name(frame_type: u8) -> &'static str {
+    match frame_type {
+        FRAME_DATA => "DATA",
+        FRAME_HEADERS => "HEADERS",
+        FRAME_PRIORITY => "PRIORITY",
+        FRAME_RST_STREAM => "RST_STREAM",
+        FRAME_SETTINGS => "SETTINGS",
+        FRAME_PUSH_PROMISE => "PUSH_PROMISE",
+        FRAME_PING => "PING",
+        FRAME_GOAWAY => "GOAWAY",
+        FRAME_WINDOW_UPDATE => "WINDOW_UPDATE",
+        FRAME_CONTINUATION => "CONTINUATION",
+        _ => "UNKNOWN",
+    }
+}
+
+pub fn error_name(code: u32) -> String {
+    match code {
+        0x0 => "NO_ERROR".to_string(),
+        0x1 => "PROTOCOL_ERROR".to_string(),
+        0x2 => "INTERNAL_ERROR".to_string(),
+        0x3 => "FLOW_CONTROL_ERROR".to_string(),
+        0x4 => "SETTINGS_TIMEOUT".to_string(),
+        0x5 => "STREAM_CLOSED".to_string(),
+        0x6 => "FRAME_SIZE_ERROR".to_string(),
+        0x7 => "REFUSED_STREAM".to_string(),
+        0x8 => "CANCEL".to_string(),
+        0x9 => "COMPRESSION_ERROR".to_string(),
+        0xa => "CONNECT_ERROR".to_string(),
+        0xb => "ENHANCE_YOUR_CALM".to_string(),
+        0xc => "INADEQUATE_SECURITY".to_string(),
+        0xd => "HTTP_1_1_REQUIRED".to_string(),
+        other => format!("0x{other:x}"),
+    }
+}
+
+fn setting_name(id: u16) -> String {
+    match id {
+        0x1 => "HEADER_TABLE_SIZE".to_string(),
+        0x2 => "ENABLE_PUSH".to_string(),
+        0x3 => "MAX_CONCURRENT_STREAMS".to_string(),
+        0x4 => "INITIAL_WINDOW_SIZE".to_string(),
+        0x5 => "MAX_FRAME_SIZE".to_string(),
+        0x6 => "MAX_HEADER_LIST_SIZE".to_string(),
+        0x8 => "ENABLE_CONNECT_PROTOCOL".to_string(),
+        other => format!("0x{other:x}"),
+    }
+}
+
+fn read_u32(data: &[u8], off: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(off..off + 4)?.try_into().ok()?))
+}
+
+/// Removes the Pad Length byte and the trailing padding of a PADDED frame.
+fn unpad(flags: u8, payload: &[u8]) -> Option<&[u8]> {
+    if flags & FLAG_PADDED == 0 {
+        return Some(payload);
+    }
+    let (&pad, rest) = payload.split_first()?;
+    rest.get(..rest.len().checked_sub(pad as usize)?)
+}
+
+fn pseudo_summary(headers: &[(String, String)]) -> String {
+    headers
+        .iter()
+        .filter(|(n, _)| n.starts_with(':'))
+        .map(|(n, v)| format!("{n} {v}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+struct FrameHeader {
+    frame_type: u8,
+    flags: u8,
+    stream_id: u32,
+}
+
+struct HeaderBlock {
+    stream_id: u32,
+    end_stream: bool,
+    block: Vec<u8>,
+    promised: Option<u32>,
+}
+
+#[derive(Default)]
+struct Side {
+    buf: Vec<u8>,
+    ready: bool,
+    hpack: HpackDecoder,
+    hpack_broken: bool,
+    continuation: Option<HeaderBlock>,
+}
+
+#[derive(Default)]
+struct StreamState {
+    stream: Http2Stream,
+    request_done: bool,
+    response_done: bool,
+    request_ts: Option<u128>,
+}
+
+#[derive(Default, PartialEq, Eq)]
+enum Detection {
+    #[default]
+    Unknown,
+    Http2,
+    NotHttp2,
+}
+
+#[derive(Default)]
+struct Connection {
+    detection: Detection,
+    client: Side,
+    server: Side,
+    streams: HashMap<u32, StreamState>,
+}
+
+impl Connection {
+    fn side(&mut self, direction: Direction) -> &mut Side {
+        match direction {
+            Direction::ToServer => &mut self.client,
+            Direction::ToClient => &mut self.server,
+        }
+    }
+
+    fn stream(&mut self, id: u32) -> &mut StreamState {
+        if !self.streams.contains_key(&id) && self.streams.len() >= MAX_STREAMS {
+            self.streams.clear();
+        }
+        self.streams.entry(id).or_insert_with(|| StreamState {
+            stream: Http2Stream {
+                stream_id: id,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    }
+
+    /// Prior knowledge (client preface) or HTTP/1.1 `Upgrade: h2c`.
+    fn detect(&mut self, ts: u128) {
+        let buf = &self.client.buf;
+        if buf.len() < PREFACE.len() && PREFACE.starts_with(buf) {
+            return;
+        }
+        if buf.starts_with(PREFACE) {
+            self.client.buf.drain(..PREFACE.len());
+            self.client.ready = true;
+            self.server.ready = true;
+            self.detection = Detection::Http2;
+            return;
+        }
+
+        let head_end = buf.windows(4).position(|w| w == b"\r\n\r\n");
+        let head = String::from_utf8_lossy(&buf[..head_end.unwrap_or(buf.len())]).to_ascii_lowercase();
+        if http::looks_like_request(buf) && head.contains("upgrade: h2c") {
+            // The upgraded request becomes stream 1.
+            let head = String::from_utf8_lossy(&buf[..head_end.unwrap_or(buf.len())]).to_string();
+            let mut parts = head.split_whitespace();
+            let mut request = vec![
+                (":method".to_string(), parts.next().unwrap_or("").to_string()),
+                (":path".to_string(), parts.next().unwrap_or("").to_string()),
+            ];
+            if let Some(host) = head
+                .lines()
+                .find_map(|l| l.split_once(':').filter(|(n, _)| n.eq_ignore_ascii_case("host")))
+            {
+                request.push((":authority".to_string(), host.1.trim().to_string()));
+            }
+            let st = self.stream(1);
+            st.stream.request_headers = request;
+            st.request_done = true;
+            st.request_ts = Some(ts);
+            self.detection = Detection::Http2;
+            return;
+        }
+
+        self.detection = Detection::NotHttp2;
+        self.client.buf.clear();
+    }
+
+    /// Skips what precedes HTTP/2 framing on an upgraded connection.
+    fn await_upgrade(&mut self, direction: Direction) {
+        let side = self.side(direction);
+        match direction {
+            Direction::ToServer => {
+                if let Some(pos) = side.buf.windows(PREFACE.len()).position(|w| w == PREFACE) {
+                    side.buf.drain(..pos + PREFACE.len());
+                    side.ready = true;
+                }
+            }
+            Direction::ToClient => {
+                let Some(end) = side.buf.windows(4).position(|w| w == b"\r\n\r\n") else {
+                    return;
+                };
+                if side.buf.get(9..12) != Some(b"101") {
+                    self.detection = Detection::NotHttp2;
+                    return;
+                }
+                side.buf.drain(..end + 4);
+                side.ready = true;
+            }
+        }
+    }
+
+    fn feed(&mut self, direction: Direction, data: &[u8], ts: u128) -> Option<Http2Info> {
+        if self.detection == Detection::NotHttp2 {
+            return None;
+        }
+        self.side(direction).buf.extend_from_slice(data);
+
+        if self.detection == Detection::Unknown {
+            if direction == Direction::ToClient {
+                self.detection = Detection::NotHttp2;
+                return None;
+            }
+            self.detect(ts);
+            if self.detection != Detection::Http2 {
+                return None;
+            }
+        }
+        if !self.side(direction).ready {
+            self.await_upgrade(direction);
+            if !self.side(direction).ready {
+                return None;
+            }
+        }
+
+        let mut info = Http2Info::default();
+        loop {
+            let side = self.side(direction);
+            if side.buf.len() < 9 {
+                break;
+            }
+            let length = ((side.buf[0] as usize) << 16) | ((side.buf[1] as usize) << 8) | side.buf[2] as usize;
+            if length > MAX_FRAME {
+                self.detection = Detection::NotHttp2;
+                break;
+            }
+            if side.buf.len() < 9 + length {
+                break;
+            }
+            let header = FrameHeader {
+                frame_type: side.buf[3],
+                flags: side.buf[4],
+                stream_id: read_u32(&side.buf, 5).unwrap_or(0) & 0x7fff_ffff,
+            };
+            let payload: Vec<u8> = side.buf.drain(..9 + length).skip(9).collect();
+
+            let detail = self.process_frame(direction, &header, &payload, ts, &mut info);
+            info.frames.push(Http2Frame {
+                stream_id: header.stream_id,
+                frame_type: header.frame_type,
+                flags: header.flags,
+                length: length as u32,
+                detail,
+            });
+        }
+
+        if info.frames.is_empty() {
+            None
+        } else {
+            Some(info)
+        }
+    }
+
+    fn process_frame(
+        &mut self,
+        direction: Direction,
+        header: &FrameHeader,
+        payload: &[u8],
+        ts: u128,
+        info: &mut Http2Info,
+    ) -> String {
+        let (frame_type, flags, stream_id) = (header.frame_type, header.flags, header.stream_id);
+        match frame_type {
+            FRAME_DATA => {
+                let Some(data) = unpad(flags, payload) else {
+                    return "padding invalide".to_string();
+                };
+                let st = self.stream(stream_id);
+                let (body, body_len) = match direction {
+                    Direction::ToServer => (&mut st.stream.request_body, &mut st.stream.request_body_len),
+                    Direction::ToClient => (&mut st.stream.response_body, &mut st.stream.response_body_len),
+                };
+                let room = MAX_BODY.saturating_sub(body.len()).min(data.len());
+                body.extend_from_slice(&data[..room]);
+                *body_len += data.len();
+                if flags & FLAG_END_STREAM != 0 {
+                    self.end_stream(direction, stream_id, info);
+                    return format!("{} octets, END_STREAM", data.len());
+                }
+                format!("{} octets", data.len())
+            }
+            FRAME_HEADERS | FRAME_PUSH_PROMISE => {
+                let Some(mut block) = unpad(flags, payload) else {
+                    return "padding invalide".to_string();
+                };
+                let mut promised = None;
+                if frame_type == FRAME_PUSH_PROMISE {
+                    promised = read_u32(block, 0).map(|id| id & 0x7fff_ffff);
+                    block = block.get(4..).unwrap_or_default();
+                } else if flags & FLAG_PRIORITY != 0 {
+                    block = block.get(5..).unwrap_or_default();
+                }
+                let pending = HeaderBlock {
+                    stream_id,
+                    end_stream: frame_type == FRAME_HEADERS && flags & FLAG_END_STREAM != 0,
+                    block: block.to_vec(),
+                    promised,
+                };
+                if flags & FLAG_END_HEADERS == 0 {
+                    self.side(direction).continuation = Some(pending);
+                    return "suite dans CONTINUATION".to_string();
+                }
+                self.apply_headers(direction, pending, ts, info)
+            }
+            FRAME_CONTINUATION => {
+                let side = self.side(direction);
+                let Some(pending) = side.continuation.as_mut() else {
+                    return "CONTINUATION inattendu".to_string();
+                };
+                pending.block.extend_from_slice(payload);
+                if flags & FLAG_END_HEADERS == 0 {
+                    return String::new();
+                }
+                let pending = side.continuation.take().unwrap_or_else(|| HeaderBlock {
+                    stream_id,
+                    end_stream: false,
+                    block: Vec::new(),
+                    promised: None,
+                });
+                self.apply_headers(direction, pending, ts, info)
+            }
+            FRAME_RST_STREAM => {
+                let code = read_u32(payload, 0).unwrap_or(0);
+                let mut st = self.streams.remove(&stream_id).unwrap_or_default();
+                st.stream.stream_id = stream_id;
+                st.stream.reset_error = Some(code);
+                info.streams.push(st.stream);
+                error_name(code)
+            }
+            FRAME_SETTINGS => {
+                if flags & FLAG_ACK != 0 {
+                    return "ACK".to_string();
+                }
+                payload
+                    .chunks_exact(6)
+                    .map(|s| {
+                        let id = u16::from_be_bytes([s[0], s[1]]);
+                        let value = u32::from_be_bytes([s[2], s[3], s[4], s[5]]);
+                        format!("{}={value}", setting_name(id))
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }
+            FRAME_PING => {
+                if flags & FLAG_ACK != 0 {
+                    "ACK".to_string()
+                } else {
+                    String::new()
+                }
+            }
+            FRAME_GOAWAY => {
+                let last = read_u32(payload, 0).unwrap_or(0) & 0x7fff_ffff;
+                let code = read_u32(payload, 4).unwrap_or(0);
+                format!("dernier flux {last}, {}", error_name(code))
+            }
+            FRAME_WINDOW_UPDATE => {
+                let increment = read_u32(payload, 0).unwrap_or(0) & 0x7fff_ffff;
+                format!("+{increment}")
+            }
+            _ => String::new(),
+        }
+    }
+
+    fn apply_headers(&mut self, direction: Direction, pending: HeaderBlock, ts: u128, info: &mut Http2Info) -> String {
+        let side = self.side(direction);
+        if side.hpack_broken {
+            return "HPACK désynchronisé".to_string();
+        }
+        let headers = match side.hpack.decode(&pending.block) {
+            Ok(h) => h,
+            Err(e) => {
+                // The dynamic table can no longer be trusted for this direction.
+                side.hpack_broken = true;
+                return e;
+            }
+        };
+        let summary = pseudo_summary(&headers);
+
+        if let Some(promised) = pending.promised {
+            let st = self.stream(promised);
+            st.stream.request_headers = headers;
+            st.request_done = true;
+            return format!("flux promis {promised} {summary}");
+        }
+
+        let st = self.stream(pending.stream_id);
+        match direction {
+            Direction::ToServer if st.stream.request_headers.is_empty() => {
+                st.stream.request_headers = headers;
+                st.request_ts = Some(ts);
+            }
+            Direction::ToClient
+                if st.stream.response_headers.is_empty()
+                    || http2_status(&st.stream.response_headers).is_some_and(|s| s < 200) =>
+            {
+                st.stream.response_headers = headers;
+                st.stream.latency_ms = st.request_ts.map(|t| ts.saturating_sub(t) as u64);
+            }
+            _ => st.stream.trailers.extend(headers),
+        }
+
+        if pending.end_stream {
+            self.end_stream(direction, pending.stream_id, info);
+            return format!("{summary} END_STREAM").trim_start().to_string();
+        }
+        summary
+    }
+
+    fn end_stream(&mut self, direction: Direction, stream_id: u32, info: &mut Http2Info) {
+        let st = self.stream(stream_id);
+        match direction {
+            Direction::ToServer => st.request_done = true,
+            Direction::ToClient => st.response_done = true,
+        }
+        if st.request_done && st.response_done {
+            if let Some(st) = self.streams.remove(&stream_id) {
+                info.streams.push(st.stream);
+            }
+        }
+    }
+}
+
+/// `:status` of a response header list.
+pub fn http2_status(headers: &[(String, String)]) -> Option<u16> {
+    http::header(headers, ":status").and_then(|s| s.parse().ok())
+}
+
+/// Follows HTTP/2 connections (h2c, by prior knowledge or `Upgrade: h2c`)
+/// over reassembled TCP streams, with one HPACK context per direction.
+#[derive(Default)]
+pub struct Http2Tracker {
+    conns: HashMap<StreamKey, Connection>,
+}
+
+impl Http2Tracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds cleartext HTTP/2 bytes of one direction of a connection.
+    /// WireFish only calls it with h2c streams; nothing feeds it TLS
+    /// traffic, decrypted or not.
+    pub fn feed(&mut self, key: &StreamKey, direction: Direction, data: &[u8], ts: u128) -> Option<Http2Info> {
+        if !self.conns.contains_key(key) && self.conns.len() >= MAX_CONNECTIONS {
+            self.conns.clear();
+        }
+        self.conns.entry(key.clone()).or_default().feed(direction, data, ts)
+    }
+
+    pub fn on_event(&mut self, event: &StreamEvent, ts: u128) -> Option<Http2Info> {
+        match event {
+            StreamEvent::Data { key, direction, data } => self.feed(key, *direction, data, ts),
+            // HPACK state is lost with the missing bytes.
+            StreamEvent::Gap { key, .. } | StreamEvent::Closed { key, .. } => {
+                self.conns.remove(key);
+                None
+            }
+        }
+    }
+
+    pub fn observe(&mut self, packet: &mut Packet, events: &[StreamEvent]) {
+        for event in events {
+            if let Some(info) = self.on_event(event, packet.timestamp) {
+                let merged = packet.http2.get_or_insert_with(Http2Info::default);
+                merged.frames.extend(info.frames);
+                merged.streams.extend(info.streams);
+            }
+        }
+    }
+}
+
+/// Short description used in the live table next to the protocol label.
+pub fn summary(info: &Http2Info) -> String {
+    if let Some(st) = info.streams.last() {
+        let path = http::header(&st.request_headers, ":path").unwrap_or("");
+        return match (st.reset_error, http2_status(&st.response_headers)) {
+            (Some(code), _) => format!("[{}] RST {} {path}", st.stream_id, error_name(code)),
+            (None, Some(status)) => format!("[{}] {status} {path}", st.stream_id),
+            (None, None) => format!("[{}] {path}", st.stream_id),
+        };
+    }
+    if let Some(f) = info.frames.iter().find(|f| f.frame_type == FRAME_HEADERS) {
+        return format!("[{}] {}", f.stream_id, f.detail);
+    }
+    info.frames
+        .iter()
+        .map(|f| frame_type_name(f.frame_type))
+        .collect::<Vec<_>>()
+        .join(",")
+}
@@ -0,0 +1,297 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::core::dns;
+use crate::core::models::{
+    Device, DeviceName, DeviceService, DnsRecord, NbnsMessage, Packet, TransportProtocol,
+};
+use crate::core::netbios;
+
+const SOURCE_MDNS: &str = "mDNS";
+const SOURCE_LLMNR: &str = "LLMNR";
+const SOURCE_NETBIOS: &str = "NetBIOS";
+
+/// DNS-SD meta-query listing service types, not instances.
+const DNS_SD_ENUMERATION: &str = "_services._dns-sd._udp.local";
+
+/// Devices, host names and service instances kept at most each; once full,
+/// new ones are ignored.
+const MAX_ENTRIES: usize = 4096;
+/// Names kept per device.
+const MAX_NAMES: usize = 64;
+
+/// Passive inventory of LAN device names and DNS-SD services, learned from
+/// mDNS announcements, LLMNR answers and NetBIOS name service traffic.
+#[derive(Debug, Default)]
+pub struct NameInventory {
+    devices: BTreeMap<String, Device>,
+    // host name (e.g. "printer.local") -> address
+    hosts: HashMap<String, String>,
+    // service instance -> (service, address of the announcer)
+    services: BTreeMap<String, (DeviceService, String)>,
+}
+
+fn udp_ports(packet: &Packet) -> Option<(u16, u16)> {
+    match &packet.transport {
+        Some(TransportProtocol::Udp(u)) => Some((u.src_port, u.dst_port)),
+        _ => None,
+    }
+}
+
+/// `4.3.2.1.in-addr.arpa` -> `1.2.3.4`
+fn reverse_ipv4(name: &str) -> Option<String> {
+    let octets = name.strip_suffix(".in-addr.arpa")?;
+    let mut parts: Vec<&str> = octets.split('.').collect();
+    if parts.len() != 4 || parts.iter().any(|p| p.parse::<u8>().is_err()) {
+        return None;
+    }
+    parts.reverse();
+    Some(parts.join("."))
+}
+
+fn txt_entries(data: &str) -> Vec<String> {
+    data.split("\" \"")
+        .map(|s| s.trim_matches('"').to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+impl NameInventory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn observe(&mut self, packet: &Packet) {
+        let Some((sport, dport)) = udp_ports(packet) else {
+            return;
+        };
+
+        if let Some(msg) = packet.dns.first() {
+            let source = if sport == 5353 || dport == 5353 {
+                SOURCE_MDNS
+            } else if sport == 5355 || dport == 5355 {
+                SOURCE_LLMNR
+            } else {
+                return;
+            };
+            if !msg.is_response {
+                return;
+            }
+            self.touch_sender(packet);
+            for rec in msg.answers.iter().chain(msg.additional.iter()) {
+                self.learn_dns_record(packet, rec, source);
+            }
+        }
+
+        if let Some(msg) = &packet.nbns {
+            self.learn_netbios(packet, msg);
+        }
+    }
+
+    fn device(&mut self, ip: &str, ts: u128) -> Option<&mut Device> {
+        if self.devices.len() >= MAX_ENTRIES && !self.devices.contains_key(ip) {
+            return None;
+        }
+        let dev = self.devices.entry(ip.to_string()).or_insert_with(|| Device {
+            ip: ip.to_string(),
+            first_seen: ts,
+            ..Default::default()
+        });
+        dev.last_seen = dev.last_seen.max(ts);
+        Some(dev)
+    }
+
+    fn add_name(&mut self, ip: &str, name: &str, source: &str, ts: u128) {
+        let name = name.trim_end_matches('.');
+        if name.is_empty() {
+            return;
+        }
+        let Some(dev) = self.device(ip, ts) else {
+            return;
+        };
+        if dev.names.len() < MAX_NAMES
+            && !dev.names.iter().any(|n| n.name.eq_ignore_ascii_case(name) && n.source == source)
+        {
+            dev.names.push(DeviceName {
+                name: name.to_string(),
+                source: source.to_string(),
+            });
+        }
+    }
+
+    /// The sender of a name service answer is on the local link: remember its MAC.
+    fn touch_sender(&mut self, packet: &Packet) {
+        let (Some(ip), Some(eth)) = (&packet.ip, &packet.eth) else {
+            return;
+        };
+        let mac = eth.src_mac.clone();
+        if let Some(dev) = self.device(&ip.src_ip, packet.timestamp) {
+            dev.mac.get_or_insert(mac);
+        }
+    }
+
+    fn learn_dns_record(&mut self, packet: &Packet, rec: &DnsRecord, source: &str) {
+        let ts = packet.timestamp;
+        let sender = packet.ip.as_ref().map(|ip| ip.src_ip.clone()).unwrap_or_default();
+
+        match rec.rtype {
+            dns::TYPE_A | dns::TYPE_AAAA => {
+                let name = rec.name.to_ascii_lowercase();
+                if self.hosts.len() < MAX_ENTRIES || self.hosts.contains_key(&name) {
+                    self.hosts.insert(name, rec.data.clone());
+                }
+                self.add_name(&rec.data, &rec.name, source, ts);
+            }
+            dns::TYPE_PTR => {
+                if let Some(ip) = reverse_ipv4(&rec.name) {
+                    self.add_name(&ip, &rec.data, source, ts);
+                } else if rec.name.starts_with('_') && rec.name != DNS_SD_ENUMERATION && self.has_room(&rec.data) {
+                    let entry = self
+                        .services
+                        .entry(rec.data.clone())
+                        .or_insert_with(|| (DeviceService::default(), sender.clone()));
+                    entry.0.instance = rec.data.clone();
+                    entry.0.service_type = rec.name.clone();
+                }
+            }
+            dns::TYPE_SRV => {
+                // priority weight port target
+                let fields: Vec<&str> = rec.data.split_whitespace().collect();
+                if let ([_, _, port, target], true) = (&fields[..], self.has_room(&rec.name)) {
+                    let entry = self
+                        .services
+                        .entry(rec.name.clone())
+                        .or_insert_with(|| (DeviceService::default(), sender.clone()));
+                    entry.0.instance = rec.name.clone();
+                    entry.0.port = port.parse().unwrap_or(0);
+                    entry.0.target = target.to_string();
+                    if entry.0.service_type.is_empty() {
+                        entry.0.service_type = service_type_of(&rec.name);
+                    }
+                }
+            }
+            dns::TYPE_TXT => {
+                if let Some(entry) = self.services.get_mut(&rec.name) {
+                    entry.0.txt = txt_entries(&rec.data);
+                } else if rec.name.contains("._") && self.has_room(&rec.name) {
+                    self.services.insert(
+                        rec.name.clone(),
+                        (
+                            DeviceService {
+                                instance: rec.name.clone(),
+                                service_type: service_type_of(&rec.name),
+                                txt: txt_entries(&rec.data),
+                                ..Default::default()
+                            },
+                            sender,
+                        ),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn learn_netbios(&mut self, packet: &Packet, msg: &NbnsMessage) {
+        let ts = packet.timestamp;
+        let sender = packet.ip.as_ref().map(|ip| ip.src_ip.clone()).unwrap_or_default();
+
+        let announces = matches!(
+            msg.opcode,
+            netbios::OPCODE_REGISTRATION | netbios::OPCODE_REFRESH | 9 | 15
+        );
+        if !msg.is_response && !announces {
+            return;
+        }
+        if msg.is_response && msg.rcode != 0 {
+            return;
+        }
+        self.touch_sender(packet);
+
+        for rec in &msg.records {
+            let label = if rec.name.suffix == 0x00 || rec.name.suffix == 0x20 {
+                rec.name.name.clone()
+            } else {
+                format!("{}<{:02x}>", rec.name.name, rec.name.suffix)
+            };
+            for addr in &rec.addresses {
+                self.add_name(addr, &label, SOURCE_NETBIOS, ts);
+            }
+
+            // NBSTAT: the whole name table of the answering host
+            for n in &rec.names {
+                if n.suffix == 0x00 || n.suffix == 0x20 {
+                    self.add_name(&sender, &n.name, SOURCE_NETBIOS, ts);
+                }
+            }
+            if let Some(mac) = &rec.mac {
+                if let Some(dev) = self.device(&sender, ts).filter(|_| mac != "00:00:00:00:00:00") {
+                    dev.mac = Some(mac.clone());
+                }
+            }
+        }
+    }
+
+    fn has_room(&self, instance: &str) -> bool {
+        self.services.len() < MAX_ENTRIES || self.services.contains_key(instance)
+    }
+
+    /// Snapshot of every device with its names and services.
+    pub fn devices(&self) -> Vec<Device> {
+        let mut devices = self.devices.clone();
+
+        for (service, announcer) in self.services.values() {
+            let ip = self
+                .hosts
+                .get(&service.target.to_ascii_lowercase())
+                .unwrap_or(announcer);
+            if ip.is_empty() {
+                continue;
+            }
+            let dev = devices.entry(ip.clone()).or_insert_with(|| Device {
+                ip: ip.clone(),
+                ..Default::default()
+            });
+            dev.services.push(service.clone());
+        }
+
+        devices.into_values().collect()
+    }
+}
+
+/// `My Printer._ipp._tcp.local` -> `_ipp._tcp.local`
+fn service_type_of(instance: &str) -> String {
+    match instance.find("._") {
+        Some(idx) => instance[idx + 1..].to_string(),
+        None => String::new(),
+    }
+}
+
+pub fn print_report(devices: &[Device]) {
+    println!();
+    println!("══════════════════════════════════════════════════════════════════════");
+    println!(" WireFish - Inventaire des appareils ({})", devices.len());
+    println!("══════════════════════════════════════════════════════════════════════");
+
+    for dev in devices {
+        println!();
+        println!(
+            "{}{}",
+            dev.ip,
+            dev.mac.as_ref().map(|m| format!("  [{m}]")).unwrap_or_default()
+        );
+        for n in &dev.names {
+            println!("  Nom      : {} ({})", n.name, n.source);
+        }
+        for s in &dev.services {
+            let target = if s.target.is_empty() {
+                String::new()
+            } else {
+                format!(" → {}:{}", s.target, s.port)
+            };
+            println!("  Service  : {} [{}]{}", s.instance, s.service_type, target);
+            if !s.txt.is_empty() {
+                println!("             {}", s.txt.join(" "));
+            }
+        }
+    }
+}
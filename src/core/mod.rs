@@ -1,7 +1,37 @@
 pub mod alerts;
+pub mod arpwatch;
+pub mod beacon;
 pub mod capture;
 pub mod classifier;
+pub mod credentials;
+pub mod dhcp;
+pub mod dns;
+pub mod dnsanomaly;
 pub mod enrichment;
+pub mod filter;
+pub mod fingerprint;
+pub mod flow;
+pub mod follow;
+pub mod grpc;
+pub mod handshake;
+pub mod hpack;
+pub mod http;
+pub mod http2;
+pub mod inventory;
 pub mod models;
+pub mod netbios;
+pub mod netflow;
+pub mod objects;
 pub mod parser;
+pub mod protobuf;
+pub mod rules;
+pub mod scan;
 pub mod storage;
+pub mod stream;
+pub mod suricata;
+pub mod tcpanalysis;
+pub mod tls;
+pub mod triage;
+pub mod volume;
+pub mod x509;
+pub mod zeek;
@@ -7,6 +7,27 @@ pub struct Packet {
     pub ip: Option<IpHeader>,
     pub transport: Option<TransportProtocol>, 
     pub payload: Vec<u8>,
+    #[serde(default)]
+    pub tls: Option<TlsInfo>,
+    /// The DNS message of a UDP datagram, or those a TCP segment completed.
+    #[serde(default)]
+    pub dns: Vec<DnsMessage>,
+    #[serde(default)]
+    pub nbns: Option<NbnsMessage>,
+    #[serde(default)]
+    pub dhcp: Option<DhcpMessage>,
+    #[serde(default)]
+    pub http: Vec<HttpTransaction>,
+    #[serde(default)]
+    pub http2: Option<Http2Info>,
+    #[serde(default)]
+    pub grpc: Vec<GrpcCall>,
+    #[serde(default)]
+    pub tcp_analysis: Option<TcpAnalysis>,
+    /// Hellos, fingerprints and certificates of the TLS connection the
+    /// packet belongs to, on all of its packets (`FingerprintTracker`).
+    #[serde(skip)]
+    pub connection_tls: Option<TlsInfo>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,6 +35,33 @@ pub struct EthernetHeader {
     pub src_mac: String,
     pub dst_mac: String,
     pub ethertype: u16,
+    /// Outer 802.1Q tag, removed from the frame before decoding.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vlan: Option<u16>,
+}
+
+/// ARP request (operation 1) or reply (operation 2) over Ethernet/IPv4.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArpMessage {
+    pub operation: u16,
+    pub sender_mac: String,
+    pub sender_ip: String,
+    pub target_mac: String,
+    pub target_ip: String,
+}
+
+/// IP ↔ MAC association learned from ARP or DHCP.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArpBinding {
+    pub ip: String,
+    pub mac: String,
+    /// `arp` or `dhcp`.
+    pub learned_from: String,
+    pub first_seen: u128,
+    pub last_seen: u128,
+    /// Times the IP moved to another MAC.
+    #[serde(default)]
+    pub changes: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -36,6 +84,16 @@ pub struct TcpHeader {
     pub src_port: u16,
     pub dst_port: u16,
     pub flags: u8,
+    #[serde(default)]
+    pub seq: u32,
+    #[serde(default)]
+    pub ack: u32,
+    /// Raw advertised window (before scaling).
+    #[serde(default)]
+    pub window: u16,
+    /// Window scale option, only carried by SYN segments.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_scale: Option<u8>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -52,3 +110,567 @@ pub struct IpReputation {
     pub score: u32, // risk score (0 = safe, 100 = malicious)
     pub tags: Vec<String>,
 }
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TlsInfo {
+    pub record_version: u16,
+    pub client_hello: Option<ClientHello>,
+    pub server_hello: Option<ServerHello>,
+    #[serde(default)]
+    pub fingerprints: TlsFingerprints,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub certificates: Vec<X509Certificate>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClientHello {
+    pub legacy_version: u16,
+    pub cipher_suites: Vec<u16>,
+    pub extensions: Vec<u16>,
+    pub sni: Option<String>,
+    pub alpn: Vec<String>,
+    pub supported_groups: Vec<u16>,
+    #[serde(default)]
+    pub ec_point_formats: Vec<u8>,
+    pub signature_algorithms: Vec<u16>,
+    pub key_share_groups: Vec<u16>,
+    pub supported_versions: Vec<u16>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ServerHello {
+    pub legacy_version: u16,
+    pub cipher_suite: u16,
+    pub extensions: Vec<u16>,
+    pub alpn: Option<String>,
+    pub selected_version: Option<u16>,
+    pub key_share_group: Option<u16>,
+}
+
+/// JA3/JA3S (string + MD5) and JA4/JA4S fingerprints of a TLS connection.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TlsFingerprints {
+    pub ja3: Option<String>,
+    pub ja3_hash: Option<String>,
+    pub ja3s: Option<String>,
+    pub ja3s_hash: Option<String>,
+    pub ja4: Option<String>,
+    pub ja4s: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct X509Certificate {
+    pub subject: String,
+    pub subject_cn: Option<String>,
+    pub issuer: String,
+    pub serial: String,
+    pub not_before: i64, // unix seconds
+    pub not_after: i64,
+    pub san_dns: Vec<String>,
+    pub san_ip: Vec<String>,
+    pub key_type: String,
+    pub signature_algorithm: String,
+    pub is_ca: bool,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DnsMessage {
+    pub id: u16,
+    pub is_response: bool,
+    pub opcode: u8,
+    pub rcode: u16,
+    pub authoritative: bool,
+    pub truncated: bool,
+    pub recursion_desired: bool,
+    pub recursion_available: bool,
+    pub authentic_data: bool,
+    pub checking_disabled: bool,
+    pub questions: Vec<DnsQuestion>,
+    pub answers: Vec<DnsRecord>,
+    pub authority: Vec<DnsRecord>,
+    pub additional: Vec<DnsRecord>,
+    pub edns: Option<Edns>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DnsQuestion {
+    pub name: String,
+    pub qtype: u16,
+    pub qclass: u16,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DnsRecord {
+    pub name: String,
+    pub rtype: u16,
+    pub class: u16,
+    pub ttl: u32,
+    pub data: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Edns {
+    pub udp_payload_size: u16,
+    pub version: u8,
+    pub dnssec_ok: bool,
+    pub options: Vec<u16>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NetbiosName {
+    pub name: String,
+    pub suffix: u8,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NbnsRecord {
+    pub name: NetbiosName,
+    pub rtype: u16,
+    pub ttl: u32,
+    pub addresses: Vec<String>,
+    pub names: Vec<NetbiosName>,
+    pub mac: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NbnsMessage {
+    pub id: u16,
+    pub is_response: bool,
+    pub opcode: u8,
+    pub rcode: u16,
+    pub questions: Vec<NetbiosName>,
+    pub records: Vec<NbnsRecord>,
+}
+
+/// DHCPv4 or DHCPv6 message. Addresses and identifiers the message does not
+/// carry stay empty.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DhcpMessage {
+    /// 4 or 6.
+    pub version: u8,
+    /// Option 53 for DHCPv4, msg-type for DHCPv6.
+    pub message_type: u8,
+    pub transaction_id: u32,
+    pub client_mac: Option<String>,
+    /// Client identifier (option 61) or DUID, in hex.
+    pub client_id: Option<String>,
+    /// Address the client already uses (ciaddr).
+    pub client_ip: Option<String>,
+    /// Address offered or assigned (yiaddr, IA address).
+    pub assigned_ip: Option<String>,
+    pub requested_ip: Option<String>,
+    /// Server identifier (option 54) or server DUID, in hex.
+    pub server_id: Option<String>,
+    /// Lease (or valid lifetime) in seconds.
+    pub lease_time: Option<u32>,
+    pub routers: Vec<String>,
+    pub dns_servers: Vec<String>,
+    pub hostname: Option<String>,
+    pub vendor_class: Option<String>,
+}
+
+/// DHCP server seen answering clients.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DhcpServer {
+    pub version: u8,
+    /// Server identifier, or source address when there is none.
+    pub id: String,
+    pub ip: String,
+    pub mac: Option<String>,
+    pub first_seen: u128,
+    pub last_seen: u128,
+    pub offers: u64,
+    pub acks: u64,
+    pub naks: u64,
+    pub routers: Vec<String>,
+    pub dns_servers: Vec<String>,
+    /// Configured with `--dhcp-server`, or the first one seen.
+    pub trusted: bool,
+}
+
+/// Address handed out by a DHCP server.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DhcpLease {
+    pub version: u8,
+    pub ip: String,
+    /// Client MAC, or client identifier when the MAC is unknown.
+    pub client: String,
+    pub hostname: Option<String>,
+    pub server: String,
+    pub granted: u128,
+    pub lease_time: Option<u32>,
+    pub released: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeviceName {
+    pub name: String,
+    pub source: String, // mDNS / LLMNR / NetBIOS
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeviceService {
+    pub instance: String,
+    pub service_type: String,
+    pub target: String,
+    pub port: u16,
+    pub txt: Vec<String>,
+}
+
+/// A LAN host as seen through passive name service traffic.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Device {
+    pub ip: String,
+    pub mac: Option<String>,
+    pub names: Vec<DeviceName>,
+    pub services: Vec<DeviceService>,
+    pub first_seen: u128,
+    pub last_seen: u128,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HttpRequest {
+    pub method: String,
+    pub uri: String,
+    pub version: String,
+    pub host: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub body_len: usize,
+    #[serde(skip)]
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HttpResponse {
+    pub version: String,
+    pub status: u16,
+    pub reason: String,
+    pub headers: Vec<(String, String)>,
+    pub body_len: usize,
+    #[serde(skip)]
+    pub body: Vec<u8>,
+}
+
+/// A request and/or its response, as completed by one segment.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HttpTransaction {
+    pub request: Option<HttpRequest>,
+    pub response: Option<HttpResponse>,
+    pub latency_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Http2Frame {
+    pub stream_id: u32,
+    pub frame_type: u8,
+    pub flags: u8,
+    pub length: u32,
+    pub detail: String,
+}
+
+/// Request and response of one HTTP/2 stream, reported when it ends.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Http2Stream {
+    pub stream_id: u32,
+    pub request_headers: Vec<(String, String)>,
+    pub response_headers: Vec<(String, String)>,
+    pub trailers: Vec<(String, String)>,
+    pub request_body_len: usize,
+    pub response_body_len: usize,
+    #[serde(skip)]
+    pub request_body: Vec<u8>,
+    #[serde(skip)]
+    pub response_body: Vec<u8>,
+    pub reset_error: Option<u32>,
+    pub latency_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Http2Info {
+    pub frames: Vec<Http2Frame>,
+    pub streams: Vec<Http2Stream>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GrpcMessage {
+    pub compressed: bool,
+    pub length: usize,
+    /// JSON rendering of the protobuf payload, when it could be decoded.
+    pub json: Option<String>,
+    /// Why a compressed payload could not be decompressed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GrpcCall {
+    pub stream_id: u32,
+    pub service: String,
+    pub method: String,
+    pub requests: Vec<GrpcMessage>,
+    pub responses: Vec<GrpcMessage>,
+    pub status: Option<u32>,
+    pub status_message: Option<String>,
+    pub trailers: Vec<(String, String)>,
+    pub latency_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FlowState {
+    /// Handshake in progress, or a single direction seen so far.
+    #[default]
+    New,
+    Established,
+    /// FIN seen in one direction.
+    Closing,
+    Closed,
+    Reset,
+}
+
+/// One conversation: packets sharing a bidirectional 5-tuple on the same
+/// interface and VLAN.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Flow {
+    pub id: u64,
+    pub iface: Option<String>,
+    pub vlan: Option<u16>,
+    /// IP protocol number (6 = TCP, 17 = UDP, ...).
+    pub protocol: u8,
+    pub client_ip: String,
+    pub client_port: Option<u16>,
+    pub server_ip: String,
+    pub server_port: Option<u16>,
+    pub first_seen: u128,
+    pub last_seen: u128,
+    pub packets_to_server: u64,
+    pub bytes_to_server: u64,
+    pub packets_to_client: u64,
+    pub bytes_to_client: u64,
+    /// Union of the TCP flags seen in each direction.
+    pub tcp_flags_to_server: u8,
+    pub tcp_flags_to_client: u8,
+    pub application: String,
+    pub state: FlowState,
+    #[serde(default)]
+    pub tcp_perf: TcpPerformance,
+    /// Server name from the TLS ClientHello.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sni: Option<String>,
+    /// ALPN chosen by the server, else the client's first offer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alpn: Option<String>,
+}
+
+impl Flow {
+    pub fn packets(&self) -> u64 {
+        self.packets_to_server + self.packets_to_client
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes_to_server + self.bytes_to_client
+    }
+
+    pub fn duration_ms(&self) -> u128 {
+        self.last_seen.saturating_sub(self.first_seen)
+    }
+}
+
+/// Expert findings on a TCP segment, as in Wireshark's tcp.analysis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TcpExpert {
+    Retransmission,
+    /// Resent after duplicate ACKs asked for it.
+    FastRetransmission,
+    /// Resent although the receiver had already acknowledged it.
+    SpuriousRetransmission,
+    OutOfOrder,
+    /// A gap in the sequence numbers: a segment was not captured.
+    LostSegment,
+    /// Acknowledges data that was not captured.
+    AckedUnseenSegment,
+    DuplicateAck,
+    ZeroWindow,
+    ZeroWindowProbe,
+    /// The sender filled the receiver's advertised window.
+    WindowFull,
+    KeepAlive,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TcpAnalysis {
+    pub flags: Vec<TcpExpert>,
+    /// Time since the segment this packet acknowledges.
+    pub ack_rtt_ms: Option<u64>,
+    /// Set on the ACK completing the three-way handshake (SYN to ACK).
+    pub handshake_rtt_ms: Option<u64>,
+    /// Advertised window, scaled when the scale factor is known.
+    pub window: u32,
+}
+
+/// TCP health counters of a conversation (or of a whole capture).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TcpPerformance {
+    pub handshake_rtt_ms: Option<u64>,
+    pub rtt_samples: u64,
+    pub rtt_sum_ms: u64,
+    pub rtt_min_ms: Option<u64>,
+    pub rtt_max_ms: Option<u64>,
+    pub retransmissions: u64,
+    pub fast_retransmissions: u64,
+    pub spurious_retransmissions: u64,
+    pub out_of_order: u64,
+    pub lost_segments: u64,
+    pub duplicate_acks: u64,
+    pub zero_windows: u64,
+    pub window_full: u64,
+}
+
+impl TcpPerformance {
+    pub fn record(&mut self, analysis: &TcpAnalysis) {
+        if let Some(rtt) = analysis.handshake_rtt_ms {
+            self.handshake_rtt_ms = Some(rtt);
+        }
+        if let Some(rtt) = analysis.ack_rtt_ms {
+            self.rtt_samples += 1;
+            self.rtt_sum_ms += rtt;
+            self.rtt_min_ms = Some(self.rtt_min_ms.map_or(rtt, |m| m.min(rtt)));
+            self.rtt_max_ms = Some(self.rtt_max_ms.map_or(rtt, |m| m.max(rtt)));
+        }
+        for flag in &analysis.flags {
+            let counter = match flag {
+                TcpExpert::Retransmission => &mut self.retransmissions,
+                TcpExpert::FastRetransmission => &mut self.fast_retransmissions,
+                TcpExpert::SpuriousRetransmission => &mut self.spurious_retransmissions,
+                TcpExpert::OutOfOrder => &mut self.out_of_order,
+                TcpExpert::LostSegment => &mut self.lost_segments,
+                TcpExpert::DuplicateAck => &mut self.duplicate_acks,
+                TcpExpert::ZeroWindow => &mut self.zero_windows,
+                TcpExpert::WindowFull => &mut self.window_full,
+                TcpExpert::AckedUnseenSegment | TcpExpert::ZeroWindowProbe | TcpExpert::KeepAlive => continue,
+            };
+            *counter += 1;
+        }
+    }
+
+    pub fn avg_rtt_ms(&self) -> Option<f64> {
+        (self.rtt_samples > 0).then(|| self.rtt_sum_ms as f64 / self.rtt_samples as f64)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Low,
+    #[default]
+    Medium,
+    High,
+    Critical,
+}
+
+/// A detection raised on a packet or a stream, with the endpoints of the
+/// packet (or, for stream rules, of the stream direction) that triggered it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Alert {
+    pub rule_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule_rev: Option<u32>,
+    pub severity: Severity,
+    pub message: String,
+    /// Category of the rule (Suricata `classtype`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classtype: Option<String>,
+    pub timestamp: u128,
+    /// Capture number of the packet that completed the match.
+    pub packet_id: Option<u64>,
+    pub protocol: Option<u8>,
+    pub src_ip: Option<String>,
+    pub src_port: Option<u16>,
+    pub dst_ip: Option<String>,
+    pub dst_port: Option<u16>,
+    /// First packet of each conversation that contributed to the detection.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub related_packets: Vec<u64>,
+}
+
+/// Alerts of one capture with the same rule, source and destination
+/// addresses, merged into one record.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertGroup {
+    /// Derived from the rule and addresses, so it is the same from one
+    /// capture to the next.
+    pub id: String,
+    pub rule_id: String,
+    /// Highest severity seen.
+    pub severity: Severity,
+    /// Message of the latest alert.
+    pub message: String,
+    pub src_ip: Option<String>,
+    pub dst_ip: Option<String>,
+    pub count: u64,
+    /// Alerts dropped by rate limiting, included in `count`.
+    pub rate_limited: u64,
+    pub first_seen: u128,
+    pub last_seen: u128,
+    pub first_packet: Option<u64>,
+    pub last_packet: Option<u64>,
+    pub acknowledged: bool,
+}
+
+/// Acknowledged alert group, kept across captures.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertAck {
+    pub id: String,
+    pub rule_id: String,
+    pub src_ip: Option<String>,
+    pub dst_ip: Option<String>,
+    pub acknowledged_at: u128,
+}
+
+/// Exponentially weighted moving average and variance of a rate.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Ewma {
+    pub mean: f64,
+    pub variance: f64,
+    pub samples: u64,
+}
+
+impl Ewma {
+    pub fn update(&mut self, value: f64, alpha: f64) {
+        if self.samples == 0 {
+            self.mean = value;
+            self.variance = 0.0;
+        } else {
+            let diff = value - self.mean;
+            let increment = alpha * diff;
+            self.mean += increment;
+            self.variance = (1.0 - alpha) * (self.variance + diff * increment);
+        }
+        self.samples += 1;
+    }
+
+    pub fn deviation(&self) -> f64 {
+        self.variance.sqrt()
+    }
+}
+
+/// Learned rate of one metric: packets or bytes of a host or a protocol,
+/// or half-open TCP connections to a host, per measurement interval.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Baseline {
+    /// `host_packets`, `host_bytes`, `protocol_packets`, `protocol_bytes`
+    /// or `half_open`.
+    pub metric: String,
+    /// Host IP or protocol label.
+    pub key: String,
+    pub interval_ms: u128,
+    /// Value of the last complete interval.
+    pub current: f64,
+    pub overall: Ewma,
+    /// One average per hour of the day (UTC), for daily seasonality.
+    pub hourly: Vec<Ewma>,
+}
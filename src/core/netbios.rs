@@ -0,0 +1,189 @@
+use std::net::Ipv4Addr;
+
+use crate::core::dns;
+use crate::core::models::{NbnsMessage, NbnsRecord, NetbiosName, Packet, TransportProtocol};
+
+pub const OPCODE_QUERY: u8 = 0;
+pub const OPCODE_REGISTRATION: u8 = 5;
+pub const OPCODE_RELEASE: u8 = 6;
+pub const OPCODE_REFRESH: u8 = 8;
+
+const TYPE_NB: u16 = 0x0020;
+const TYPE_NBSTAT: u16 = 0x0021;
+
+fn read_u16(data: &[u8], off: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*data.get(off)?, *data.get(off + 1)?]))
+}
+
+/// Reverses the RFC 1001 "first-level encoding": every byte of the 16-byte
+/// NetBIOS name is split in two nibbles, each stored as `'A' + nibble`.
+pub fn decode_name(encoded: &str) -> Option<NetbiosName> {
+    let first_label = encoded.split('.').next()?;
+    let bytes = first_label.as_bytes();
+    if bytes.len() != 32 {
+        return None;
+    }
+
+    let mut raw = [0u8; 16];
+    for (i, pair) in bytes.chunks_exact(2).enumerate() {
+        let hi = pair[0].checked_sub(b'A')?;
+        let lo = pair[1].checked_sub(b'A')?;
+        if hi > 15 || lo > 15 {
+            return None;
+        }
+        raw[i] = (hi << 4) | lo;
+    }
+
+    Some(NetbiosName {
+        name: String::from_utf8_lossy(&raw[..15]).trim_end_matches([' ', '\0']).to_string(),
+        suffix: raw[15],
+    })
+}
+
+/// Well-known meaning of the 16th byte of a NetBIOS name.
+pub fn suffix_name(suffix: u8) -> &'static str {
+    match suffix {
+        0x00 => "Workstation",
+        0x03 => "Messenger",
+        0x1b => "Domain Master Browser",
+        0x1c => "Domain Controllers",
+        0x1d => "Master Browser",
+        0x1e => "Browser Election",
+        0x20 => "File Server",
+        _ => "?",
+    }
+}
+
+pub fn opcode_name(opcode: u8) -> &'static str {
+    match opcode {
+        OPCODE_QUERY => "query",
+        OPCODE_REGISTRATION => "registration",
+        OPCODE_RELEASE => "release",
+        7 => "wack",
+        OPCODE_REFRESH | 9 => "refresh",
+        15 => "multi-homed registration",
+        _ => "?",
+    }
+}
+
+fn parse_nbstat(rdata: &[u8], rec: &mut NbnsRecord) {
+    let Some((&count, mut rest)) = rdata.split_first() else {
+        return;
+    };
+    for _ in 0..count {
+        if rest.len() < 18 {
+            return;
+        }
+        rec.names.push(NetbiosName {
+            name: String::from_utf8_lossy(&rest[..15]).trim_end_matches([' ', '\0']).to_string(),
+            suffix: rest[15],
+        });
+        rest = &rest[18..];
+    }
+    if rest.len() >= 6 {
+        rec.mac = Some(
+            rest[..6]
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<Vec<_>>()
+                .join(":"),
+        );
+    }
+}
+
+/// Decodes a NetBIOS Name Service datagram (UDP 137).
+pub fn parse(msg: &[u8]) -> Option<NbnsMessage> {
+    if msg.len() < 12 {
+        return None;
+    }
+    let flags = read_u16(msg, 2)?;
+    let qdcount = read_u16(msg, 4)? as usize;
+    let rrcount = read_u16(msg, 6)? as usize + read_u16(msg, 8)? as usize + read_u16(msg, 10)? as usize;
+    if qdcount > 8 || rrcount > 32 {
+        return None;
+    }
+
+    let mut m = NbnsMessage {
+        id: read_u16(msg, 0)?,
+        is_response: flags & 0x8000 != 0,
+        opcode: ((flags >> 11) & 0x0f) as u8,
+        rcode: flags & 0x000f,
+        ..Default::default()
+    };
+
+    let mut off = 12;
+    for _ in 0..qdcount {
+        let (name, next) = dns::read_name(msg, off)?;
+        m.questions.push(decode_name(&name)?);
+        off = next + 4;
+    }
+
+    for _ in 0..rrcount {
+        let Some((name, next)) = dns::read_name(msg, off) else {
+            break;
+        };
+        let (Some(rtype), Some(ttl_hi), Some(ttl_lo), Some(rdlen)) = (
+            read_u16(msg, next),
+            read_u16(msg, next + 4),
+            read_u16(msg, next + 6),
+            read_u16(msg, next + 8),
+        ) else {
+            break;
+        };
+        let start = next + 10;
+        let Some(rdata) = msg.get(start..start + rdlen as usize) else {
+            break;
+        };
+        off = start + rdlen as usize;
+
+        let mut rec = NbnsRecord {
+            name: decode_name(&name).unwrap_or_default(),
+            rtype,
+            ttl: ((ttl_hi as u32) << 16) | ttl_lo as u32,
+            ..Default::default()
+        };
+        match rtype {
+            // NB: {flags: u16, address: IPv4} per entry
+            TYPE_NB => {
+                for entry in rdata.chunks_exact(6) {
+                    rec.addresses
+                        .push(Ipv4Addr::new(entry[2], entry[3], entry[4], entry[5]).to_string());
+                }
+            }
+            TYPE_NBSTAT => parse_nbstat(rdata, &mut rec),
+            _ => {}
+        }
+        m.records.push(rec);
+    }
+
+    Some(m)
+}
+
+pub fn from_packet(packet: &Packet, payload: &[u8]) -> Option<NbnsMessage> {
+    match &packet.transport {
+        Some(TransportProtocol::Udp(u)) if u.src_port == 137 || u.dst_port == 137 => parse(payload),
+        _ => None,
+    }
+}
+
+/// Short description used in the live table next to the protocol label.
+pub fn summary(msg: &NbnsMessage) -> String {
+    let kind = opcode_name(msg.opcode);
+    let name = msg
+        .questions
+        .first()
+        .or_else(|| msg.records.first().map(|r| &r.name))
+        .map(|n| format!("{}<{:02x}>", n.name, n.suffix))
+        .unwrap_or_default();
+    if msg.is_response {
+        let addr = msg
+            .records
+            .iter()
+            .flat_map(|r| r.addresses.iter())
+            .next()
+            .cloned()
+            .unwrap_or_default();
+        return format!("R {kind} {name} {addr}").trim_end().to_string();
+    }
+    format!("{kind} {name}")
+}
@@ -0,0 +1,638 @@
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs, UdpSocket};
+
+use crate::core::flow::{EndReason, FlowRecord, FlowTable};
+use crate::core::models::{Flow, FlowState};
+
+/// IANA enterprise number used for the WireFish IPFIX elements. 32473 is
+/// the PEN reserved for documentation (RFC 5612): replace it with your
+/// organisation's own if the collector keeps a registry.
+pub const ENTERPRISE_NUMBER: u32 = 32473;
+/// Enterprise element: application label from the classifier (string).
+pub const IE_APPLICATION: u16 = 1;
+/// Enterprise element: conversation state (see `state_code`).
+pub const IE_FLOW_STATE: u16 = 2;
+
+const TEMPLATE_V4: u16 = 256;
+const TEMPLATE_V6: u16 = 257;
+/// Fixed size of the v9 APPLICATION_NAME field (v9 has no variable length).
+const V9_APPLICATION_LEN: usize = 32;
+const V5_MAX_RECORDS: usize = 30;
+const MAX_DATAGRAM: usize = 1400;
+/// Templates are repeated every this many datagrams (UDP may drop them).
+const TEMPLATE_REFRESH: u64 = 20;
+const OBSERVATION_DOMAIN: u32 = 1;
+/// Minimum interval between two expiry checks.
+const CHECK_INTERVAL_MS: u128 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    NetflowV5,
+    NetflowV9,
+    Ipfix,
+}
+
+impl ExportFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "v5" | "netflow5" => Some(ExportFormat::NetflowV5),
+            "v9" | "netflow9" => Some(ExportFormat::NetflowV9),
+            "ipfix" | "v10" => Some(ExportFormat::Ipfix),
+            _ => None,
+        }
+    }
+}
+
+pub fn state_code(state: FlowState) -> u8 {
+    match state {
+        FlowState::New => 0,
+        FlowState::Established => 1,
+        FlowState::Closing => 2,
+        FlowState::Closed => 3,
+        FlowState::Reset => 4,
+    }
+}
+
+/// One direction of a conversation, as NetFlow/IPFIX records are
+/// unidirectional.
+#[derive(Debug, Clone)]
+pub struct FlowDirection {
+    pub src: IpAddr,
+    pub dst: IpAddr,
+    pub src_port: u16,
+    pub dst_port: u16,
+    pub protocol: u8,
+    pub tcp_flags: u8,
+    pub packets: u64,
+    pub bytes: u64,
+    pub start_ms: u128,
+    pub end_ms: u128,
+    pub vlan: u16,
+    pub application: String,
+    pub state: FlowState,
+    pub reason: EndReason,
+}
+
+/// Splits a record into its client → server and server → client halves
+/// (directions without packets are left out).
+pub fn directions(record: &FlowRecord) -> Vec<FlowDirection> {
+    let f: &Flow = &record.flow;
+    let (Ok(client), Ok(server)) = (f.client_ip.parse::<IpAddr>(), f.server_ip.parse::<IpAddr>()) else {
+        return Vec::new();
+    };
+    let half = |src, dst, sport: Option<u16>, dport: Option<u16>, flags, packets, bytes| FlowDirection {
+        src,
+        dst,
+        src_port: sport.unwrap_or(0),
+        dst_port: dport.unwrap_or(0),
+        protocol: f.protocol,
+        tcp_flags: flags,
+        packets,
+        bytes,
+        start_ms: f.first_seen,
+        end_ms: f.last_seen,
+        vlan: f.vlan.unwrap_or(0),
+        application: f.application.clone(),
+        state: f.state,
+        reason: record.reason,
+    };
+    let mut out = Vec::new();
+    if f.packets_to_server > 0 {
+        out.push(half(
+            client,
+            server,
+            f.client_port,
+            f.server_port,
+            f.tcp_flags_to_server,
+            f.packets_to_server,
+            f.bytes_to_server,
+        ));
+    }
+    if f.packets_to_client > 0 {
+        out.push(half(
+            server,
+            client,
+            f.server_port,
+            f.client_port,
+            f.tcp_flags_to_client,
+            f.packets_to_client,
+            f.bytes_to_client,
+        ));
+    }
+    out
+}
+
+fn put_ip(buf: &mut Vec<u8>, ip: IpAddr) {
+    match ip {
+        IpAddr::V4(v4) => buf.extend_from_slice(&v4.octets()),
+        IpAddr::V6(v6) => buf.extend_from_slice(&v6.octets()),
+    }
+}
+
+fn pad4(buf: &mut Vec<u8>, set_start: usize) {
+    while !(buf.len() - set_start).is_multiple_of(4) {
+        buf.push(0);
+    }
+}
+
+fn set_length(buf: &mut [u8], set_start: usize) {
+    let len = (buf.len() - set_start) as u16;
+    buf[set_start + 2..set_start + 4].copy_from_slice(&len.to_be_bytes());
+}
+
+/// (element id, length) of the v9 and IPFIX templates, without the
+/// enterprise elements.
+fn template_fields(v6: bool, format: ExportFormat) -> Vec<(u16, u16)> {
+    let (src, dst, len) = if v6 { (27, 28, 16) } else { (8, 12, 4) };
+    let mut fields = vec![(src, len), (dst, len), (7, 2), (11, 2), (4, 1), (6, 1), (2, 8), (1, 8)];
+    match format {
+        // FIRST_SWITCHED / LAST_SWITCHED (sysUptime), VLAN, APPLICATION_NAME
+        ExportFormat::NetflowV9 => fields.extend([(22, 4), (21, 4), (58, 2), (96, V9_APPLICATION_LEN as u16)]),
+        // flowStart/EndMilliseconds, vlanId, flowEndReason
+        _ => fields.extend([(152, 8), (153, 8), (58, 2), (136, 1)]),
+    }
+    fields
+}
+
+fn encode_template(buf: &mut Vec<u8>, format: ExportFormat) {
+    let set_start = buf.len();
+    let set_id: u16 = if format == ExportFormat::Ipfix { 2 } else { 0 };
+    buf.extend_from_slice(&set_id.to_be_bytes());
+    buf.extend_from_slice(&[0, 0]);
+    for (id, v6) in [(TEMPLATE_V4, false), (TEMPLATE_V6, true)] {
+        let fields = template_fields(v6, format);
+        let enterprise = if format == ExportFormat::Ipfix { 2 } else { 0 };
+        buf.extend_from_slice(&id.to_be_bytes());
+        buf.extend_from_slice(&((fields.len() + enterprise) as u16).to_be_bytes());
+        for (element, len) in fields {
+            buf.extend_from_slice(&element.to_be_bytes());
+            buf.extend_from_slice(&len.to_be_bytes());
+        }
+        if format == ExportFormat::Ipfix {
+            for (element, len) in [(IE_APPLICATION, 0xFFFF), (IE_FLOW_STATE, 1)] {
+                buf.extend_from_slice(&(element | 0x8000).to_be_bytes());
+                buf.extend_from_slice(&(len as u16).to_be_bytes());
+                buf.extend_from_slice(&ENTERPRISE_NUMBER.to_be_bytes());
+            }
+        }
+    }
+    set_length(buf, set_start);
+}
+
+fn encode_record(buf: &mut Vec<u8>, d: &FlowDirection, format: ExportFormat, boot_ms: u128) {
+    put_ip(buf, d.src);
+    put_ip(buf, d.dst);
+    buf.extend_from_slice(&d.src_port.to_be_bytes());
+    buf.extend_from_slice(&d.dst_port.to_be_bytes());
+    buf.push(d.protocol);
+    buf.push(d.tcp_flags);
+    buf.extend_from_slice(&d.packets.to_be_bytes());
+    buf.extend_from_slice(&d.bytes.to_be_bytes());
+    match format {
+        ExportFormat::NetflowV9 => {
+            buf.extend_from_slice(&uptime(d.start_ms, boot_ms).to_be_bytes());
+            buf.extend_from_slice(&uptime(d.end_ms, boot_ms).to_be_bytes());
+            buf.extend_from_slice(&d.vlan.to_be_bytes());
+            let mut name = [0u8; V9_APPLICATION_LEN];
+            let label = d.application.as_bytes();
+            let n = label.len().min(V9_APPLICATION_LEN);
+            name[..n].copy_from_slice(&label[..n]);
+            buf.extend_from_slice(&name);
+        }
+        _ => {
+            buf.extend_from_slice(&(d.start_ms as u64).to_be_bytes());
+            buf.extend_from_slice(&(d.end_ms as u64).to_be_bytes());
+            buf.extend_from_slice(&d.vlan.to_be_bytes());
+            buf.push(d.reason as u8);
+            // Variable-length string (RFC 7011 §7).
+            let label = &d.application.as_bytes()[..d.application.len().min(0xFFFF)];
+            if label.len() < 255 {
+                buf.push(label.len() as u8);
+            } else {
+                buf.push(255);
+                buf.extend_from_slice(&(label.len() as u16).to_be_bytes());
+            }
+            buf.extend_from_slice(label);
+            buf.push(state_code(d.state));
+        }
+    }
+}
+
+/// Milliseconds since the exporter started, as used by v5/v9 timestamps.
+fn uptime(ms: u128, boot_ms: u128) -> u32 {
+    ms.saturating_sub(boot_ms) as u32
+}
+
+/// Sequence counters and clock shared by the datagrams of one exporter.
+#[derive(Debug, Clone, Default)]
+pub struct ExportState {
+    pub boot_ms: u128,
+    /// Datagrams produced (v9 sequence number).
+    pub datagrams: u64,
+    /// Flow records produced (v5 and IPFIX sequence number).
+    pub records: u64,
+}
+
+/// Encodes flow directions into as many datagrams as needed.
+pub fn encode(
+    format: ExportFormat,
+    directions: &[FlowDirection],
+    now_ms: u128,
+    state: &mut ExportState,
+) -> Vec<Vec<u8>> {
+    match format {
+        ExportFormat::NetflowV5 => encode_v5(directions, now_ms, state),
+        _ => encode_templated(format, directions, now_ms, state),
+    }
+}
+
+fn encode_v5(directions: &[FlowDirection], now_ms: u128, state: &mut ExportState) -> Vec<Vec<u8>> {
+    // v5 only carries IPv4.
+    let v4: Vec<&FlowDirection> = directions
+        .iter()
+        .filter(|d| d.src.is_ipv4() && d.dst.is_ipv4())
+        .collect();
+    let mut out = Vec::new();
+    for chunk in v4.chunks(V5_MAX_RECORDS) {
+        let mut buf = Vec::with_capacity(24 + 48 * chunk.len());
+        buf.extend_from_slice(&5u16.to_be_bytes());
+        buf.extend_from_slice(&(chunk.len() as u16).to_be_bytes());
+        buf.extend_from_slice(&uptime(now_ms, state.boot_ms).to_be_bytes());
+        buf.extend_from_slice(&((now_ms / 1000) as u32).to_be_bytes());
+        buf.extend_from_slice(&(((now_ms % 1000) * 1_000_000) as u32).to_be_bytes());
+        buf.extend_from_slice(&(state.records as u32).to_be_bytes());
+        buf.extend_from_slice(&[0, 0, 0, 0]); // engine type/id, sampling
+        for d in chunk {
+            put_ip(&mut buf, d.src);
+            put_ip(&mut buf, d.dst);
+            buf.extend_from_slice(&[0; 4]); // next hop
+            buf.extend_from_slice(&[0; 4]); // input/output ifIndex
+            buf.extend_from_slice(&(d.packets.min(u32::MAX as u64) as u32).to_be_bytes());
+            buf.extend_from_slice(&(d.bytes.min(u32::MAX as u64) as u32).to_be_bytes());
+            buf.extend_from_slice(&uptime(d.start_ms, state.boot_ms).to_be_bytes());
+            buf.extend_from_slice(&uptime(d.end_ms, state.boot_ms).to_be_bytes());
+            buf.extend_from_slice(&d.src_port.to_be_bytes());
+            buf.extend_from_slice(&d.dst_port.to_be_bytes());
+            buf.push(0);
+            buf.push(d.tcp_flags);
+            buf.push(d.protocol);
+            buf.push(0); // ToS
+            buf.extend_from_slice(&[0; 8]); // AS numbers, masks, padding
+        }
+        state.records += chunk.len() as u64;
+        state.datagrams += 1;
+        out.push(buf);
+    }
+    out
+}
+
+fn encode_templated(
+    format: ExportFormat,
+    directions: &[FlowDirection],
+    now_ms: u128,
+    state: &mut ExportState,
+) -> Vec<Vec<u8>> {
+    let v4: Vec<&FlowDirection> = directions.iter().filter(|d| d.src.is_ipv4()).collect();
+    let v6: Vec<&FlowDirection> = directions.iter().filter(|d| d.src.is_ipv6()).collect();
+    let mut next = [0usize, 0usize];
+    let mut out = Vec::new();
+
+    // Templates go out even without records, so collectors learn them early.
+    while next[0] < v4.len() || next[1] < v6.len() || (out.is_empty() && state.datagrams.is_multiple_of(TEMPLATE_REFRESH)) {
+        let header_len = if format == ExportFormat::Ipfix { 16 } else { 20 };
+        let mut buf = vec![0u8; header_len];
+        let mut sets = 0u16;
+        if state.datagrams.is_multiple_of(TEMPLATE_REFRESH) {
+            encode_template(&mut buf, format);
+            sets += 1;
+        }
+
+        let mut records = 0u16;
+        for (family, (template, list)) in [(TEMPLATE_V4, &v4), (TEMPLATE_V6, &v6)].into_iter().enumerate() {
+            if next[family] >= list.len() {
+                continue;
+            }
+            let set_start = buf.len();
+            buf.extend_from_slice(&template.to_be_bytes());
+            buf.extend_from_slice(&[0, 0]);
+            let mut in_set = 0;
+            while let Some(d) = list.get(next[family]) {
+                let mut rec = Vec::new();
+                encode_record(&mut rec, d, format, state.boot_ms);
+                // Always make progress, even with an oversized record.
+                if buf.len() + rec.len() + 3 > MAX_DATAGRAM && (in_set > 0 || records > 0) {
+                    break;
+                }
+                buf.extend_from_slice(&rec);
+                next[family] += 1;
+                in_set += 1;
+            }
+            if in_set == 0 {
+                buf.truncate(set_start);
+                continue;
+            }
+            if format == ExportFormat::NetflowV9 {
+                pad4(&mut buf, set_start);
+            }
+            set_length(&mut buf, set_start);
+            records += in_set;
+        }
+
+        match format {
+            ExportFormat::Ipfix => {
+                let len = buf.len() as u16;
+                buf[0..2].copy_from_slice(&10u16.to_be_bytes());
+                buf[2..4].copy_from_slice(&len.to_be_bytes());
+                buf[4..8].copy_from_slice(&((now_ms / 1000) as u32).to_be_bytes());
+                buf[8..12].copy_from_slice(&(state.records as u32).to_be_bytes());
+                buf[12..16].copy_from_slice(&OBSERVATION_DOMAIN.to_be_bytes());
+            }
+            _ => {
+                // v9 count: template records plus data records.
+                let count = if sets > 0 { 2 } else { 0 } + records;
+                buf[0..2].copy_from_slice(&9u16.to_be_bytes());
+                buf[2..4].copy_from_slice(&count.to_be_bytes());
+                buf[4..8].copy_from_slice(&uptime(now_ms, state.boot_ms).to_be_bytes());
+                buf[8..12].copy_from_slice(&((now_ms / 1000) as u32).to_be_bytes());
+                buf[12..16].copy_from_slice(&(state.datagrams as u32).to_be_bytes());
+                buf[16..20].copy_from_slice(&OBSERVATION_DOMAIN.to_be_bytes());
+            }
+        }
+        state.records += records as u64;
+        state.datagrams += 1;
+        out.push(buf);
+    }
+    out
+}
+
+/// Sends expired flows of a `FlowTable` to a collector over UDP.
+pub struct FlowExporter {
+    socket: UdpSocket,
+    collector: SocketAddr,
+    format: ExportFormat,
+    state: ExportState,
+    idle_timeout_ms: u128,
+    active_timeout_ms: u128,
+    last_check: u128,
+    exported: u64,
+}
+
+impl FlowExporter {
+    pub fn new(
+        collector: &str,
+        format: ExportFormat,
+        idle_timeout_ms: u128,
+        active_timeout_ms: u128,
+        now_ms: u128,
+    ) -> Result<Self, String> {
+        let collector = collector
+            .to_socket_addrs()
+            .map_err(|e| format!("{collector}: {e}"))?
+            .next()
+            .ok_or_else(|| format!("{collector}: adresse introuvable"))?;
+        let bind = if collector.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = UdpSocket::bind(bind).map_err(|e| format!("{bind}: {e}"))?;
+        Ok(FlowExporter {
+            socket,
+            collector,
+            format,
+            state: ExportState {
+                boot_ms: now_ms,
+                ..Default::default()
+            },
+            idle_timeout_ms,
+            active_timeout_ms,
+            last_check: now_ms,
+            exported: 0,
+        })
+    }
+
+    pub fn collector(&self) -> SocketAddr {
+        self.collector
+    }
+
+    /// Flow directions sent so far.
+    pub fn exported(&self) -> u64 {
+        self.exported
+    }
+
+    pub fn send(&mut self, records: &[FlowRecord], now_ms: u128) -> std::io::Result<()> {
+        let dirs: Vec<FlowDirection> = records.iter().flat_map(directions).collect();
+        for datagram in encode(self.format, &dirs, now_ms, &mut self.state) {
+            self.socket.send_to(&datagram, self.collector)?;
+        }
+        // v5 drops IPv6 flows.
+        self.exported += dirs
+            .iter()
+            .filter(|d| self.format != ExportFormat::NetflowV5 || (d.src.is_ipv4() && d.dst.is_ipv4()))
+            .count() as u64;
+        Ok(())
+    }
+
+    /// Exports the flows that timed out or ended; checks at most once per
+    /// second.
+    pub fn tick(&mut self, table: &mut FlowTable, now_ms: u128) -> std::io::Result<()> {
+        if now_ms < self.last_check + CHECK_INTERVAL_MS {
+            return Ok(());
+        }
+        self.last_check = now_ms;
+        let records = table.expire(now_ms, self.idle_timeout_ms, self.active_timeout_ms);
+        if records.is_empty() && self.format == ExportFormat::NetflowV5 {
+            return Ok(());
+        }
+        self.send(&records, now_ms)
+    }
+
+    /// End of the capture: exports every remaining flow.
+    pub fn flush(&mut self, table: &mut FlowTable, now_ms: u128) -> std::io::Result<()> {
+        let records = table.drain();
+        self.send(&records, now_ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn direction(src: &str, dst: &str, application: &str) -> FlowDirection {
+        FlowDirection {
+            src: src.parse().unwrap(),
+            dst: dst.parse().unwrap(),
+            src_port: 40000,
+            dst_port: 443,
+            protocol: 6,
+            tcp_flags: 0x1b,
+            packets: 12,
+            bytes: 3400,
+            start_ms: 1_700_000_001_000,
+            end_ms: 1_700_000_002_500,
+            vlan: 10,
+            application: application.to_string(),
+            state: FlowState::Closed,
+            reason: EndReason::EndOfFlow,
+        }
+    }
+
+    fn u16_at(buf: &[u8], at: usize) -> u16 {
+        u16::from_be_bytes([buf[at], buf[at + 1]])
+    }
+
+    fn u32_at(buf: &[u8], at: usize) -> u32 {
+        u32::from_be_bytes(buf[at..at + 4].try_into().unwrap())
+    }
+
+    /// (set id, set body) of a v9 or IPFIX message, checking that the set
+    /// lengths add up to the message.
+    fn sets(msg: &[u8], header_len: usize) -> Vec<(u16, &[u8])> {
+        let mut out = Vec::new();
+        let mut at = header_len;
+        while at < msg.len() {
+            let len = u16_at(msg, at + 2) as usize;
+            assert!(len >= 4 && at + len <= msg.len(), "set de longueur {len} à {at}");
+            out.push((u16_at(msg, at), &msg[at + 4..at + len]));
+            at += len;
+        }
+        assert_eq!(at, msg.len());
+        out
+    }
+
+    const BOOT: u128 = 1_700_000_000_000;
+    const NOW: u128 = 1_700_000_003_250;
+
+    #[test]
+    fn v5_header_and_records() {
+        let dirs = vec![direction("10.0.0.1", "10.0.0.2", "HTTPS"), direction("2001:db8::1", "2001:db8::2", "HTTPS")];
+        let mut state = ExportState { boot_ms: BOOT, ..Default::default() };
+        let out = encode(ExportFormat::NetflowV5, &dirs, NOW, &mut state);
+        assert_eq!(out.len(), 1);
+        let msg = &out[0];
+        // Header of 24 bytes and one 48-byte record: IPv6 is left out.
+        assert_eq!(msg.len(), 24 + 48);
+        assert_eq!(u16_at(msg, 0), 5);
+        assert_eq!(u16_at(msg, 2), 1);
+        assert_eq!(u32_at(msg, 4), 3250);
+        assert_eq!(u32_at(msg, 8), 1_700_000_003);
+        assert_eq!(u32_at(msg, 12), 250_000_000);
+        assert_eq!(u32_at(msg, 16), 0);
+
+        let rec = &msg[24..];
+        assert_eq!(&rec[0..4], &[10, 0, 0, 1]);
+        assert_eq!(&rec[4..8], &[10, 0, 0, 2]);
+        assert_eq!(u32_at(rec, 16), 12);
+        assert_eq!(u32_at(rec, 20), 3400);
+        assert_eq!(u32_at(rec, 24), 1000);
+        assert_eq!(u32_at(rec, 28), 2500);
+        assert_eq!(u16_at(rec, 32), 40000);
+        assert_eq!(u16_at(rec, 34), 443);
+        assert_eq!((rec[37], rec[38]), (0x1b, 6));
+        assert_eq!(state.records, 1);
+    }
+
+    #[test]
+    fn v5_splits_at_thirty_records() {
+        let dirs = vec![direction("10.0.0.1", "10.0.0.2", ""); 31];
+        let mut state = ExportState::default();
+        let out = encode(ExportFormat::NetflowV5, &dirs, NOW, &mut state);
+        let counts: Vec<(u16, u32)> = out.iter().map(|m| (u16_at(m, 2), u32_at(m, 16))).collect();
+        // (count, flow sequence) of each datagram.
+        assert_eq!(counts, [(30, 0), (1, 30)]);
+        assert_eq!(out[1].len(), 24 + 48);
+    }
+
+    /// Template record of `id` in a template set: its (element, length,
+    /// enterprise) fields.
+    fn template(body: &[u8], id: u16) -> Vec<(u16, u16, Option<u32>)> {
+        let mut at = 0;
+        while at < body.len() {
+            let count = u16_at(body, at + 2) as usize;
+            let mut fields = Vec::new();
+            let mut p = at + 4;
+            for _ in 0..count {
+                let (element, len) = (u16_at(body, p), u16_at(body, p + 2));
+                p += 4;
+                let enterprise = (element & 0x8000 != 0).then(|| {
+                    p += 4;
+                    u32_at(body, p - 4)
+                });
+                fields.push((element & 0x7FFF, len, enterprise));
+            }
+            if u16_at(body, at) == id {
+                return fields;
+            }
+            at = p;
+        }
+        panic!("gabarit {id} absent");
+    }
+
+    #[test]
+    fn templated_messages() {
+        // (format, header length, version, template set id)
+        let cases = [(ExportFormat::NetflowV9, 20, 9, 0), (ExportFormat::Ipfix, 16, 10, 2)];
+        for (format, header_len, version, template_set) in cases {
+            let dirs = vec![direction("10.0.0.1", "10.0.0.2", "HTTPS"), direction("2001:db8::1", "2001:db8::2", "DNS")];
+            let mut state = ExportState { boot_ms: BOOT, ..Default::default() };
+            let out = encode(format, &dirs, NOW, &mut state);
+            assert_eq!(out.len(), 1, "{format:?}");
+            let msg = &out[0];
+            assert_eq!(u16_at(msg, 0), version);
+            let found = sets(msg, header_len);
+            let ids: Vec<u16> = found.iter().map(|s| s.0).collect();
+            assert_eq!(ids, [template_set, TEMPLATE_V4, TEMPLATE_V6], "{format:?}");
+
+            let v4 = template(found[0].1, TEMPLATE_V4);
+            let v6 = template(found[0].1, TEMPLATE_V6);
+            assert_eq!((v4[0], v6[0]), ((8, 4, None), (27, 16, None)));
+            let fixed = |fields: &[(u16, u16, Option<u32>)]| {
+                fields.iter().filter(|f| f.1 != 0xFFFF).map(|f| f.1 as usize).sum::<usize>()
+            };
+            match format {
+                ExportFormat::Ipfix => {
+                    // Message length, export time, sequence, domain (RFC 7011 §3.1).
+                    assert_eq!(u16_at(msg, 2) as usize, msg.len());
+                    assert_eq!(u32_at(msg, 4), 1_700_000_003);
+                    assert_eq!(u32_at(msg, 8), 0);
+                    assert_eq!(u32_at(msg, 12), OBSERVATION_DOMAIN);
+                    let enterprise = &v4[v4.len() - 2..];
+                    assert_eq!(
+                        enterprise,
+                        [(IE_APPLICATION, 0xFFFF, Some(ENTERPRISE_NUMBER)), (IE_FLOW_STATE, 1, Some(ENTERPRISE_NUMBER))]
+                    );
+                    // Fixed fields, then "HTTPS" with a one-byte length.
+                    assert_eq!(found[1].1.len(), fixed(&v4) + 1 + 5);
+                    assert_eq!(found[2].1.len(), fixed(&v6) + 1 + 3);
+                    assert_eq!(state.records, 2);
+                }
+                _ => {
+                    // Two template records plus two data records.
+                    assert_eq!(u16_at(msg, 2), 4);
+                    assert_eq!(u32_at(msg, 4), 3250);
+                    assert_eq!(u32_at(msg, 12), 0);
+                    assert_eq!(found[1].1.len(), fixed(&v4).next_multiple_of(4));
+                    assert_eq!(found[2].1.len(), fixed(&v6).next_multiple_of(4));
+                    let name = &found[1].1[fixed(&v4) - V9_APPLICATION_LEN..][..6];
+                    assert_eq!(name, b"HTTPS\0");
+                }
+            }
+
+            // The next datagram only carries data.
+            let out = encode(format, &dirs[..1], NOW, &mut state);
+            let ids: Vec<u16> = sets(&out[0], header_len).iter().map(|s| s.0).collect();
+            assert_eq!(ids, [TEMPLATE_V4], "{format:?}");
+        }
+    }
+
+    #[test]
+    fn ipfix_variable_length() {
+        // Application label length and its encoding (RFC 7011 §7).
+        let cases: [(usize, &[u8]); 4] = [(0, &[0]), (254, &[254]), (255, &[255, 0, 255]), (300, &[255, 1, 44])];
+        for (len, prefix) in cases {
+            let d = direction("10.0.0.1", "10.0.0.2", &"a".repeat(len));
+            let mut rec = Vec::new();
+            encode_record(&mut rec, &d, ExportFormat::Ipfix, 0);
+            let fixed = rec.len() - prefix.len() - len - 1;
+            assert_eq!(fixed, 49, "{len}");
+            assert_eq!(&rec[fixed..fixed + prefix.len()], prefix, "{len}");
+            assert_eq!(rec[rec.len() - 1], state_code(FlowState::Closed));
+            assert_eq!(rec[fixed - 1], EndReason::EndOfFlow as u8);
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use md5::Md5;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::core::capture;
+use crate::core::http::{self, HttpTracker};
+use crate::core::models::{HttpRequest, HttpResponse, HttpTransaction};
+use crate::core::stream::TcpReassembler;
+
+/// Decompressed size limit per object (guards against compression bombs).
+const MAX_DECODED: u64 = 256 * 1024 * 1024;
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// One line of the export manifest.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportedObject {
+    pub file: String,
+    /// Frame number of the packet that completed the response.
+    pub frame: u64,
+    pub host: Option<String>,
+    pub uri: Option<String>,
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub content_encoding: Option<String>,
+    /// Body size on the wire (after de-chunking, before decompression).
+    pub encoded_size: usize,
+    pub size: usize,
+    /// The capture did not contain the whole body.
+    pub truncated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decode_error: Option<String>,
+    pub md5: String,
+    pub sha256: String,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn too_large() -> String {
+    format!("corps décodé de plus de {} Mio", MAX_DECODED / (1024 * 1024))
+}
+
+/// Fails rather than cutting the body at `MAX_DECODED`: a partial object
+/// would be exported and hashed as if it were complete.
+fn read_limited(reader: impl Read) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    reader
+        .take(MAX_DECODED + 1)
+        .read_to_end(&mut out)
+        .map_err(|e| e.to_string())?;
+    if out.len() as u64 > MAX_DECODED {
+        return Err(too_large());
+    }
+    Ok(out)
+}
+
+/// Undoes a `Content-Encoding` (codings are applied in the listed order,
+/// so they are removed from last to first).
+pub fn decode_body(body: &[u8], encoding: Option<&str>) -> Result<Vec<u8>, String> {
+    let mut data = body.to_vec();
+    let Some(encoding) = encoding else {
+        return Ok(data);
+    };
+
+    for coding in encoding.rsplit(',') {
+        data = match coding.trim().to_ascii_lowercase().as_str() {
+            "" | "identity" => data,
+            "gzip" | "x-gzip" => read_limited(flate2::read::MultiGzDecoder::new(&data[..]))?,
+            // RFC 9110 says zlib, but some servers send raw deflate.
+            "deflate" => read_limited(flate2::read::ZlibDecoder::new(&data[..])).or_else(|e| {
+                if e == too_large() {
+                    return Err(e);
+                }
+                read_limited(flate2::read::DeflateDecoder::new(&data[..]))
+            })?,
+            "br" => read_limited(brotli_decompressor::Decompressor::new(&data[..], 4096))?,
+            other => return Err(format!("encodage non supporté : {other}")),
+        };
+    }
+    Ok(data)
+}
+
+/// `filename*=UTF-8''...` wins over `filename="..."`.
+fn disposition_filename(value: &str) -> Option<String> {
+    let mut plain = None;
+    for param in value.split(';').map(str::trim) {
+        let Some((name, val)) = param.split_once('=') else {
+            continue;
+        };
+        match name.trim().to_ascii_lowercase().as_str() {
+            "filename*" => {
+                let encoded = val.trim().splitn(3, '\'').nth(2)?;
+                return Some(http::percent_decode(encoded));
+            }
+            "filename" => plain = Some(val.trim().trim_matches('"').to_string()),
+            _ => {}
+        }
+    }
+    plain
+}
+
+fn extension_for(content_type: Option<&str>) -> Option<&'static str> {
+    let mime = content_type?.split(';').next()?.trim().to_ascii_lowercase();
+    Some(match mime.as_str() {
+        "text/html" => "html",
+        "text/plain" => "txt",
+        "text/css" => "css",
+        "text/javascript" | "application/javascript" => "js",
+        "application/json" => "json",
+        "application/xml" | "text/xml" => "xml",
+        "application/pdf" => "pdf",
+        "application/zip" => "zip",
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/svg+xml" => "svg",
+        "image/x-icon" | "image/vnd.microsoft.icon" => "ico",
+        _ => return None,
+    })
+}
+
+fn sanitize(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() || ".-_()+ ".contains(c) { c } else { '_' })
+        .take(120)
+        .collect();
+    cleaned.trim().trim_start_matches('.').to_string()
+}
+
+/// File name for an exported body: Content-Disposition, else the last
+/// segment of the request URI, with an extension guessed from the
+/// Content-Type when it has none.
+pub fn object_name(req: Option<&HttpRequest>, resp: &HttpResponse) -> String {
+    let from_header = http::header(&resp.headers, "Content-Disposition").and_then(disposition_filename);
+    let from_uri = req.map(|r| {
+        let path = r.uri.split(['?', '#']).next().unwrap_or("");
+        http::percent_decode(path.rsplit('/').next().unwrap_or(""))
+    });
+
+    let mut name = sanitize(&from_header.or(from_uri).unwrap_or_default());
+    if name.is_empty() {
+        name = "index".to_string();
+    }
+    if !name.contains('.') {
+        if let Some(ext) = extension_for(http::header(&resp.headers, "Content-Type")) {
+            name = format!("{name}.{ext}");
+        }
+    }
+    name
+}
+
+/// Writes HTTP response bodies to a directory and keeps the manifest.
+pub struct ObjectExporter {
+    out: PathBuf,
+    used: HashSet<String>,
+    objects: Vec<ExportedObject>,
+}
+
+impl ObjectExporter {
+    pub fn new(out: &Path) -> std::io::Result<Self> {
+        fs::create_dir_all(out)?;
+        Ok(ObjectExporter {
+            out: out.to_path_buf(),
+            used: HashSet::from([MANIFEST_FILE.to_string()]),
+            objects: Vec::new(),
+        })
+    }
+
+    fn unique_name(&mut self, name: String) -> String {
+        let (stem, ext) = match name.rfind('.') {
+            Some(idx) if idx > 0 => (name[..idx].to_string(), name[idx..].to_string()),
+            _ => (name.clone(), String::new()),
+        };
+        let mut candidate = name;
+        let mut n = 1;
+        while self.used.contains(&candidate) {
+            candidate = format!("{stem}_{n}{ext}");
+            n += 1;
+        }
+        self.used.insert(candidate.clone());
+        candidate
+    }
+
+    /// Exports the response body of a transaction, if it has one.
+    pub fn export(&mut self, frame: u64, tx: &HttpTransaction) -> std::io::Result<Option<&ExportedObject>> {
+        let Some(resp) = &tx.response else {
+            return Ok(None);
+        };
+        if resp.body.is_empty() {
+            return Ok(None);
+        }
+
+        let encoding = http::header(&resp.headers, "Content-Encoding");
+        let (data, decode_error) = match decode_body(&resp.body, encoding) {
+            Ok(d) => (d, None),
+            Err(e) => (resp.body.clone(), Some(e)),
+        };
+
+        let file = self.unique_name(object_name(tx.request.as_ref(), resp));
+        fs::write(self.out.join(&file), &data)?;
+
+        self.objects.push(ExportedObject {
+            file,
+            frame,
+            host: tx.request.as_ref().and_then(|r| r.host.clone()),
+            uri: tx.request.as_ref().map(|r| r.uri.clone()),
+            status: resp.status,
+            content_type: http::header(&resp.headers, "Content-Type").map(str::to_string),
+            content_encoding: encoding.map(str::to_string),
+            encoded_size: resp.body.len(),
+            size: data.len(),
+            truncated: resp.body.len() < resp.body_len,
+            decode_error,
+            md5: hex(&Md5::digest(&data)),
+            sha256: hex(&Sha256::digest(&data)),
+        });
+        Ok(self.objects.last())
+    }
+
+    pub fn objects(&self) -> &[ExportedObject] {
+        &self.objects
+    }
+
+    pub fn write_manifest(&self) -> std::io::Result<PathBuf> {
+        let path = self.out.join(MANIFEST_FILE);
+        let json = serde_json::to_string_pretty(&self.objects).map_err(std::io::Error::other)?;
+        fs::write(&path, json)?;
+        Ok(path)
+    }
+}
+
+/// `wirefish export-objects http`: replays a capture file through the TCP
+/// and HTTP layers and exports every response body found.
+pub fn export_http(capture_file: &Path, out: &Path) -> Result<Vec<ExportedObject>, String> {
+    let mut exporter = ObjectExporter::new(out).map_err(|e| format!("{}: {e}", out.display()))?;
+    let mut streams = TcpReassembler::new();
+    let mut http_tracker = HttpTracker::new();
+    let mut error = None;
+
+    let mut last_frame = 0;
+    capture::read_file(capture_file, |frame, mut packet| {
+        last_frame = frame;
+        let events = streams.observe(&packet);
+        http_tracker.observe(&mut packet, &events);
+        for tx in &packet.http {
+            if let Err(e) = exporter.export(frame, tx) {
+                error.get_or_insert(e);
+            }
+        }
+    })?;
+
+    // Bytes still buffered behind a hole, then responses delimited by a
+    // connection close that never came.
+    let mut pending = Vec::new();
+    for event in streams.finish() {
+        pending.extend(http_tracker.on_event(&event, 0));
+    }
+    pending.extend(http_tracker.finish());
+    for tx in pending {
+        if let Err(e) = exporter.export(last_frame, &tx) {
+            error.get_or_insert(e);
+        }
+    }
+
+    if let Some(e) = error {
+        return Err(format!("{}: {e}", out.display()));
+    }
+    exporter
+        .write_manifest()
+        .map_err(|e| format!("{}: {e}", out.display()))?;
+    Ok(exporter.objects().to_vec())
+}
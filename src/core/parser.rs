@@ -1,11 +1,25 @@
 use crate::core::models::*;
+use crate::core::dhcp;
+use crate::core::dns;
+use crate::core::netbios;
+use crate::core::tls;
 use chrono::Utc;
+use std::net::Ipv6Addr;
 
 pub fn parse_packet(raw: &[u8]) -> Option<Packet> {
+    parse_packet_at(raw, Utc::now().timestamp_millis() as u128)
+}
+
+/// Same as `parse_packet` with an explicit capture time (ms), e.g. the
+/// timestamp recorded in a capture file.
+pub fn parse_packet_at(raw: &[u8], timestamp: u128) -> Option<Packet> {
     if raw.len() < 14 {
         return None;
     }
 
+    let (untagged, vlan) = strip_vlan_tags(raw);
+    let raw = untagged.as_deref().unwrap_or(raw);
+
     // Ethernet header
     let ethertype = u16::from_be_bytes([raw[12], raw[13]]);
 
@@ -19,10 +33,9 @@ pub fn parse_packet(raw: &[u8]) -> Option<Packet> {
             raw[0], raw[1], raw[2], raw[3], raw[4], raw[5]
         ),
         ethertype,
+        vlan,
     };
 
-    let timestamp = Utc::now().timestamp_millis() as u128;
-
     // Path 1: IPv4 (0x0800) with transport parsing
     if ethertype == 0x0800 && raw.len() >= 34 {
         let src_ip = format!("{}.{}.{}.{}", raw[26], raw[27], raw[28], raw[29]);
@@ -36,56 +49,282 @@ pub fn parse_packet(raw: &[u8]) -> Option<Packet> {
             protocol,
         };
 
+        let l4 = 14 + ((raw[14] & 0x0f) as usize) * 4;
         let transport = match protocol {
-            6 => parse_tcp(raw),
-            17 => parse_udp(raw),
+            6 => parse_tcp(raw, l4),
+            17 => parse_udp(raw, l4),
             1 => Some(TransportProtocol::Icmp),
             _ => Some(TransportProtocol::Unknown),
         };
 
-        return Some(Packet {
+        let mut packet = Packet {
             timestamp,
             eth: Some(eth),
             ip: Some(ip_header),
             transport,
             payload: raw.to_vec(),
-        });
+            tls: None,
+            dns: Vec::new(),
+            nbns: None,
+            dhcp: None,
+            http: Vec::new(),
+            http2: None,
+            grpc: Vec::new(),
+            tcp_analysis: None,
+            connection_tls: None,
+        };
+        decode_app_layers(&mut packet);
+        return Some(packet);
+    }
+
+    // Path 2: IPv6 (0x86DD) — fixed header only, extension headers are not walked
+    if ethertype == 0x86DD && raw.len() >= 54 {
+        let src = Ipv6Addr::from(<[u8; 16]>::try_from(&raw[22..38]).unwrap());
+        let dst = Ipv6Addr::from(<[u8; 16]>::try_from(&raw[38..54]).unwrap());
+        let next_header = raw[20];
+
+        let transport = match next_header {
+            6 => parse_tcp(raw, 54),
+            17 => parse_udp(raw, 54),
+            58 => Some(TransportProtocol::Icmp),
+            _ => Some(TransportProtocol::Unknown),
+        };
+
+        let mut packet = Packet {
+            timestamp,
+            eth: Some(eth),
+            ip: Some(IpHeader {
+                src_ip: src.to_string(),
+                dst_ip: dst.to_string(),
+                protocol: next_header,
+            }),
+            transport,
+            payload: raw.to_vec(),
+            tls: None,
+            dns: Vec::new(),
+            nbns: None,
+            dhcp: None,
+            http: Vec::new(),
+            http2: None,
+            grpc: Vec::new(),
+            tcp_analysis: None,
+            connection_tls: None,
+        };
+        decode_app_layers(&mut packet);
+        return Some(packet);
     }
 
-    // Path 2: other EtherTypes (IPv6/ARP/etc.) — return packet without IP/transport layers
+    // Path 3: other EtherTypes (ARP/LLDP/etc.) — return packet without IP/transport layers
     Some(Packet {
         timestamp,
         eth: Some(eth),
         ip: None,
         transport: None,
         payload: raw.to_vec(),
+        tls: None,
+        dns: Vec::new(),
+        nbns: None,
+        dhcp: None,
+        http: Vec::new(),
+        http2: None,
+        grpc: Vec::new(),
+        tcp_analysis: None,
+        connection_tls: None,
     })
 }
 
-fn parse_tcp(raw: &[u8]) -> Option<TransportProtocol> {
-    if raw.len() < 38 {
+/// Removes 802.1Q/802.1ad tags so the decoders see a plain Ethernet frame.
+/// Returns the untagged copy (if the frame was tagged) and the outer VLAN ID.
+fn strip_vlan_tags(raw: &[u8]) -> (Option<Vec<u8>>, Option<u16>) {
+    let mut frame: Option<Vec<u8>> = None;
+    let mut vlan = None;
+    loop {
+        let cur = frame.as_deref().unwrap_or(raw);
+        let ethertype = u16::from_be_bytes([cur[12], cur[13]]);
+        if !matches!(ethertype, 0x8100 | 0x88A8) || cur.len() < 18 {
+            return (frame, vlan);
+        }
+        vlan.get_or_insert(u16::from_be_bytes([cur[14], cur[15]]) & 0x0fff);
+        let mut next = cur[..12].to_vec();
+        next.extend_from_slice(&cur[16..]);
+        frame = Some(next);
+    }
+}
+
+fn parse_tcp(raw: &[u8], l4: usize) -> Option<TransportProtocol> {
+    if raw.len() < l4 + 4 {
         return None;
     }
-    let src_port = u16::from_be_bytes([raw[34], raw[35]]);
-    let dst_port = u16::from_be_bytes([raw[36], raw[37]]);
-    let flags = if raw.len() > 47 { raw[47] } else { 0 };
+    let src_port = u16::from_be_bytes([raw[l4], raw[l4 + 1]]);
+    let dst_port = u16::from_be_bytes([raw[l4 + 2], raw[l4 + 3]]);
+    let flags = if raw.len() > l4 + 13 { raw[l4 + 13] } else { 0 };
+    let seq = if raw.len() >= l4 + 8 {
+        u32::from_be_bytes([raw[l4 + 4], raw[l4 + 5], raw[l4 + 6], raw[l4 + 7]])
+    } else {
+        0
+    };
+    let ack = if raw.len() >= l4 + 12 {
+        u32::from_be_bytes([raw[l4 + 8], raw[l4 + 9], raw[l4 + 10], raw[l4 + 11]])
+    } else {
+        0
+    };
+    let window = if raw.len() >= l4 + 16 {
+        u16::from_be_bytes([raw[l4 + 14], raw[l4 + 15]])
+    } else {
+        0
+    };
+    let window_scale = if flags & 0x02 != 0 { tcp_window_scale(raw, l4) } else { None };
 
     Some(TransportProtocol::Tcp(TcpHeader {
         src_port,
         dst_port,
         flags,
+        seq,
+        ack,
+        window,
+        window_scale,
     }))
 }
 
-fn parse_udp(raw: &[u8]) -> Option<TransportProtocol> {
-    if raw.len() < 38 {
+/// Window scale shift from the options of a SYN segment.
+fn tcp_window_scale(raw: &[u8], l4: usize) -> Option<u8> {
+    let header_len = ((*raw.get(l4 + 12)? >> 4) as usize) * 4;
+    let options = raw.get(l4 + 20..(l4 + header_len).min(raw.len()))?;
+    let mut i = 0;
+    while i < options.len() {
+        match options[i] {
+            0 => break,
+            1 => i += 1,
+            kind => {
+                let len = *options.get(i + 1)? as usize;
+                if len < 2 {
+                    break;
+                }
+                if kind == 3 && len == 3 {
+                    return options.get(i + 2).map(|&shift| shift.min(14));
+                }
+                i += len;
+            }
+        }
+    }
+    None
+}
+
+fn parse_udp(raw: &[u8], l4: usize) -> Option<TransportProtocol> {
+    if raw.len() < l4 + 4 {
         return None;
     }
-    let src_port = u16::from_be_bytes([raw[34], raw[35]]);
-    let dst_port = u16::from_be_bytes([raw[36], raw[37]]);
+    let src_port = u16::from_be_bytes([raw[l4], raw[l4 + 1]]);
+    let dst_port = u16::from_be_bytes([raw[l4 + 2], raw[l4 + 3]]);
 
     Some(TransportProtocol::Udp(UdpHeader {
         src_port,
         dst_port,
     }))
 }
+
+/// Runs the application-layer decoders that work on a single segment.
+fn decode_app_layers(packet: &mut Packet) {
+    let payload = transport_payload(&packet.payload);
+    if payload.is_empty() {
+        return;
+    }
+
+    if let Some(TransportProtocol::Tcp(_)) = &packet.transport {
+        packet.tls = tls::parse(payload);
+    }
+    packet.dns = dns::from_packet(packet, payload).into_iter().collect();
+    packet.nbns = netbios::from_packet(packet, payload);
+    packet.dhcp = dhcp::from_packet(packet, payload);
+}
+
+/// Returns the application payload (after the TCP/UDP header) of a raw
+/// Ethernet frame, without the Ethernet trailer padding. Empty when the
+/// frame carries no TCP/UDP segment over IPv4/IPv6.
+pub fn transport_payload(raw: &[u8]) -> &[u8] {
+    if raw.len() < 14 {
+        return &[];
+    }
+
+    let ethertype = u16::from_be_bytes([raw[12], raw[13]]);
+    let (proto, l4, ip_end) = match ethertype {
+        0x0800 if raw.len() >= 34 => {
+            let total_len = u16::from_be_bytes([raw[16], raw[17]]) as usize;
+            (raw[23], 14 + ((raw[14] & 0x0f) as usize) * 4, 14 + total_len)
+        }
+        0x86DD if raw.len() >= 54 => {
+            let payload_len = u16::from_be_bytes([raw[18], raw[19]]) as usize;
+            (raw[20], 54, 54 + payload_len)
+        }
+        _ => return &[],
+    };
+    // A zero IPv4 length shows up with TSO offloading: trust the frame size then.
+    let ip_end = if ip_end <= 14 { raw.len() } else { ip_end.min(raw.len()) };
+
+    let start = match proto {
+        6 if raw.len() >= l4 + 20 => l4 + ((raw[l4 + 12] >> 4) as usize) * 4,
+        17 if raw.len() >= l4 + 8 => l4 + 8,
+        _ => return &[],
+    };
+
+    if start >= ip_end {
+        return &[];
+    }
+    &raw[start..ip_end]
+}
+
+/// Length of the IPv4/IPv6 packet carried by a raw Ethernet frame, from
+/// its header and without the Ethernet trailer padding (0 without IP).
+pub fn ip_length(raw: &[u8]) -> usize {
+    if raw.len() < 14 {
+        return 0;
+    }
+    let frame = raw.len() - 14;
+    let len = match u16::from_be_bytes([raw[12], raw[13]]) {
+        0x0800 if raw.len() >= 34 => u16::from_be_bytes([raw[16], raw[17]]) as usize,
+        0x86DD if raw.len() >= 54 => 40 + u16::from_be_bytes([raw[18], raw[19]]) as usize,
+        _ => return 0,
+    };
+    // A zero IPv4 length shows up with TSO offloading: trust the frame size then.
+    if len == 0 { frame } else { len.min(frame) }
+}
+
+/// Returns the type and code of the ICMP or ICMPv6 message carried by a
+/// raw Ethernet frame.
+pub fn icmp_header(raw: &[u8]) -> Option<(u8, u8)> {
+    if raw.len() < 14 {
+        return None;
+    }
+    let start = match u16::from_be_bytes([raw[12], raw[13]]) {
+        0x0800 if raw.len() >= 34 && raw[23] == 1 => 14 + ((raw[14] & 0x0f) as usize) * 4,
+        0x86DD if raw.len() >= 54 && raw[20] == 58 => 54,
+        _ => return None,
+    };
+    Some((*raw.get(start)?, *raw.get(start + 1)?))
+}
+
+/// Decodes an Ethernet/IPv4 ARP message.
+pub fn parse_arp(raw: &[u8]) -> Option<ArpMessage> {
+    if raw.len() < 42 || raw[12..14] != [0x08, 0x06] {
+        return None;
+    }
+    let arp = &raw[14..];
+    // Hardware type Ethernet, protocol type IPv4, 6-byte and 4-byte addresses.
+    if arp[0..6] != [0x00, 0x01, 0x08, 0x00, 6, 4] {
+        return None;
+    }
+    let mac = |b: &[u8]| {
+        format!(
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            b[0], b[1], b[2], b[3], b[4], b[5]
+        )
+    };
+    let ip = |b: &[u8]| format!("{}.{}.{}.{}", b[0], b[1], b[2], b[3]);
+    Some(ArpMessage {
+        operation: u16::from_be_bytes([arp[6], arp[7]]),
+        sender_mac: mac(&arp[8..14]),
+        sender_ip: ip(&arp[14..18]),
+        target_mac: mac(&arp[18..24]),
+        target_ip: ip(&arp[24..28]),
+    })
+}
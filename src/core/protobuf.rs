@@ -0,0 +1,490 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde_json::{Map, Value};
+
+/// Nesting limit when decoding messages (guards against hostile payloads).
+const MAX_DEPTH: usize = 64;
+
+// FieldDescriptorProto.Type
+const TYPE_DOUBLE: i32 = 1;
+const TYPE_FLOAT: i32 = 2;
+const TYPE_INT64: i32 = 3;
+const TYPE_UINT64: i32 = 4;
+const TYPE_INT32: i32 = 5;
+const TYPE_FIXED64: i32 = 6;
+const TYPE_FIXED32: i32 = 7;
+const TYPE_BOOL: i32 = 8;
+const TYPE_STRING: i32 = 9;
+const TYPE_MESSAGE: i32 = 11;
+const TYPE_BYTES: i32 = 12;
+const TYPE_UINT32: i32 = 13;
+const TYPE_ENUM: i32 = 14;
+const TYPE_SFIXED32: i32 = 15;
+const TYPE_SFIXED64: i32 = 16;
+const TYPE_SINT32: i32 = 17;
+const TYPE_SINT64: i32 = 18;
+
+const LABEL_REPEATED: i32 = 3;
+
+#[derive(Debug, Clone, Copy)]
+pub enum WireValue<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let b = *data.get(*pos)?;
+        *pos += 1;
+        value |= ((b & 0x7f) as u64) << shift;
+        if b & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Splits a serialized message into (field number, value) pairs.
+/// Returns `None` if the bytes are not a well-formed message.
+pub fn parse_fields(data: &[u8]) -> Option<Vec<(u32, WireValue<'_>)>> {
+    let mut fields = Vec::new();
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let key = read_varint(data, &mut pos)?;
+        let number = u32::try_from(key >> 3).ok().filter(|&n| n > 0)?;
+        let value = match key & 7 {
+            0 => WireValue::Varint(read_varint(data, &mut pos)?),
+            1 => {
+                let b = data.get(pos..pos + 8)?;
+                pos += 8;
+                WireValue::Fixed64(u64::from_le_bytes(b.try_into().ok()?))
+            }
+            2 => {
+                let len = usize::try_from(read_varint(data, &mut pos)?).ok()?;
+                let b = data.get(pos..pos.checked_add(len)?)?;
+                pos += len;
+                WireValue::Bytes(b)
+            }
+            5 => {
+                let b = data.get(pos..pos + 4)?;
+                pos += 4;
+                WireValue::Fixed32(u32::from_le_bytes(b.try_into().ok()?))
+            }
+            // Groups (3/4) are deprecated and not supported.
+            _ => return None,
+        };
+        fields.push((number, value));
+    }
+
+    Some(fields)
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn float_value(v: f64) -> Value {
+    serde_json::Number::from_f64(v)
+        .map(Value::Number)
+        .unwrap_or_else(|| Value::String(v.to_string()))
+}
+
+fn zigzag(v: u64) -> i64 {
+    ((v >> 1) as i64) ^ -((v & 1) as i64)
+}
+
+fn push_field(obj: &mut Map<String, Value>, key: String, value: Value, repeated: bool) {
+    if !repeated {
+        obj.insert(key, value);
+        return;
+    }
+    match obj.entry(key).or_insert_with(|| Value::Array(Vec::new())) {
+        Value::Array(items) => items.push(value),
+        other => *other = Value::Array(vec![other.take(), value]),
+    }
+}
+
+/// Schema-less decoding: field numbers as keys, nested messages guessed.
+pub fn decode_raw(data: &[u8]) -> Option<Value> {
+    decode_raw_at(data, 0)
+}
+
+fn decode_raw_at(data: &[u8], depth: usize) -> Option<Value> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    let fields = parse_fields(data)?;
+    let mut counts: HashMap<u32, usize> = HashMap::new();
+    for (n, _) in &fields {
+        *counts.entry(*n).or_default() += 1;
+    }
+
+    let mut obj = Map::new();
+    for (number, value) in fields {
+        let json = match value {
+            WireValue::Varint(v) => Value::from(v),
+            WireValue::Fixed64(v) => Value::from(v),
+            WireValue::Fixed32(v) => Value::from(v),
+            WireValue::Bytes(b) => match std::str::from_utf8(b) {
+                Ok(s) if !s.chars().any(|c| c.is_control() && !c.is_whitespace()) => Value::String(s.to_string()),
+                _ => decode_raw_at(b, depth + 1)
+                    .filter(|_| !b.is_empty())
+                    .unwrap_or_else(|| Value::String(base64(b))),
+            },
+        };
+        push_field(&mut obj, number.to_string(), json, counts[&number] > 1);
+    }
+    Some(Value::Object(obj))
+}
+
+#[derive(Debug, Clone, Default)]
+struct FieldDesc {
+    name: String,
+    json_name: String,
+    number: u32,
+    repeated: bool,
+    field_type: i32,
+    type_name: String,
+}
+
+#[derive(Debug, Clone, Default)]
+struct MessageDesc {
+    fields: Vec<FieldDesc>,
+    map_entry: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct MethodDesc {
+    pub input_type: String,
+    pub output_type: String,
+    pub client_streaming: bool,
+    pub server_streaming: bool,
+}
+
+fn string_of(value: WireValue) -> String {
+    match value {
+        WireValue::Bytes(b) => String::from_utf8_lossy(b).to_string(),
+        _ => String::new(),
+    }
+}
+
+fn int_of(value: WireValue) -> i64 {
+    match value {
+        WireValue::Varint(v) => v as i64,
+        _ => 0,
+    }
+}
+
+fn bytes_of<'a>(value: WireValue<'a>) -> &'a [u8] {
+    match value {
+        WireValue::Bytes(b) => b,
+        _ => &[],
+    }
+}
+
+/// lowerCamelCase name used by the proto3 JSON mapping.
+fn json_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            out.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Message, enum and service definitions loaded from a FileDescriptorSet
+/// (`protoc --include_imports --descriptor_set_out=...`).
+#[derive(Debug, Default)]
+pub struct DescriptorPool {
+    messages: HashMap<String, MessageDesc>,
+    enums: HashMap<String, HashMap<i32, String>>,
+    // "/package.Service/Method"
+    methods: HashMap<String, MethodDesc>,
+}
+
+impl DescriptorPool {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        Self::from_bytes(&data).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, String> {
+        let invalid = || "FileDescriptorSet invalide".to_string();
+        let mut pool = DescriptorPool::default();
+
+        for (number, file) in parse_fields(data).ok_or_else(invalid)? {
+            if number != 1 {
+                continue;
+            }
+            let fields = parse_fields(bytes_of(file)).ok_or_else(invalid)?;
+            let package = fields
+                .iter()
+                .find(|(n, _)| *n == 2)
+                .map(|(_, v)| string_of(*v))
+                .unwrap_or_default();
+            let scope = if package.is_empty() { String::new() } else { format!(".{package}") };
+
+            for (n, v) in &fields {
+                match n {
+                    4 => pool.add_message(&scope, bytes_of(*v)).ok_or_else(invalid)?,
+                    5 => pool.add_enum(&scope, bytes_of(*v)).ok_or_else(invalid)?,
+                    6 => pool.add_service(&package, bytes_of(*v)).ok_or_else(invalid)?,
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(pool)
+    }
+
+    fn add_message(&mut self, scope: &str, data: &[u8]) -> Option<()> {
+        let fields = parse_fields(data)?;
+        let name = fields.iter().find(|(n, _)| *n == 1).map(|(_, v)| string_of(*v))?;
+        let full = format!("{scope}.{name}");
+        let mut desc = MessageDesc::default();
+
+        for (n, v) in fields {
+            match n {
+                2 => {
+                    let mut f = FieldDesc::default();
+                    for (fnum, fv) in parse_fields(bytes_of(v))? {
+                        match fnum {
+                            1 => f.name = string_of(fv),
+                            3 => f.number = int_of(fv) as u32,
+                            4 => f.repeated = int_of(fv) as i32 == LABEL_REPEATED,
+                            5 => f.field_type = int_of(fv) as i32,
+                            6 => f.type_name = string_of(fv),
+                            10 => f.json_name = string_of(fv),
+                            _ => {}
+                        }
+                    }
+                    if f.json_name.is_empty() {
+                        f.json_name = json_name(&f.name);
+                    }
+                    desc.fields.push(f);
+                }
+                3 => self.add_message(&full, bytes_of(v))?,
+                4 => self.add_enum(&full, bytes_of(v))?,
+                // MessageOptions.map_entry
+                7 => desc.map_entry = parse_fields(bytes_of(v))?
+                    .iter()
+                    .any(|(o, ov)| *o == 7 && int_of(*ov) != 0),
+                _ => {}
+            }
+        }
+
+        self.messages.insert(full, desc);
+        Some(())
+    }
+
+    fn add_enum(&mut self, scope: &str, data: &[u8]) -> Option<()> {
+        let fields = parse_fields(data)?;
+        let name = fields.iter().find(|(n, _)| *n == 1).map(|(_, v)| string_of(*v))?;
+        let mut values = HashMap::new();
+        for (n, v) in fields {
+            if n != 2 {
+                continue;
+            }
+            let value = parse_fields(bytes_of(v))?;
+            let vname = value.iter().find(|(n, _)| *n == 1).map(|(_, v)| string_of(*v));
+            let number = value.iter().find(|(n, _)| *n == 2).map(|(_, v)| int_of(*v) as i32);
+            if let (Some(vname), Some(number)) = (vname, number) {
+                values.insert(number, vname);
+            }
+        }
+        self.enums.insert(format!("{scope}.{name}"), values);
+        Some(())
+    }
+
+    fn add_service(&mut self, package: &str, data: &[u8]) -> Option<()> {
+        let fields = parse_fields(data)?;
+        let name = fields.iter().find(|(n, _)| *n == 1).map(|(_, v)| string_of(*v))?;
+        let service = if package.is_empty() { name } else { format!("{package}.{name}") };
+
+        for (n, v) in fields {
+            if n != 2 {
+                continue;
+            }
+            let mut method = MethodDesc {
+                input_type: String::new(),
+                output_type: String::new(),
+                client_streaming: false,
+                server_streaming: false,
+            };
+            let mut mname = String::new();
+            for (mn, mv) in parse_fields(bytes_of(v))? {
+                match mn {
+                    1 => mname = string_of(mv),
+                    2 => method.input_type = string_of(mv),
+                    3 => method.output_type = string_of(mv),
+                    5 => method.client_streaming = int_of(mv) != 0,
+                    6 => method.server_streaming = int_of(mv) != 0,
+                    _ => {}
+                }
+            }
+            self.methods.insert(format!("/{service}/{mname}"), method);
+        }
+        Some(())
+    }
+
+    pub fn message_count(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn method_count(&self) -> usize {
+        self.methods.len()
+    }
+
+    /// Method of a gRPC `:path` (`/package.Service/Method`).
+    pub fn method(&self, path: &str) -> Option<&MethodDesc> {
+        self.methods.get(path)
+    }
+
+    /// Decodes a serialized message of type `type_name` (e.g. `.pkg.Msg`)
+    /// with the proto3 JSON mapping. Unknown fields are kept by number.
+    pub fn decode(&self, type_name: &str, data: &[u8]) -> Option<Value> {
+        self.decode_at(type_name, data, 0)
+    }
+
+    fn decode_at(&self, type_name: &str, data: &[u8], depth: usize) -> Option<Value> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        let Some(desc) = self.messages.get(type_name) else {
+            return decode_raw_at(data, depth);
+        };
+
+        let mut obj = Map::new();
+        for (number, value) in parse_fields(data)? {
+            let Some(field) = desc.fields.iter().find(|f| f.number == number) else {
+                push_field(&mut obj, number.to_string(), decode_raw_value(value), false);
+                continue;
+            };
+
+            let map_entry = field.field_type == TYPE_MESSAGE
+                && self.messages.get(&field.type_name).map(|m| m.map_entry).unwrap_or(false);
+            if map_entry {
+                let entry = self.decode_at(&field.type_name, bytes_of(value), depth + 1)?;
+                let key = match entry.get("key") {
+                    Some(Value::String(s)) => s.clone(),
+                    Some(other) => other.to_string(),
+                    None => String::new(),
+                };
+                let val = entry.get("value").cloned().unwrap_or(Value::Null);
+                if let Value::Object(map) = obj
+                    .entry(field.json_name.clone())
+                    .or_insert_with(|| Value::Object(Map::new()))
+                {
+                    map.insert(key, val);
+                }
+                continue;
+            }
+
+            // Packed repeated scalars arrive as one length-delimited field.
+            if let (true, WireValue::Bytes(b), false) = (
+                field.repeated,
+                value,
+                matches!(field.field_type, TYPE_STRING | TYPE_BYTES | TYPE_MESSAGE),
+            ) {
+                for item in self.unpack(field, b)? {
+                    push_field(&mut obj, field.json_name.clone(), item, true);
+                }
+                continue;
+            }
+
+            let json = self.field_value(field, value, depth)?;
+            push_field(&mut obj, field.json_name.clone(), json, field.repeated);
+        }
+        Some(Value::Object(obj))
+    }
+
+    fn unpack(&self, field: &FieldDesc, data: &[u8]) -> Option<Vec<Value>> {
+        let mut items = Vec::new();
+        let mut pos = 0;
+        while pos < data.len() {
+            let value = match field.field_type {
+                TYPE_DOUBLE | TYPE_FIXED64 | TYPE_SFIXED64 => {
+                    let b = data.get(pos..pos + 8)?;
+                    pos += 8;
+                    WireValue::Fixed64(u64::from_le_bytes(b.try_into().ok()?))
+                }
+                TYPE_FLOAT | TYPE_FIXED32 | TYPE_SFIXED32 => {
+                    let b = data.get(pos..pos + 4)?;
+                    pos += 4;
+                    WireValue::Fixed32(u32::from_le_bytes(b.try_into().ok()?))
+                }
+                _ => WireValue::Varint(read_varint(data, &mut pos)?),
+            };
+            items.push(self.field_value(field, value, 0)?);
+        }
+        Some(items)
+    }
+
+    fn field_value(&self, field: &FieldDesc, value: WireValue, depth: usize) -> Option<Value> {
+        Some(match (field.field_type, value) {
+            (TYPE_DOUBLE, WireValue::Fixed64(v)) => float_value(f64::from_bits(v)),
+            (TYPE_FLOAT, WireValue::Fixed32(v)) => float_value(f32::from_bits(v) as f64),
+            // 64-bit integers are strings in the JSON mapping.
+            (TYPE_INT64, WireValue::Varint(v)) => Value::String((v as i64).to_string()),
+            (TYPE_UINT64, WireValue::Varint(v)) => Value::String(v.to_string()),
+            (TYPE_SINT64, WireValue::Varint(v)) => Value::String(zigzag(v).to_string()),
+            (TYPE_FIXED64, WireValue::Fixed64(v)) => Value::String(v.to_string()),
+            (TYPE_SFIXED64, WireValue::Fixed64(v)) => Value::String((v as i64).to_string()),
+            (TYPE_INT32, WireValue::Varint(v)) => Value::from(v as i64 as i32),
+            (TYPE_UINT32, WireValue::Varint(v)) => Value::from(v as u32),
+            (TYPE_SINT32, WireValue::Varint(v)) => Value::from(zigzag(v) as i32),
+            (TYPE_FIXED32, WireValue::Fixed32(v)) => Value::from(v),
+            (TYPE_SFIXED32, WireValue::Fixed32(v)) => Value::from(v as i32),
+            (TYPE_BOOL, WireValue::Varint(v)) => Value::Bool(v != 0),
+            (TYPE_ENUM, WireValue::Varint(v)) => {
+                let n = v as i64 as i32;
+                match self.enums.get(&field.type_name).and_then(|e| e.get(&n)) {
+                    Some(name) => Value::String(name.clone()),
+                    None => Value::from(n),
+                }
+            }
+            (TYPE_STRING, WireValue::Bytes(b)) => Value::String(String::from_utf8_lossy(b).to_string()),
+            (TYPE_BYTES, WireValue::Bytes(b)) => Value::String(base64(b)),
+            (TYPE_MESSAGE, WireValue::Bytes(b)) => self.decode_at(&field.type_name, b, depth + 1)?,
+            // Wire type does not match the schema: show what was sent.
+            (_, other) => decode_raw_value(other),
+        })
+    }
+}
+
+fn decode_raw_value(value: WireValue) -> Value {
+    match value {
+        WireValue::Varint(v) | WireValue::Fixed64(v) => Value::from(v),
+        WireValue::Fixed32(v) => Value::from(v),
+        WireValue::Bytes(b) => decode_raw(b)
+            .filter(|_| !b.is_empty())
+            .unwrap_or_else(|| Value::String(base64(b))),
+    }
+}
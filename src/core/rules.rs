@@ -0,0 +1,990 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+
+use regex::bytes::{Regex, RegexBuilder};
+use serde::Deserialize;
+
+use crate::core::filter::Filter;
+use crate::core::flow::FlowTable;
+use crate::core::models::{Alert, FlowState, Packet, Severity, TransportProtocol};
+use crate::core::parser;
+use crate::core::stream::{Direction, StreamEvent, StreamKey};
+
+/// Bytes of each stream direction inspected by stream rules.
+pub const STREAM_INSPECT_DEPTH: usize = 16 * 1024;
+/// Content match attempts per rule and buffer, bounding the backtracking
+/// of relative contents.
+const MAX_MATCH_STEPS: usize = 1_000;
+
+/// Conversations without packets for this long are forgotten (capture time).
+const FLOW_IDLE_MS: u128 = 300_000;
+const SWEEP_INTERVAL_MS: u128 = 10_000;
+
+/// An address or a network in CIDR notation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (addr, prefix) = match spec.split_once('/') {
+            Some((a, p)) => (a, Some(p)),
+            None => (spec, None),
+        };
+        let addr: IpAddr = addr
+            .trim()
+            .parse()
+            .map_err(|_| format!("adresse invalide : {spec}"))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p
+                .trim()
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= max)
+                .ok_or_else(|| format!("préfixe invalide : {spec}"))?,
+            None => max,
+        };
+        Ok(Cidr { addr, prefix })
+    }
+
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(*ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(*ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Splits `a, [b, c], d` at the commas outside brackets.
+fn split_top_level(list: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in list.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(&list[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&list[start..]);
+    parts
+}
+
+/// Flattens `!x`, `[a, !b]` and nested groups into (negated, item) pairs.
+fn list_items(spec: &str, negated: bool, out: &mut Vec<(bool, String)>) -> Result<(), String> {
+    let spec = spec.trim();
+    if let Some(rest) = spec.strip_prefix('!') {
+        return list_items(rest, !negated, out);
+    }
+    if let Some(inner) = spec.strip_prefix('[') {
+        let inner = inner
+            .strip_suffix(']')
+            .ok_or_else(|| format!("crochet non fermé : {spec}"))?;
+        for part in split_top_level(inner) {
+            list_items(part, negated, out)?;
+        }
+        return Ok(());
+    }
+    if spec.is_empty() {
+        return Err("élément de liste vide".to_string());
+    }
+    out.push((negated, spec.to_string()));
+    Ok(())
+}
+
+/// Address part of a rule header: `any`, an address or network, a
+/// bracketed list, each item possibly negated with `!`.
+#[derive(Debug, Clone, Default)]
+pub struct AddrMatch {
+    include: Vec<Cidr>,
+    exclude: Vec<Cidr>,
+}
+
+impl AddrMatch {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut items = Vec::new();
+        list_items(spec, false, &mut items)?;
+        let mut m = AddrMatch::default();
+        let mut any = false;
+        for (negated, item) in items {
+            if item.eq_ignore_ascii_case("any") {
+                if negated {
+                    return Err(format!("« !any » ne correspond à rien : {spec}"));
+                }
+                any = true;
+            } else if negated {
+                m.exclude.push(Cidr::parse(&item)?);
+            } else {
+                m.include.push(Cidr::parse(&item)?);
+            }
+        }
+        if any {
+            m.include.clear();
+        }
+        Ok(m)
+    }
+
+    pub fn is_any(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn matches(&self, ip: Option<&IpAddr>) -> bool {
+        let Some(ip) = ip else {
+            return self.is_any();
+        };
+        (self.include.is_empty() || self.include.iter().any(|n| n.contains(ip)))
+            && !self.exclude.iter().any(|n| n.contains(ip))
+    }
+}
+
+/// Port part of a rule header: `any`, `80`, ranges `1024:`, `:1023`,
+/// `6000:6010`, bracketed lists and `!` negation.
+#[derive(Debug, Clone, Default)]
+pub struct PortMatch {
+    include: Vec<(u16, u16)>,
+    exclude: Vec<(u16, u16)>,
+}
+
+fn parse_port_range(spec: &str) -> Result<(u16, u16), String> {
+    let port = |s: &str, default: u16| -> Result<u16, String> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Ok(default);
+        }
+        s.parse().map_err(|_| format!("port invalide : {spec}"))
+    };
+    let (low, high) = match spec.split_once(':') {
+        Some((a, b)) => (port(a, 0)?, port(b, u16::MAX)?),
+        None => {
+            let p = port(spec, 0)?;
+            (p, p)
+        }
+    };
+    if low > high {
+        return Err(format!("plage de ports inversée : {spec}"));
+    }
+    Ok((low, high))
+}
+
+impl PortMatch {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut items = Vec::new();
+        list_items(spec, false, &mut items)?;
+        let mut m = PortMatch::default();
+        let mut any = false;
+        for (negated, item) in items {
+            if item.eq_ignore_ascii_case("any") {
+                if negated {
+                    return Err(format!("« !any » ne correspond à rien : {spec}"));
+                }
+                any = true;
+            } else if negated {
+                m.exclude.push(parse_port_range(&item)?);
+            } else {
+                m.include.push(parse_port_range(&item)?);
+            }
+        }
+        if any {
+            m.include.clear();
+        }
+        Ok(m)
+    }
+
+    pub fn is_any(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    fn matches(&self, port: Option<u16>) -> bool {
+        let Some(port) = port else {
+            return self.is_any();
+        };
+        let within = |&(low, high): &(u16, u16)| (low..=high).contains(&port);
+        (self.include.is_empty() || self.include.iter().any(within)) && !self.exclude.iter().any(within)
+    }
+}
+
+/// A byte pattern searched in the payload (or stream). `offset`/`depth`
+/// bound the search from the start of the buffer; `distance`/`within`
+/// from the end of the previous content match.
+#[derive(Debug, Clone, Default)]
+pub struct ContentMatch {
+    pub pattern: Vec<u8>,
+    pub nocase: bool,
+    /// The pattern must not occur in its search window.
+    pub negated: bool,
+    pub offset: Option<usize>,
+    pub depth: Option<usize>,
+    pub distance: Option<i64>,
+    pub within: Option<usize>,
+}
+
+impl ContentMatch {
+    fn relative(&self) -> bool {
+        self.distance.is_some() || self.within.is_some()
+    }
+
+    /// Search window `[start, end)` in a buffer of `len` bytes.
+    fn window(&self, prev_end: usize, len: usize) -> (usize, usize) {
+        let (start, limit) = if self.relative() {
+            let start = (prev_end as i64 + self.distance.unwrap_or(0)).max(0) as usize;
+            (start, self.within.map(|w| start.saturating_add(w)))
+        } else {
+            let start = self.offset.unwrap_or(0);
+            (start, self.depth.map(|d| start.saturating_add(d)))
+        };
+        (start.min(len), limit.unwrap_or(len).min(len))
+    }
+
+    fn positions<'a>(&'a self, buf: &'a [u8], prev_end: usize) -> impl Iterator<Item = usize> + 'a {
+        let (start, end) = self.window(prev_end, buf.len());
+        let n = self.pattern.len();
+        let last = if end >= start + n { end - n + 1 } else { start };
+        (start..last).filter(move |&i| {
+            let hay = &buf[i..i + n];
+            if self.nocase {
+                hay.eq_ignore_ascii_case(&self.pattern)
+            } else {
+                hay == self.pattern.as_slice()
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RegexMatch {
+    pub regex: Regex,
+    pub negated: bool,
+    /// Searched after the end of the last content match.
+    pub relative: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThresholdKind {
+    /// Alert on the first `count` matches of each period.
+    Limit,
+    /// Alert on every `count`-th match within a period.
+    Threshold,
+    /// Alert once per period, after `count` matches.
+    Both,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Track {
+    BySrc,
+    ByDst,
+}
+
+/// Rate limit of a rule, counted per source or destination address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Threshold {
+    #[serde(rename = "type")]
+    pub kind: ThresholdKind,
+    pub track: Track,
+    pub count: u32,
+    pub seconds: u64,
+}
+
+/// A compiled detection rule. Empty constraints match everything.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub id: String,
+    pub revision: Option<u32>,
+    pub severity: Severity,
+    pub message: String,
+    pub classtype: Option<String>,
+    /// `tcp`, `udp`, `icmp`, or a classifier label (`http`, `dns`, `tls`…).
+    pub protocol: Option<String>,
+    pub src: AddrMatch,
+    pub src_ports: PortMatch,
+    pub dst: AddrMatch,
+    pub dst_ports: PortMatch,
+    /// The header also matches with source and destination swapped.
+    pub bidirectional: bool,
+    pub direction: Option<Direction>,
+    /// Only on established conversations (`Some(true)`) or before
+    /// (`Some(false)`).
+    pub established: Option<bool>,
+    pub filter: Option<Filter>,
+    pub contents: Vec<ContentMatch>,
+    pub regexes: Vec<RegexMatch>,
+    /// Match on the reassembled TCP stream (first `STREAM_INSPECT_DEPTH`
+    /// bytes of each direction) instead of single packets; fires once per
+    /// stream direction.
+    pub stream: bool,
+    pub threshold: Option<Threshold>,
+}
+
+impl Rule {
+    /// A rule matching every packet, to be narrowed by the caller.
+    pub fn new(id: &str, message: &str) -> Self {
+        Rule {
+            id: id.to_string(),
+            revision: None,
+            severity: Severity::default(),
+            message: message.to_string(),
+            classtype: None,
+            protocol: None,
+            src: AddrMatch::default(),
+            src_ports: PortMatch::default(),
+            dst: AddrMatch::default(),
+            dst_ports: PortMatch::default(),
+            bidirectional: false,
+            direction: None,
+            established: None,
+            filter: None,
+            contents: Vec::new(),
+            regexes: Vec::new(),
+            stream: false,
+            threshold: None,
+        }
+    }
+
+    /// Stream rules need something to look for in the stream.
+    pub fn check_stream(&self) -> Result<(), String> {
+        if self.stream && self.contents.iter().all(|c| c.negated) && self.regexes.is_empty() {
+            return Err("une règle de flux doit avoir au moins un content ou une regex".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Who sent the inspected bytes, and the state of their conversation.
+struct Context {
+    protocol: u8,
+    src: (Option<IpAddr>, Option<u16>),
+    dst: (Option<IpAddr>, Option<u16>),
+    src_text: Option<String>,
+    dst_text: Option<String>,
+    direction: Option<Direction>,
+    established: bool,
+}
+
+impl Context {
+    fn from_packet(packet: &Packet, flow: Option<(Direction, bool)>) -> Self {
+        let (sport, dport) = match &packet.transport {
+            Some(TransportProtocol::Tcp(t)) => (Some(t.src_port), Some(t.dst_port)),
+            Some(TransportProtocol::Udp(u)) => (Some(u.src_port), Some(u.dst_port)),
+            _ => (None, None),
+        };
+        let ip = packet.ip.as_ref();
+        Context {
+            protocol: ip.map_or(0, |ip| ip.protocol),
+            src: (ip.and_then(|ip| ip.src_ip.parse().ok()), sport),
+            dst: (ip.and_then(|ip| ip.dst_ip.parse().ok()), dport),
+            src_text: ip.map(|ip| ip.src_ip.clone()),
+            dst_text: ip.map(|ip| ip.dst_ip.clone()),
+            direction: flow.map(|(d, _)| d),
+            established: flow.is_some_and(|(_, e)| e),
+        }
+    }
+
+    fn from_stream(key: &StreamKey, direction: Direction) -> Self {
+        let split = |endpoint: &str| -> (Option<String>, Option<u16>) {
+            match endpoint.rsplit_once(':') {
+                Some((ip, port)) => (Some(ip.to_string()), port.parse().ok()),
+                None => (Some(endpoint.to_string()), None),
+            }
+        };
+        let (src, dst) = match direction {
+            Direction::ToServer => (split(&key.client), split(&key.server)),
+            Direction::ToClient => (split(&key.server), split(&key.client)),
+        };
+        Context {
+            protocol: 6,
+            src: (src.0.as_deref().and_then(|ip| ip.parse().ok()), src.1),
+            dst: (dst.0.as_deref().and_then(|ip| ip.parse().ok()), dst.1),
+            src_text: src.0,
+            dst_text: dst.0,
+            direction: Some(direction),
+            established: true,
+        }
+    }
+}
+
+impl Rule {
+    fn header_matches(&self, ctx: &Context, application: &str) -> bool {
+        let protocol_ok = match self.protocol.as_deref() {
+            None => true,
+            Some("tcp") => ctx.protocol == 6,
+            Some("udp") => ctx.protocol == 17,
+            Some("icmp") => ctx.protocol == 1 || ctx.protocol == 58,
+            Some(label) => application.eq_ignore_ascii_case(label),
+        };
+        let forward = |src: &(Option<IpAddr>, Option<u16>), dst: &(Option<IpAddr>, Option<u16>)| {
+            self.src.matches(src.0.as_ref())
+                && self.src_ports.matches(src.1)
+                && self.dst.matches(dst.0.as_ref())
+                && self.dst_ports.matches(dst.1)
+        };
+        protocol_ok
+            && (forward(&ctx.src, &ctx.dst) || (self.bidirectional && forward(&ctx.dst, &ctx.src)))
+            && self.direction.is_none_or(|d| ctx.direction == Some(d))
+            && self.established.is_none_or(|e| ctx.established == e)
+    }
+
+    /// Whether what follows content `index` depends on where it matched.
+    fn depends_on_end(&self, index: usize) -> bool {
+        for next in &self.contents[index + 1..] {
+            if next.relative() {
+                return true;
+            }
+            if !next.negated {
+                return false;
+            }
+        }
+        self.regexes.iter().any(|r| r.relative)
+    }
+
+    /// Whether a chain of content matches from `index` on, starting after
+    /// `prev_end`, satisfies every content and relative regex. Other
+    /// positions of a content are only tried when what follows depends on
+    /// it, within `MAX_MATCH_STEPS`.
+    fn match_contents(&self, buf: &[u8], index: usize, prev_end: usize, steps: &mut usize) -> bool {
+        *steps += 1;
+        if *steps > MAX_MATCH_STEPS {
+            return false;
+        }
+        let Some(content) = self.contents.get(index) else {
+            return self
+                .regexes
+                .iter()
+                .filter(|r| r.relative)
+                .all(|r| r.regex.is_match(&buf[prev_end..]) != r.negated);
+        };
+        if content.negated {
+            return content.positions(buf, prev_end).next().is_none()
+                && self.match_contents(buf, index + 1, prev_end, steps);
+        }
+        let retry = self.depends_on_end(index);
+        for pos in content.positions(buf, prev_end) {
+            if self.match_contents(buf, index + 1, pos + content.pattern.len(), steps) {
+                return true;
+            }
+            if !retry || *steps > MAX_MATCH_STEPS {
+                return false;
+            }
+        }
+        false
+    }
+
+    fn payload_matches(&self, buf: &[u8]) -> bool {
+        self.regexes
+            .iter()
+            .filter(|r| !r.relative)
+            .all(|r| r.regex.is_match(buf) != r.negated)
+            && self.match_contents(buf, 0, 0, &mut 0)
+    }
+
+    fn alert(&self, ctx: &Context, timestamp: u128, packet_id: u64) -> Alert {
+        Alert {
+            rule_id: self.id.clone(),
+            rule_rev: self.revision,
+            severity: self.severity,
+            message: self.message.clone(),
+            classtype: self.classtype.clone(),
+            timestamp,
+            packet_id: Some(packet_id),
+            protocol: Some(ctx.protocol).filter(|p| *p != 0),
+            src_ip: ctx.src_text.clone(),
+            src_port: ctx.src.1,
+            dst_ip: ctx.dst_text.clone(),
+            dst_port: ctx.dst.1,
+            related_packets: Vec::new(),
+        }
+    }
+}
+
+/// Byte patterns: text with `|0d 0a|` hex blocks, as in Snort/Suricata.
+pub fn parse_pattern(spec: &str) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    let mut parts = spec.split('|');
+    let mut hex = false;
+    for part in parts.by_ref() {
+        if hex {
+            let digits: Vec<char> = part.chars().filter(|c| !c.is_whitespace()).collect();
+            if !digits.len().is_multiple_of(2) {
+                return Err(format!("bloc hexadécimal incomplet « {part} » dans : {spec}"));
+            }
+            for pair in digits.chunks(2) {
+                let byte: String = pair.iter().collect();
+                let value = u8::from_str_radix(&byte, 16)
+                    .map_err(|_| format!("octet hexadécimal invalide « {byte} » dans : {spec}"))?;
+                out.push(value);
+            }
+        } else {
+            out.extend_from_slice(part.as_bytes());
+        }
+        hex = !hex;
+    }
+    // An odd number of segments means every `|` was paired.
+    if !hex {
+        return Err(format!("bloc hexadécimal non fermé dans : {spec}"));
+    }
+    if out.is_empty() {
+        return Err("motif vide".to_string());
+    }
+    Ok(out)
+}
+
+/// Compiles a regex with byte semantics, as in PCRE: `.` and classes
+/// match any byte, not UTF-8 characters.
+pub fn byte_regex(pattern: &str, nocase: bool) -> Result<Regex, String> {
+    RegexBuilder::new(pattern)
+        .unicode(false)
+        .case_insensitive(nocase)
+        .build()
+        .map_err(|e| {
+            // The parser's message spans several lines, ending with the cause.
+            let e = e.to_string();
+            let cause = e.lines().last().unwrap_or_default().trim_start_matches("error: ");
+            format!("regex invalide « {pattern} » : {cause}")
+        })
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PortSpec {
+    Number(u16),
+    Text(String),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ContentSpec {
+    pattern: String,
+    #[serde(default)]
+    nocase: bool,
+    #[serde(default)]
+    negated: bool,
+    offset: Option<usize>,
+    depth: Option<usize>,
+    distance: Option<i64>,
+    within: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RegexSpec {
+    pattern: String,
+    #[serde(default)]
+    nocase: bool,
+    #[serde(default)]
+    negated: bool,
+    #[serde(default)]
+    relative: bool,
+}
+
+/// A rule as written in a JSON rules file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleSpec {
+    id: String,
+    message: String,
+    #[serde(default)]
+    severity: Severity,
+    protocol: Option<String>,
+    src: Option<String>,
+    src_port: Option<PortSpec>,
+    dst: Option<String>,
+    dst_port: Option<PortSpec>,
+    #[serde(default)]
+    bidirectional: bool,
+    direction: Option<String>,
+    state: Option<String>,
+    filter: Option<String>,
+    #[serde(default)]
+    content: Vec<ContentSpec>,
+    #[serde(default)]
+    regex: Vec<RegexSpec>,
+    #[serde(default)]
+    stream: bool,
+    classtype: Option<String>,
+    threshold: Option<Threshold>,
+}
+
+fn compile(spec: RuleSpec) -> Result<Rule, String> {
+    let ports = |p: Option<PortSpec>| match p {
+        None => Ok(PortMatch::default()),
+        Some(PortSpec::Number(n)) => PortMatch::parse(&n.to_string()),
+        Some(PortSpec::Text(t)) => PortMatch::parse(&t),
+    };
+    let addrs = |a: Option<String>| a.map_or(Ok(AddrMatch::default()), |a| AddrMatch::parse(&a));
+
+    let direction = match spec.direction.as_deref().map(str::to_ascii_lowercase).as_deref() {
+        None => None,
+        Some("to_server" | "from_client") => Some(Direction::ToServer),
+        Some("to_client" | "from_server") => Some(Direction::ToClient),
+        Some(other) => return Err(format!("direction inconnue : {other} (to_server, to_client)")),
+    };
+    let established = match spec.state.as_deref().map(str::to_ascii_lowercase).as_deref() {
+        None | Some("stateless") => None,
+        Some("established") => Some(true),
+        Some("not_established") => Some(false),
+        Some(other) => {
+            return Err(format!(
+                "état inconnu : {other} (established, not_established, stateless)"
+            ))
+        }
+    };
+    let protocol = spec
+        .protocol
+        .map(|p| p.to_ascii_lowercase())
+        .filter(|p| p != "ip" && p != "any");
+
+    let filter = match spec.filter {
+        Some(expr) => {
+            let mut filter = Filter::default();
+            filter.add(&expr)?;
+            Some(filter)
+        }
+        None => None,
+    };
+
+    let mut contents = Vec::new();
+    for c in spec.content {
+        let content = ContentMatch {
+            pattern: parse_pattern(&c.pattern)?,
+            nocase: c.nocase,
+            negated: c.negated,
+            offset: c.offset,
+            depth: c.depth,
+            distance: c.distance,
+            within: c.within,
+        };
+        if content.relative() && (content.offset.is_some() || content.depth.is_some()) {
+            return Err(format!(
+                "content « {} » : offset/depth et distance/within sont exclusifs",
+                c.pattern
+            ));
+        }
+        contents.push(content);
+    }
+
+    let mut regexes = Vec::new();
+    for r in spec.regex {
+        regexes.push(RegexMatch {
+            regex: byte_regex(&r.pattern, r.nocase)?,
+            negated: r.negated,
+            relative: r.relative,
+        });
+    }
+
+    if spec.threshold.is_some_and(|t| t.count == 0 || t.seconds == 0) {
+        return Err("threshold : count et seconds doivent être positifs".to_string());
+    }
+
+    let rule = Rule {
+        id: spec.id,
+        revision: None,
+        severity: spec.severity,
+        message: spec.message,
+        classtype: spec.classtype,
+        protocol,
+        src: addrs(spec.src)?,
+        src_ports: ports(spec.src_port)?,
+        dst: addrs(spec.dst)?,
+        dst_ports: ports(spec.dst_port)?,
+        bidirectional: spec.bidirectional,
+        direction,
+        established,
+        filter,
+        contents,
+        regexes,
+        stream: spec.stream,
+        threshold: spec.threshold,
+    };
+    rule.check_stream()?;
+    Ok(rule)
+}
+
+/// Loads a JSON rules file: an array of rules with `id`, `message`, an
+/// optional `severity` and the constraints to match (see README).
+pub fn load_rules(path: &Path) -> Result<Vec<Rule>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let specs: Vec<RuleSpec> =
+        serde_json::from_str(&text).map_err(|e| format!("{}: JSON invalide : {e}", path.display()))?;
+
+    let mut seen = HashSet::new();
+    let mut rules = Vec::new();
+    for spec in specs {
+        let id = spec.id.clone();
+        if !seen.insert(id.clone()) {
+            return Err(format!("{}: identifiant de règle dupliqué : {id}", path.display()));
+        }
+        rules.push(compile(spec).map_err(|e| format!("{}: règle {id} : {e}", path.display()))?);
+    }
+    Ok(rules)
+}
+
+#[derive(Default)]
+struct StreamBuffer {
+    data: Vec<u8>,
+    /// Indexes of the stream rules already raised on this direction.
+    fired: HashSet<usize>,
+    /// Bytes went missing: offsets no longer mean anything.
+    broken: bool,
+    /// Classifier label of the last packet that brought data.
+    application: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ThresholdState {
+    period_start: u128,
+    count: u32,
+}
+
+/// Counts a match of `rule` (index `index`) against its threshold and
+/// tells whether it should raise an alert.
+fn threshold_allows(
+    states: &mut HashMap<(usize, Option<IpAddr>), ThresholdState>,
+    index: usize,
+    rule: &Rule,
+    ctx: &Context,
+    now: u128,
+) -> bool {
+    let Some(t) = rule.threshold else {
+        return true;
+    };
+    let tracked = match t.track {
+        Track::BySrc => ctx.src.0,
+        Track::ByDst => ctx.dst.0,
+    };
+    let state = states.entry((index, tracked)).or_insert(ThresholdState {
+        period_start: now,
+        count: 0,
+    });
+    if now.saturating_sub(state.period_start) >= t.seconds as u128 * 1000 {
+        state.period_start = now;
+        state.count = 0;
+    }
+    state.count = state.count.saturating_add(1);
+    match t.kind {
+        ThresholdKind::Limit => state.count <= t.count,
+        ThresholdKind::Threshold => {
+            if state.count >= t.count {
+                state.period_start = now;
+                state.count = 0;
+                true
+            } else {
+                false
+            }
+        }
+        ThresholdKind::Both => state.count == t.count,
+    }
+}
+
+/// Evaluates rules on every packet, and stream rules on the reassembled
+/// TCP data, keeping its own conversation table for direction and state.
+pub struct RuleEngine {
+    rules: Vec<Rule>,
+    flows: FlowTable,
+    streams: HashMap<(StreamKey, Direction), StreamBuffer>,
+    thresholds: HashMap<(usize, Option<IpAddr>), ThresholdState>,
+    last_sweep: u128,
+}
+
+impl RuleEngine {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        RuleEngine {
+            rules,
+            flows: FlowTable::new(),
+            streams: HashMap::new(),
+            thresholds: HashMap::new(),
+            last_sweep: 0,
+        }
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Checks `packet` (number `packet_id`, classified as `application`)
+    /// and the stream `events` it produced.
+    pub fn observe(
+        &mut self,
+        packet: &Packet,
+        packet_id: u64,
+        application: &str,
+        events: &[StreamEvent],
+    ) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        if self.rules.is_empty() {
+            return alerts;
+        }
+
+        let flow = self.flows.observe(packet, None, application).map(|flow| {
+            let from_client = packet.ip.as_ref().is_some_and(|ip| ip.src_ip == flow.client_ip)
+                && match &packet.transport {
+                    Some(TransportProtocol::Tcp(t)) => flow.client_port == Some(t.src_port),
+                    Some(TransportProtocol::Udp(u)) => flow.client_port == Some(u.src_port),
+                    _ => true,
+                };
+            let direction = if from_client { Direction::ToServer } else { Direction::ToClient };
+            let established = matches!(flow.state, FlowState::Established | FlowState::Closing);
+            (direction, established)
+        });
+        let ctx = Context::from_packet(packet, flow);
+        let payload = parser::transport_payload(&packet.payload);
+        for (index, rule) in self.rules.iter().enumerate() {
+            if !rule.stream
+                && rule.header_matches(&ctx, application)
+                && rule.filter.as_ref().is_none_or(|f| f.matches(packet))
+                && rule.payload_matches(payload)
+                && threshold_allows(&mut self.thresholds, index, rule, &ctx, packet.timestamp)
+            {
+                alerts.push(rule.alert(&ctx, packet.timestamp, packet_id));
+            }
+        }
+
+        self.inspect_streams(packet, packet_id, Some(application), events, &mut alerts);
+
+        if packet.timestamp >= self.last_sweep + SWEEP_INTERVAL_MS {
+            self.last_sweep = packet.timestamp;
+            self.flows.expire(packet.timestamp, FLOW_IDLE_MS, u128::MAX);
+            let rules = &self.rules;
+            self.thresholds.retain(|(index, _), state| {
+                rules[*index]
+                    .threshold
+                    .is_some_and(|t| packet.timestamp.saturating_sub(state.period_start) < t.seconds as u128 * 1000)
+            });
+        }
+        alerts
+    }
+
+    /// End of the capture: runs the stream rules on the events of
+    /// `TcpReassembler::finish`, `packet` standing for the connection
+    /// (`stream::end_of_capture`).
+    pub fn finish(&mut self, packet: &Packet, packet_id: u64, events: &[StreamEvent]) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        self.inspect_streams(packet, packet_id, None, events, &mut alerts);
+        alerts
+    }
+
+    /// Stream rules, matched with the classifier label of the last packet
+    /// that brought data (`application`, unknown at the end of the capture).
+    fn inspect_streams(
+        &mut self,
+        packet: &Packet,
+        packet_id: u64,
+        application: Option<&str>,
+        events: &[StreamEvent],
+        alerts: &mut Vec<Alert>,
+    ) {
+        for event in events {
+            match event {
+                StreamEvent::Data { key, direction, data } => {
+                    let buf = self.streams.entry((key.clone(), *direction)).or_default();
+                    if buf.broken || buf.data.len() >= STREAM_INSPECT_DEPTH {
+                        continue;
+                    }
+                    let take = (STREAM_INSPECT_DEPTH - buf.data.len()).min(data.len());
+                    buf.data.extend_from_slice(&data[..take]);
+                    if let Some(application) = application.filter(|a| *a != buf.application) {
+                        buf.application = application.to_string();
+                    }
+
+                    let ctx = Context::from_stream(key, *direction);
+                    for (index, rule) in self.rules.iter().enumerate() {
+                        if rule.stream
+                            && !buf.fired.contains(&index)
+                            && rule.header_matches(&ctx, &buf.application)
+                            && rule.filter.as_ref().is_none_or(|f| f.matches(packet))
+                            && rule.payload_matches(&buf.data)
+                        {
+                            buf.fired.insert(index);
+                            if !threshold_allows(&mut self.thresholds, index, rule, &ctx, packet.timestamp) {
+                                continue;
+                            }
+                            alerts.push(rule.alert(&ctx, packet.timestamp, packet_id));
+                        }
+                    }
+                }
+                StreamEvent::Gap { key, direction, .. } => {
+                    self.streams.entry((key.clone(), *direction)).or_default().broken = true;
+                }
+                StreamEvent::Closed { key, .. } => {
+                    self.streams.remove(&(key.clone(), Direction::ToServer));
+                    self.streams.remove(&(key.clone(), Direction::ToClient));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compiles a rule from its JSON fields besides `id` and `message`.
+    fn json_rule(text: &str) -> Rule {
+        let mut spec: serde_json::Value = serde_json::from_str(text).unwrap();
+        spec["id"] = "test".into();
+        spec["message"] = "test".into();
+        compile(serde_json::from_value(spec).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn contents_and_regexes() {
+        // (rule, [(payload, expected)])
+        let cases: [(&str, &[(&str, bool)]); 4] = [
+            // Only the second "a=" is followed by "b".
+            (
+                r#"{"content": [{"pattern": "a="}, {"pattern": "b", "distance": 0, "within": 1}]}"#,
+                &[("a=x a=b", true), ("a=x a=c", false)],
+            ),
+            // Relative regexes are tried after every candidate chain.
+            (
+                r#"{"content": [{"pattern": "id="}], "regex": [{"pattern": "^[0-9]+;", "relative": true}]}"#,
+                &[("id=x; id=42;", true), ("id=x; id=y;", false)],
+            ),
+            (
+                r#"{"content": [{"pattern": "GET "}, {"pattern": "..", "negated": true, "distance": 0, "within": 8}]}"#,
+                &[("GET ../x GET /ok", true), ("GET ../x", false)],
+            ),
+            (
+                r#"{"content": [{"pattern": "x", "offset": 2, "depth": 1}], "regex": [{"pattern": "y$"}]}"#,
+                &[("abxy", true), ("axby", false), ("abxyz", false)],
+            ),
+        ];
+        for (spec, payloads) in cases {
+            let rule = json_rule(spec);
+            for &(payload, expected) in payloads {
+                assert_eq!(rule.payload_matches(payload.as_bytes()), expected, "{spec} / {payload}");
+            }
+        }
+    }
+
+    #[test]
+    fn backtracking_is_bounded() {
+        // Four unbounded relative contents over 16 KiB of "a": without a
+        // bound, every combination of positions would be tried.
+        let rule = json_rule(
+            r#"{"content": [{"pattern": "a"}, {"pattern": "a", "distance": 0}, {"pattern": "a", "distance": 0},
+                {"pattern": "a", "distance": 0}, {"pattern": "b", "distance": 0}]}"#,
+        );
+        let buf = vec![b'a'; STREAM_INSPECT_DEPTH];
+        let mut steps = 0;
+        assert!(!rule.match_contents(&buf, 0, 0, &mut steps));
+        assert!(steps <= MAX_MATCH_STEPS + 1);
+    }
+}
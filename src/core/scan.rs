@@ -0,0 +1,407 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+use crate::core::flow::FlowTable;
+use crate::core::models::{Alert, Packet, Severity, TransportProtocol};
+use crate::core::parser;
+use crate::core::stream::{TCP_ACK, TCP_FIN, TCP_RST, TCP_SYN};
+
+const TCP_PSH: u8 = 0x08;
+const TCP_URG: u8 = 0x20;
+
+const FLOW_IDLE_MS: u128 = 300_000;
+const SWEEP_INTERVAL_MS: u128 = 10_000;
+/// Time a TCP/UDP probe has to be accepted before it counts toward a sweep.
+const ANSWER_TIMEOUT_MS: u128 = 3_000;
+/// TCP/UDP probes waiting for an answer; beyond that new ones are ignored.
+const MAX_PENDING_PROBES: usize = 65_536;
+
+const CLASSTYPE: &str = "attempted-recon";
+
+/// Sliding window and thresholds, shared by all scan kinds.
+#[derive(Debug, Clone, Copy)]
+pub struct ScanConfig {
+    pub window_ms: u128,
+    /// Distinct ports probed on one host by one source.
+    pub port_threshold: usize,
+    /// Distinct hosts probed by one source.
+    pub host_threshold: usize,
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        ScanConfig {
+            window_ms: 60_000,
+            port_threshold: 25,
+            host_threshold: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum ScanKind {
+    Syn,
+    Fin,
+    Null,
+    Xmas,
+    Udp,
+    IcmpSweep,
+    ArpSweep,
+}
+
+impl ScanKind {
+    /// Scan type of the first packet of a TCP conversation. Segments
+    /// carrying ACK or RST belong to an existing connection and are ignored.
+    fn from_tcp_flags(flags: u8) -> Option<Self> {
+        if flags & (TCP_ACK | TCP_RST) != 0 {
+            return None;
+        }
+        match flags & (TCP_SYN | TCP_FIN | TCP_PSH | TCP_URG) {
+            TCP_SYN => Some(ScanKind::Syn),
+            TCP_FIN => Some(ScanKind::Fin),
+            0 => Some(ScanKind::Null),
+            f if f == TCP_FIN | TCP_PSH | TCP_URG => Some(ScanKind::Xmas),
+            _ => None,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            ScanKind::Syn => "SYN",
+            ScanKind::Fin => "FIN",
+            ScanKind::Null => "NULL",
+            ScanKind::Xmas => "Xmas",
+            ScanKind::Udp => "UDP",
+            ScanKind::IcmpSweep => "ICMP",
+            ScanKind::ArpSweep => "ARP",
+        }
+    }
+
+    fn protocol(self, src: &str) -> Option<u8> {
+        match self {
+            ScanKind::Syn | ScanKind::Fin | ScanKind::Null | ScanKind::Xmas => Some(6),
+            ScanKind::Udp => Some(17),
+            ScanKind::IcmpSweep => Some(if src.contains(':') { 58 } else { 1 }),
+            ScanKind::ArpSweep => None,
+        }
+    }
+
+    fn rule_id(self) -> &'static str {
+        match self {
+            ScanKind::Syn => "scan-tcp-syn",
+            ScanKind::Fin => "scan-tcp-fin",
+            ScanKind::Null => "scan-tcp-null",
+            ScanKind::Xmas => "scan-tcp-xmas",
+            ScanKind::Udp => "scan-udp",
+            ScanKind::IcmpSweep => "sweep-icmp",
+            ScanKind::ArpSweep => "sweep-arp",
+        }
+    }
+}
+
+/// Distinct targets probed by one source within the sliding window. Once
+/// it has alerted, it stays quiet until the source stops for a whole window.
+struct Tracker<T> {
+    last_probe: HashMap<T, u128>,
+    probes: VecDeque<(u128, T)>,
+    last_seen: u128,
+    alerted: bool,
+}
+
+impl<T: Clone + Eq + Hash> Tracker<T> {
+    fn new() -> Self {
+        Tracker {
+            last_probe: HashMap::new(),
+            probes: VecDeque::new(),
+            last_seen: 0,
+            alerted: false,
+        }
+    }
+
+    /// Records a probe of `target` and returns the number of distinct
+    /// targets seen during the last `window` ms.
+    fn probe(&mut self, target: T, now: u128, window: u128) -> usize {
+        self.last_seen = now;
+        self.last_probe.insert(target.clone(), now);
+        self.probes.push_back((now, target));
+        while let Some((at, _)) = self.probes.front() {
+            if now.saturating_sub(*at) < window {
+                break;
+            }
+            let (at, target) = self.probes.pop_front().unwrap();
+            if self.last_probe.get(&target) == Some(&at) {
+                self.last_probe.remove(&target);
+            }
+        }
+        self.last_probe.len()
+    }
+}
+
+/// TCP/UDP probe that opened a conversation, waiting to be accepted.
+struct Probe {
+    at: u128,
+    packet_id: u64,
+    kind: ScanKind,
+    /// Source, destination and destination port.
+    key: (String, String, u16),
+}
+
+/// Detects port scans (TCP SYN/FIN/NULL/Xmas, UDP) and host sweeps (the
+/// same probes, ICMP echo, ARP) from the conversations each source opens.
+pub struct ScanDetector {
+    config: ScanConfig,
+    flows: FlowTable,
+    ports: HashMap<(String, ScanKind, String), Tracker<u16>>,
+    hosts: HashMap<(String, ScanKind), Tracker<String>>,
+    /// TCP/UDP probes in order, and whether each (source, destination,
+    /// port) was accepted (SYN-ACK or UDP reply) with the time of its probe.
+    probes: VecDeque<Probe>,
+    pending: HashMap<(String, String, u16), (u128, bool)>,
+    /// Sources that alerted for a kind, with their last probe: their
+    /// per-destination alerts would only repeat it.
+    reported: HashMap<(String, ScanKind), u128>,
+    last_sweep: u128,
+}
+
+impl ScanDetector {
+    pub fn new(config: ScanConfig) -> Self {
+        ScanDetector {
+            config,
+            flows: FlowTable::new(),
+            ports: HashMap::new(),
+            hosts: HashMap::new(),
+            probes: VecDeque::new(),
+            pending: HashMap::new(),
+            reported: HashMap::new(),
+            last_sweep: 0,
+        }
+    }
+
+    /// Checks `packet` (number `packet_id`).
+    pub fn observe(&mut self, packet: &Packet, packet_id: u64) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        let now = packet.timestamp;
+
+        if let Some(arp) = parser::parse_arp(&packet.payload) {
+            // Probes from 0.0.0.0 (address conflict detection) and gratuitous
+            // announcements are not looking for anyone.
+            if arp.operation == 1 && arp.sender_ip != "0.0.0.0" && arp.sender_ip != arp.target_ip {
+                let alert = self.host_probe(&arp.sender_ip, ScanKind::ArpSweep, arp.target_ip, now, packet_id);
+                alerts.extend(alert);
+            }
+        } else if let Some(ip) = &packet.ip {
+            match &packet.transport {
+                Some(TransportProtocol::Icmp) => {
+                    if let Some((8, _) | (128, _)) = parser::icmp_header(&packet.payload) {
+                        let alert =
+                            self.host_probe(&ip.src_ip, ScanKind::IcmpSweep, ip.dst_ip.clone(), now, packet_id);
+                        alerts.extend(alert);
+                    }
+                }
+                Some(TransportProtocol::Tcp(_) | TransportProtocol::Udp(_)) => {
+                    let opened = self.flows.observe(packet, None, "").is_some_and(|flow| flow.packets() == 1);
+                    let (probe, accepted) = match &packet.transport {
+                        Some(TransportProtocol::Tcp(t)) => (
+                            ScanKind::from_tcp_flags(t.flags).map(|k| (k, t.dst_port)),
+                            (t.flags & (TCP_SYN | TCP_ACK) == (TCP_SYN | TCP_ACK)).then_some(t.src_port),
+                        ),
+                        Some(TransportProtocol::Udp(u)) => (Some((ScanKind::Udp, u.dst_port)), Some(u.src_port)),
+                        _ => (None, None),
+                    };
+                    if let Some(sport) = accepted.filter(|_| !self.pending.is_empty()) {
+                        let key = (ip.dst_ip.clone(), ip.src_ip.clone(), sport);
+                        if let Some((_, answered)) = self.pending.get_mut(&key) {
+                            *answered = true;
+                        }
+                    }
+                    if let (true, Some((kind, port))) = (opened, probe) {
+                        alerts.extend(self.port_probe(packet, packet_id, kind, port));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Probes left unanswered or rejected count toward a sweep of their kind.
+        while self.probes.front().is_some_and(|p| now.saturating_sub(p.at) >= ANSWER_TIMEOUT_MS) {
+            let Some(probe) = self.probes.pop_front() else {
+                break;
+            };
+            // A later probe of the same port waits on its own.
+            if self.pending.get(&probe.key).is_none_or(|&(at, _)| at != probe.at) {
+                continue;
+            }
+            if self.pending.remove(&probe.key).is_some_and(|(_, answered)| !answered) {
+                let (src, dst, _) = probe.key;
+                alerts.extend(self.host_probe(&src, probe.kind, dst, probe.at, probe.packet_id));
+            }
+        }
+
+        if now >= self.last_sweep + SWEEP_INTERVAL_MS {
+            self.last_sweep = now;
+            self.flows.expire(now, FLOW_IDLE_MS, u128::MAX);
+            let window = self.config.window_ms;
+            self.ports.retain(|_, t| now.saturating_sub(t.last_seen) < window);
+            self.hosts.retain(|_, t| now.saturating_sub(t.last_seen) < window);
+            self.reported.retain(|_, last| now.saturating_sub(*last) < window);
+        }
+        alerts
+    }
+
+    /// Counts the port of a TCP/UDP probe for its destination, and queues
+    /// the destination for the sweep of its kind unless it gets accepted.
+    fn port_probe(&mut self, packet: &Packet, packet_id: u64, kind: ScanKind, port: u16) -> Option<Alert> {
+        let ip = packet.ip.as_ref()?;
+        let now = packet.timestamp;
+        let window = self.config.window_ms;
+        let key = (ip.src_ip.clone(), ip.dst_ip.clone(), port);
+        if self.pending.len() < MAX_PENDING_PROBES || self.pending.contains_key(&key) {
+            self.pending.insert(key.clone(), (now, false));
+            self.probes.push_back(Probe {
+                at: now,
+                packet_id,
+                kind,
+                key,
+            });
+        }
+
+        let reported = match self.reported.get_mut(&(ip.src_ip.clone(), kind)) {
+            Some(last) if now.saturating_sub(*last) < window => {
+                *last = now;
+                true
+            }
+            _ => false,
+        };
+        let tracker = self
+            .ports
+            .entry((ip.src_ip.clone(), kind, ip.dst_ip.clone()))
+            .or_insert_with(Tracker::new);
+        let count = check(tracker, port, now, window, self.config.port_threshold)?;
+        if reported {
+            return None;
+        }
+        self.reported.insert((ip.src_ip.clone(), kind), now);
+        let message = format!(
+            "Scan {} de {} vers {} : {count} ports en {} s",
+            kind.label(),
+            ip.src_ip,
+            ip.dst_ip,
+            window / 1000
+        );
+        let mut alert = scan_alert(kind, now, packet_id, &ip.src_ip, message);
+        alert.protocol = Some(ip.protocol);
+        alert.dst_ip = Some(ip.dst_ip.clone());
+        Some(alert)
+    }
+
+    fn host_probe(&mut self, src: &str, kind: ScanKind, target: String, now: u128, packet_id: u64) -> Option<Alert> {
+        let tracker = self.hosts.entry((src.to_string(), kind)).or_insert_with(Tracker::new);
+        let count = check(tracker, target, now, self.config.window_ms, self.config.host_threshold)?;
+        let message = format!(
+            "Balayage {} depuis {src} : {count} hôtes en {} s",
+            kind.label(),
+            self.config.window_ms / 1000
+        );
+        self.reported.insert((src.to_string(), kind), now);
+        let mut alert = scan_alert(kind, now, packet_id, src, message);
+        alert.protocol = kind.protocol(src);
+        Some(alert)
+    }
+}
+
+/// Records the probe and returns the distinct target count the first time
+/// it reaches `threshold` during a scan.
+fn check<T: Clone + Eq + Hash>(
+    tracker: &mut Tracker<T>,
+    target: T,
+    now: u128,
+    window: u128,
+    threshold: usize,
+) -> Option<usize> {
+    if tracker.alerted && now.saturating_sub(tracker.last_seen) >= window {
+        *tracker = Tracker::new();
+    }
+    let count = tracker.probe(target, now, window);
+    if tracker.alerted || count < threshold.max(1) {
+        return None;
+    }
+    tracker.alerted = true;
+    Some(count)
+}
+
+fn scan_alert(kind: ScanKind, timestamp: u128, packet_id: u64, src: &str, message: String) -> Alert {
+    Alert {
+        rule_id: kind.rule_id().to_string(),
+        rule_rev: None,
+        severity: Severity::Medium,
+        message,
+        classtype: Some(CLASSTYPE.to_string()),
+        timestamp,
+        packet_id: Some(packet_id),
+        protocol: None,
+        src_ip: Some(src.to_string()),
+        src_port: None,
+        dst_ip: None,
+        dst_port: None,
+        related_packets: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ethernet + IPv4 + TCP segment from 10.0.0.1 to 10.0.1.`host`.
+    fn segment(to_host: bool, host: u8, port: u16, flags: u8, at: u128) -> Packet {
+        let (src, sport, dst, dport) = if to_host {
+            ([10, 0, 0, 1], 40000u16, [10, 0, 1, host], port)
+        } else {
+            ([10, 0, 1, host], port, [10, 0, 0, 1], 40000)
+        };
+        let mut f = vec![0u8; 12];
+        f.extend_from_slice(&[0x08, 0x00, 0x45, 0, 0, 40, 0, 0, 0, 0, 64, 6, 0, 0]);
+        f.extend_from_slice(&src);
+        f.extend_from_slice(&dst);
+        f.extend_from_slice(&sport.to_be_bytes());
+        f.extend_from_slice(&dport.to_be_bytes());
+        f.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 0, 0x50, flags, 0xff, 0xff, 0, 0, 0, 0]);
+        parser::parse_packet_at(&f, at).unwrap()
+    }
+
+    /// Alerts raised by SYNs to `ports` on hosts 1..=`hosts`, answered with
+    /// a SYN-ACK when `accepted`.
+    fn probe(hosts: u8, ports: &[u16], accepted: bool) -> Vec<Alert> {
+        let mut scans = ScanDetector::new(ScanConfig::default());
+        let mut alerts = Vec::new();
+        let mut at = 1_000;
+        for host in 1..=hosts {
+            for &port in ports {
+                at += 10;
+                alerts.extend(scans.observe(&segment(true, host, port, TCP_SYN, at), 1));
+                if accepted {
+                    alerts.extend(scans.observe(&segment(false, host, port, TCP_SYN | TCP_ACK, at + 1), 2));
+                }
+            }
+        }
+        alerts.extend(scans.observe(&segment(true, 1, 1, TCP_ACK, at + ANSWER_TIMEOUT_MS), 3));
+        alerts
+    }
+
+    #[test]
+    fn sweeps_and_scans_of_a_subnet() {
+        // One port on 30 hosts: a sweep, once.
+        let alerts = probe(30, &[22], false);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].rule_id, "scan-tcp-syn");
+        assert_eq!((alerts[0].dst_ip.as_deref(), alerts[0].protocol), (None, Some(6)));
+
+        // 30 ports on 30 hosts: the first host's port scan, then the sweep.
+        let ports: Vec<u16> = (1..=30).collect();
+        let alerts = probe(30, &ports, false);
+        let targets: Vec<Option<&str>> = alerts.iter().map(|a| a.dst_ip.as_deref()).collect();
+        assert_eq!(targets, [Some("10.0.1.1"), None]);
+
+        // Connections the servers accept are not probes.
+        assert!(probe(30, &[443], true).is_empty());
+    }
+}
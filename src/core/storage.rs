@@ -4,29 +4,67 @@ use std::path::Path;
 
 use serde::{Deserialize, Serialize};
 
-use crate::core::models::Packet;
+use crate::core::alerts;
+use crate::core::dhcp;
+use crate::core::dns;
+use crate::core::grpc;
+use crate::core::netbios;
+use crate::core::http2;
+use crate::core::models::{
+    Alert, AlertAck, AlertGroup, ArpBinding, Baseline, DhcpMessage, DnsMessage, DnsRecord, GrpcCall, GrpcMessage, Http2Info, HttpTransaction, NbnsMessage, Packet,
+    TcpAnalysis, TlsInfo,
+};
+use crate::core::parser;
+use crate::core::tcpanalysis;
+use crate::core::tls;
 
 const STORAGE_FILE: &str = "wirefish_packets.jsonl";
+const ALERTS_FILE: &str = "wirefish_alerts.jsonl";
+const ALERT_GROUPS_FILE: &str = "wirefish_alert_groups.json";
+/// Kept across captures, unlike the files above.
+const ARP_FILE: &str = "wirefish_arp.json";
+const BASELINES_FILE: &str = "wirefish_baselines.json";
+const ACKS_FILE: &str = "wirefish_alert_acks.json";
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct InspectRecord {
     pub id: u64,
+    #[serde(default)]
+    pub timestamp: u128,
     pub iface: String,
     pub proto: String,
     pub src_ip: String,
     pub dst_ip: String,
     pub size: usize,
     pub payload: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsInfo>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dns: Vec<DnsMessage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nbns: Option<NbnsMessage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dhcp: Option<DhcpMessage>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub http: Vec<HttpTransaction>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http2: Option<Http2Info>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub grpc: Vec<GrpcCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tcp_analysis: Option<TcpAnalysis>,
 }
 
 pub fn reset_storage() {
-    if let Err(e) = OpenOptions::new()
-        .write(true)
-        .truncate(true)
-        .create(true)
-        .open(STORAGE_FILE)
-    {
-        eprintln!("[storage] Impossible de réinitialiser {STORAGE_FILE}: {e}");
+    for path in [STORAGE_FILE, ALERTS_FILE, ALERT_GROUPS_FILE] {
+        if let Err(e) = OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .create(true)
+            .open(path)
+        {
+            eprintln!("[storage] Impossible de réinitialiser {path}: {e}");
+        }
     }
 }
 
@@ -44,12 +82,21 @@ pub fn save_packet_for_inspect(
 
     let rec = InspectRecord {
         id,
+        timestamp: packet.timestamp,
         iface: iface.to_string(),
         proto: proto.to_string(),
         src_ip: src,
         dst_ip: dst,
         size: packet.payload.len(),
         payload: packet.payload.clone(), 
+        tls: packet.tls.clone(),
+        dns: packet.dns.clone(),
+        nbns: packet.nbns.clone(),
+        dhcp: packet.dhcp.clone(),
+        http: packet.http.clone(),
+        http2: packet.http2.clone(),
+        grpc: packet.grpc.clone(),
+        tcp_analysis: packet.tcp_analysis.clone(),
     };
 
     if let Err(e) = append_record(&rec) {
@@ -67,6 +114,113 @@ fn append_record(rec: &InspectRecord) -> std::io::Result<()> {
     Ok(())
 }
 
+pub fn save_alert(alert: &Alert) {
+    let result = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(ALERTS_FILE)
+        .and_then(|mut file| writeln!(file, "{}", serde_json::to_string(alert).unwrap()));
+    if let Err(e) = result {
+        eprintln!("⚠️ [storage] Impossible d’enregistrer l’alerte {}: {e}", alert.rule_id);
+    }
+}
+
+/// Alerts raised during the last capture, in order.
+pub fn stored_alerts() -> Result<Vec<Alert>, String> {
+    let file = File::open(ALERTS_FILE).map_err(|e| {
+        format!("Aucun fichier d’alertes lisible ({ALERTS_FILE}: {e}). Lance d’abord une capture.")
+    })?;
+    Ok(BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str::<Alert>(&line).ok())
+        .collect())
+}
+
+/// Alert groups of the last capture, written when it ends.
+pub fn stored_alert_groups() -> Result<Vec<AlertGroup>, String> {
+    let text = std::fs::read_to_string(ALERT_GROUPS_FILE).map_err(|e| {
+        format!("Aucun fichier d’alertes lisible ({ALERT_GROUPS_FILE}: {e}). Lance d’abord une capture.")
+    })?;
+    if text.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    serde_json::from_str(&text).map_err(|e| format!("{ALERT_GROUPS_FILE}: {e}"))
+}
+
+pub fn save_alert_groups(groups: &[AlertGroup]) {
+    let result = serde_json::to_string_pretty(groups)
+        .map_err(std::io::Error::other)
+        .and_then(|json| std::fs::write(ALERT_GROUPS_FILE, json));
+    if let Err(e) = result {
+        eprintln!("⚠️ [storage] Impossible d’enregistrer les groupes d’alertes ({ALERT_GROUPS_FILE}): {e}");
+    }
+}
+
+/// Acknowledged alert groups (none before the first acknowledgement).
+pub fn stored_alert_acks() -> Result<Vec<AlertAck>, String> {
+    if !Path::new(ACKS_FILE).exists() {
+        return Ok(Vec::new());
+    }
+    let text = std::fs::read_to_string(ACKS_FILE).map_err(|e| format!("{ACKS_FILE}: {e}"))?;
+    serde_json::from_str(&text).map_err(|e| format!("{ACKS_FILE}: {e}"))
+}
+
+pub fn save_alert_acks(acks: &[AlertAck]) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(acks).map_err(|e| e.to_string())?;
+    std::fs::write(ACKS_FILE, json).map_err(|e| format!("{ACKS_FILE}: {e}"))
+}
+
+/// IP ↔ MAC bindings learned by previous captures (none before the first).
+pub fn stored_arp_bindings() -> Result<Vec<ArpBinding>, String> {
+    if !Path::new(ARP_FILE).exists() {
+        return Ok(Vec::new());
+    }
+    let text = std::fs::read_to_string(ARP_FILE).map_err(|e| format!("{ARP_FILE}: {e}"))?;
+    serde_json::from_str(&text).map_err(|e| format!("{ARP_FILE}: {e}"))
+}
+
+pub fn save_arp_bindings(bindings: &[ArpBinding]) {
+    let result = serde_json::to_string_pretty(bindings)
+        .map_err(std::io::Error::other)
+        .and_then(|json| std::fs::write(ARP_FILE, json));
+    if let Err(e) = result {
+        eprintln!("⚠️ [storage] Impossible d’enregistrer la table ARP ({ARP_FILE}): {e}");
+    }
+}
+
+/// Traffic baselines learned by previous captures.
+pub fn stored_baselines() -> Result<Vec<Baseline>, String> {
+    if !Path::new(BASELINES_FILE).exists() {
+        return Ok(Vec::new());
+    }
+    let text = std::fs::read_to_string(BASELINES_FILE).map_err(|e| format!("{BASELINES_FILE}: {e}"))?;
+    serde_json::from_str(&text).map_err(|e| format!("{BASELINES_FILE}: {e}"))
+}
+
+pub fn save_baselines(baselines: &[Baseline]) {
+    let result = serde_json::to_string(baselines)
+        .map_err(std::io::Error::other)
+        .and_then(|json| std::fs::write(BASELINES_FILE, json));
+    if let Err(e) = result {
+        eprintln!("⚠️ [storage] Impossible d’enregistrer les références de trafic ({BASELINES_FILE}): {e}");
+    }
+}
+
+/// Packets saved by the last capture with their record, parsed again from
+/// their raw frames.
+pub fn stored_packets() -> Result<Vec<(InspectRecord, Packet)>, String> {
+    let file = File::open(STORAGE_FILE).map_err(|e| {
+        format!("Aucun fichier de capture lisible ({STORAGE_FILE}: {e}). Lance d’abord une capture.")
+    })?;
+    Ok(BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str::<InspectRecord>(&line).ok())
+        .filter_map(|rec| parser::parse_packet_at(&rec.payload, rec.timestamp).map(|p| (rec, p)))
+        .collect())
+}
+
 pub fn inspect_packet(id: u64) {
     if !Path::new(STORAGE_FILE).exists() {
         eprintln!("❌ Aucun fichier de capture trouvé ({STORAGE_FILE}). Lance d’abord une capture.");
@@ -82,21 +236,19 @@ pub fn inspect_packet(id: u64) {
     };
 
     let reader = BufReader::new(file);
-    for line in reader.lines() {
-        if let Ok(line) = line {
-            if line.trim().is_empty() {
-                continue;
-            }
+    for line in reader.lines().map_while(Result::ok) {
+        if line.trim().is_empty() {
+            continue;
+        }
 
-            let rec: InspectRecord = match serde_json::from_str(&line) {
-                Ok(r) => r,
-                Err(_) => continue,
-            };
+        let rec: InspectRecord = match serde_json::from_str(&line) {
+            Ok(r) => r,
+            Err(_) => continue,
+        };
 
-            if rec.id == id {
-                print_record(&rec);
-                return;
-            }
+        if rec.id == id {
+            print_record(&rec);
+            return;
         }
     }
 
@@ -115,6 +267,58 @@ fn print_record(rec: &InspectRecord) {
     println!("  Source IP   : {}", rec.src_ip);
     println!("  Dest IP     : {}", rec.dst_ip);
 
+    if let Some(info) = &rec.tls {
+        println!("\n[TLS]");
+        print_tls(info);
+    }
+
+    for msg in &rec.dns {
+        println!("\n[DNS]");
+        print_dns(msg);
+    }
+
+    if let Some(msg) = &rec.nbns {
+        println!("\n[NetBIOS]");
+        print_nbns(msg);
+    }
+
+    if let Some(msg) = &rec.dhcp {
+        println!("\n[DHCP]");
+        print_dhcp(msg);
+    }
+
+    if !rec.http.is_empty() {
+        println!("\n[HTTP]");
+        print_http(&rec.http);
+    }
+
+    if let Some(info) = &rec.http2 {
+        println!("\n[HTTP/2]");
+        print_http2(info);
+    }
+
+    if !rec.grpc.is_empty() {
+        println!("\n[gRPC]");
+        print_grpc(&rec.grpc);
+    }
+
+    if let Some(analysis) = &rec.tcp_analysis {
+        println!("\n[Analyse TCP]");
+        print_tcp_analysis(analysis);
+    }
+
+    let alerts: Vec<Alert> = stored_alerts()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|a| a.packet_id == Some(rec.id) || a.related_packets.contains(&rec.id))
+        .collect();
+    if !alerts.is_empty() {
+        println!("\n[Alertes]");
+        for a in &alerts {
+            println!("  [{}] {} ({})", alerts::severity_label(a.severity), a.message, a.rule_id);
+        }
+    }
+
     println!("\n[Analyse L3/L4]");
     analyze_layers(&rec.payload);
 
@@ -123,6 +327,332 @@ fn print_record(rec: &InspectRecord) {
     println!("══════════════════════════════════════════════════════════════════════");
 }
 
+fn print_tcp_analysis(analysis: &TcpAnalysis) {
+    println!("  Fenêtre     : {} octets", analysis.window);
+    if let Some(rtt) = analysis.handshake_rtt_ms {
+        println!("  RTT initial : {rtt} ms (SYN → ACK)");
+    }
+    if let Some(rtt) = analysis.ack_rtt_ms {
+        println!("  RTT de l'ACK: {rtt} ms");
+    }
+    for flag in &analysis.flags {
+        println!("  ⚠ {}", tcpanalysis::expert_label(*flag));
+    }
+}
+
+fn join_names(ids: &[u16], name: fn(u16) -> String) -> String {
+    if ids.is_empty() {
+        return "-".to_string();
+    }
+    ids.iter().map(|&id| name(id)).collect::<Vec<_>>().join(", ")
+}
+
+fn print_tls(info: &TlsInfo) {
+    let version = tls::effective_version(info);
+    println!("  Version     : {} (0x{:04x})", tls::version_label(version), version);
+    println!("  Record      : 0x{:04x}", info.record_version);
+
+    if let Some(ch) = &info.client_hello {
+        println!("  Handshake   : ClientHello");
+        println!("  SNI         : {}", ch.sni.as_deref().unwrap_or("-"));
+        println!(
+            "  ALPN        : {}",
+            if ch.alpn.is_empty() { "-".to_string() } else { ch.alpn.join(", ") }
+        );
+        println!("  Versions    : {}", join_names(&ch.supported_versions, |v| {
+            format!("{} (0x{v:04x})", tls::version_label(v))
+        }));
+        println!("  Ciphers ({}) :", ch.cipher_suites.len());
+        for &c in &ch.cipher_suites {
+            println!("      0x{:04x} {}", c, tls::cipher_suite_name(c));
+        }
+        println!("  Groups      : {}", join_names(&ch.supported_groups, tls::group_name));
+        println!("  Sig. algos  : {}", join_names(&ch.signature_algorithms, tls::signature_algorithm_name));
+        println!("  Key share   : {}", join_names(&ch.key_share_groups, tls::group_name));
+        println!("  Extensions  : {}", join_names(&ch.extensions, |e| e.to_string()));
+    }
+
+    if let Some(sh) = &info.server_hello {
+        println!("  Handshake   : ServerHello");
+        println!("  Cipher      : 0x{:04x} {}", sh.cipher_suite, tls::cipher_suite_name(sh.cipher_suite));
+        println!("  ALPN        : {}", sh.alpn.as_deref().unwrap_or("-"));
+        println!(
+            "  Key share   : {}",
+            sh.key_share_group.map(tls::group_name).unwrap_or_else(|| "-".to_string())
+        );
+        println!("  Extensions  : {}", join_names(&sh.extensions, |e| e.to_string()));
+    }
+
+    let fp = &info.fingerprints;
+    if let (Some(s), Some(h)) = (&fp.ja3, &fp.ja3_hash) {
+        println!("  JA3         : {h}");
+        println!("                {s}");
+    }
+    if let (Some(s), Some(h)) = (&fp.ja3s, &fp.ja3s_hash) {
+        println!("  JA3S        : {h}");
+        println!("                {s}");
+    }
+    if let Some(ja4) = &fp.ja4 {
+        println!("  JA4         : {ja4}");
+    }
+    if let Some(ja4s) = &fp.ja4s {
+        println!("  JA4S        : {ja4s}");
+    }
+
+    for (i, cert) in info.certificates.iter().enumerate() {
+        println!("  Certificat #{i}{}", if cert.is_ca { " (CA)" } else { "" });
+        println!("    Sujet      : {}", cert.subject);
+        println!("    Émetteur   : {}", cert.issuer);
+        println!("    Série      : {}", cert.serial);
+        println!(
+            "    Validité   : {} → {}",
+            format_unix(cert.not_before),
+            format_unix(cert.not_after)
+        );
+        if !cert.san_dns.is_empty() || !cert.san_ip.is_empty() {
+            let sans: Vec<&str> = cert.san_dns.iter().chain(cert.san_ip.iter()).map(String::as_str).collect();
+            println!("    SAN        : {}", sans.join(", "));
+        }
+        println!("    Clé        : {}", cert.key_type);
+        println!("    Signature  : {}", cert.signature_algorithm);
+        println!("    SHA-256    : {}", cert.sha256);
+    }
+}
+
+fn print_http_headers(headers: &[(String, String)]) {
+    for (name, value) in headers {
+        println!("      {name}: {value}");
+    }
+}
+
+fn print_http(transactions: &[HttpTransaction]) {
+    for tx in transactions {
+        if let Some(req) = &tx.request {
+            println!("  Requête     : {} {} {}", req.method, req.uri, req.version);
+            println!("  Host        : {}", req.host.as_deref().unwrap_or("-"));
+            if tx.response.is_none() {
+                print_http_headers(&req.headers);
+                println!("  Corps       : {} octets", req.body_len);
+            }
+        }
+        if let Some(resp) = &tx.response {
+            println!("  Réponse     : {} {} {}", resp.version, resp.status, resp.reason);
+            if let Some(ms) = tx.latency_ms {
+                println!("  Latence     : {ms} ms");
+            }
+            print_http_headers(&resp.headers);
+            println!("  Corps       : {} octets", resp.body_len);
+        }
+    }
+}
+
+fn print_http2(info: &Http2Info) {
+    for f in &info.frames {
+        println!(
+            "  Trame       : {} flux {} ({} octets, flags 0x{:02x}) {}",
+            http2::frame_type_name(f.frame_type),
+            f.stream_id,
+            f.length,
+            f.flags,
+            f.detail
+        );
+    }
+    for st in &info.streams {
+        println!("  Flux {} :", st.stream_id);
+        print_http_headers(&st.request_headers);
+        println!("      (corps requête : {} octets)", st.request_body_len);
+        print_http_headers(&st.response_headers);
+        println!("      (corps réponse : {} octets)", st.response_body_len);
+        if !st.trailers.is_empty() {
+            println!("    Trailers :");
+            print_http_headers(&st.trailers);
+        }
+        if let Some(code) = st.reset_error {
+            println!("    RST_STREAM : {}", http2::error_name(code));
+        }
+        if let Some(ms) = st.latency_ms {
+            println!("    Latence    : {ms} ms");
+        }
+    }
+}
+
+fn print_grpc_messages(title: &str, messages: &[GrpcMessage]) {
+    for (i, m) in messages.iter().enumerate() {
+        println!(
+            "    {title} #{i} : {} octets{}",
+            m.length,
+            if m.compressed { " (compressé)" } else { "" }
+        );
+        let pretty = m
+            .json
+            .as_deref()
+            .and_then(|j| serde_json::from_str::<serde_json::Value>(j).ok())
+            .and_then(|v| serde_json::to_string_pretty(&v).ok());
+        match pretty {
+            Some(p) => {
+                for line in p.lines() {
+                    println!("      {line}");
+                }
+            }
+            None => match &m.error {
+                Some(e) => println!("      (non décodé : {e})"),
+                None => println!("      (non décodé)"),
+            },
+        }
+    }
+}
+
+fn print_grpc(calls: &[GrpcCall]) {
+    for call in calls {
+        println!("  Appel       : {}/{} (flux {})", call.service, call.method, call.stream_id);
+        match call.status {
+            Some(code) => println!(
+                "  Statut      : {code} {}{}",
+                grpc::status_name(code),
+                call.status_message.as_deref().map(|m| format!(" — {m}")).unwrap_or_default()
+            ),
+            None => println!("  Statut      : -"),
+        }
+        if let Some(ms) = call.latency_ms {
+            println!("  Latence     : {ms} ms");
+        }
+        print_grpc_messages("Requête", &call.requests);
+        print_grpc_messages("Réponse", &call.responses);
+        if !call.trailers.is_empty() {
+            println!("  Trailers :");
+            print_http_headers(&call.trailers);
+        }
+    }
+}
+
+fn print_nbns(msg: &NbnsMessage) {
+    println!(
+        "  Transaction : 0x{:04x} ({})",
+        msg.id,
+        if msg.is_response { "réponse" } else { "requête" }
+    );
+    println!("  Opcode      : {}", netbios::opcode_name(msg.opcode));
+    if msg.is_response {
+        println!("  Rcode       : {}", msg.rcode);
+    }
+    for q in &msg.questions {
+        println!("  Question    : {}<{:02x}> {}", q.name, q.suffix, netbios::suffix_name(q.suffix));
+    }
+    for rec in &msg.records {
+        println!(
+            "  Nom         : {}<{:02x}> {} (TTL {})",
+            rec.name.name,
+            rec.name.suffix,
+            netbios::suffix_name(rec.name.suffix),
+            rec.ttl
+        );
+        if !rec.addresses.is_empty() {
+            println!("  Adresses    : {}", rec.addresses.join(", "));
+        }
+        for n in &rec.names {
+            println!("      {}<{:02x}> {}", n.name, n.suffix, netbios::suffix_name(n.suffix));
+        }
+        if let Some(mac) = &rec.mac {
+            println!("  MAC         : {mac}");
+        }
+    }
+}
+
+fn print_dhcp(msg: &DhcpMessage) {
+    println!(
+        "  Message     : DHCPv{} {} (transaction 0x{:x})",
+        msg.version,
+        dhcp::message_type_name(msg.version, msg.message_type),
+        msg.transaction_id
+    );
+    for (label, value) in [
+        ("MAC client  ", &msg.client_mac),
+        ("ID client   ", &msg.client_id),
+        ("IP client   ", &msg.client_ip),
+        ("IP attribuée", &msg.assigned_ip),
+        ("IP demandée ", &msg.requested_ip),
+        ("Serveur     ", &msg.server_id),
+        ("Nom d’hôte  ", &msg.hostname),
+        ("Classe      ", &msg.vendor_class),
+    ] {
+        if let Some(value) = value {
+            println!("  {label}: {value}");
+        }
+    }
+    if let Some(t) = msg.lease_time {
+        println!("  Bail        : {t} s");
+    }
+    if !msg.routers.is_empty() {
+        println!("  Passerelle  : {}", msg.routers.join(", "));
+    }
+    if !msg.dns_servers.is_empty() {
+        println!("  DNS         : {}", msg.dns_servers.join(", "));
+    }
+}
+
+fn print_dns(msg: &DnsMessage) {
+    println!(
+        "  Transaction : 0x{:04x} ({})",
+        msg.id,
+        if msg.is_response { "réponse" } else { "requête" }
+    );
+    let mut flags = Vec::new();
+    for (set, name) in [
+        (msg.authoritative, "AA"),
+        (msg.truncated, "TC"),
+        (msg.recursion_desired, "RD"),
+        (msg.recursion_available, "RA"),
+        (msg.authentic_data, "AD"),
+        (msg.checking_disabled, "CD"),
+    ] {
+        if set {
+            flags.push(name);
+        }
+    }
+    println!("  Opcode      : {}", msg.opcode);
+    println!("  Flags       : {}", if flags.is_empty() { "-".to_string() } else { flags.join(" ") });
+    if msg.is_response {
+        println!("  Rcode       : {}", dns::rcode_name(msg.rcode));
+    }
+    if let Some(ms) = msg.latency_ms {
+        println!("  Latence     : {ms} ms");
+    }
+    if let Some(edns) = &msg.edns {
+        println!(
+            "  EDNS0       : v{} udp={} DO={} options={:?}",
+            edns.version, edns.udp_payload_size, edns.dnssec_ok, edns.options
+        );
+    }
+
+    println!("  Questions ({}) :", msg.questions.len());
+    for q in &msg.questions {
+        println!("      {} {} (classe {})", q.name, dns::type_name(q.qtype), q.qclass);
+    }
+    for (title, records) in [
+        ("Réponses", &msg.answers),
+        ("Autorité", &msg.authority),
+        ("Additionnels", &msg.additional),
+    ] {
+        if records.is_empty() {
+            continue;
+        }
+        println!("  {title} ({}) :", records.len());
+        for r in records.iter() {
+            print_dns_record(r);
+        }
+    }
+}
+
+fn print_dns_record(r: &DnsRecord) {
+    println!("      {} {} TTL={} {}", r.name, dns::type_name(r.rtype), r.ttl, r.data);
+}
+
+fn format_unix(ts: i64) -> String {
+    chrono::DateTime::from_timestamp(ts, 0)
+        .map(|d| d.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| "?".to_string())
+}
+
 fn analyze_layers(data: &[u8]) {
     if data.is_empty() {
         println!("  (Pas de payload)");
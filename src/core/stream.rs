@@ -0,0 +1,731 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::core::models::{IpHeader, Packet, TcpHeader, TransportProtocol};
+use crate::core::parser;
+
+pub const TCP_FIN: u8 = 0x01;
+pub const TCP_SYN: u8 = 0x02;
+pub const TCP_RST: u8 = 0x04;
+pub const TCP_ACK: u8 = 0x10;
+
+/// Interval between two sweeps of idle connections (capture time).
+const SWEEP_INTERVAL_MS: u128 = 1000;
+
+/// A TCP connection, identified by its two `ip:port` endpoints.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StreamKey {
+    pub client: String,
+    pub server: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    ToServer,
+    ToClient,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    /// FIN seen and acknowledged by the stream in both directions.
+    Fin,
+    Reset,
+    /// No traffic for `ReassemblyConfig::idle_timeout_ms`.
+    Idle,
+    /// Dropped to stay under `ReassemblyConfig::max_connections`.
+    Evicted,
+    /// A new SYN reused the endpoints of the connection.
+    Reused,
+}
+
+#[derive(Debug, Clone)]
+pub enum StreamEvent {
+    /// In-order bytes of one direction of the connection.
+    Data {
+        key: StreamKey,
+        direction: Direction,
+        data: Vec<u8>,
+    },
+    /// Bytes that never arrived were skipped: the next `Data` of this
+    /// direction does not follow the previous one.
+    Gap {
+        key: StreamKey,
+        direction: Direction,
+        missing: u64,
+    },
+    Closed { key: StreamKey, reason: CloseReason },
+}
+
+/// Which bytes win when two segments cover the same sequence range.
+/// Bytes already delivered are never replaced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverlapPolicy {
+    /// Keep the bytes received first.
+    #[default]
+    First,
+    /// Later segments overwrite the buffered bytes they cover.
+    Last,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReassemblyConfig {
+    pub overlap: OverlapPolicy,
+    /// Connections followed at the same time; the least recently active
+    /// one is dropped beyond that.
+    pub max_connections: usize,
+    /// Out-of-order bytes kept per direction before skipping the hole.
+    pub max_pending: usize,
+    /// Out-of-order bytes kept across all connections.
+    pub max_total_pending: usize,
+    pub idle_timeout_ms: u128,
+}
+
+impl Default for ReassemblyConfig {
+    fn default() -> Self {
+        ReassemblyConfig {
+            overlap: OverlapPolicy::First,
+            max_connections: 4096,
+            max_pending: 1024 * 1024,
+            max_total_pending: 64 * 1024 * 1024,
+            idle_timeout_ms: 5 * 60 * 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReassemblyStats {
+    pub connections: u64,
+    pub delivered_bytes: u64,
+    /// Payload bytes already received or delivered (retransmissions).
+    pub duplicate_bytes: u64,
+    /// Overlapping segments whose bytes differ from the kept ones.
+    pub overlap_conflicts: u64,
+    /// Bytes skipped because they never arrived.
+    pub missing_bytes: u64,
+    pub closed_idle: u64,
+    pub evicted: u64,
+}
+
+enum Piece {
+    Data(Vec<u8>),
+    Gap(u64),
+}
+
+fn count_conflict(stats: &mut ReassemblyStats, old: &[u8], new: &[u8]) {
+    if old != new {
+        stats.overlap_conflicts += 1;
+    }
+}
+
+/// One direction of a connection. Positions are 64-bit offsets from the
+/// first byte of the stream, so sequence wraparound does not matter.
+#[derive(Default)]
+struct HalfStream {
+    /// Sequence number of offset 0.
+    base: Option<u32>,
+    next: u64,
+    /// Non-overlapping out-of-order segments, by offset.
+    pending: BTreeMap<u64, Vec<u8>>,
+    pending_bytes: usize,
+    fin: Option<u64>,
+}
+
+impl HalfStream {
+    fn start(&mut self, seq: u32) {
+        if self.base.is_none() {
+            self.base = Some(seq);
+        }
+    }
+
+    fn offset(&self, seq: u32) -> i64 {
+        let next_seq = self.base.unwrap_or(seq).wrapping_add(self.next as u32);
+        self.next as i64 + seq.wrapping_sub(next_seq) as i32 as i64
+    }
+
+    fn finished(&self) -> bool {
+        self.fin.is_some_and(|f| self.next >= f)
+    }
+
+    fn push(&mut self, seq: u32, data: &[u8], policy: OverlapPolicy, stats: &mut ReassemblyStats) {
+        self.start(seq);
+        let offset = self.offset(seq);
+        let skip = (self.next as i64 - offset).max(0) as usize;
+        if skip >= data.len() {
+            stats.duplicate_bytes += data.len() as u64;
+            return;
+        }
+        stats.duplicate_bytes += skip as u64;
+        // A retransmission may start before offset 0 when the capture began
+        // mid-stream; skipping the delivered bytes brings it back to `next`.
+        let start = (offset + skip as i64) as u64;
+        let data = &data[skip..];
+        let end = start + data.len() as u64;
+
+        // The only segment starting before `start` that can reach it.
+        let mut overlapping: Vec<u64> = self
+            .pending
+            .range(..start)
+            .next_back()
+            .filter(|(&s, seg)| s + seg.len() as u64 > start)
+            .map(|(&s, _)| s)
+            .into_iter()
+            .collect();
+        overlapping.extend(self.pending.range(start..end).map(|(&s, _)| s));
+
+        match policy {
+            OverlapPolicy::First => {
+                let mut pos = start;
+                let mut pieces = Vec::new();
+                for s in overlapping {
+                    let seg = &self.pending[&s];
+                    let seg_end = s + seg.len() as u64;
+                    if s > pos {
+                        pieces.push((pos, data[(pos - start) as usize..(s - start) as usize].to_vec()));
+                    }
+                    let (lo, hi) = (s.max(start), seg_end.min(end));
+                    count_conflict(
+                        stats,
+                        &seg[(lo - s) as usize..(hi - s) as usize],
+                        &data[(lo - start) as usize..(hi - start) as usize],
+                    );
+                    stats.duplicate_bytes += hi - lo;
+                    pos = pos.max(seg_end);
+                }
+                if pos < end {
+                    pieces.push((pos, data[(pos - start) as usize..].to_vec()));
+                }
+                for (s, piece) in pieces {
+                    self.pending_bytes += piece.len();
+                    self.pending.insert(s, piece);
+                }
+            }
+            OverlapPolicy::Last => {
+                for s in overlapping {
+                    let seg = self.pending.remove(&s).unwrap_or_default();
+                    self.pending_bytes -= seg.len();
+                    let seg_end = s + seg.len() as u64;
+                    let (lo, hi) = (s.max(start), seg_end.min(end));
+                    count_conflict(
+                        stats,
+                        &seg[(lo - s) as usize..(hi - s) as usize],
+                        &data[(lo - start) as usize..(hi - start) as usize],
+                    );
+                    stats.duplicate_bytes += hi - lo;
+                    if s < start {
+                        let head = seg[..(start - s) as usize].to_vec();
+                        self.pending_bytes += head.len();
+                        self.pending.insert(s, head);
+                    }
+                    if seg_end > end {
+                        let tail = seg[(end - s) as usize..].to_vec();
+                        self.pending_bytes += tail.len();
+                        self.pending.insert(end, tail);
+                    }
+                }
+                self.pending_bytes += data.len();
+                self.pending.insert(start, data.to_vec());
+            }
+        }
+    }
+
+    /// Moves the bytes that became contiguous to `out`. With `skip_holes`,
+    /// missing ranges are skipped and reported as gaps.
+    fn drain(&mut self, out: &mut Vec<Piece>, skip_holes: bool, stats: &mut ReassemblyStats) {
+        while let Some((&s, _)) = self.pending.iter().next() {
+            if s > self.next {
+                if !skip_holes {
+                    break;
+                }
+                stats.missing_bytes += s - self.next;
+                out.push(Piece::Gap(s - self.next));
+                self.next = s;
+            }
+            let seg = self.pending.remove(&s).unwrap_or_default();
+            self.pending_bytes -= seg.len();
+            self.next += seg.len() as u64;
+            stats.delivered_bytes += seg.len() as u64;
+            match out.last_mut() {
+                Some(Piece::Data(d)) => d.extend_from_slice(&seg),
+                _ => out.push(Piece::Data(seg)),
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Connection {
+    to_server: HalfStream,
+    to_client: HalfStream,
+    last_seen: u128,
+}
+
+impl Connection {
+    fn half(&mut self, direction: Direction) -> &mut HalfStream {
+        match direction {
+            Direction::ToServer => &mut self.to_server,
+            Direction::ToClient => &mut self.to_client,
+        }
+    }
+
+    fn pending_bytes(&self) -> usize {
+        self.to_server.pending_bytes + self.to_client.pending_bytes
+    }
+}
+
+fn emit(events: &mut Vec<StreamEvent>, key: &StreamKey, direction: Direction, pieces: Vec<Piece>) {
+    for piece in pieces {
+        events.push(match piece {
+            Piece::Data(data) => StreamEvent::Data {
+                key: key.clone(),
+                direction,
+                data,
+            },
+            Piece::Gap(missing) => StreamEvent::Gap {
+                key: key.clone(),
+                direction,
+                missing,
+            },
+        });
+    }
+}
+
+/// Rebuilds the byte streams of TCP connections from captured segments:
+/// ordered by sequence number, without retransmitted bytes, with holes
+/// reported once a buffer limit is hit or the connection ends.
+#[derive(Default)]
+pub struct TcpReassembler {
+    config: ReassemblyConfig,
+    conns: HashMap<StreamKey, Connection>,
+    stats: ReassemblyStats,
+    last_sweep: u128,
+}
+
+impl TcpReassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_config(config: ReassemblyConfig) -> Self {
+        TcpReassembler {
+            config,
+            ..Default::default()
+        }
+    }
+
+    pub fn stats(&self) -> &ReassemblyStats {
+        &self.stats
+    }
+
+    pub fn observe(&mut self, packet: &Packet) -> Vec<StreamEvent> {
+        let mut events = Vec::new();
+        if packet.timestamp >= self.last_sweep + SWEEP_INTERVAL_MS {
+            self.last_sweep = packet.timestamp;
+            self.expire(packet.timestamp, &mut events);
+        }
+
+        let (Some(ip), Some(TransportProtocol::Tcp(tcp))) = (&packet.ip, &packet.transport) else {
+            return events;
+        };
+        let src = format!("{}:{}", ip.src_ip, tcp.src_port);
+        let dst = format!("{}:{}", ip.dst_ip, tcp.dst_port);
+        let payload = parser::transport_payload(&packet.payload);
+        let syn = tcp.flags & TCP_SYN != 0;
+        let (key, direction) = self.locate(src, dst, tcp.flags, tcp.src_port, tcp.dst_port);
+
+        // A client SYN with a new ISN on known endpoints starts a new connection.
+        if syn && tcp.flags & TCP_ACK == 0 {
+            let reused = self
+                .conns
+                .get(&key)
+                .and_then(|c| c.to_server.base)
+                .is_some_and(|base| base != tcp.seq.wrapping_add(1));
+            if reused {
+                self.close(&key, CloseReason::Reused, &mut events);
+            }
+        }
+
+        if !self.conns.contains_key(&key) {
+            // Late ACK/FIN/RST of a connection already closed or never seen.
+            if !syn && payload.is_empty() {
+                return events;
+            }
+            if self.conns.len() >= self.config.max_connections {
+                self.evict_oldest(&mut events);
+            }
+            self.stats.connections += 1;
+        }
+
+        let conn = self.conns.entry(key.clone()).or_default();
+        conn.last_seen = packet.timestamp;
+        let half = conn.half(direction);
+        let mut seq = tcp.seq;
+        if syn {
+            seq = seq.wrapping_add(1);
+            half.start(seq);
+        }
+
+        let mut pieces = Vec::new();
+        let mut buffered = false;
+        if !payload.is_empty() {
+            half.push(seq, payload, self.config.overlap, &mut self.stats);
+            half.drain(&mut pieces, false, &mut self.stats);
+            if half.pending_bytes > self.config.max_pending {
+                // Too much buffered: give up on the missing bytes.
+                half.drain(&mut pieces, true, &mut self.stats);
+            }
+            buffered = half.pending_bytes > 0;
+        }
+        if tcp.flags & TCP_FIN != 0 {
+            half.start(seq);
+            let fin = half.offset(seq) + payload.len() as i64;
+            half.fin = Some(fin.max(0) as u64);
+        }
+        emit(&mut events, &key, direction, pieces);
+
+        if tcp.flags & TCP_RST != 0 {
+            self.close(&key, CloseReason::Reset, &mut events);
+        } else if self
+            .conns
+            .get(&key)
+            .is_some_and(|c| c.to_server.finished() && c.to_client.finished())
+        {
+            self.close(&key, CloseReason::Fin, &mut events);
+        } else if buffered
+            && self.conns.values().map(Connection::pending_bytes).sum::<usize>() > self.config.max_total_pending
+        {
+            self.release_largest(&mut events);
+        }
+        events
+    }
+
+    /// End of the capture: delivers what is still buffered and closes
+    /// every connection.
+    pub fn finish(&mut self) -> Vec<StreamEvent> {
+        let mut events = Vec::new();
+        let keys: Vec<StreamKey> = self.conns.keys().cloned().collect();
+        for key in keys {
+            self.close(&key, CloseReason::Idle, &mut events);
+        }
+        events
+    }
+
+    fn flush(&mut self, key: &StreamKey, events: &mut Vec<StreamEvent>) {
+        let Some(conn) = self.conns.get_mut(key) else {
+            return;
+        };
+        for direction in [Direction::ToServer, Direction::ToClient] {
+            let mut pieces = Vec::new();
+            conn.half(direction).drain(&mut pieces, true, &mut self.stats);
+            emit(events, key, direction, pieces);
+        }
+    }
+
+    fn close(&mut self, key: &StreamKey, reason: CloseReason, events: &mut Vec<StreamEvent>) {
+        self.flush(key, events);
+        if self.conns.remove(key).is_some() {
+            events.push(StreamEvent::Closed {
+                key: key.clone(),
+                reason,
+            });
+        }
+    }
+
+    fn expire(&mut self, now: u128, events: &mut Vec<StreamEvent>) {
+        let timeout = self.config.idle_timeout_ms;
+        let idle: Vec<StreamKey> = self
+            .conns
+            .iter()
+            .filter(|(_, c)| now.saturating_sub(c.last_seen) > timeout)
+            .map(|(k, _)| k.clone())
+            .collect();
+        for key in idle {
+            self.stats.closed_idle += 1;
+            self.close(&key, CloseReason::Idle, events);
+        }
+    }
+
+    fn evict_oldest(&mut self, events: &mut Vec<StreamEvent>) {
+        let oldest = self.conns.iter().min_by_key(|(_, c)| c.last_seen).map(|(k, _)| k.clone());
+        if let Some(key) = oldest {
+            self.stats.evicted += 1;
+            self.close(&key, CloseReason::Evicted, events);
+        }
+    }
+
+    /// Global buffer limit hit: the connection holding the most
+    /// out-of-order bytes skips its holes.
+    fn release_largest(&mut self, events: &mut Vec<StreamEvent>) {
+        let largest = self
+            .conns
+            .iter()
+            .max_by_key(|(_, c)| c.pending_bytes())
+            .map(|(k, _)| k.clone());
+        if let Some(key) = largest {
+            self.flush(&key, events);
+        }
+    }
+
+    /// Finds the connection of a segment, or decides who the client is for
+    /// a new one: the SYN sender, else the endpoint with the higher port.
+    fn locate(&self, src: String, dst: String, flags: u8, sport: u16, dport: u16) -> (StreamKey, Direction) {
+        let forward = StreamKey { client: src, server: dst };
+        if self.conns.contains_key(&forward) {
+            return (forward, Direction::ToServer);
+        }
+        let reverse = StreamKey {
+            client: forward.server.clone(),
+            server: forward.client.clone(),
+        };
+        if self.conns.contains_key(&reverse) {
+            return (reverse, Direction::ToClient);
+        }
+
+        let syn_ack = flags & TCP_SYN != 0 && flags & TCP_ACK != 0;
+        let client_is_src = if flags & TCP_SYN != 0 { !syn_ack } else { sport >= dport };
+        if client_is_src {
+            (forward, Direction::ToServer)
+        } else {
+            (reverse, Direction::ToClient)
+        }
+    }
+}
+
+/// Splits the events of `TcpReassembler::finish` by connection, each with a
+/// packet standing for the end of the capture on it (client to server, no
+/// flags nor payload) for the decoders to attach what the events complete.
+pub fn end_of_capture(events: Vec<StreamEvent>, timestamp: u128) -> Vec<(Packet, Vec<StreamEvent>)> {
+    let mut groups: Vec<(Packet, Vec<StreamEvent>)> = Vec::new();
+    for event in events {
+        let key = event_key(&event);
+        match groups.last_mut() {
+            Some((_, group)) if event_key(&group[0]) == key => group.push(event),
+            _ => groups.push((end_packet(key, timestamp), vec![event])),
+        }
+    }
+    groups
+}
+
+fn event_key(event: &StreamEvent) -> &StreamKey {
+    match event {
+        StreamEvent::Data { key, .. } | StreamEvent::Gap { key, .. } | StreamEvent::Closed { key, .. } => key,
+    }
+}
+
+fn end_packet(key: &StreamKey, timestamp: u128) -> Packet {
+    let split = |endpoint: &str| match endpoint.rsplit_once(':') {
+        Some((ip, port)) => (ip.to_string(), port.parse().unwrap_or(0)),
+        None => (endpoint.to_string(), 0),
+    };
+    let (src_ip, src_port) = split(&key.client);
+    let (dst_ip, dst_port) = split(&key.server);
+    Packet {
+        timestamp,
+        eth: None,
+        ip: Some(IpHeader { src_ip, dst_ip, protocol: 6 }),
+        transport: Some(TransportProtocol::Tcp(TcpHeader {
+            src_port,
+            dst_port,
+            flags: 0,
+            seq: 0,
+            ack: 0,
+            window: 0,
+            window_scale: None,
+        })),
+        payload: Vec::new(),
+        tls: None,
+        dns: Vec::new(),
+        nbns: None,
+        dhcp: None,
+        http: Vec::new(),
+        http2: None,
+        grpc: Vec::new(),
+        tcp_analysis: None,
+        connection_tls: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT: [u8; 4] = [10, 0, 0, 1];
+    const SERVER: [u8; 4] = [10, 0, 0, 2];
+
+    /// Ethernet + IPv4 + TCP segment between CLIENT:40000 and SERVER:80.
+    fn segment(to_server: bool, seq: u32, flags: u8, payload: &[u8]) -> Packet {
+        let (src, sport, dst, dport) = if to_server {
+            (CLIENT, 40000u16, SERVER, 80u16)
+        } else {
+            (SERVER, 80, CLIENT, 40000)
+        };
+        let mut f = vec![0u8; 12];
+        f.extend_from_slice(&[0x08, 0x00, 0x45, 0]);
+        f.extend_from_slice(&((40 + payload.len()) as u16).to_be_bytes());
+        f.extend_from_slice(&[0, 0, 0, 0, 64, 6, 0, 0]);
+        f.extend_from_slice(&src);
+        f.extend_from_slice(&dst);
+        f.extend_from_slice(&sport.to_be_bytes());
+        f.extend_from_slice(&dport.to_be_bytes());
+        f.extend_from_slice(&seq.to_be_bytes());
+        f.extend_from_slice(&[0, 0, 0, 0, 0x50, flags, 0xff, 0xff, 0, 0, 0, 0]);
+        f.extend_from_slice(payload);
+        parser::parse_packet_at(&f, 1000).unwrap()
+    }
+
+    /// Bytes delivered to the server side, and gaps as `None`.
+    fn to_server(events: &[StreamEvent]) -> Vec<Option<Vec<u8>>> {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                StreamEvent::Data {
+                    direction: Direction::ToServer,
+                    data,
+                    ..
+                } => Some(Some(data.clone())),
+                StreamEvent::Gap {
+                    direction: Direction::ToServer,
+                    ..
+                } => Some(None),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn closed(events: &[StreamEvent]) -> Vec<CloseReason> {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                StreamEvent::Closed { reason, .. } => Some(*reason),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Segments as (sequence number, payload).
+    type Segments = &'static [(u32, &'static [u8])];
+
+    #[test]
+    fn delivers_in_order_and_reorders() {
+        let cases: &[(&str, Segments, &[u8])] = &[
+            ("dans l'ordre", &[(1, b"abc"), (4, b"def")], b"abcdef"),
+            ("désordre", &[(4, b"def"), (7, b"ghi"), (1, b"abc")], b"abcdefghi"),
+            ("retransmission", &[(1, b"abc"), (1, b"abc"), (4, b"def")], b"abcdef"),
+            ("chevauchement", &[(1, b"abcd"), (3, b"cdef")], b"abcdef"),
+            ("trou comblé", &[(1, b"ab"), (5, b"ef"), (3, b"cd")], b"abcdef"),
+        ];
+        for (name, segments, expected) in cases {
+            let mut r = TcpReassembler::new();
+            r.observe(&segment(true, 0, TCP_SYN, b""));
+            let mut out = Vec::new();
+            for (seq, data) in segments.iter() {
+                for piece in to_server(&r.observe(&segment(true, *seq, TCP_ACK, data))) {
+                    out.extend(piece.expect(name));
+                }
+            }
+            assert_eq!(out, expected.to_vec(), "{name}");
+        }
+    }
+
+    /// Capture started mid-stream, then a repacketized retransmission that
+    /// begins before the first byte seen.
+    #[test]
+    fn retransmission_before_stream_start() {
+        let mut r = TcpReassembler::new();
+        let first = r.observe(&segment(true, 1000, TCP_ACK, &[b'a'; 100]));
+        assert_eq!(to_server(&first), vec![Some(vec![b'a'; 100])]);
+        assert!(r.observe(&segment(false, 5000, TCP_ACK, b"")).is_empty());
+
+        let mut data = vec![b'a'; 200];
+        data.extend_from_slice(&[b'b'; 100]);
+        let events = r.observe(&segment(true, 900, TCP_ACK, &data));
+        assert_eq!(to_server(&events), vec![Some(vec![b'b'; 100])]);
+        assert_eq!(r.stats().duplicate_bytes, 200);
+        assert_eq!(r.stats().delivered_bytes, 200);
+    }
+
+    #[test]
+    fn sequence_wraparound() {
+        let mut r = TcpReassembler::new();
+        r.observe(&segment(true, u32::MAX - 2, TCP_SYN, b""));
+        let a = r.observe(&segment(true, u32::MAX - 1, TCP_ACK, b"abcd"));
+        let b = r.observe(&segment(true, 2, TCP_ACK, b"efgh"));
+        assert_eq!(to_server(&a), vec![Some(b"abcd".to_vec())]);
+        assert_eq!(to_server(&b), vec![Some(b"efgh".to_vec())]);
+    }
+
+    #[test]
+    fn overlap_policies() {
+        for (policy, expected) in [(OverlapPolicy::First, b"abcdef"), (OverlapPolicy::Last, b"abCDef")] {
+            let mut r = TcpReassembler::with_config(ReassemblyConfig {
+                overlap: policy,
+                ..Default::default()
+            });
+            r.observe(&segment(true, 0, TCP_SYN, b""));
+            r.observe(&segment(true, 3, TCP_ACK, b"cdef"));
+            r.observe(&segment(true, 3, TCP_ACK, b"CD"));
+            let events = r.observe(&segment(true, 1, TCP_ACK, b"ab"));
+            assert_eq!(to_server(&events), vec![Some(expected.to_vec())], "{policy:?}");
+            assert_eq!(r.stats().overlap_conflicts, 1, "{policy:?}");
+        }
+    }
+
+    #[test]
+    fn holes_become_gaps() {
+        // Buffer limit hit.
+        let mut r = TcpReassembler::with_config(ReassemblyConfig {
+            max_pending: 4,
+            ..Default::default()
+        });
+        r.observe(&segment(true, 0, TCP_SYN, b""));
+        r.observe(&segment(true, 1, TCP_ACK, b"ab"));
+        assert!(to_server(&r.observe(&segment(true, 6, TCP_ACK, b"fg"))).is_empty());
+        let events = r.observe(&segment(true, 8, TCP_ACK, b"hij"));
+        assert_eq!(to_server(&events), vec![None, Some(b"fghij".to_vec())]);
+        assert_eq!(r.stats().missing_bytes, 3);
+
+        // End of the capture.
+        let mut r = TcpReassembler::new();
+        r.observe(&segment(true, 0, TCP_SYN, b""));
+        r.observe(&segment(true, 5, TCP_ACK, b"ef"));
+        let events = r.finish();
+        assert_eq!(to_server(&events), vec![None, Some(b"ef".to_vec())]);
+        assert_eq!(closed(&events), vec![CloseReason::Idle]);
+    }
+
+    #[test]
+    fn closes_on_fin_reset_and_reuse() {
+        let mut r = TcpReassembler::new();
+        r.observe(&segment(true, 0, TCP_SYN, b""));
+        r.observe(&segment(false, 100, TCP_SYN | TCP_ACK, b""));
+        r.observe(&segment(true, 1, TCP_ACK | TCP_FIN, b"bye"));
+        let events = r.observe(&segment(false, 101, TCP_ACK | TCP_FIN, b""));
+        assert_eq!(closed(&events), vec![CloseReason::Fin]);
+
+        let mut r = TcpReassembler::new();
+        r.observe(&segment(true, 0, TCP_SYN, b""));
+        assert_eq!(closed(&r.observe(&segment(false, 0, TCP_RST, b""))), vec![CloseReason::Reset]);
+
+        let mut r = TcpReassembler::new();
+        r.observe(&segment(true, 0, TCP_SYN, b""));
+        r.observe(&segment(true, 1, TCP_ACK, b"a"));
+        assert_eq!(closed(&r.observe(&segment(true, 7000, TCP_SYN, b""))), vec![CloseReason::Reused]);
+    }
+
+    #[test]
+    fn end_of_capture_flushes_behind_holes() {
+        let mut r = TcpReassembler::new();
+        r.observe(&segment(true, 0, TCP_SYN, b""));
+        r.observe(&segment(true, 1, TCP_ACK, b"GET "));
+        // "/x" never arrives.
+        let events = r.observe(&segment(true, 7, TCP_ACK, b" HTTP/1.1\r\n\r\n"));
+        assert!(to_server(&events).is_empty());
+
+        let groups = end_of_capture(r.finish(), 2000);
+        assert_eq!(groups.len(), 1);
+        let (packet, events) = &groups[0];
+        assert_eq!(packet.timestamp, 2000);
+        assert_eq!(packet.ip.as_ref().map(|ip| ip.src_ip.as_str()), Some("10.0.0.1"));
+        assert!(matches!(&packet.transport, Some(TransportProtocol::Tcp(t)) if t.src_port == 40000 && t.dst_port == 80));
+        assert_eq!(to_server(events), [None, Some(b" HTTP/1.1\r\n\r\n".to_vec())]);
+        assert_eq!(closed(events), [CloseReason::Idle]);
+    }
+}
@@ -0,0 +1,695 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::core::models::Severity;
+use crate::core::rules::{
+    self, AddrMatch, ContentMatch, PortMatch, RegexMatch, Rule, Threshold, ThresholdKind, Track,
+};
+use crate::core::stream::Direction;
+
+/// Keywords that only describe a rule or tune Suricata's prefilter: they
+/// do not change what matches.
+const INFORMATIONAL: &[&str] = &["reference", "metadata", "gid", "target", "fast_pattern", "rawbytes"];
+
+/// Suricata application-layer protocols with a WireFish classifier label.
+const APP_PROTOCOLS: &[&str] = &["http", "http2", "tls", "dns", "dhcp", "ntp", "quic"];
+
+/// Default priorities of Suricata's classification.config.
+const CLASSTYPES: &[(&str, u8)] = &[
+    ("not-suspicious", 3),
+    ("unknown", 3),
+    ("bad-unknown", 2),
+    ("attempted-recon", 2),
+    ("successful-recon-limited", 2),
+    ("successful-recon-largescale", 2),
+    ("attempted-dos", 2),
+    ("successful-dos", 2),
+    ("attempted-user", 1),
+    ("unsuccessful-user", 1),
+    ("successful-user", 1),
+    ("attempted-admin", 1),
+    ("successful-admin", 1),
+    ("rpc-portmap-decode", 2),
+    ("shellcode-detect", 1),
+    ("string-detect", 3),
+    ("suspicious-filename-detect", 2),
+    ("suspicious-login", 2),
+    ("system-call-detect", 2),
+    ("tcp-connection", 4),
+    ("trojan-activity", 1),
+    ("unusual-client-port-connection", 2),
+    ("network-scan", 3),
+    ("denial-of-service", 2),
+    ("non-standard-protocol", 2),
+    ("protocol-command-decode", 3),
+    ("web-application-activity", 2),
+    ("web-application-attack", 1),
+    ("misc-activity", 3),
+    ("misc-attack", 2),
+    ("icmp-event", 3),
+    ("inappropriate-content", 1),
+    ("policy-violation", 1),
+    ("default-login-attempt", 2),
+    ("targeted-activity", 1),
+    ("exploit-kit", 1),
+    ("external-ip-check", 2),
+    ("domain-c2", 1),
+    ("pup-activity", 2),
+    ("credential-theft", 1),
+    ("social-engineering", 2),
+    ("coin-mining", 2),
+    ("command-and-control", 1),
+];
+
+/// Suricata priority (1 = most severe) to WireFish severity.
+fn priority_severity(priority: u8) -> Severity {
+    match priority {
+        1 => Severity::High,
+        2 => Severity::Medium,
+        3 => Severity::Low,
+        _ => Severity::Info,
+    }
+}
+
+/// Address and port variables (`$HOME_NET`, `$HTTP_PORTS`…), with the
+/// defaults of suricata.yaml.
+#[derive(Debug, Clone)]
+pub struct RuleVars {
+    vars: HashMap<String, String>,
+}
+
+impl Default for RuleVars {
+    fn default() -> Self {
+        let mut vars = HashMap::new();
+        let defaults = [
+            ("HOME_NET", "[192.168.0.0/16,10.0.0.0/8,172.16.0.0/12]"),
+            ("EXTERNAL_NET", "!$HOME_NET"),
+            ("HTTP_SERVERS", "$HOME_NET"),
+            ("SMTP_SERVERS", "$HOME_NET"),
+            ("SQL_SERVERS", "$HOME_NET"),
+            ("DNS_SERVERS", "$HOME_NET"),
+            ("TELNET_SERVERS", "$HOME_NET"),
+            ("AIM_SERVERS", "$EXTERNAL_NET"),
+            ("DC_SERVERS", "$HOME_NET"),
+            ("DNP3_SERVER", "$HOME_NET"),
+            ("DNP3_CLIENT", "$HOME_NET"),
+            ("MODBUS_CLIENT", "$HOME_NET"),
+            ("MODBUS_SERVER", "$HOME_NET"),
+            ("ENIP_CLIENT", "$HOME_NET"),
+            ("ENIP_SERVER", "$HOME_NET"),
+            ("HTTP_PORTS", "80"),
+            ("SHELLCODE_PORTS", "!80"),
+            ("ORACLE_PORTS", "1521"),
+            ("SSH_PORTS", "22"),
+            ("DNP3_PORTS", "20000"),
+            ("MODBUS_PORTS", "502"),
+            ("FILE_DATA_PORTS", "[$HTTP_PORTS,110,143]"),
+            ("FTP_PORTS", "21"),
+            ("GENEVE_PORTS", "6081"),
+            ("VXLAN_PORTS", "4789"),
+            ("TEREDO_PORTS", "3544"),
+        ];
+        for (name, value) in defaults {
+            vars.insert(name.to_string(), value.to_string());
+        }
+        RuleVars { vars }
+    }
+}
+
+impl RuleVars {
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.vars
+            .insert(name.trim_start_matches('$').to_string(), value.trim().to_string());
+    }
+
+    /// Replaces every `$NAME` in `spec`, recursively.
+    fn expand(&self, spec: &str) -> Result<String, String> {
+        let mut text = spec.to_string();
+        for _ in 0..16 {
+            let Some(start) = text.find('$') else {
+                return Ok(text);
+            };
+            let end = text[start + 1..]
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .map_or(text.len(), |i| start + 1 + i);
+            let name = &text[start + 1..end];
+            let value = self
+                .vars
+                .get(name)
+                .ok_or_else(|| format!("variable inconnue : ${name}"))?;
+            text = format!("{}{value}{}", &text[..start], &text[end..]);
+        }
+        Err(format!("variables trop imbriquées : {spec}"))
+    }
+}
+
+/// A rule of the file that was not loaded.
+#[derive(Debug, Clone)]
+pub struct SkippedRule {
+    /// First line of the rule in the file.
+    pub line: usize,
+    pub sid: Option<String>,
+    /// Keywords (or header parts) WireFish cannot evaluate.
+    pub unsupported: Vec<String>,
+    pub error: Option<String>,
+}
+
+impl SkippedRule {
+    pub fn describe(&self) -> String {
+        let sid = self.sid.as_deref().map(|s| format!(" (sid {s})")).unwrap_or_default();
+        let mut reasons = Vec::new();
+        if !self.unsupported.is_empty() {
+            reasons.push(format!("non pris en charge : {}", self.unsupported.join(", ")));
+        }
+        if let Some(e) = &self.error {
+            reasons.push(e.clone());
+        }
+        format!("ligne {}{sid} : {}", self.line, reasons.join(" ; "))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RuleImport {
+    pub rules: Vec<Rule>,
+    pub skipped: Vec<SkippedRule>,
+}
+
+/// Splits a rule header at the blanks outside brackets.
+fn header_tokens(header: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0usize;
+    for c in header.chars() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            c if c.is_whitespace() && depth == 0 => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// Splits the option block at the `;` outside quotes, keeping escapes.
+fn split_options(body: &str) -> Result<Vec<(String, Option<String>)>, String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                current.push(c);
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+            }
+            '"' => {
+                in_quotes = !in_quotes;
+                current.push(c);
+            }
+            ';' if !in_quotes => parts.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    if in_quotes {
+        return Err("guillemet non fermé dans les options".to_string());
+    }
+    parts.push(current);
+
+    Ok(parts
+        .iter()
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .map(|p| match p.split_once(':') {
+            Some((key, value)) => (key.trim().to_ascii_lowercase(), Some(value.trim().to_string())),
+            None => (p.to_ascii_lowercase(), None),
+        })
+        .collect())
+}
+
+/// `!"text"` → (true, `text`) with `\"`, `\;`, `\\` and `\:` unescaped.
+fn quoted(value: &str, unescape_all: bool) -> Result<(bool, String), String> {
+    let (negated, value) = match value.strip_prefix('!') {
+        Some(rest) => (true, rest.trim()),
+        None => (false, value),
+    };
+    let inner = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .ok_or_else(|| format!("valeur entre guillemets attendue : {value}"))?;
+
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some(n @ ('"' | ';')) => out.push(n),
+                Some(n @ ('\\' | ':')) if unescape_all => out.push(n),
+                Some(n) => {
+                    out.push('\\');
+                    out.push(n);
+                }
+                None => out.push('\\'),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    Ok((negated, out))
+}
+
+fn number<T: std::str::FromStr>(key: &str, value: Option<&str>) -> Result<T, String> {
+    value
+        .and_then(|v| v.trim().parse().ok())
+        .ok_or_else(|| format!("{key} attend un nombre"))
+}
+
+/// `pcre:"/pattern/flags"` with the flags WireFish can honour.
+fn parse_pcre(value: &str, unsupported: &mut Vec<String>) -> Result<RegexMatch, String> {
+    let (negated, text) = quoted(value, false)?;
+    let body = text
+        .strip_prefix('/')
+        .ok_or_else(|| format!("pcre doit commencer par « / » : {text}"))?;
+    let end = body
+        .rfind('/')
+        .ok_or_else(|| format!("pcre sans « / » final : {text}"))?;
+    let (pattern, flags) = (&body[..end], &body[end + 1..]);
+
+    let mut inline = String::new();
+    let mut nocase = false;
+    let mut relative = false;
+    let mut anchored = false;
+    for flag in flags.chars() {
+        match flag {
+            'i' => nocase = true,
+            's' | 'm' | 'x' => inline.push(flag),
+            // PCRE's ungreedy switch is `U` in the regex crate.
+            'G' => inline.push('U'),
+            'A' => anchored = true,
+            'R' => relative = true,
+            'B' | 'O' => {}
+            other => unsupported.push(format!("pcre/{other}")),
+        }
+    }
+    let mut pattern = if anchored { format!("^(?:{pattern})") } else { pattern.to_string() };
+    if !inline.is_empty() {
+        pattern = format!("(?{inline}){pattern}");
+    }
+    Ok(RegexMatch {
+        regex: rules::byte_regex(&pattern, nocase)?,
+        negated,
+        relative,
+    })
+}
+
+fn parse_threshold(value: &str) -> Result<Threshold, String> {
+    let mut kind = None;
+    let mut track = None;
+    let mut count = None;
+    let mut seconds = None;
+    for part in value.split(',') {
+        let mut words = part.split_whitespace();
+        let (Some(key), Some(v)) = (words.next(), words.next()) else {
+            return Err(format!("threshold mal formé : {value}"));
+        };
+        match key {
+            "type" => {
+                kind = Some(match v {
+                    "limit" => ThresholdKind::Limit,
+                    "threshold" => ThresholdKind::Threshold,
+                    "both" => ThresholdKind::Both,
+                    _ => return Err(format!("type de threshold inconnu : {v}")),
+                })
+            }
+            "track" => {
+                track = Some(match v {
+                    "by_src" => Track::BySrc,
+                    "by_dst" => Track::ByDst,
+                    _ => return Err(format!("threshold track {v} non pris en charge (by_src, by_dst)")),
+                })
+            }
+            "count" => count = v.parse::<u32>().ok().filter(|c| *c > 0),
+            "seconds" => seconds = v.parse::<u64>().ok().filter(|s| *s > 0),
+            _ => return Err(format!("paramètre de threshold inconnu : {key}")),
+        }
+    }
+    match (kind, track, count, seconds) {
+        (Some(kind), Some(track), Some(count), Some(seconds)) => Ok(Threshold {
+            kind,
+            track,
+            count,
+            seconds,
+        }),
+        _ => Err(format!("threshold incomplet (type, track, count, seconds) : {value}")),
+    }
+}
+
+/// Parses one rule. `Err` carries the sid when it could be read.
+fn parse_rule(text: &str, vars: &RuleVars, line: usize) -> Result<Rule, SkippedRule> {
+    let mut skipped = SkippedRule {
+        line,
+        sid: None,
+        unsupported: Vec::new(),
+        error: None,
+    };
+    let fail = |mut skipped: SkippedRule, e: String| {
+        skipped.error = Some(e);
+        skipped
+    };
+
+    let (Some(open), Some(close)) = (text.find('('), text.rfind(')')) else {
+        return Err(fail(skipped, "options « ( … ) » manquantes".to_string()));
+    };
+    if close < open {
+        return Err(fail(skipped, "parenthèses mal placées".to_string()));
+    }
+    let options = match split_options(&text[open + 1..close]) {
+        Ok(o) => o,
+        Err(e) => return Err(fail(skipped, e)),
+    };
+    skipped.sid = options
+        .iter()
+        .find(|(k, _)| k == "sid")
+        .and_then(|(_, v)| v.clone());
+
+    let header = header_tokens(&text[..open]);
+    let [action, proto, src, sport, dir, dst, dport] = header.as_slice() else {
+        return Err(fail(
+            skipped,
+            format!("en-tête attendu : action proto src port -> dst port ({} éléments)", header.len()),
+        ));
+    };
+
+    let mut rule = Rule::new("", "");
+    match action.as_str() {
+        // A passive sensor can only report: Suricata in IDS mode does the same.
+        "alert" | "drop" | "reject" | "rejectsrc" | "rejectdst" | "rejectboth" | "sdrop" => {}
+        other => skipped.unsupported.push(format!("action {other}")),
+    }
+
+    match proto.to_ascii_lowercase().as_str() {
+        "ip" | "any" => {}
+        p @ ("tcp" | "udp" | "icmp") => rule.protocol = Some(p.to_string()),
+        "tcp-pkt" => rule.protocol = Some("tcp".to_string()),
+        "tcp-stream" => {
+            rule.protocol = Some("tcp".to_string());
+            rule.stream = true;
+        }
+        p if APP_PROTOCOLS.contains(&p) => rule.protocol = Some(p.to_string()),
+        other => skipped.unsupported.push(format!("protocole {other}")),
+    }
+
+    let header_parts = (|| -> Result<(), String> {
+        rule.src = AddrMatch::parse(&vars.expand(src)?)?;
+        rule.src_ports = PortMatch::parse(&vars.expand(sport)?)?;
+        rule.dst = AddrMatch::parse(&vars.expand(dst)?)?;
+        rule.dst_ports = PortMatch::parse(&vars.expand(dport)?)?;
+        rule.bidirectional = match dir.as_str() {
+            "->" => false,
+            "<>" => true,
+            other => return Err(format!("direction d'en-tête inconnue : {other}")),
+        };
+        Ok(())
+    })();
+    if let Err(e) = header_parts {
+        return Err(fail(skipped, e));
+    }
+
+    let mut sid = None;
+    let mut priority = None;
+    // Content modifiers apply to the last content; a relative content
+    // right after a pcre would be anchored on the pcre match.
+    let mut after_pcre = false;
+    let mut relative_pcre = false;
+    for (key, value) in &options {
+        let value = value.as_deref();
+        let result: Result<(), String> = (|| {
+            match key.as_str() {
+                "msg" => rule.message = quoted(value.unwrap_or_default(), true)?.1,
+                "sid" => sid = Some(number::<u64>(key, value)?),
+                "rev" => rule.revision = Some(number(key, value)?),
+                "classtype" => rule.classtype = value.map(|v| v.trim().to_string()),
+                "priority" => priority = Some(number::<u8>(key, value)?),
+                "content" => {
+                    let (negated, text) = quoted(value.unwrap_or_default(), true)?;
+                    if relative_pcre {
+                        skipped.unsupported.push("content après pcre/R".to_string());
+                    }
+                    rule.contents.push(ContentMatch {
+                        pattern: rules::parse_pattern(&text)?,
+                        negated,
+                        ..Default::default()
+                    });
+                    after_pcre = false;
+                }
+                "nocase" | "offset" | "depth" | "distance" | "within" => {
+                    let content = rule
+                        .contents
+                        .last_mut()
+                        .ok_or_else(|| format!("{key} sans content"))?;
+                    match key.as_str() {
+                        "nocase" => content.nocase = true,
+                        "offset" => content.offset = Some(number(key, value)?),
+                        "depth" => content.depth = Some(number(key, value)?),
+                        "distance" => content.distance = Some(number(key, value)?),
+                        _ => content.within = Some(number(key, value)?),
+                    }
+                    if matches!(key.as_str(), "distance" | "within") && after_pcre {
+                        skipped.unsupported.push(format!("{key} relatif à une pcre"));
+                    }
+                    if (content.offset.is_some() || content.depth.is_some())
+                        && (content.distance.is_some() || content.within.is_some())
+                    {
+                        return Err("offset/depth et distance/within sur le même content".to_string());
+                    }
+                }
+                "pcre" => {
+                    let regex = parse_pcre(value.unwrap_or_default(), &mut skipped.unsupported)?;
+                    relative_pcre |= regex.relative;
+                    rule.regexes.push(regex);
+                    after_pcre = true;
+                }
+                "flow" => {
+                    for part in value.unwrap_or_default().split(',').map(str::trim) {
+                        match part {
+                            "to_server" | "from_client" => rule.direction = Some(Direction::ToServer),
+                            "to_client" | "from_server" => rule.direction = Some(Direction::ToClient),
+                            "established" => rule.established = Some(true),
+                            "not_established" => rule.established = Some(false),
+                            "stateless" | "no_stream" => {}
+                            "only_stream" => rule.stream = true,
+                            other => skipped.unsupported.push(format!("flow:{other}")),
+                        }
+                    }
+                }
+                "threshold" => rule.threshold = Some(parse_threshold(value.unwrap_or_default())?),
+                k if INFORMATIONAL.contains(&k) => {}
+                other => skipped.unsupported.push(other.to_string()),
+            }
+            Ok(())
+        })();
+        if let Err(e) = result {
+            return Err(fail(skipped, e));
+        }
+    }
+
+    let Some(sid) = sid else {
+        return Err(fail(skipped, "sid manquant".to_string()));
+    };
+    if !skipped.unsupported.is_empty() {
+        return Err(skipped);
+    }
+    if let Err(e) = rule.check_stream() {
+        return Err(fail(skipped, e));
+    }
+
+    rule.id = sid.to_string();
+    if rule.message.is_empty() {
+        rule.message = format!("sid {sid}");
+    }
+    let class_priority = rule
+        .classtype
+        .as_deref()
+        .and_then(|c| CLASSTYPES.iter().find(|(name, _)| *name == c))
+        .map(|(_, p)| *p);
+    rule.severity = priority_severity(priority.or(class_priority).unwrap_or(3));
+    Ok(rule)
+}
+
+/// Parses a Snort/Suricata rules file. Rules using keywords WireFish
+/// cannot evaluate are left out and listed in `RuleImport::skipped`.
+pub fn parse_rules(text: &str, vars: &RuleVars) -> RuleImport {
+    let mut import = RuleImport::default();
+    let mut pending = String::new();
+    let mut start = 0;
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if pending.is_empty() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            start = index + 1;
+        }
+        // A trailing backslash continues the rule on the next line.
+        if let Some(head) = line.strip_suffix('\\') {
+            pending.push_str(head);
+            pending.push(' ');
+            continue;
+        }
+        pending.push_str(line);
+
+        match parse_rule(&pending, vars, start) {
+            Ok(rule) => import.rules.push(rule),
+            Err(skipped) => import.skipped.push(skipped),
+        }
+        pending.clear();
+    }
+    if !pending.is_empty() {
+        match parse_rule(&pending, vars, start) {
+            Ok(rule) => import.rules.push(rule),
+            Err(skipped) => import.skipped.push(skipped),
+        }
+    }
+    import
+}
+
+pub fn load_rules(path: &Path, vars: &RuleVars) -> Result<RuleImport, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    Ok(parse_rules(&text, vars))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> std::net::IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn header_and_options() {
+        let text = concat!(
+            r#"alert tcp $HOME_NET any -> $EXTERNAL_NET $HTTP_PORTS (msg:"Agent \"x\"\; beacon"; "#,
+            r#"flow:established,to_server; content:"GET /|20|"; nocase; depth:8; pcre:"/id=[0-9]+/Ri"; "#,
+            r#"classtype:trojan-activity; reference:url,example.org; sid:2000001; rev:3;)"#,
+        );
+        let rule = parse_rule(text, &RuleVars::default(), 1).unwrap();
+        assert_eq!(rule.id, "2000001");
+        assert_eq!(rule.revision, Some(3));
+        assert_eq!(rule.message, "Agent \"x\"; beacon");
+        assert_eq!(rule.severity, Severity::High);
+        assert_eq!(rule.protocol.as_deref(), Some("tcp"));
+        assert!(rule.src.matches(Some(&ip("10.1.2.3"))));
+        assert!(!rule.src.matches(Some(&ip("8.8.8.8"))));
+        assert!(rule.dst.matches(Some(&ip("8.8.8.8"))));
+        assert!(!rule.dst.matches(Some(&ip("192.168.1.1"))));
+        assert!(rule.src_ports.is_any() && !rule.dst_ports.is_any());
+        assert!(!rule.bidirectional);
+        assert_eq!(rule.direction, Some(Direction::ToServer));
+        assert_eq!(rule.established, Some(true));
+
+        let [content] = rule.contents.as_slice() else {
+            panic!("un seul content attendu");
+        };
+        assert_eq!(content.pattern, b"GET / ");
+        assert!(content.nocase);
+        assert_eq!(content.depth, Some(8));
+        let [pcre] = rule.regexes.as_slice() else {
+            panic!("une seule pcre attendue");
+        };
+        assert!(pcre.relative && pcre.regex.is_match(b"ID=42"));
+    }
+
+    #[test]
+    fn priority_overrides_the_classtype() {
+        let vars = RuleVars::default();
+        let rule = parse_rule("alert udp any any <> any 53 (classtype:trojan-activity; priority:3; sid:1;)", &vars, 1);
+        let rule = rule.unwrap();
+        assert_eq!(rule.severity, Severity::Low);
+        assert!(rule.bidirectional);
+        assert_eq!(rule.message, "sid 1");
+    }
+
+    #[test]
+    fn unsupported_keywords_are_reported() {
+        let text = "alert smb any any -> any any (msg:\"x\"; flowbits:set,a; byte_test:4,>,1,0; sid:7;)";
+        let skipped = parse_rule(text, &RuleVars::default(), 12).unwrap_err();
+        assert_eq!(skipped.sid.as_deref(), Some("7"));
+        assert_eq!(skipped.unsupported, ["protocole smb", "flowbits", "byte_test"]);
+        assert!(skipped.error.is_none());
+        assert_eq!(
+            skipped.describe(),
+            "ligne 12 (sid 7) : non pris en charge : protocole smb, flowbits, byte_test"
+        );
+    }
+
+    #[test]
+    fn malformed_rules_are_skipped_with_a_reason() {
+        let vars = RuleVars::default();
+        let cases = [
+            ("alert tcp any any -> any any (msg:\"x\";)", "sid manquant"),
+            ("alert tcp any any any any (sid:1;)", "en-tête attendu"),
+            ("alert tcp $NOPE any -> any any (sid:1;)", "variable inconnue : $NOPE"),
+            ("alert tcp any any -> any any (msg:\"x; sid:1;)", "guillemet non fermé"),
+            ("alert tcp any any -> any any (nocase; sid:1;)", "nocase sans content"),
+        ];
+        for (text, reason) in cases {
+            let skipped = parse_rule(text, &vars, 1).unwrap_err();
+            assert!(skipped.error.as_deref().unwrap_or_default().contains(reason), "{text}: {skipped:?}");
+        }
+    }
+
+    #[test]
+    fn threshold_options() {
+        let threshold = parse_threshold("type both, track by_dst, count 5, seconds 60").unwrap();
+        assert_eq!(
+            threshold,
+            Threshold {
+                kind: ThresholdKind::Both,
+                track: Track::ByDst,
+                count: 5,
+                seconds: 60,
+            }
+        );
+        assert!(parse_threshold("type limit, track by_rule, count 1, seconds 60").is_err());
+        assert!(parse_threshold("type limit, track by_src, count 0, seconds 60").is_err());
+        assert!(parse_threshold("type limit, track by_src").is_err());
+
+        let text = "alert tcp any any -> any any (threshold: type limit, track by_src, count 1, seconds 30; sid:3;)";
+        let rule = parse_rule(text, &RuleVars::default(), 1).unwrap();
+        assert_eq!(rule.threshold.map(|t| (t.kind, t.count, t.seconds)), Some((ThresholdKind::Limit, 1, 30)));
+    }
+
+    #[test]
+    fn rules_file() {
+        let text = "# commentaire\n\
+                    \n\
+                    alert tcp any any -> any 23 (msg:\"telnet\"; \\\n\
+                    sid:10;)\n\
+                    alert tcp any any -> any any (dsize:>100; sid:11;)\n\
+                    alert icmp $HOME_NET any -> any any (sid:12;)\n";
+        let mut vars = RuleVars::default();
+        vars.set("$HOME_NET", "192.0.2.0/24");
+        let import = parse_rules(text, &vars);
+        let ids: Vec<&str> = import.rules.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, ["10", "12"]);
+        assert_eq!(import.rules[0].message, "telnet");
+        assert!(import.rules[1].src.matches(Some(&ip("192.0.2.5"))));
+        assert!(!import.rules[1].src.matches(Some(&ip("10.0.0.1"))));
+        let [skipped] = import.skipped.as_slice() else {
+            panic!("une seule règle écartée attendue");
+        };
+        assert_eq!((skipped.line, skipped.unsupported.as_slice()), (5, ["dsize".to_string()].as_slice()));
+    }
+}
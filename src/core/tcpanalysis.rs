@@ -0,0 +1,344 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::core::models::{Packet, TcpAnalysis, TcpExpert, TcpPerformance, TransportProtocol};
+use crate::core::parser;
+use crate::core::stream::{TCP_ACK, TCP_FIN, TCP_RST, TCP_SYN};
+
+const MAX_CONNECTIONS: usize = 4096;
+const IDLE_TIMEOUT_MS: u128 = 5 * 60 * 1000;
+/// Segments remembered per direction for ACK RTT samples.
+const MAX_UNACKED: usize = 256;
+const MAX_HOLES: usize = 32;
+/// A segment filling a hole this soon arrived out of order rather than
+/// being resent (raised to the handshake RTT when known).
+const OUT_OF_ORDER_MS: u128 = 3;
+
+fn seq_lt(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) < 0
+}
+
+fn seq_gt(a: u32, b: u32) -> bool {
+    seq_lt(b, a)
+}
+
+/// What one endpoint sent so far.
+#[derive(Default)]
+struct Side {
+    /// `next_seq` is valid.
+    seen: bool,
+    isn: Option<u32>,
+    next_seq: u32,
+    last_ack: Option<u32>,
+    window: Option<u32>,
+    window_scale: Option<u8>,
+    dup_acks: u32,
+    /// (end sequence, time, resent) of segments not acknowledged yet.
+    unacked: VecDeque<(u32, u128, bool)>,
+    /// Sequence ranges skipped in this direction, with when they appeared.
+    holes: Vec<(u32, u32, u128)>,
+}
+
+#[derive(Default)]
+struct Connection {
+    /// Indexed by the sender: 0 for the lower endpoint.
+    sides: [Side; 2],
+    syn_ts: Option<(usize, u128)>,
+    syn_ack_seen: bool,
+    handshake_rtt_ms: Option<u64>,
+    last_seen: u128,
+}
+
+impl Connection {
+    /// Both SYNs were seen, so window scaling is known to be on or off.
+    fn scaling_known(&self) -> bool {
+        self.syn_ts.is_some() && self.syn_ack_seen
+    }
+
+    fn scale(&self, sender: usize) -> u8 {
+        match (self.sides[0].window_scale, self.sides[1].window_scale) {
+            (Some(_), Some(_)) if self.scaling_known() => self.sides[sender].window_scale.unwrap_or(0),
+            _ => 0,
+        }
+    }
+}
+
+type Endpoint = (String, u16);
+
+/// Computes RTTs and Wireshark-like expert flags (retransmissions,
+/// duplicate ACKs, zero windows, out-of-order and lost segments) for every
+/// TCP segment, and keeps capture-wide counters.
+#[derive(Default)]
+pub struct TcpAnalyzer {
+    conns: HashMap<(Endpoint, Endpoint), Connection>,
+    stats: TcpPerformance,
+}
+
+impl TcpAnalyzer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stats(&self) -> &TcpPerformance {
+        &self.stats
+    }
+
+    /// Fills `packet.tcp_analysis` for TCP segments.
+    pub fn observe(&mut self, packet: &mut Packet) {
+        let (Some(ip), Some(TransportProtocol::Tcp(tcp))) = (&packet.ip, &packet.transport) else {
+            return;
+        };
+        let src = (ip.src_ip.clone(), tcp.src_port);
+        let dst = (ip.dst_ip.clone(), tcp.dst_port);
+        let (key, sender) = if src <= dst { ((src, dst), 0) } else { ((dst, src), 1) };
+        let receiver = 1 - sender;
+        let ts = packet.timestamp;
+        let (seq, ack, flags) = (tcp.seq, tcp.ack, tcp.flags);
+        let (syn, fin, rst, has_ack) = (
+            flags & TCP_SYN != 0,
+            flags & TCP_FIN != 0,
+            flags & TCP_RST != 0,
+            flags & TCP_ACK != 0,
+        );
+        let seglen = parser::transport_payload(&packet.payload).len() as u32;
+        let seq_len = seglen + syn as u32 + fin as u32;
+
+        if !self.conns.contains_key(&key) && self.conns.len() >= MAX_CONNECTIONS {
+            self.conns.retain(|_, c| ts.saturating_sub(c.last_seen) < IDLE_TIMEOUT_MS);
+            if self.conns.len() >= MAX_CONNECTIONS {
+                self.conns.clear();
+            }
+        }
+        let conn = self.conns.entry(key).or_default();
+        // A SYN with a new ISN reuses the endpoints for a new connection.
+        if syn && !has_ack && conn.sides[sender].isn.is_some_and(|isn| isn != seq) {
+            *conn = Connection::default();
+        }
+        conn.last_seen = ts;
+
+        if syn {
+            let side = &mut conn.sides[sender];
+            side.isn = Some(seq);
+            side.window_scale = tcp.window_scale;
+            if has_ack {
+                conn.syn_ack_seen = true;
+            } else if conn.syn_ts.is_none() {
+                conn.syn_ts = Some((sender, ts));
+            }
+        }
+        // The window of a SYN is never scaled.
+        let window = if syn {
+            tcp.window as u32
+        } else {
+            (tcp.window as u32) << conn.scale(sender)
+        };
+
+        let mut analysis = TcpAnalysis {
+            window,
+            ..Default::default()
+        };
+        let out_of_order_ms = conn.handshake_rtt_ms.map_or(OUT_OF_ORDER_MS, |rtt| (rtt as u128).max(OUT_OF_ORDER_MS));
+        let receiver_window = conn.sides[receiver].window;
+        let receiver_ack = conn.sides[receiver].last_ack;
+        let receiver_dup_acks = conn.sides[receiver].dup_acks;
+        let scaling_known = conn.scaling_known();
+        let s = &mut conn.sides[sender];
+        let mut probe_or_keepalive = false;
+
+        if s.seen && !syn && !fin && !rst {
+            if seglen == 1 && seq == s.next_seq && receiver_window == Some(0) {
+                analysis.flags.push(TcpExpert::ZeroWindowProbe);
+                probe_or_keepalive = true;
+            } else if seglen <= 1 && seq == s.next_seq.wrapping_sub(1) {
+                analysis.flags.push(TcpExpert::KeepAlive);
+                probe_or_keepalive = true;
+            }
+        }
+        if window == 0 && !syn && !fin && !rst {
+            analysis.flags.push(TcpExpert::ZeroWindow);
+        }
+        if s.seen && !rst && seq_gt(seq, s.next_seq) {
+            analysis.flags.push(TcpExpert::LostSegment);
+            if s.holes.len() >= MAX_HOLES {
+                s.holes.remove(0);
+            }
+            s.holes.push((s.next_seq, seq, ts));
+        }
+        if seglen > 0 && scaling_known {
+            if let (Some(rwin), Some(rack)) = (receiver_window, receiver_ack) {
+                if seq.wrapping_add(seglen) == rack.wrapping_add(rwin) {
+                    analysis.flags.push(TcpExpert::WindowFull);
+                }
+            }
+        }
+        if has_ack
+            && seglen == 0
+            && !syn
+            && !fin
+            && !rst
+            && s.last_ack == Some(ack)
+            && s.window == Some(window)
+            && (!s.seen || seq == s.next_seq)
+        {
+            s.dup_acks += 1;
+            analysis.flags.push(TcpExpert::DuplicateAck);
+        }
+
+        let end = seq.wrapping_add(seq_len);
+        let mut resent = false;
+        if s.seen && seq_len > 0 && !probe_or_keepalive && seq_lt(seq, s.next_seq) {
+            resent = true;
+            let fast = receiver_dup_acks >= 2 && receiver_ack == Some(seq);
+            let hole = s
+                .holes
+                .iter()
+                .position(|&(start, stop, _)| !seq_lt(seq, start) && seq_lt(seq, stop));
+            let flag = if let Some(i) = hole {
+                let (start, stop, created) = s.holes.remove(i);
+                // Keep what the segment did not fill.
+                if seq_gt(seq, start) {
+                    s.holes.push((start, seq, created));
+                }
+                if seq_lt(end, stop) {
+                    s.holes.push((end, stop, created));
+                }
+                if fast {
+                    TcpExpert::FastRetransmission
+                } else if ts.saturating_sub(created) <= out_of_order_ms {
+                    resent = false;
+                    TcpExpert::OutOfOrder
+                } else {
+                    TcpExpert::Retransmission
+                }
+            } else if fast {
+                TcpExpert::FastRetransmission
+            } else if receiver_ack.is_some_and(|a| !seq_lt(a, end)) {
+                TcpExpert::SpuriousRetransmission
+            } else {
+                TcpExpert::Retransmission
+            };
+            analysis.flags.push(flag);
+            // Karn: resent segments give no RTT sample.
+            if resent {
+                for entry in s.unacked.iter_mut().filter(|(e, _, _)| seq_lt(seq, *e)) {
+                    entry.2 = true;
+                }
+            }
+        }
+
+        if !s.seen || seq_gt(end, s.next_seq) {
+            if seq_len > 0 {
+                if s.unacked.len() >= MAX_UNACKED {
+                    s.unacked.pop_front();
+                }
+                s.unacked.push_back((end, ts, resent));
+            }
+            s.next_seq = end;
+            s.seen = true;
+        }
+
+        if has_ack {
+            let advanced = s.last_ack.is_none_or(|last| seq_gt(ack, last));
+            if advanced {
+                s.last_ack = Some(ack);
+                s.dup_acks = 0;
+            }
+            s.window = Some(window);
+            let r = &mut conn.sides[receiver];
+            if r.seen && seq_gt(ack, r.next_seq) && !rst {
+                analysis.flags.push(TcpExpert::AckedUnseenSegment);
+            }
+            if advanced {
+                let mut sample = None;
+                while let Some(&(seg_end, seg_ts, seg_resent)) = r.unacked.front() {
+                    if seq_gt(seg_end, ack) {
+                        break;
+                    }
+                    r.unacked.pop_front();
+                    sample = (!seg_resent).then(|| ts.saturating_sub(seg_ts) as u64);
+                }
+                analysis.ack_rtt_ms = sample;
+            }
+            // The client's first ACK after the SYN/ACK completes the handshake.
+            if let Some((client, syn_ts)) = conn.syn_ts {
+                if client == sender && !syn && conn.syn_ack_seen && conn.handshake_rtt_ms.is_none() {
+                    let rtt = ts.saturating_sub(syn_ts) as u64;
+                    conn.handshake_rtt_ms = Some(rtt);
+                    analysis.handshake_rtt_ms = Some(rtt);
+                }
+            }
+        }
+
+        self.stats.record(&analysis);
+        packet.tcp_analysis = Some(analysis);
+    }
+}
+
+pub fn expert_label(flag: TcpExpert) -> &'static str {
+    match flag {
+        TcpExpert::Retransmission => "Retransmission",
+        TcpExpert::FastRetransmission => "Retransmission rapide",
+        TcpExpert::SpuriousRetransmission => "Retransmission inutile",
+        TcpExpert::OutOfOrder => "Hors séquence",
+        TcpExpert::LostSegment => "Segment précédent perdu",
+        TcpExpert::AckedUnseenSegment => "ACK d'un segment non vu",
+        TcpExpert::DuplicateAck => "ACK dupliqué",
+        TcpExpert::ZeroWindow => "Fenêtre nulle",
+        TcpExpert::ZeroWindowProbe => "Sonde de fenêtre nulle",
+        TcpExpert::WindowFull => "Fenêtre pleine",
+        TcpExpert::KeepAlive => "Keep-alive",
+    }
+}
+
+/// Filter value of a flag (`tcp.analysis==retransmission`).
+pub fn expert_code(flag: TcpExpert) -> &'static str {
+    match flag {
+        TcpExpert::Retransmission => "retransmission",
+        TcpExpert::FastRetransmission => "fast_retransmission",
+        TcpExpert::SpuriousRetransmission => "spurious_retransmission",
+        TcpExpert::OutOfOrder => "out_of_order",
+        TcpExpert::LostSegment => "lost_segment",
+        TcpExpert::AckedUnseenSegment => "acked_unseen_segment",
+        TcpExpert::DuplicateAck => "duplicate_ack",
+        TcpExpert::ZeroWindow => "zero_window",
+        TcpExpert::ZeroWindowProbe => "zero_window_probe",
+        TcpExpert::WindowFull => "window_full",
+        TcpExpert::KeepAlive => "keep_alive",
+    }
+}
+
+/// Short description used in the live table, e.g. `[ACK dupliqué]`.
+pub fn summary(analysis: &TcpAnalysis) -> Option<String> {
+    let labels: Vec<&str> = analysis.flags.iter().map(|&f| expert_label(f)).collect();
+    (!labels.is_empty()).then(|| format!("[{}]", labels.join(", ")))
+}
+
+/// One line of RTT and problem counters, e.g. for a conversation.
+pub fn perf_line(perf: &TcpPerformance) -> Option<String> {
+    let mut parts = Vec::new();
+    if let Some(rtt) = perf.handshake_rtt_ms {
+        parts.push(format!("RTT poignée de main {rtt} ms"));
+    }
+    if let Some(avg) = perf.avg_rtt_ms() {
+        parts.push(format!(
+            "RTT ACK {avg:.1} ms (min {} / max {})",
+            perf.rtt_min_ms.unwrap_or(0),
+            perf.rtt_max_ms.unwrap_or(0)
+        ));
+    }
+    let counters = [
+        (perf.retransmissions, "retransmissions"),
+        (perf.fast_retransmissions, "retransmissions rapides"),
+        (perf.spurious_retransmissions, "retransmissions inutiles"),
+        (perf.duplicate_acks, "ACK dupliqués"),
+        (perf.out_of_order, "hors séquence"),
+        (perf.lost_segments, "segments perdus"),
+        (perf.zero_windows, "fenêtres nulles"),
+        (perf.window_full, "fenêtres pleines"),
+    ];
+    for (n, label) in counters {
+        if n > 0 {
+            parts.push(format!("{n} {label}"));
+        }
+    }
+    (!parts.is_empty()).then(|| parts.join(", "))
+}
@@ -0,0 +1,492 @@
+use crate::core::fingerprint;
+use crate::core::models::{ClientHello, ServerHello, TlsInfo};
+
+const CONTENT_HANDSHAKE: u8 = 0x16;
+
+const HS_CLIENT_HELLO: u8 = 1;
+const HS_SERVER_HELLO: u8 = 2;
+
+const EXT_SERVER_NAME: u16 = 0;
+const EXT_SUPPORTED_GROUPS: u16 = 10;
+const EXT_EC_POINT_FORMATS: u16 = 11;
+const EXT_SIGNATURE_ALGORITHMS: u16 = 13;
+const EXT_ALPN: u16 = 16;
+const EXT_SUPPORTED_VERSIONS: u16 = 43;
+const EXT_KEY_SHARE: u16 = 51;
+
+/// Minimal big-endian cursor over a handshake buffer.
+/// Every read returns `None` once the buffer is exhausted.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data, pos: 0 }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        let b = *self.data.get(self.pos)?;
+        self.pos += 1;
+        Some(b)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let b = self.bytes(2)?;
+        Some(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u24(&mut self) -> Option<usize> {
+        let b = self.bytes(3)?;
+        Some(((b[0] as usize) << 16) | ((b[1] as usize) << 8) | b[2] as usize)
+    }
+
+    fn bytes(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.remaining() < n {
+            return None;
+        }
+        let out = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Some(out)
+    }
+
+    fn vec8(&mut self) -> Option<&'a [u8]> {
+        let n = self.u8()? as usize;
+        self.bytes(n)
+    }
+
+    fn vec16(&mut self) -> Option<&'a [u8]> {
+        let n = self.u16()? as usize;
+        self.bytes(n)
+    }
+}
+
+fn u16_list(data: &[u8]) -> Vec<u16> {
+    data.chunks_exact(2)
+        .map(|c| u16::from_be_bytes([c[0], c[1]]))
+        .collect()
+}
+
+/// GREASE values (RFC 8701) are random placeholders and carry no meaning.
+pub fn is_grease(v: u16) -> bool {
+    (v & 0x0f0f) == 0x0a0a && (v >> 8) == (v & 0xff)
+}
+
+/// Decodes the TLS handshake records found at the start of a TCP payload.
+/// Only complete ClientHello and ServerHello messages are extracted; other
+/// handshake messages are skipped. Returns `None` when no hello message
+/// could be decoded.
+pub fn parse(payload: &[u8]) -> Option<TlsInfo> {
+    if payload.len() < 5 || payload[0] != CONTENT_HANDSHAKE || payload[1] != 0x03 {
+        return None;
+    }
+
+    let mut info = TlsInfo {
+        record_version: u16::from_be_bytes([payload[1], payload[2]]),
+        ..Default::default()
+    };
+
+    // Handshake messages may span several records: concatenate the
+    // fragments of consecutive handshake records before walking them.
+    let mut handshake = Vec::new();
+    let mut off = 0usize;
+    while off + 5 <= payload.len() && payload[off] == CONTENT_HANDSHAKE {
+        let len = u16::from_be_bytes([payload[off + 3], payload[off + 4]]) as usize;
+        let end = (off + 5 + len).min(payload.len());
+        handshake.extend_from_slice(&payload[off + 5..end]);
+        off += 5 + len;
+    }
+
+    let mut r = Reader::new(&handshake);
+    while r.remaining() >= 4 {
+        let msg_type = r.u8()?;
+        let len = r.u24()?;
+        // A hello cut by the end of the segment is left to the stream
+        // (`handshake::HelloReassembler`): decoding part of it would give
+        // wrong fingerprints.
+        let Some(body) = r.bytes(len) else {
+            break;
+        };
+
+        match msg_type {
+            HS_CLIENT_HELLO => info.client_hello = parse_client_hello(body),
+            HS_SERVER_HELLO => info.server_hello = parse_server_hello(body),
+            _ => {}
+        }
+    }
+
+    if info.client_hello.is_none() && info.server_hello.is_none() {
+        return None;
+    }
+    info.fingerprints = fingerprint::compute(&info);
+    Some(info)
+}
+
+fn parse_client_hello(body: &[u8]) -> Option<ClientHello> {
+    let mut r = Reader::new(body);
+    let mut ch = ClientHello {
+        legacy_version: r.u16()?,
+        ..Default::default()
+    };
+    r.bytes(32)?; // random
+    r.vec8()?; // session id
+    ch.cipher_suites = u16_list(r.vec16()?);
+    r.vec8()?; // compression methods
+
+    let Some(exts) = r.vec16() else {
+        return Some(ch);
+    };
+
+    let mut er = Reader::new(exts);
+    while er.remaining() >= 4 {
+        let ext_type = er.u16()?;
+        let data = er.vec16()?;
+        ch.extensions.push(ext_type);
+
+        let mut d = Reader::new(data);
+        match ext_type {
+            EXT_SERVER_NAME => {
+                let mut list = Reader::new(d.vec16().unwrap_or_default());
+                while list.remaining() >= 3 {
+                    let name_type = list.u8()?;
+                    let name = list.vec16()?;
+                    if name_type == 0 {
+                        ch.sni = Some(String::from_utf8_lossy(name).into_owned());
+                    }
+                }
+            }
+            EXT_ALPN => {
+                let mut list = Reader::new(d.vec16().unwrap_or_default());
+                while let Some(proto) = list.vec8() {
+                    ch.alpn.push(String::from_utf8_lossy(proto).into_owned());
+                }
+            }
+            EXT_SUPPORTED_GROUPS => {
+                ch.supported_groups = u16_list(d.vec16().unwrap_or_default());
+            }
+            EXT_EC_POINT_FORMATS => {
+                ch.ec_point_formats = d.vec8().unwrap_or_default().to_vec();
+            }
+            EXT_SIGNATURE_ALGORITHMS => {
+                ch.signature_algorithms = u16_list(d.vec16().unwrap_or_default());
+            }
+            EXT_SUPPORTED_VERSIONS => {
+                ch.supported_versions = u16_list(d.vec8().unwrap_or_default());
+            }
+            EXT_KEY_SHARE => {
+                let mut list = Reader::new(d.vec16().unwrap_or_default());
+                while list.remaining() >= 4 {
+                    let group = list.u16()?;
+                    list.vec16()?;
+                    ch.key_share_groups.push(group);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Some(ch)
+}
+
+fn parse_server_hello(body: &[u8]) -> Option<ServerHello> {
+    let mut r = Reader::new(body);
+    let mut sh = ServerHello {
+        legacy_version: r.u16()?,
+        ..Default::default()
+    };
+    r.bytes(32)?; // random
+    r.vec8()?; // session id
+    sh.cipher_suite = r.u16()?;
+    r.u8()?; // compression method
+
+    let Some(exts) = r.vec16() else {
+        return Some(sh);
+    };
+
+    let mut er = Reader::new(exts);
+    while er.remaining() >= 4 {
+        let ext_type = er.u16()?;
+        let data = er.vec16()?;
+        sh.extensions.push(ext_type);
+
+        let mut d = Reader::new(data);
+        match ext_type {
+            EXT_ALPN => {
+                let mut list = Reader::new(d.vec16().unwrap_or_default());
+                sh.alpn = list
+                    .vec8()
+                    .map(|p| String::from_utf8_lossy(p).into_owned());
+            }
+            EXT_SUPPORTED_VERSIONS => sh.selected_version = d.u16(),
+            EXT_KEY_SHARE => sh.key_share_group = d.u16(),
+            _ => {}
+        }
+    }
+
+    Some(sh)
+}
+
+/// Best known protocol version: the negotiated one for a ServerHello,
+/// the highest offered one for a ClientHello.
+pub fn effective_version(info: &TlsInfo) -> u16 {
+    if let Some(sh) = &info.server_hello {
+        return sh.selected_version.unwrap_or(sh.legacy_version);
+    }
+    if let Some(ch) = &info.client_hello {
+        return ch
+            .supported_versions
+            .iter()
+            .copied()
+            .filter(|v| !is_grease(*v))
+            .max()
+            .unwrap_or(ch.legacy_version);
+    }
+    info.record_version
+}
+
+pub fn version_label(version: u16) -> &'static str {
+    match version {
+        0x0300 => "SSLv3",
+        0x0301 => "TLSv1.0",
+        0x0302 => "TLSv1.1",
+        0x0303 => "TLSv1.2",
+        0x0304 => "TLSv1.3",
+        _ => "TLS",
+    }
+}
+
+/// Short description used in the live table next to the protocol label.
+pub fn summary(info: &TlsInfo) -> String {
+    if let Some(ch) = &info.client_hello {
+        return match &ch.sni {
+            Some(sni) => format!("CH {sni}"),
+            None => "CH".to_string(),
+        };
+    }
+    if let Some(sh) = &info.server_hello {
+        return match &sh.alpn {
+            Some(alpn) => format!("SH {alpn}"),
+            None => format!("SH {}", cipher_suite_name(sh.cipher_suite)),
+        };
+    }
+    if let Some(leaf) = info.certificates.first() {
+        return format!("Cert {}", leaf.subject_cn.as_deref().unwrap_or(&leaf.subject));
+    }
+    String::new()
+}
+
+pub fn cipher_suite_name(id: u16) -> String {
+    let name = match id {
+        0x1301 => "TLS_AES_128_GCM_SHA256",
+        0x1302 => "TLS_AES_256_GCM_SHA384",
+        0x1303 => "TLS_CHACHA20_POLY1305_SHA256",
+        0x1304 => "TLS_AES_128_CCM_SHA256",
+        0x1305 => "TLS_AES_128_CCM_8_SHA256",
+        0xc02b => "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256",
+        0xc02c => "TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384",
+        0xc02f => "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256",
+        0xc030 => "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384",
+        0xcca8 => "TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256",
+        0xcca9 => "TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256",
+        0xc009 => "TLS_ECDHE_ECDSA_WITH_AES_128_CBC_SHA",
+        0xc00a => "TLS_ECDHE_ECDSA_WITH_AES_256_CBC_SHA",
+        0xc013 => "TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA",
+        0xc014 => "TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA",
+        0xc023 => "TLS_ECDHE_ECDSA_WITH_AES_128_CBC_SHA256",
+        0xc024 => "TLS_ECDHE_ECDSA_WITH_AES_256_CBC_SHA384",
+        0xc027 => "TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA256",
+        0xc028 => "TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA384",
+        0x009c => "TLS_RSA_WITH_AES_128_GCM_SHA256",
+        0x009d => "TLS_RSA_WITH_AES_256_GCM_SHA384",
+        0x009e => "TLS_DHE_RSA_WITH_AES_128_GCM_SHA256",
+        0x009f => "TLS_DHE_RSA_WITH_AES_256_GCM_SHA384",
+        0x002f => "TLS_RSA_WITH_AES_128_CBC_SHA",
+        0x0035 => "TLS_RSA_WITH_AES_256_CBC_SHA",
+        0x003c => "TLS_RSA_WITH_AES_128_CBC_SHA256",
+        0x003d => "TLS_RSA_WITH_AES_256_CBC_SHA256",
+        0x000a => "TLS_RSA_WITH_3DES_EDE_CBC_SHA",
+        0x0005 => "TLS_RSA_WITH_RC4_128_SHA",
+        0x00ff => "TLS_EMPTY_RENEGOTIATION_INFO_SCSV",
+        0x5600 => "TLS_FALLBACK_SCSV",
+        _ if is_grease(id) => "GREASE",
+        _ => return format!("0x{id:04x}"),
+    };
+    name.to_string()
+}
+
+pub fn group_name(id: u16) -> String {
+    let name = match id {
+        0x0017 => "secp256r1",
+        0x0018 => "secp384r1",
+        0x0019 => "secp521r1",
+        0x001d => "x25519",
+        0x001e => "x448",
+        0x0100 => "ffdhe2048",
+        0x0101 => "ffdhe3072",
+        0x0102 => "ffdhe4096",
+        0x11ec => "X25519MLKEM768",
+        0x6399 => "X25519Kyber768Draft00",
+        _ if is_grease(id) => "GREASE",
+        _ => return format!("0x{id:04x}"),
+    };
+    name.to_string()
+}
+
+pub fn signature_algorithm_name(id: u16) -> String {
+    let name = match id {
+        0x0201 => "rsa_pkcs1_sha1",
+        0x0203 => "ecdsa_sha1",
+        0x0401 => "rsa_pkcs1_sha256",
+        0x0501 => "rsa_pkcs1_sha384",
+        0x0601 => "rsa_pkcs1_sha512",
+        0x0403 => "ecdsa_secp256r1_sha256",
+        0x0503 => "ecdsa_secp384r1_sha384",
+        0x0603 => "ecdsa_secp521r1_sha512",
+        0x0804 => "rsa_pss_rsae_sha256",
+        0x0805 => "rsa_pss_rsae_sha384",
+        0x0806 => "rsa_pss_rsae_sha512",
+        0x0807 => "ed25519",
+        0x0808 => "ed448",
+        0x0809 => "rsa_pss_pss_sha256",
+        0x080a => "rsa_pss_pss_sha384",
+        0x080b => "rsa_pss_pss_sha512",
+        _ if is_grease(id) => "GREASE",
+        _ => return format!("0x{id:04x}"),
+    };
+    name.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vec8(data: &[u8]) -> Vec<u8> {
+        let mut out = vec![data.len() as u8];
+        out.extend_from_slice(data);
+        out
+    }
+
+    fn vec16(data: &[u8]) -> Vec<u8> {
+        let mut out = (data.len() as u16).to_be_bytes().to_vec();
+        out.extend_from_slice(data);
+        out
+    }
+
+    fn ext(ext_type: u16, data: &[u8]) -> Vec<u8> {
+        let mut out = ext_type.to_be_bytes().to_vec();
+        out.extend(vec16(data));
+        out
+    }
+
+    fn handshake(msg_type: u8, body: &[u8]) -> Vec<u8> {
+        let mut out = vec![msg_type];
+        out.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+        out.extend_from_slice(body);
+        out
+    }
+
+    fn record(fragment: &[u8]) -> Vec<u8> {
+        let mut out = vec![CONTENT_HANDSHAKE, 0x03, 0x01];
+        out.extend(vec16(fragment));
+        out
+    }
+
+    fn client_hello() -> Vec<u8> {
+        let mut sni = vec![0];
+        sni.extend(vec16(b"example.org"));
+        let mut alpn = vec8(b"h2");
+        alpn.extend(vec8(b"http/1.1"));
+        let mut key_share = 0x001du16.to_be_bytes().to_vec();
+        key_share.extend(vec16(&[0xaa; 32]));
+
+        let mut exts = ext(0x0a0a, &[]);
+        exts.extend(ext(EXT_SERVER_NAME, &vec16(&sni)));
+        exts.extend(ext(EXT_SUPPORTED_GROUPS, &vec16(&[0x0a, 0x0a, 0x00, 0x1d, 0x00, 0x17])));
+        exts.extend(ext(EXT_EC_POINT_FORMATS, &vec8(&[0])));
+        exts.extend(ext(EXT_SIGNATURE_ALGORITHMS, &vec16(&[0x04, 0x03, 0x08, 0x04])));
+        exts.extend(ext(EXT_ALPN, &vec16(&alpn)));
+        exts.extend(ext(EXT_SUPPORTED_VERSIONS, &vec8(&[0x0a, 0x0a, 0x03, 0x04, 0x03, 0x03])));
+        exts.extend(ext(EXT_KEY_SHARE, &vec16(&key_share)));
+
+        let mut body = vec![0x03, 0x03];
+        body.extend([0x11; 32]);
+        body.extend(vec8(&[0x22; 32]));
+        body.extend(vec16(&[0x0a, 0x0a, 0x13, 0x01, 0xc0, 0x2f]));
+        body.extend(vec8(&[0]));
+        body.extend(vec16(&exts));
+        handshake(HS_CLIENT_HELLO, &body)
+    }
+
+    #[test]
+    fn client_hello_fields() {
+        let info = parse(&record(&client_hello())).unwrap();
+        assert_eq!(info.record_version, 0x0301);
+        let ch = info.client_hello.unwrap();
+        assert_eq!(ch.legacy_version, 0x0303);
+        assert_eq!(ch.cipher_suites, [0x0a0a, 0x1301, 0xc02f]);
+        assert_eq!(ch.extensions, [0x0a0a, 0, 10, 11, 13, 16, 43, 51]);
+        assert_eq!(ch.sni.as_deref(), Some("example.org"));
+        assert_eq!(ch.alpn, ["h2", "http/1.1"]);
+        assert_eq!(ch.supported_groups, [0x0a0a, 0x001d, 0x0017]);
+        assert_eq!(ch.ec_point_formats, [0]);
+        assert_eq!(ch.signature_algorithms, [0x0403, 0x0804]);
+        assert_eq!(ch.supported_versions, [0x0a0a, 0x0304, 0x0303]);
+        assert_eq!(ch.key_share_groups, [0x001d]);
+        assert_eq!(info.fingerprints.ja3.as_deref(), Some("771,4865-49199,0-10-11-13-16-43-51,29-23,0"));
+        assert!(info.fingerprints.ja4.as_deref().unwrap().starts_with("t13d0207h2_"));
+    }
+
+    #[test]
+    fn hello_split_across_records() {
+        let hello = client_hello();
+        let (first, second) = hello.split_at(40);
+        let mut payload = record(first);
+        payload.extend(record(second));
+        let whole = parse(&record(&hello)).unwrap();
+        let split = parse(&payload).unwrap();
+        assert_eq!(split.client_hello.unwrap().sni.as_deref(), Some("example.org"));
+        assert_eq!(split.fingerprints.ja4, whole.fingerprints.ja4);
+    }
+
+    #[test]
+    fn truncated_hello_is_not_decoded() {
+        let payload = record(&client_hello());
+        // Cut by the end of the segment, in the extensions and in the
+        // handshake header.
+        assert!(parse(&payload[..payload.len() - 10]).is_none());
+        assert!(parse(&payload[..7]).is_none());
+    }
+
+    #[test]
+    fn server_hello_fields() {
+        let mut exts = ext(EXT_SUPPORTED_VERSIONS, &[0x03, 0x04]);
+        exts.extend(ext(EXT_KEY_SHARE, &[0x00, 0x1d, 0x00, 0x00]));
+        exts.extend(ext(EXT_ALPN, &vec16(&vec8(b"h2"))));
+        let mut body = vec![0x03, 0x03];
+        body.extend([0x33; 32]);
+        body.extend(vec8(&[]));
+        body.extend([0x13, 0x01, 0x00]);
+        body.extend(vec16(&exts));
+
+        let info = parse(&record(&handshake(HS_SERVER_HELLO, &body))).unwrap();
+        let sh = info.server_hello.unwrap();
+        assert_eq!(sh.cipher_suite, 0x1301);
+        assert_eq!(sh.selected_version, Some(0x0304));
+        assert_eq!(sh.key_share_group, Some(0x001d));
+        assert_eq!(sh.alpn.as_deref(), Some("h2"));
+        assert_eq!(sh.extensions, [43, 51, 16]);
+        assert!(info.fingerprints.ja4s.as_deref().unwrap().starts_with("t1303h2_1301_"));
+    }
+
+    #[test]
+    fn other_records_are_ignored() {
+        // Application data, then a handshake record without a hello.
+        assert!(parse(&[0x17, 0x03, 0x03, 0x00, 0x02, 0xde, 0xad]).is_none());
+        assert!(parse(&record(&handshake(11, &[0; 8]))).is_none());
+    }
+}
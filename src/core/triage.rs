@@ -0,0 +1,340 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+
+use chrono::{TimeZone, Utc};
+use serde::Deserialize;
+use sha2::{Digest as _, Sha256};
+
+use crate::core::alerts;
+use crate::core::models::{Alert, AlertAck, AlertGroup};
+use crate::core::rules::AddrMatch;
+
+const SWEEP_INTERVAL_MS: u128 = 60_000;
+
+/// Alerts of matching rules between matching addresses are dropped.
+#[derive(Debug, Clone)]
+struct Suppression {
+    rule: Option<String>,
+    src: AddrMatch,
+    dst: AddrMatch,
+    /// Either side.
+    ip: AddrMatch,
+}
+
+/// What a rate limit counts alerts by, besides their rule.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+enum RateTrack {
+    #[default]
+    #[serde(rename = "by_rule")]
+    Rule,
+    #[serde(rename = "by_src")]
+    Src,
+    #[serde(rename = "by_dst")]
+    Dst,
+    #[serde(rename = "by_pair")]
+    Pair,
+}
+
+/// At most `count` alerts per `seconds` for each rule and tracked key.
+#[derive(Debug, Clone)]
+struct RateLimit {
+    rule: Option<String>,
+    track: RateTrack,
+    count: u32,
+    seconds: u64,
+}
+
+/// Suppression lists and rate limits applied to every alert, whatever
+/// raised it.
+#[derive(Debug, Clone, Default)]
+pub struct AlertFilters {
+    suppressions: Vec<Suppression>,
+    rate_limits: Vec<RateLimit>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SuppressSpec {
+    rule: Option<String>,
+    src: Option<String>,
+    dst: Option<String>,
+    ip: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RateLimitSpec {
+    rule: Option<String>,
+    #[serde(default)]
+    track: RateTrack,
+    count: u32,
+    seconds: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FiltersSpec {
+    #[serde(default)]
+    suppress: Vec<SuppressSpec>,
+    #[serde(default)]
+    rate_limit: Vec<RateLimitSpec>,
+}
+
+/// Loads a JSON alert filters file: `suppress` and `rate_limit` lists (see
+/// README).
+pub fn load_filters(path: &Path) -> Result<AlertFilters, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let spec: FiltersSpec =
+        serde_json::from_str(&text).map_err(|e| format!("{}: JSON invalide : {e}", path.display()))?;
+    let addrs = |a: Option<String>| a.map_or(Ok(AddrMatch::default()), |a| AddrMatch::parse(&a));
+
+    let mut filters = AlertFilters::default();
+    for (i, s) in spec.suppress.into_iter().enumerate() {
+        let context = |e: String| format!("{}: suppression {} : {e}", path.display(), i + 1);
+        if s.rule.is_none() && s.src.is_none() && s.dst.is_none() && s.ip.is_none() {
+            return Err(context("rule, src, dst ou ip attendu".to_string()));
+        }
+        filters.suppressions.push(Suppression {
+            rule: s.rule,
+            src: addrs(s.src).map_err(context)?,
+            dst: addrs(s.dst).map_err(context)?,
+            ip: addrs(s.ip).map_err(context)?,
+        });
+    }
+    for (i, r) in spec.rate_limit.into_iter().enumerate() {
+        if r.count == 0 || r.seconds == 0 {
+            return Err(format!(
+                "{}: limite {} : count et seconds doivent être positifs",
+                path.display(),
+                i + 1
+            ));
+        }
+        filters.rate_limits.push(RateLimit {
+            rule: r.rule,
+            track: r.track,
+            count: r.count,
+            seconds: r.seconds,
+        });
+    }
+    Ok(filters)
+}
+
+/// `scan-*` matches every rule starting with `scan-`, `*` every rule.
+fn rule_matches(pattern: Option<&str>, rule_id: &str) -> bool {
+    match pattern {
+        None => true,
+        Some(p) => match p.strip_suffix('*') {
+            Some(prefix) => rule_id.starts_with(prefix),
+            None => p == rule_id,
+        },
+    }
+}
+
+/// Rule and addresses: what alerts are grouped and acknowledgements
+/// matched on.
+pub type GroupKey = (String, Option<String>, Option<String>);
+
+pub fn group_key(rule_id: &str, src: Option<&str>, dst: Option<&str>) -> GroupKey {
+    (rule_id.to_string(), src.map(str::to_string), dst.map(str::to_string))
+}
+
+/// Keys of the acknowledged groups.
+pub fn ack_keys(acks: &[AlertAck]) -> HashSet<GroupKey> {
+    acks.iter()
+        .map(|a| group_key(&a.rule_id, a.src_ip.as_deref(), a.dst_ip.as_deref()))
+        .collect()
+}
+
+/// Identifier of the group of alerts of `rule_id` from `src` to `dst`, to
+/// name it on the command line.
+pub fn group_id(rule_id: &str, src: Option<&str>, dst: Option<&str>) -> String {
+    let key = format!("{rule_id}|{}|{}", src.unwrap_or_default(), dst.unwrap_or_default());
+    Sha256::digest(key.as_bytes())[..8].iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// What becomes of an alert.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    /// Matches a suppression entry: dropped and not counted.
+    Suppressed,
+    /// Over a rate limit: only counted in its group.
+    RateLimited,
+    /// Its group was acknowledged: stored, not shown.
+    Acknowledged,
+    /// Its group already has alerts: stored, not shown again.
+    Repeated,
+    /// First of its group.
+    New,
+}
+
+impl Admission {
+    pub fn stored(self) -> bool {
+        matches!(self, Admission::Acknowledged | Admission::Repeated | Admission::New)
+    }
+
+    pub fn shown(self) -> bool {
+        self == Admission::New
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RateState {
+    period_start: u128,
+    count: u32,
+}
+
+/// Applies the alert filters, merges identical alerts into groups and
+/// keeps acknowledged groups quiet.
+#[derive(Default)]
+pub struct AlertTriage {
+    filters: AlertFilters,
+    acknowledged: HashSet<GroupKey>,
+    groups: HashMap<GroupKey, AlertGroup>,
+    /// By (rate limit index, rule, tracked key).
+    rates: HashMap<(usize, String, String), RateState>,
+    suppressed: u64,
+    last_sweep: u128,
+}
+
+impl AlertTriage {
+    pub fn new(filters: AlertFilters, acks: &[AlertAck]) -> Self {
+        AlertTriage {
+            filters,
+            acknowledged: ack_keys(acks),
+            ..Self::default()
+        }
+    }
+
+    pub fn admit(&mut self, alert: &Alert) -> Admission {
+        let now = alert.timestamp;
+        let ip = |a: &Option<String>| a.as_deref().and_then(|a| a.parse::<IpAddr>().ok());
+        let (src, dst) = (ip(&alert.src_ip), ip(&alert.dst_ip));
+        let suppressed = self.filters.suppressions.iter().any(|s| {
+            rule_matches(s.rule.as_deref(), &alert.rule_id)
+                && s.src.matches(src.as_ref())
+                && s.dst.matches(dst.as_ref())
+                && (s.ip.is_any() || s.ip.matches(src.as_ref()) || s.ip.matches(dst.as_ref()))
+        });
+        if suppressed {
+            self.suppressed += 1;
+            return Admission::Suppressed;
+        }
+
+        if now >= self.last_sweep + SWEEP_INTERVAL_MS {
+            self.last_sweep = now;
+            let limits = &self.filters.rate_limits;
+            self.rates
+                .retain(|(i, _, _), r| now.saturating_sub(r.period_start) < limits[*i].seconds as u128 * 1000);
+        }
+        let mut limited = false;
+        for (i, limit) in self.filters.rate_limits.iter().enumerate() {
+            if !rule_matches(limit.rule.as_deref(), &alert.rule_id) {
+                continue;
+            }
+            let side = |a: &Option<String>| a.clone().unwrap_or_default();
+            let key = match limit.track {
+                RateTrack::Rule => String::new(),
+                RateTrack::Src => side(&alert.src_ip),
+                RateTrack::Dst => side(&alert.dst_ip),
+                RateTrack::Pair => format!("{} {}", side(&alert.src_ip), side(&alert.dst_ip)),
+            };
+            let state = self.rates.entry((i, alert.rule_id.clone(), key)).or_insert(RateState {
+                period_start: now,
+                count: 0,
+            });
+            if now.saturating_sub(state.period_start) >= limit.seconds as u128 * 1000 {
+                state.period_start = now;
+                state.count = 0;
+            }
+            state.count = state.count.saturating_add(1);
+            limited |= state.count > limit.count;
+        }
+
+        let key = group_key(&alert.rule_id, alert.src_ip.as_deref(), alert.dst_ip.as_deref());
+        let acknowledged = self.acknowledged.contains(&key);
+        let new = !self.groups.contains_key(&key);
+        let group = self.groups.entry(key).or_insert_with(|| AlertGroup {
+            id: group_id(&alert.rule_id, alert.src_ip.as_deref(), alert.dst_ip.as_deref()),
+            rule_id: alert.rule_id.clone(),
+            severity: alert.severity,
+            message: String::new(),
+            src_ip: alert.src_ip.clone(),
+            dst_ip: alert.dst_ip.clone(),
+            count: 0,
+            rate_limited: 0,
+            first_seen: now,
+            last_seen: now,
+            first_packet: alert.packet_id,
+            last_packet: None,
+            acknowledged,
+        });
+        group.count += 1;
+        group.severity = group.severity.max(alert.severity);
+        group.message = alert.message.clone();
+        group.last_seen = group.last_seen.max(now);
+        group.last_packet = alert.packet_id.or(group.last_packet);
+        if limited {
+            group.rate_limited += 1;
+            return Admission::RateLimited;
+        }
+        match (acknowledged, new) {
+            (true, _) => Admission::Acknowledged,
+            (false, false) => Admission::Repeated,
+            (false, true) => Admission::New,
+        }
+    }
+
+    /// Groups in order of first appearance.
+    pub fn groups(&self) -> Vec<AlertGroup> {
+        let mut list: Vec<AlertGroup> = self.groups.values().cloned().collect();
+        list.sort_by(|a, b| (a.first_seen, &a.id).cmp(&(b.first_seen, &b.id)));
+        list
+    }
+
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed
+    }
+
+    pub fn rate_limited(&self) -> u64 {
+        self.groups.values().map(|g| g.rate_limited).sum()
+    }
+}
+
+fn time_label(ms: u128) -> String {
+    Utc.timestamp_millis_opt(ms as i64)
+        .single()
+        .map(|t| t.format("%H:%M:%S").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+pub fn print_groups(groups: &[AlertGroup]) {
+    println!();
+    println!("══════════════════════════════════════════════════════════════════════");
+    println!(" WireFish - Alertes regroupées ({})", groups.len());
+    println!("══════════════════════════════════════════════════════════════════════");
+    println!(
+        "{:<16} {:>6} {:<9} {:<9} {:<9} {:<24} {:<18} {:<18} Message",
+        "Groupe", "Nb", "Début", "Fin", "Gravité", "Règle", "Source", "Destination"
+    );
+
+    for g in groups {
+        println!(
+            "{:<16} {:>6} {:<9} {:<9} {:<9} {:<24} {:<18} {:<18} {}{}",
+            g.id,
+            g.count,
+            time_label(g.first_seen),
+            time_label(g.last_seen),
+            alerts::severity_label(g.severity),
+            g.rule_id,
+            g.src_ip.as_deref().unwrap_or("?"),
+            g.dst_ip.as_deref().unwrap_or("?"),
+            if g.acknowledged { "✓ " } else { "" },
+            g.message
+        );
+        if g.rate_limited > 0 {
+            println!("                 dont {} écartées par limitation de débit", g.rate_limited);
+        }
+    }
+}
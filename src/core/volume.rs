@@ -0,0 +1,489 @@
+use std::collections::HashMap;
+
+use crate::core::models::{Alert, Baseline, Ewma, Packet, Severity, TransportProtocol};
+use crate::core::parser;
+use crate::core::stream::{TCP_ACK, TCP_RST, TCP_SYN};
+
+const INTERVAL_MS: u128 = 10_000;
+const ALPHA: f64 = 0.05;
+const HOURS: usize = 24;
+/// Intervals a baseline needs before it is trusted.
+const WARMUP: u64 = 30;
+/// A server gives up on a handshake after this long.
+const HALF_OPEN_TIMEOUT_MS: u128 = 30_000;
+const MAX_HALF_OPEN: usize = 65_536;
+/// Host series kept at most, for rates and for half-open connections each.
+const MAX_HOST_SERIES: usize = 3072;
+/// Rate series freed, those of idle hosts first, once the limit comes near.
+const HOST_ROOM: usize = MAX_HOST_SERIES / 4;
+/// Below these rates (per interval) nothing is worth an alert.
+const MIN_PACKETS: f64 = 1000.0;
+const MIN_BYTES: f64 = 1_000_000.0;
+/// Idle intervals replayed as zeros after a pause in the capture.
+const MAX_CATCH_UP: u32 = 360;
+/// Consecutive anomalous intervals after which the values are folded into
+/// the baseline anyway, with `ADAPT_ALPHA`: a lasting change becomes the
+/// norm instead of staying anomalous forever.
+const ADAPT_AFTER: u32 = 30;
+const ADAPT_ALPHA: f64 = 0.01;
+
+/// How far above its baseline a rate must go to raise an alert.
+#[derive(Debug, Clone, Copy)]
+pub struct VolumeConfig {
+    pub rate_factor: f64,
+    pub half_open_factor: f64,
+    /// Half-open connections to one host below which no SYN flood is
+    /// reported.
+    pub half_open_min: usize,
+}
+
+impl Default for VolumeConfig {
+    fn default() -> Self {
+        VolumeConfig {
+            rate_factor: 3.0,
+            half_open_factor: 3.0,
+            half_open_min: 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Metric {
+    HostPackets,
+    HostBytes,
+    ProtocolPackets,
+    ProtocolBytes,
+    HalfOpen,
+}
+
+impl Metric {
+    fn name(self) -> &'static str {
+        match self {
+            Metric::HostPackets => "host_packets",
+            Metric::HostBytes => "host_bytes",
+            Metric::ProtocolPackets => "protocol_packets",
+            Metric::ProtocolBytes => "protocol_bytes",
+            Metric::HalfOpen => "half_open",
+        }
+    }
+
+    fn per_host(self) -> bool {
+        !matches!(self, Metric::ProtocolPackets | Metric::ProtocolBytes)
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        [
+            Metric::HostPackets,
+            Metric::HostBytes,
+            Metric::ProtocolPackets,
+            Metric::ProtocolBytes,
+            Metric::HalfOpen,
+        ]
+        .into_iter()
+        .find(|m| m.name() == name)
+    }
+}
+
+/// A metric's baseline, its count in the running interval, and for how
+/// many intervals in a row it has been anomalous.
+struct Series {
+    baseline: Baseline,
+    count: f64,
+    anomalous: u32,
+}
+
+impl Series {
+    fn new(metric: Metric, key: &str) -> Self {
+        Series {
+            baseline: Baseline {
+                metric: metric.name().to_string(),
+                key: key.to_string(),
+                interval_ms: INTERVAL_MS,
+                current: 0.0,
+                overall: Ewma::default(),
+                hourly: vec![Ewma::default(); HOURS],
+            },
+            count: 0.0,
+            anomalous: 0,
+        }
+    }
+
+    /// The hour-of-day average once it has learned enough, the overall
+    /// one otherwise.
+    fn reference(&self, hour: usize) -> Option<Ewma> {
+        let seasonal = self.baseline.hourly.get(hour).copied().filter(|e| e.samples >= WARMUP);
+        seasonal.or(Some(self.baseline.overall).filter(|e| e.samples >= WARMUP))
+    }
+}
+
+/// Tracks half-open TCP connections per destination and packet/byte rates
+/// per host and per protocol, against moving baselines learned per hour of
+/// the day.
+pub struct VolumeDetector {
+    config: VolumeConfig,
+    series: HashMap<(Metric, String), Series>,
+    /// SYNs waiting for the handshake to complete, per destination host
+    /// (at most `MAX_HOST_SERIES` hosts).
+    half_open: HashMap<String, HashMap<(String, u16, u16), u128>>,
+    host_series: usize,
+    half_open_series: usize,
+    interval_start: Option<u128>,
+}
+
+impl VolumeDetector {
+    pub fn new(config: VolumeConfig) -> Self {
+        VolumeDetector {
+            config,
+            series: HashMap::new(),
+            half_open: HashMap::new(),
+            host_series: 0,
+            half_open_series: 0,
+            interval_start: None,
+        }
+    }
+
+    /// Resumes from the baselines of a previous run.
+    pub fn with_baselines(config: VolumeConfig, baselines: Vec<Baseline>) -> Self {
+        let mut detector = Self::new(config);
+        for mut baseline in baselines {
+            let Some(metric) = Metric::parse(&baseline.metric) else {
+                continue;
+            };
+            if baseline.interval_ms != INTERVAL_MS {
+                continue;
+            }
+            baseline.hourly.resize(HOURS, Ewma::default());
+            if let Some(n) = detector.host_count(metric) {
+                if *n >= MAX_HOST_SERIES {
+                    continue;
+                }
+                *n += 1;
+            }
+            let key = baseline.key.clone();
+            detector.series.insert(
+                (metric, key),
+                Series {
+                    baseline,
+                    count: 0.0,
+                    anomalous: 0,
+                },
+            );
+        }
+        detector
+    }
+
+    /// Current baselines, for the dashboard.
+    pub fn baselines(&self) -> Vec<Baseline> {
+        let mut list: Vec<Baseline> = self.series.values().map(|s| s.baseline.clone()).collect();
+        list.sort_by(|a, b| (&a.metric, &a.key).cmp(&(&b.metric, &b.key)));
+        list
+    }
+
+    /// Accounts `packet`, classified as `application`. Alerts come when an
+    /// interval closes.
+    pub fn observe(&mut self, packet: &Packet, application: &str) -> Vec<Alert> {
+        let now = packet.timestamp;
+        let mut alerts = Vec::new();
+        let start = *self.interval_start.get_or_insert(now - now % INTERVAL_MS);
+        if now >= start + INTERVAL_MS {
+            let elapsed = ((now - start) / INTERVAL_MS) as u32;
+            for i in 0..elapsed.min(MAX_CATCH_UP) {
+                alerts.extend(self.close_interval(start + i as u128 * INTERVAL_MS));
+            }
+            self.interval_start = Some(now - now % INTERVAL_MS);
+        }
+
+        let size = parser::ip_length(&packet.payload) as f64;
+        self.count(Metric::ProtocolPackets, application, 1.0);
+        self.count(Metric::ProtocolBytes, application, size);
+        if let Some(ip) = &packet.ip {
+            for host in [&ip.src_ip, &ip.dst_ip] {
+                self.count(Metric::HostPackets, host, 1.0);
+                self.count(Metric::HostBytes, host, size);
+            }
+            if let Some(TransportProtocol::Tcp(t)) = &packet.transport {
+                self.track_handshake(&ip.src_ip, &ip.dst_ip, t.src_port, t.dst_port, t.flags, now);
+            }
+        }
+        alerts
+    }
+
+    fn count(&mut self, metric: Metric, key: &str, amount: f64) {
+        if let Some(series) = self.series_mut(metric, key) {
+            series.count += amount;
+        }
+    }
+
+    fn host_count(&mut self, metric: Metric) -> Option<&mut usize> {
+        match metric {
+            Metric::HostPackets | Metric::HostBytes => Some(&mut self.host_series),
+            Metric::HalfOpen => Some(&mut self.half_open_series),
+            Metric::ProtocolPackets | Metric::ProtocolBytes => None,
+        }
+    }
+
+    /// The series of `metric` for `key`, created on first use. Rate series
+    /// are refused once at their limit; half-open ones are only created when
+    /// an interval closes, and then replace the quietest destination without
+    /// pending handshakes, so that hosts made up by a spoofed flood cannot
+    /// hide it.
+    fn series_mut(&mut self, metric: Metric, key: &str) -> Option<&mut Series> {
+        let id = (metric, key.to_string());
+        if !self.series.contains_key(&id) {
+            if metric == Metric::HalfOpen && self.half_open_series >= MAX_HOST_SERIES {
+                let quietest = self
+                    .series
+                    .iter()
+                    .filter(|((m, dst), _)| *m == Metric::HalfOpen && !self.half_open.contains_key(dst))
+                    .min_by(|a, b| a.1.baseline.overall.mean.total_cmp(&b.1.baseline.overall.mean))
+                    .map(|(id, _)| id.clone())?;
+                self.series.remove(&quietest);
+            } else if let Some(n) = self.host_count(metric) {
+                if *n >= MAX_HOST_SERIES {
+                    return None;
+                }
+                *n += 1;
+            }
+            self.series.insert(id.clone(), Series::new(metric, key));
+        }
+        self.series.get_mut(&id)
+    }
+
+    /// Once the rate series come near their limit, drops those of hosts idle
+    /// during the interval that just closed, quietest baseline first: hosts
+    /// of a previous run or long gone leave room for new ones.
+    fn evict_idle_hosts(&mut self) {
+        if self.host_series + HOST_ROOM <= MAX_HOST_SERIES {
+            return;
+        }
+        let mut idle: Vec<(f64, String)> = self
+            .series
+            .iter()
+            .filter(|((m, _), s)| matches!(m, Metric::HostPackets | Metric::HostBytes) && s.baseline.current == 0.0)
+            .map(|((_, key), s)| (s.baseline.overall.mean, key.clone()))
+            .collect();
+        idle.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for (_, key) in idle {
+            if self.host_series + HOST_ROOM <= MAX_HOST_SERIES {
+                break;
+            }
+            for metric in [Metric::HostPackets, Metric::HostBytes] {
+                if self.series.remove(&(metric, key.clone())).is_some() {
+                    self.host_series -= 1;
+                }
+            }
+        }
+    }
+
+    fn track_handshake(&mut self, src: &str, dst: &str, sport: u16, dport: u16, flags: u8, now: u128) {
+        if flags & TCP_SYN != 0 && flags & TCP_ACK == 0 {
+            if !self.half_open.contains_key(dst) && self.half_open.len() >= MAX_HOST_SERIES {
+                return;
+            }
+            let pending = self.half_open.entry(dst.to_string()).or_default();
+            if pending.len() < MAX_HALF_OPEN {
+                pending.entry((src.to_string(), sport, dport)).or_insert(now);
+            }
+        } else if flags & TCP_RST != 0 {
+            // Either side may abort the handshake.
+            if let Some(pending) = self.half_open.get_mut(dst) {
+                pending.remove(&(src.to_string(), sport, dport));
+            }
+            if let Some(pending) = self.half_open.get_mut(src) {
+                pending.remove(&(dst.to_string(), dport, sport));
+            }
+        } else if flags & TCP_ACK != 0 && flags & TCP_SYN == 0 {
+            if let Some(pending) = self.half_open.get_mut(dst) {
+                pending.remove(&(src.to_string(), sport, dport));
+            }
+        }
+    }
+
+    /// Compares the interval starting at `start` with the baselines, then
+    /// folds it into them (anomalous values excepted, so an attack does not
+    /// become the norm, until they last `ADAPT_AFTER` intervals).
+    fn close_interval(&mut self, start: u128) -> Vec<Alert> {
+        let end = start + INTERVAL_MS;
+        for pending in self.half_open.values_mut() {
+            pending.retain(|_, at| end.saturating_sub(*at) < HALF_OPEN_TIMEOUT_MS);
+        }
+        self.half_open.retain(|_, pending| !pending.is_empty());
+        let half_open: Vec<(String, usize)> = self.half_open.iter().map(|(dst, p)| (dst.clone(), p.len())).collect();
+        for (dst, count) in half_open {
+            if let Some(series) = self.series_mut(Metric::HalfOpen, &dst) {
+                series.count = count as f64;
+            }
+        }
+
+        let hour = ((end / 3_600_000) % HOURS as u128) as usize;
+        let mut alerts = Vec::new();
+        for ((metric, key), series) in self.series.iter_mut() {
+            let value = std::mem::take(&mut series.count);
+            series.baseline.current = value;
+            let reference = series.reference(hour);
+            let (factor, floor) = match metric {
+                Metric::HalfOpen => (self.config.half_open_factor, self.config.half_open_min as f64),
+                Metric::HostPackets | Metric::ProtocolPackets => (self.config.rate_factor, MIN_PACKETS),
+                Metric::HostBytes | Metric::ProtocolBytes => (self.config.rate_factor, MIN_BYTES),
+            };
+            // A SYN flood needs no baseline to be obvious; rates do.
+            let exceeded = value >= floor
+                && match reference {
+                    Some(r) => value > r.mean * factor,
+                    None => *metric == Metric::HalfOpen,
+                };
+
+            let alpha = if exceeded {
+                if series.anomalous == 0 {
+                    alerts.push(volume_alert(*metric, key, value, reference, end));
+                }
+                series.anomalous = series.anomalous.saturating_add(1);
+                if series.anomalous <= ADAPT_AFTER {
+                    continue;
+                }
+                ADAPT_ALPHA
+            } else {
+                series.anomalous = 0;
+                ALPHA
+            };
+            series.baseline.overall.update(value, alpha);
+            series.baseline.hourly[hour].update(value, alpha);
+        }
+        self.evict_idle_hosts();
+        alerts
+    }
+}
+
+fn volume_alert(metric: Metric, key: &str, value: f64, reference: Option<Ewma>, timestamp: u128) -> Alert {
+    let per_second = |v: f64| v * 1000.0 / INTERVAL_MS as f64;
+    let usual = reference.map_or_else(
+        || "pas encore de référence".to_string(),
+        |r| match metric {
+            Metric::HalfOpen => format!("référence {:.0} ± {:.0}", r.mean, r.deviation()),
+            _ => format!("référence {:.0} ± {:.0}/s", per_second(r.mean), per_second(r.deviation())),
+        },
+    );
+    let (rule_id, severity, classtype, message) = match metric {
+        Metric::HalfOpen => (
+            "syn-flood",
+            Severity::High,
+            "attempted-dos",
+            format!("SYN flood vers {key} : {value:.0} connexions semi-ouvertes ({usual})"),
+        ),
+        Metric::HostPackets => (
+            "volume-host-packets",
+            Severity::Medium,
+            "bad-unknown",
+            format!("Trafic anormal pour {key} : {:.0} paquets/s ({usual})", per_second(value)),
+        ),
+        Metric::HostBytes => (
+            "volume-host-bytes",
+            Severity::Medium,
+            "bad-unknown",
+            format!("Trafic anormal pour {key} : {:.0} o/s ({usual})", per_second(value)),
+        ),
+        Metric::ProtocolPackets => (
+            "volume-protocol-packets",
+            Severity::Medium,
+            "bad-unknown",
+            format!("Trafic {key} anormal : {:.0} paquets/s ({usual})", per_second(value)),
+        ),
+        Metric::ProtocolBytes => (
+            "volume-protocol-bytes",
+            Severity::Medium,
+            "bad-unknown",
+            format!("Trafic {key} anormal : {:.0} o/s ({usual})", per_second(value)),
+        ),
+    };
+    Alert {
+        rule_id: rule_id.to_string(),
+        rule_rev: None,
+        severity,
+        message,
+        classtype: Some(classtype.to_string()),
+        timestamp,
+        packet_id: None,
+        protocol: (metric == Metric::HalfOpen).then_some(6),
+        src_ip: None,
+        src_port: None,
+        dst_ip: metric.per_host().then(|| key.to_string()),
+        dst_port: None,
+        related_packets: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lasting_change_becomes_the_norm() {
+        let mut detector = VolumeDetector::new(VolumeConfig::default());
+        let key = (Metric::ProtocolPackets, "HTTP".to_string());
+        let mut alerts = Vec::new();
+        // (intervals, packets per interval)
+        for (intervals, packets) in [(WARMUP, 2000.0), (300, 20_000.0), (1, 200_000.0)] {
+            let mut raised = 0;
+            let mut last_anomalous = 0;
+            for _ in 0..intervals {
+                detector.series_mut(key.0, &key.1).unwrap().count = packets;
+                let start = detector.interval_start.unwrap_or(0);
+                raised += detector.close_interval(start).len();
+                detector.interval_start = Some(start + INTERVAL_MS);
+                last_anomalous = detector.series[&key].anomalous;
+            }
+            alerts.push((raised, last_anomalous));
+        }
+        // One alert for the new level, which stops being anomalous, then
+        // one for the next spike.
+        assert_eq!(alerts, [(0, 0), (1, 0), (1, 1)]);
+    }
+
+    #[test]
+    fn half_open_destinations_are_capped() {
+        let mut detector = VolumeDetector::new(VolumeConfig::default());
+        for i in 0..MAX_HOST_SERIES + 10 {
+            let dst = format!("10.{}.{}.{}", i >> 16, (i >> 8) & 0xff, i & 0xff);
+            detector.track_handshake("192.0.2.1", &dst, 40000, 80, TCP_SYN, 0);
+        }
+        assert_eq!(detector.half_open.len(), MAX_HOST_SERIES);
+        detector.track_handshake("192.0.2.2", "10.0.0.0", 40001, 80, TCP_SYN, 0);
+        assert_eq!(detector.half_open["10.0.0.0"].len(), 2);
+    }
+
+    /// What `observe` does with a SYN.
+    fn syn(detector: &mut VolumeDetector, src: &str, dst: &str, sport: u16, now: u128) {
+        for host in [src, dst] {
+            detector.count(Metric::HostPackets, host, 1.0);
+            detector.count(Metric::HostBytes, host, 60.0);
+        }
+        detector.track_handshake(src, dst, sport, 80, TCP_SYN, now);
+    }
+
+    #[test]
+    fn spoofed_sources_do_not_hide_a_syn_flood() {
+        let mut detector = VolumeDetector::new(VolumeConfig::default());
+        // Every made-up source takes rate series: they are full long before
+        // the interval closes and the victim's half-open series is created.
+        for i in 0..MAX_HOST_SERIES {
+            let src = format!("172.16.{}.{}", i >> 8, i & 0xff);
+            syn(&mut detector, &src, "192.0.2.80", i as u16, 1);
+        }
+        assert_eq!(detector.host_series, MAX_HOST_SERIES);
+        let alerts = detector.close_interval(0);
+        assert!(alerts.iter().any(|a| a.rule_id == "syn-flood" && a.dst_ip.as_deref() == Some("192.0.2.80")));
+    }
+
+    #[test]
+    fn stale_baselines_make_room_for_new_hosts() {
+        let baselines: Vec<Baseline> = (0..MAX_HOST_SERIES)
+            .map(|i| Series::new(Metric::HostPackets, &format!("10.0.{}.{}", i >> 8, i & 0xff)).baseline)
+            .collect();
+        let mut detector = VolumeDetector::with_baselines(VolumeConfig::default(), baselines);
+        detector.count(Metric::HostPackets, "192.0.2.1", 1.0);
+        assert!(!detector.series.contains_key(&(Metric::HostPackets, "192.0.2.1".to_string())));
+        detector.close_interval(0);
+        detector.count(Metric::HostPackets, "192.0.2.1", 1.0);
+        assert_eq!(detector.series[&(Metric::HostPackets, "192.0.2.1".to_string())].count, 1.0);
+    }
+}
@@ -0,0 +1,372 @@
+use chrono::NaiveDate;
+use sha2::{Digest, Sha256};
+
+use crate::core::models::X509Certificate;
+
+const TAG_BOOLEAN: u8 = 0x01;
+const TAG_INTEGER: u8 = 0x02;
+const TAG_BIT_STRING: u8 = 0x03;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_UTC_TIME: u8 = 0x17;
+const TAG_GENERALIZED_TIME: u8 = 0x18;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+
+/// One DER TLV: tag byte and content slice.
+struct Tlv<'a> {
+    tag: u8,
+    content: &'a [u8],
+}
+
+/// Sequential reader over DER-encoded TLVs.
+struct Der<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Der<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Der { data, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn peek_tag(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<Tlv<'a>> {
+        let tag = *self.data.get(self.pos)?;
+        let first = *self.data.get(self.pos + 1)? as usize;
+        let mut off = self.pos + 2;
+
+        let len = if first & 0x80 == 0 {
+            first
+        } else {
+            let n = first & 0x7f;
+            if n == 0 || n > 4 {
+                return None;
+            }
+            let mut len = 0usize;
+            for _ in 0..n {
+                len = (len << 8) | *self.data.get(off)? as usize;
+                off += 1;
+            }
+            len
+        };
+
+        let content = self.data.get(off..off + len)?;
+        self.pos = off + len;
+        Some(Tlv { tag, content })
+    }
+
+    fn expect(&mut self, tag: u8) -> Option<Tlv<'a>> {
+        let tlv = self.next()?;
+        (tlv.tag == tag).then_some(tlv)
+    }
+}
+
+fn oid_to_string(oid: &[u8]) -> String {
+    if oid.is_empty() {
+        return String::new();
+    }
+    let mut parts = vec![(oid[0] / 40) as u64, (oid[0] % 40) as u64];
+    let mut value: u64 = 0;
+    for &b in &oid[1..] {
+        value = (value << 7) | (b & 0x7f) as u64;
+        if b & 0x80 == 0 {
+            parts.push(value);
+            value = 0;
+        }
+    }
+    parts.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(".")
+}
+
+fn attribute_name(oid: &str) -> String {
+    match oid {
+        "2.5.4.3" => "CN",
+        "2.5.4.5" => "serialNumber",
+        "2.5.4.6" => "C",
+        "2.5.4.7" => "L",
+        "2.5.4.8" => "ST",
+        "2.5.4.10" => "O",
+        "2.5.4.11" => "OU",
+        "1.2.840.113549.1.9.1" => "emailAddress",
+        "0.9.2342.19200300.100.1.25" => "DC",
+        _ => return oid.to_string(),
+    }
+    .to_string()
+}
+
+fn signature_algorithm_name(oid: &str) -> String {
+    match oid {
+        "1.2.840.113549.1.1.4" => "md5WithRSAEncryption",
+        "1.2.840.113549.1.1.5" => "sha1WithRSAEncryption",
+        "1.2.840.113549.1.1.10" => "rsassa-pss",
+        "1.2.840.113549.1.1.11" => "sha256WithRSAEncryption",
+        "1.2.840.113549.1.1.12" => "sha384WithRSAEncryption",
+        "1.2.840.113549.1.1.13" => "sha512WithRSAEncryption",
+        "1.2.840.10045.4.1" => "ecdsa-with-SHA1",
+        "1.2.840.10045.4.3.2" => "ecdsa-with-SHA256",
+        "1.2.840.10045.4.3.3" => "ecdsa-with-SHA384",
+        "1.2.840.10045.4.3.4" => "ecdsa-with-SHA512",
+        "1.3.101.112" => "Ed25519",
+        "1.3.101.113" => "Ed448",
+        _ => return oid.to_string(),
+    }
+    .to_string()
+}
+
+fn curve_name(oid: &str) -> &str {
+    match oid {
+        "1.2.840.10045.3.1.7" => "secp256r1",
+        "1.3.132.0.34" => "secp384r1",
+        "1.3.132.0.35" => "secp521r1",
+        _ => oid,
+    }
+}
+
+/// Renders a Name as `CN=..., O=..., C=...` and returns the first CN.
+fn parse_name(content: &[u8]) -> (String, Option<String>) {
+    let mut parts = Vec::new();
+    let mut cn = None;
+
+    let mut rdns = Der::new(content);
+    while let Some(set) = rdns.next() {
+        if set.tag != TAG_SET {
+            continue;
+        }
+        let mut attrs = Der::new(set.content);
+        while let Some(attr) = attrs.next() {
+            let mut a = Der::new(attr.content);
+            let (Some(oid), Some(value)) = (a.expect(TAG_OID), a.next()) else {
+                continue;
+            };
+            let key = attribute_name(&oid_to_string(oid.content));
+            let value = String::from_utf8_lossy(value.content).into_owned();
+            if key == "CN" && cn.is_none() {
+                cn = Some(value.clone());
+            }
+            parts.push(format!("{key}={value}"));
+        }
+    }
+
+    (parts.join(", "), cn)
+}
+
+fn parse_time(tlv: &Tlv) -> Option<i64> {
+    let s = std::str::from_utf8(tlv.content).ok()?;
+    let s = s.strip_suffix('Z')?;
+    let (year, rest) = match tlv.tag {
+        TAG_UTC_TIME => {
+            let yy: i32 = s.get(0..2)?.parse().ok()?;
+            (if yy >= 50 { 1900 + yy } else { 2000 + yy }, s.get(2..)?)
+        }
+        TAG_GENERALIZED_TIME => (s.get(0..4)?.parse().ok()?, s.get(4..)?),
+        _ => return None,
+    };
+
+    let field = |i: usize| -> Option<u32> { rest.get(i..i + 2)?.parse().ok() };
+    let date = NaiveDate::from_ymd_opt(year, field(0)?, field(2)?)?;
+    let time = date.and_hms_opt(field(4)?, field(6)?, field(8).unwrap_or(0))?;
+    Some(time.and_utc().timestamp())
+}
+
+fn describe_key(spki: &[u8]) -> Option<String> {
+    let mut d = Der::new(spki);
+    let alg = d.expect(TAG_SEQUENCE)?;
+    let key = d.expect(TAG_BIT_STRING)?;
+
+    let mut a = Der::new(alg.content);
+    let oid = oid_to_string(a.expect(TAG_OID)?.content);
+
+    let desc = match oid.as_str() {
+        "1.2.840.113549.1.1.1" => {
+            // BIT STRING: unused-bits byte, then RSAPublicKey { modulus, exponent }
+            let mut k = Der::new(key.content.get(1..)?);
+            let mut rsa = Der::new(k.expect(TAG_SEQUENCE)?.content);
+            let modulus = rsa.expect(TAG_INTEGER)?.content;
+            let leading = modulus.iter().take_while(|b| **b == 0).count();
+            format!("RSA {}", (modulus.len() - leading) * 8)
+        }
+        "1.2.840.10045.2.1" => match a.expect(TAG_OID) {
+            Some(curve) => format!("EC {}", curve_name(&oid_to_string(curve.content))),
+            None => "EC".to_string(),
+        },
+        "1.3.101.112" => "Ed25519".to_string(),
+        "1.3.101.113" => "Ed448".to_string(),
+        "1.2.840.10040.4.1" => "DSA".to_string(),
+        other => other.to_string(),
+    };
+    Some(desc)
+}
+
+fn parse_san(value: &[u8], cert: &mut X509Certificate) {
+    let mut outer = Der::new(value);
+    let Some(seq) = outer.expect(TAG_SEQUENCE) else {
+        return;
+    };
+    let mut names = Der::new(seq.content);
+    while let Some(name) = names.next() {
+        match name.tag {
+            // [2] dNSName
+            0x82 => cert
+                .san_dns
+                .push(String::from_utf8_lossy(name.content).into_owned()),
+            // [7] iPAddress
+            0x87 => match name.content.len() {
+                4 => {
+                    let b: [u8; 4] = name.content.try_into().unwrap();
+                    cert.san_ip.push(std::net::Ipv4Addr::from(b).to_string());
+                }
+                16 => {
+                    let b: [u8; 16] = name.content.try_into().unwrap();
+                    cert.san_ip.push(std::net::Ipv6Addr::from(b).to_string());
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+fn parse_basic_constraints(value: &[u8]) -> bool {
+    let mut outer = Der::new(value);
+    let Some(seq) = outer.expect(TAG_SEQUENCE) else {
+        return false;
+    };
+    let mut d = Der::new(seq.content);
+    matches!(d.next(), Some(t) if t.tag == TAG_BOOLEAN && t.content.first().is_some_and(|b| *b != 0))
+}
+
+/// Decodes a DER certificate. Only the fields shown by the inspector and
+/// used by the certificate alerts are extracted.
+pub fn parse_certificate(der: &[u8]) -> Option<X509Certificate> {
+    let mut top = Der::new(der);
+    let cert = top.expect(TAG_SEQUENCE)?;
+
+    let mut c = Der::new(cert.content);
+    let tbs = c.expect(TAG_SEQUENCE)?;
+    let sig_alg = c.expect(TAG_SEQUENCE)?;
+
+    let mut out = X509Certificate {
+        sha256: Sha256::digest(der)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect(),
+        ..Default::default()
+    };
+
+    let mut sa = Der::new(sig_alg.content);
+    out.signature_algorithm = signature_algorithm_name(&oid_to_string(sa.expect(TAG_OID)?.content));
+
+    let mut t = Der::new(tbs.content);
+    if t.peek_tag() == Some(0xa0) {
+        t.next()?; // [0] version
+    }
+    let serial = t.expect(TAG_INTEGER)?;
+    out.serial = serial
+        .content
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<_>>()
+        .join(":");
+    t.expect(TAG_SEQUENCE)?; // signature (repeated)
+
+    let (issuer, _) = parse_name(t.expect(TAG_SEQUENCE)?.content);
+    out.issuer = issuer;
+
+    let mut validity = Der::new(t.expect(TAG_SEQUENCE)?.content);
+    out.not_before = parse_time(&validity.next()?).unwrap_or(0);
+    out.not_after = parse_time(&validity.next()?).unwrap_or(0);
+
+    let (subject, cn) = parse_name(t.expect(TAG_SEQUENCE)?.content);
+    out.subject = subject;
+    out.subject_cn = cn;
+
+    let spki = t.expect(TAG_SEQUENCE)?;
+    out.key_type = describe_key(spki.content).unwrap_or_else(|| "?".to_string());
+
+    while !t.is_empty() {
+        let field = t.next()?;
+        // [3] EXPLICIT Extensions
+        if field.tag != 0xa3 {
+            continue;
+        }
+        let mut wrapper = Der::new(field.content);
+        let mut exts = Der::new(wrapper.expect(TAG_SEQUENCE)?.content);
+        while let Some(ext) = exts.next() {
+            let mut e = Der::new(ext.content);
+            let Some(oid) = e.expect(TAG_OID) else {
+                continue;
+            };
+            let mut value = e.next();
+            if matches!(&value, Some(v) if v.tag == TAG_BOOLEAN) {
+                value = e.next(); // critical flag
+            }
+            let Some(value) = value.filter(|v| v.tag == TAG_OCTET_STRING) else {
+                continue;
+            };
+            match oid_to_string(oid.content).as_str() {
+                "2.5.29.17" => parse_san(value.content, &mut out),
+                "2.5.29.19" => out.is_ca = parse_basic_constraints(value.content),
+                _ => {}
+            }
+        }
+    }
+
+    Some(out)
+}
+
+/// Decodes the body of a TLS 1.0–1.2 Certificate handshake message
+/// (a 24-bit length-prefixed list of 24-bit length-prefixed DER blobs).
+pub fn parse_certificate_list(body: &[u8]) -> Vec<X509Certificate> {
+    let mut chain = Vec::new();
+    if body.len() < 3 {
+        return chain;
+    }
+
+    let total = ((body[0] as usize) << 16) | ((body[1] as usize) << 8) | body[2] as usize;
+    let list = &body[3..(3 + total).min(body.len())];
+
+    let mut off = 0usize;
+    while off + 3 <= list.len() {
+        let len = ((list[off] as usize) << 16) | ((list[off + 1] as usize) << 8) | list[off + 2] as usize;
+        let Some(der) = list.get(off + 3..off + 3 + len) else {
+            break;
+        };
+        if let Some(cert) = parse_certificate(der) {
+            chain.push(cert);
+        }
+        off += 3 + len;
+    }
+
+    chain
+}
+
+/// RFC 6125 style host matching, with a single left-most `*` label.
+pub fn hostname_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim_end_matches('.').to_ascii_lowercase();
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => match host.split_once('.') {
+            Some((label, rest)) => !label.is_empty() && rest == suffix,
+            None => false,
+        },
+        None => pattern == host,
+    }
+}
+
+/// Names a certificate is valid for: the DNS SANs, or the CN when there is no SAN.
+pub fn certificate_names(cert: &X509Certificate) -> Vec<String> {
+    if !cert.san_dns.is_empty() || !cert.san_ip.is_empty() {
+        let mut names = cert.san_dns.clone();
+        names.extend(cert.san_ip.iter().cloned());
+        return names;
+    }
+    cert.subject_cn.iter().cloned().collect()
+}
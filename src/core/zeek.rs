@@ -0,0 +1,891 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::core::dns;
+use crate::core::flow::{FlowRecord, FlowTable};
+use crate::core::http;
+use crate::core::models::{DnsMessage, Flow, HttpTransaction, Packet, TcpExpert, TlsInfo, TransportProtocol};
+use crate::core::parser;
+use crate::core::stream::{Direction, StreamEvent, TCP_ACK, TCP_FIN, TCP_RST, TCP_SYN};
+use crate::core::tls;
+
+/// Zeek's default TCP inactivity timeout.
+const IDLE_TIMEOUT_MS: u128 = 5 * 60 * 1000;
+/// Unanswered DNS queries are logged after this delay.
+const DNS_TIMEOUT_MS: u128 = 10_000;
+const CHECK_INTERVAL_MS: u128 = 1000;
+
+type Fields = &'static [(&'static str, &'static str)];
+
+const CONN_FIELDS: Fields = &[
+    ("ts", "time"),
+    ("uid", "string"),
+    ("id.orig_h", "addr"),
+    ("id.orig_p", "port"),
+    ("id.resp_h", "addr"),
+    ("id.resp_p", "port"),
+    ("proto", "enum"),
+    ("service", "string"),
+    ("duration", "interval"),
+    ("orig_bytes", "count"),
+    ("resp_bytes", "count"),
+    ("conn_state", "string"),
+    ("missed_bytes", "count"),
+    ("history", "string"),
+    ("orig_pkts", "count"),
+    ("orig_ip_bytes", "count"),
+    ("resp_pkts", "count"),
+    ("resp_ip_bytes", "count"),
+    ("vlan", "int"),
+];
+
+const DNS_FIELDS: Fields = &[
+    ("ts", "time"),
+    ("uid", "string"),
+    ("id.orig_h", "addr"),
+    ("id.orig_p", "port"),
+    ("id.resp_h", "addr"),
+    ("id.resp_p", "port"),
+    ("proto", "enum"),
+    ("trans_id", "count"),
+    ("rtt", "interval"),
+    ("query", "string"),
+    ("qclass", "count"),
+    ("qclass_name", "string"),
+    ("qtype", "count"),
+    ("qtype_name", "string"),
+    ("rcode", "count"),
+    ("rcode_name", "string"),
+    ("AA", "bool"),
+    ("TC", "bool"),
+    ("RD", "bool"),
+    ("RA", "bool"),
+    ("answers", "vector[string]"),
+    ("TTLs", "vector[interval]"),
+];
+
+const HTTP_FIELDS: Fields = &[
+    ("ts", "time"),
+    ("uid", "string"),
+    ("id.orig_h", "addr"),
+    ("id.orig_p", "port"),
+    ("id.resp_h", "addr"),
+    ("id.resp_p", "port"),
+    ("trans_depth", "count"),
+    ("method", "string"),
+    ("host", "string"),
+    ("uri", "string"),
+    ("referrer", "string"),
+    ("version", "string"),
+    ("user_agent", "string"),
+    ("origin", "string"),
+    ("request_body_len", "count"),
+    ("response_body_len", "count"),
+    ("status_code", "count"),
+    ("status_msg", "string"),
+    ("resp_mime_types", "vector[string]"),
+];
+
+const SSL_FIELDS: Fields = &[
+    ("ts", "time"),
+    ("uid", "string"),
+    ("id.orig_h", "addr"),
+    ("id.orig_p", "port"),
+    ("id.resp_h", "addr"),
+    ("id.resp_p", "port"),
+    ("version", "string"),
+    ("cipher", "string"),
+    ("curve", "string"),
+    ("server_name", "string"),
+    ("next_protocol", "string"),
+    ("established", "bool"),
+    ("cert_chain_fps", "vector[string]"),
+    ("subject", "string"),
+    ("issuer", "string"),
+    ("ja3", "string"),
+    ("ja3s", "string"),
+    ("ja4", "string"),
+    ("ja4s", "string"),
+];
+
+#[derive(Debug, Clone, Default)]
+enum Value {
+    #[default]
+    Unset,
+    /// Milliseconds since the epoch.
+    Time(u128),
+    /// Seconds.
+    Interval(f64),
+    Str(String),
+    Count(u64),
+    Bool(bool),
+    Strings(Vec<String>),
+    Intervals(Vec<f64>),
+}
+
+fn opt_str(value: Option<&str>) -> Value {
+    value.map_or(Value::Unset, |s| Value::Str(s.to_string()))
+}
+
+/// Zeek's ASCII writer escapes control characters as `\xHH`.
+fn escape(s: &str, in_set: bool) -> String {
+    if s == "-" {
+        return "\\x2d".to_string();
+    }
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c.is_control() || (in_set && c == ',') {
+            for b in c.to_string().bytes() {
+                out.push_str(&format!("\\x{b:02x}"));
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn time_secs(ms: u128) -> String {
+    format!("{}.{:06}", ms / 1000, (ms % 1000) * 1000)
+}
+
+impl Value {
+    fn tsv(&self) -> String {
+        match self {
+            Value::Unset => "-".to_string(),
+            Value::Time(ms) => time_secs(*ms),
+            Value::Interval(s) => format!("{s:.6}"),
+            Value::Str(s) if s.is_empty() => "(empty)".to_string(),
+            Value::Str(s) => escape(s, false),
+            Value::Count(n) => n.to_string(),
+            Value::Bool(b) => if *b { "T" } else { "F" }.to_string(),
+            Value::Strings(v) if v.is_empty() => "(empty)".to_string(),
+            Value::Strings(v) => v.iter().map(|s| escape(s, true)).collect::<Vec<_>>().join(","),
+            Value::Intervals(v) if v.is_empty() => "(empty)".to_string(),
+            Value::Intervals(v) => v.iter().map(|s| format!("{s:.6}")).collect::<Vec<_>>().join(","),
+        }
+    }
+
+    fn json(&self) -> Option<serde_json::Value> {
+        Some(match self {
+            Value::Unset => return None,
+            Value::Time(ms) => serde_json::json!(*ms as f64 / 1000.0),
+            Value::Interval(s) => serde_json::json!(s),
+            Value::Str(s) => serde_json::json!(s),
+            Value::Count(n) => serde_json::json!(n),
+            Value::Bool(b) => serde_json::json!(b),
+            Value::Strings(v) => serde_json::json!(v),
+            Value::Intervals(v) => serde_json::json!(v),
+        })
+    }
+}
+
+/// One `<path>.log` file, in Zeek's TSV layout or as JSON lines.
+struct LogFile {
+    path: &'static str,
+    fields: Fields,
+    out: BufWriter<File>,
+    json: bool,
+    lines: u64,
+    error: Option<io::Error>,
+}
+
+fn zeek_date() -> String {
+    chrono::Local::now().format("%Y-%m-%d-%H-%M-%S").to_string()
+}
+
+impl LogFile {
+    fn create(dir: &Path, path: &'static str, fields: Fields, json: bool) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(dir.join(format!("{path}.log")))?);
+        if !json {
+            writeln!(out, "#separator \\x09")?;
+            writeln!(out, "#set_separator\t,")?;
+            writeln!(out, "#empty_field\t(empty)")?;
+            writeln!(out, "#unset_field\t-")?;
+            writeln!(out, "#path\t{path}")?;
+            writeln!(out, "#open\t{}", zeek_date())?;
+            let names: Vec<&str> = fields.iter().map(|(n, _)| *n).collect();
+            let types: Vec<&str> = fields.iter().map(|(_, t)| *t).collect();
+            writeln!(out, "#fields\t{}", names.join("\t"))?;
+            writeln!(out, "#types\t{}", types.join("\t"))?;
+        }
+        Ok(LogFile {
+            path,
+            fields,
+            out,
+            json,
+            lines: 0,
+            error: None,
+        })
+    }
+
+    /// Keeps the first write error for `close` and stops writing.
+    fn write(&mut self, values: &[Value]) {
+        if self.error.is_some() {
+            return;
+        }
+        let line = if self.json {
+            let pairs: Vec<String> = self
+                .fields
+                .iter()
+                .zip(values)
+                .filter_map(|((name, _), v)| v.json().map(|j| format!("{}:{j}", serde_json::json!(name))))
+                .collect();
+            format!("{{{}}}", pairs.join(","))
+        } else {
+            values.iter().map(Value::tsv).collect::<Vec<_>>().join("\t")
+        };
+        match writeln!(self.out, "{line}") {
+            Ok(()) => self.lines += 1,
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    fn close(mut self) -> io::Result<u64> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        if !self.json {
+            writeln!(self.out, "#close\t{}", zeek_date())?;
+        }
+        self.out.flush()?;
+        Ok(self.lines)
+    }
+}
+
+/// Zeek-like connection UID ("C" + base62), derived from the 5-tuple and
+/// start time so the same capture always gets the same UIDs.
+fn uid(flow: &Flow) -> String {
+    const ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+    let key = format!(
+        "{}|{:?}|{}|{:?}|{}|{:?}|{}|{:?}",
+        flow.protocol, flow.iface, flow.client_ip, flow.client_port, flow.server_ip, flow.server_port, flow.first_seen, flow.vlan
+    );
+    let fnv = |seed: u64| {
+        key.bytes()
+            .fold(seed, |h, b| (h ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3))
+    };
+    let high = fnv(0xcbf2_9ce4_8422_2325);
+    let low = fnv(high);
+    // 96 bits, as Zeek's UIDs.
+    let mut n = ((high as u128) << 32) | (low as u128 >> 32);
+    let mut digits = Vec::new();
+    while n > 0 {
+        digits.push(ALPHABET[(n % 62) as usize]);
+        n /= 62;
+    }
+    digits.reverse();
+    format!("C{}", String::from_utf8(digits).unwrap_or_default())
+}
+
+fn conn_id(flow: &Flow) -> [Value; 4] {
+    let port = |p: Option<u16>| p.map_or(Value::Unset, |p| Value::Count(p as u64));
+    [
+        Value::Str(flow.client_ip.clone()),
+        port(flow.client_port),
+        Value::Str(flow.server_ip.clone()),
+        port(flow.server_port),
+    ]
+}
+
+fn proto_name(protocol: u8) -> &'static str {
+    match protocol {
+        6 => "tcp",
+        17 => "udp",
+        1 | 58 => "icmp",
+        _ => "unknown_transport",
+    }
+}
+
+/// Zeek service name for a classifier label; none for bare transports.
+fn service(application: &str) -> Option<String> {
+    match application {
+        "TCP" | "UDP" | "OTHER" | "ICMP" | "ICMPv6" | "IGMPv2" => None,
+        "HTTPS" => Some("ssl".to_string()),
+        a if a.starts_with("TLS") || a.starts_with("SSL") => Some("ssl".to_string()),
+        a => Some(a.to_lowercase()),
+    }
+}
+
+/// Zeek's conn_state from the history letters (upper case: originator).
+fn conn_state(protocol: u8, history: &str, resp_pkts: u64) -> &'static str {
+    if protocol != 6 {
+        return if resp_pkts > 0 { "SF" } else { "S0" };
+    }
+    let has = |c: char| history.contains(c);
+    match (has('S'), has('h')) {
+        (true, false) if has('r') => "REJ",
+        (true, false) if has('R') => "RSTOS0",
+        (true, false) if has('F') => "SH",
+        (true, false) => "S0",
+        (false, true) if has('r') => "RSTRH",
+        (false, true) if has('f') => "SHR",
+        (false, _) => "OTH",
+        (true, true) if has('R') => "RSTO",
+        (true, true) if has('r') => "RSTR",
+        (true, true) => match (has('F'), has('f')) {
+            (true, true) => "SF",
+            (true, false) => "S2",
+            (false, true) => "S3",
+            (false, false) => "S1",
+        },
+    }
+}
+
+#[derive(Default)]
+struct SslState {
+    ts: u128,
+    version: Option<u16>,
+    cipher: Option<u16>,
+    curve: Option<u16>,
+    server_name: Option<String>,
+    next_protocol: Option<String>,
+    established: bool,
+    cert_fps: Vec<String>,
+    subject: Option<String>,
+    issuer: Option<String>,
+    ja3: Option<String>,
+    ja3s: Option<String>,
+    ja4: Option<String>,
+    ja4s: Option<String>,
+}
+
+impl SslState {
+    fn update(&mut self, info: &TlsInfo) {
+        let fp = &info.fingerprints;
+        if let Some(ch) = &info.client_hello {
+            self.server_name = self.server_name.take().or_else(|| ch.sni.clone());
+            self.ja3 = self.ja3.take().or_else(|| fp.ja3_hash.clone());
+            self.ja4 = self.ja4.take().or_else(|| fp.ja4.clone());
+        }
+        if let Some(sh) = &info.server_hello {
+            self.version = Some(sh.selected_version.unwrap_or(sh.legacy_version));
+            self.cipher = Some(sh.cipher_suite);
+            self.curve = sh.key_share_group.or(self.curve);
+            self.next_protocol = sh.alpn.clone().or(self.next_protocol.take());
+            self.ja3s = self.ja3s.take().or_else(|| fp.ja3s_hash.clone());
+            self.ja4s = self.ja4s.take().or_else(|| fp.ja4s.clone());
+        }
+        if self.cert_fps.is_empty() {
+            if let Some(leaf) = info.certificates.first() {
+                self.subject = Some(leaf.subject.clone());
+                self.issuer = Some(leaf.issuer.clone());
+            }
+            self.cert_fps = info.certificates.iter().map(|c| c.sha256.clone()).collect();
+        }
+    }
+
+    fn values(&self, uid: &str, id: [Value; 4]) -> Vec<Value> {
+        let mut values = vec![Value::Time(self.ts), Value::Str(uid.to_string())];
+        values.extend(id);
+        values.extend([
+            self.version
+                .map_or(Value::Unset, |v| Value::Str(tls::version_label(v).replace('.', ""))),
+            self.cipher.map_or(Value::Unset, |c| Value::Str(tls::cipher_suite_name(c))),
+            self.curve.map_or(Value::Unset, |g| Value::Str(tls::group_name(g))),
+            opt_str(self.server_name.as_deref()),
+            opt_str(self.next_protocol.as_deref()),
+            Value::Bool(self.established),
+            if self.cert_fps.is_empty() {
+                Value::Unset
+            } else {
+                Value::Strings(self.cert_fps.clone())
+            },
+            opt_str(self.subject.as_deref()),
+            opt_str(self.issuer.as_deref()),
+            opt_str(self.ja3.as_deref()),
+            opt_str(self.ja3s.as_deref()),
+            opt_str(self.ja4.as_deref()),
+            opt_str(self.ja4s.as_deref()),
+        ]);
+        values
+    }
+}
+
+/// Follows the TLS record headers of one direction of a TCP stream, to
+/// tell when the handshake completed.
+#[derive(Default)]
+struct TlsRecords {
+    header: [u8; 5],
+    header_len: usize,
+    /// Bytes of the current record still to skip.
+    skip: usize,
+    change_cipher_spec: bool,
+    /// Finished (the handshake record after ChangeCipherSpec, TLS 1.2 and
+    /// older) or application data seen.
+    done: bool,
+    /// Not TLS, or a gap lost the record boundaries.
+    lost: bool,
+}
+
+impl TlsRecords {
+    fn feed(&mut self, mut data: &[u8]) {
+        while !data.is_empty() && !self.lost {
+            if self.skip > 0 {
+                let n = self.skip.min(data.len());
+                self.skip -= n;
+                data = &data[n..];
+                continue;
+            }
+            let n = (5 - self.header_len).min(data.len());
+            self.header[self.header_len..self.header_len + n].copy_from_slice(&data[..n]);
+            self.header_len += n;
+            data = &data[n..];
+            if self.header_len < 5 {
+                return;
+            }
+            self.header_len = 0;
+            let [kind, major, _, high, low] = self.header;
+            if major != 3 || !(20..=24).contains(&kind) {
+                self.lost = true;
+                return;
+            }
+            match kind {
+                20 => self.change_cipher_spec = true,
+                22 if self.change_cipher_spec => self.done = true,
+                23 => self.done = true,
+                _ => {}
+            }
+            self.skip = u16::from_be_bytes([high, low]) as usize;
+        }
+    }
+}
+
+/// What conn.log needs beyond the flow table counters.
+#[derive(Default)]
+struct ConnState {
+    uid: String,
+    /// Endpoints as logged (`id.orig_h` … `id.resp_p`).
+    id: [Value; 4],
+    history: String,
+    orig_bytes: u64,
+    resp_bytes: u64,
+    orig_ip_bytes: u64,
+    resp_ip_bytes: u64,
+    missed_bytes: u64,
+    http_depth: u64,
+    /// Requests waiting for their response: (ts, trans_depth, request).
+    http_pending: VecDeque<(u128, u64, HttpTransaction)>,
+    ssl: Option<SslState>,
+    /// Client then server direction.
+    tls_records: [TlsRecords; 2],
+}
+
+struct PendingQuery {
+    ts: u128,
+    uid: String,
+    id: [Value; 4],
+    proto: &'static str,
+    query: DnsMessage,
+}
+
+fn qclass_name(qclass: u16) -> Value {
+    match qclass {
+        1 => Value::Str("C_INTERNET".to_string()),
+        3 => Value::Str("C_CHAOS".to_string()),
+        4 => Value::Str("C_HESIOD".to_string()),
+        255 => Value::Str("C_ANY".to_string()),
+        _ => Value::Unset,
+    }
+}
+
+fn dns_values(
+    ts: u128,
+    uid: &str,
+    id: [Value; 4],
+    proto: &str,
+    query: Option<&DnsMessage>,
+    response: Option<&DnsMessage>,
+    rtt_ms: Option<u128>,
+) -> Vec<Value> {
+    let msg = response.or(query);
+    let question = msg.and_then(|m| m.questions.first());
+    let mut values = vec![Value::Time(ts), Value::Str(uid.to_string())];
+    values.extend(id);
+    values.extend([
+        Value::Str(proto.to_string()),
+        msg.map_or(Value::Unset, |m| Value::Count(m.id as u64)),
+        rtt_ms.map_or(Value::Unset, |ms| Value::Interval(ms as f64 / 1000.0)),
+        question.map_or(Value::Unset, |q| Value::Str(q.name.clone())),
+        question.map_or(Value::Unset, |q| Value::Count(q.qclass as u64)),
+        question.map_or(Value::Unset, |q| qclass_name(q.qclass)),
+        question.map_or(Value::Unset, |q| Value::Count(q.qtype as u64)),
+        question.map_or(Value::Unset, |q| Value::Str(dns::type_name(q.qtype))),
+        response.map_or(Value::Unset, |r| Value::Count(r.rcode as u64)),
+        response.map_or(Value::Unset, |r| Value::Str(dns::rcode_name(r.rcode))),
+        Value::Bool(response.is_some_and(|r| r.authoritative)),
+        Value::Bool(response.is_some_and(|r| r.truncated)),
+        Value::Bool(msg.is_some_and(|m| m.recursion_desired)),
+        Value::Bool(response.is_some_and(|r| r.recursion_available)),
+        response.map_or(Value::Unset, |r| {
+            Value::Strings(r.answers.iter().map(|a| a.data.clone()).collect())
+        }),
+        response.map_or(Value::Unset, |r| {
+            Value::Intervals(r.answers.iter().map(|a| a.ttl as f64).collect())
+        }),
+    ]);
+    values
+}
+
+fn http_values(ts: u128, uid: &str, id: [Value; 4], depth: u64, tx: &HttpTransaction) -> Vec<Value> {
+    let req = tx.request.as_ref();
+    let resp = tx.response.as_ref();
+    let req_header = |name: &str| opt_str(req.and_then(|r| http::header(&r.headers, name)));
+    let mut values = vec![Value::Time(ts), Value::Str(uid.to_string())];
+    values.extend(id);
+    values.extend([
+        Value::Count(depth),
+        opt_str(req.map(|r| r.method.as_str())),
+        opt_str(req.and_then(|r| r.host.as_deref())),
+        opt_str(req.map(|r| r.uri.as_str())),
+        req_header("referer"),
+        opt_str(req.map(|r| r.version.trim_start_matches("HTTP/"))),
+        req_header("user-agent"),
+        req_header("origin"),
+        Value::Count(req.map_or(0, |r| r.body_len as u64)),
+        Value::Count(resp.map_or(0, |r| r.body_len as u64)),
+        resp.map_or(Value::Unset, |r| Value::Count(r.status as u64)),
+        opt_str(resp.map(|r| r.reason.as_str())),
+        match resp.and_then(|r| http::header(&r.headers, "content-type")) {
+            Some(ct) => Value::Strings(vec![ct.split(';').next().unwrap_or(ct).trim().to_string()]),
+            None => Value::Unset,
+        },
+    ]);
+    values
+}
+
+/// Writes conn.log, dns.log, http.log and ssl.log (Zeek TSV, or JSON
+/// lines) from the decoded packets. Records of the same connection share
+/// the conn.log UID.
+pub struct ZeekLogger {
+    dir: PathBuf,
+    conn: LogFile,
+    dns: LogFile,
+    http: LogFile,
+    ssl: LogFile,
+    table: FlowTable,
+    conns: HashMap<u64, ConnState>,
+    /// "ip:port" pairs of TCP flows, to credit stream gaps to their flow.
+    tcp_flows: HashMap<(String, String), u64>,
+    pending_dns: HashMap<(u64, u16), PendingQuery>,
+    last_check: u128,
+}
+
+impl ZeekLogger {
+    pub fn create(dir: &Path, json: bool) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(ZeekLogger {
+            dir: dir.to_path_buf(),
+            conn: LogFile::create(dir, "conn", CONN_FIELDS, json)?,
+            dns: LogFile::create(dir, "dns", DNS_FIELDS, json)?,
+            http: LogFile::create(dir, "http", HTTP_FIELDS, json)?,
+            ssl: LogFile::create(dir, "ssl", SSL_FIELDS, json)?,
+            table: FlowTable::new(),
+            conns: HashMap::new(),
+            tcp_flows: HashMap::new(),
+            pending_dns: HashMap::new(),
+            last_check: 0,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Accounts a packet once the decoders (DNS pairing, HTTP, TLS
+    /// certificates) ran on it; `events` are its TCP stream events.
+    pub fn observe(&mut self, packet: &Packet, iface: &str, application: &str, events: &[StreamEvent]) {
+        let Some(flow) = self.table.observe(packet, Some(iface), application).cloned() else {
+            return;
+        };
+        let Some(ip) = &packet.ip else {
+            return;
+        };
+        let (sport, flags, tcp) = match &packet.transport {
+            Some(TransportProtocol::Tcp(t)) => (Some(t.src_port), t.flags, true),
+            Some(TransportProtocol::Udp(u)) => (Some(u.src_port), 0, false),
+            _ => (None, 0, false),
+        };
+        let to_server = ip.src_ip == flow.client_ip && sport == flow.client_port;
+
+        let conn = self.conns.entry(flow.id).or_insert_with(|| ConnState {
+            uid: uid(&flow),
+            id: conn_id(&flow),
+            ..Default::default()
+        });
+        let payload = parser::transport_payload(&packet.payload).len() as u64;
+        let ip_bytes = parser::ip_length(&packet.payload) as u64;
+        if to_server {
+            conn.orig_bytes += payload;
+            conn.orig_ip_bytes += ip_bytes;
+        } else {
+            conn.resp_bytes += payload;
+            conn.resp_ip_bytes += ip_bytes;
+        }
+
+        let mut letters = Vec::new();
+        if tcp {
+            if flags & TCP_SYN != 0 {
+                letters.push(if flags & TCP_ACK == 0 { 'S' } else { 'H' });
+            }
+            if flags & TCP_FIN != 0 {
+                letters.push('F');
+            }
+            if flags & TCP_RST != 0 {
+                letters.push('R');
+            }
+            if flags == TCP_ACK && payload == 0 {
+                letters.push('A');
+            }
+            for flag in packet.tcp_analysis.iter().flat_map(|a| &a.flags) {
+                match flag {
+                    TcpExpert::Retransmission | TcpExpert::FastRetransmission | TcpExpert::SpuriousRetransmission => {
+                        letters.push('T')
+                    }
+                    TcpExpert::ZeroWindow => letters.push('W'),
+                    _ => {}
+                }
+            }
+        }
+        if payload > 0 {
+            letters.push('D');
+        }
+        for c in letters {
+            let c = if to_server { c } else { c.to_ascii_lowercase() };
+            if !conn.history.contains(c) {
+                conn.history.push(c);
+            }
+        }
+
+        if tcp {
+            if let (Some(cp), Some(sp)) = (flow.client_port, flow.server_port) {
+                let client = format!("{}:{cp}", flow.client_ip);
+                let server = format!("{}:{sp}", flow.server_ip);
+                self.tcp_flows.insert((server.clone(), client.clone()), flow.id);
+                self.tcp_flows.insert((client, server), flow.id);
+            }
+        }
+        if let Some(info) = &packet.tls {
+            conn.ssl
+                .get_or_insert_with(|| SslState {
+                    ts: packet.timestamp,
+                    ..Default::default()
+                })
+                .update(info);
+        }
+        self.stream_events(events);
+        self.log_decoded(flow.id, tcp, packet);
+        self.tick(packet.timestamp);
+    }
+
+    /// End of the capture: accounts the events of `TcpReassembler::finish`
+    /// for one connection once the decoders ran on `packet`, which stands
+    /// for it (`stream::end_of_capture`).
+    pub fn observe_end(&mut self, packet: &Packet, events: &[StreamEvent]) {
+        self.stream_events(events);
+        let (Some(ip), Some(TransportProtocol::Tcp(t))) = (&packet.ip, &packet.transport) else {
+            return;
+        };
+        let client = format!("{}:{}", ip.src_ip, t.src_port);
+        let server = format!("{}:{}", ip.dst_ip, t.dst_port);
+        if let Some(&flow_id) = self.tcp_flows.get(&(client, server)) {
+            self.log_decoded(flow_id, true, packet);
+        }
+    }
+
+    fn stream_events(&mut self, events: &[StreamEvent]) {
+        for event in events {
+            let (key, direction) = match event {
+                StreamEvent::Data { key, direction, .. } | StreamEvent::Gap { key, direction, .. } => (key, direction),
+                StreamEvent::Closed { .. } => continue,
+            };
+            let id = self.tcp_flows.get(&(key.client.clone(), key.server.clone()));
+            let Some(conn) = id.and_then(|id| self.conns.get_mut(id)) else {
+                continue;
+            };
+            let records = &mut conn.tls_records[(*direction == Direction::ToClient) as usize];
+            match event {
+                StreamEvent::Data { data, .. } => records.feed(data),
+                StreamEvent::Gap { missing, .. } => {
+                    conn.missed_bytes += missing;
+                    records.lost = true;
+                }
+                StreamEvent::Closed { .. } => {}
+            }
+            if let Some(ssl) = &mut conn.ssl {
+                ssl.established |= conn.tls_records.iter().all(|r| r.done);
+            }
+        }
+    }
+
+    /// Logs the HTTP transactions and DNS messages the decoders attached.
+    fn log_decoded(&mut self, flow_id: u64, tcp: bool, packet: &Packet) {
+        let Some(conn) = self.conns.get_mut(&flow_id) else {
+            return;
+        };
+        // The HTTP tracker reports each request on its own first, then
+        // again with its response.
+        for tx in &packet.http {
+            if tx.response.is_none() {
+                conn.http_depth += 1;
+                conn.http_pending.push_back((packet.timestamp, conn.http_depth, tx.clone()));
+                continue;
+            }
+            let (ts, depth) = match tx.request.is_some().then(|| conn.http_pending.pop_front()).flatten() {
+                Some((ts, depth, _)) => (ts, depth),
+                None => {
+                    conn.http_depth += 1;
+                    (packet.timestamp, conn.http_depth)
+                }
+            };
+            self.http.write(&http_values(ts, &conn.uid, conn.id.clone(), depth, tx));
+        }
+        for msg in &packet.dns {
+            let proto = if tcp { "tcp" } else { "udp" };
+            if !msg.is_response {
+                let key = (flow_id, msg.id);
+                // A retransmitted query keeps the first timestamp.
+                self.pending_dns.entry(key).or_insert_with(|| PendingQuery {
+                    ts: packet.timestamp,
+                    uid: conn.uid.clone(),
+                    id: conn.id.clone(),
+                    proto,
+                    query: msg.clone(),
+                });
+            } else {
+                let values = match self.pending_dns.remove(&(flow_id, msg.id)) {
+                    Some(q) => {
+                        let rtt = msg
+                            .latency_ms
+                            .map(|ms| ms as u128)
+                            .unwrap_or(packet.timestamp.saturating_sub(q.ts));
+                        dns_values(q.ts, &q.uid, q.id, q.proto, Some(&q.query), Some(msg), Some(rtt))
+                    }
+                    None => dns_values(packet.timestamp, &conn.uid, conn.id.clone(), proto, None, Some(msg), None),
+                };
+                self.dns.write(&values);
+            }
+        }
+    }
+
+    /// Logs the connections that ended or went idle and the DNS queries
+    /// left unanswered; checks at most once per second.
+    pub fn tick(&mut self, now: u128) {
+        if now < self.last_check + CHECK_INTERVAL_MS {
+            return;
+        }
+        self.last_check = now;
+        for record in self.table.expire(now, IDLE_TIMEOUT_MS, u128::MAX) {
+            self.write_conn(&record);
+        }
+        let stale: Vec<(u64, u16)> = self
+            .pending_dns
+            .iter()
+            .filter(|(_, q)| now.saturating_sub(q.ts) > DNS_TIMEOUT_MS)
+            .map(|(k, _)| *k)
+            .collect();
+        for key in stale {
+            if let Some(q) = self.pending_dns.remove(&key) {
+                self.dns
+                    .write(&dns_values(q.ts, &q.uid, q.id, q.proto, Some(&q.query), None, None));
+            }
+        }
+    }
+
+    fn write_conn(&mut self, record: &FlowRecord) {
+        let flow = &record.flow;
+        let conn = self.conns.remove(&flow.id).unwrap_or_else(|| ConnState {
+            uid: uid(flow),
+            ..Default::default()
+        });
+        self.tcp_flows.retain(|_, id| *id != flow.id);
+
+        let mut values = vec![Value::Time(flow.first_seen), Value::Str(conn.uid.clone())];
+        values.extend(conn_id(flow));
+        values.extend([
+            Value::Str(proto_name(flow.protocol).to_string()),
+            service(&flow.application).map_or(Value::Unset, Value::Str),
+            Value::Interval(flow.duration_ms() as f64 / 1000.0),
+            Value::Count(conn.orig_bytes),
+            Value::Count(conn.resp_bytes),
+            Value::Str(conn_state(flow.protocol, &conn.history, flow.packets_to_client).to_string()),
+            Value::Count(conn.missed_bytes),
+            Value::Str(conn.history.clone()),
+            Value::Count(flow.packets_to_server),
+            Value::Count(conn.orig_ip_bytes),
+            Value::Count(flow.packets_to_client),
+            Value::Count(conn.resp_ip_bytes),
+            flow.vlan.map_or(Value::Unset, |v| Value::Count(v as u64)),
+        ]);
+        self.conn.write(&values);
+
+        for (ts, depth, tx) in &conn.http_pending {
+            self.http.write(&http_values(*ts, &conn.uid, conn_id(flow), *depth, tx));
+        }
+        if let Some(ssl) = &conn.ssl {
+            self.ssl.write(&ssl.values(&conn.uid, conn_id(flow)));
+        }
+    }
+
+    /// End of the capture: logs everything still open and closes the files.
+    /// Returns the number of records written per log.
+    pub fn finish(mut self) -> io::Result<Vec<(&'static str, u64)>> {
+        for record in self.table.drain() {
+            self.write_conn(&record);
+        }
+        let mut pending: Vec<PendingQuery> = self.pending_dns.drain().map(|(_, q)| q).collect();
+        pending.sort_by_key(|q| q.ts);
+        for q in pending {
+            self.dns
+                .write(&dns_values(q.ts, &q.uid, q.id, q.proto, Some(&q.query), None, None));
+        }
+        let mut counts = Vec::new();
+        for log in [self.conn, self.dns, self.http, self.ssl] {
+            let path = log.path;
+            counts.push((path, log.close()?));
+        }
+        Ok(counts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(kind: u8, len: usize) -> Vec<u8> {
+        let mut out = vec![kind, 3, 3, (len >> 8) as u8, len as u8];
+        out.resize(5 + len, 0xAA);
+        out
+    }
+
+    #[test]
+    fn handshake_completion() {
+        // (records of one direction, done)
+        let cases: [(&[(u8, usize)], bool); 5] = [
+            (&[(22, 512)], false),
+            (&[(22, 512), (20, 1)], false),
+            // TLS 1.2: Finished follows ChangeCipherSpec.
+            (&[(22, 512), (20, 1), (22, 40)], true),
+            // TLS 1.3: encrypted records are application data.
+            (&[(22, 90), (20, 1), (23, 2048)], true),
+            (&[(23, 300)], true),
+        ];
+        for (records, done) in cases {
+            let stream: Vec<u8> = records.iter().flat_map(|&(kind, len)| record(kind, len)).collect();
+            for chunk in [stream.len(), 7, 1] {
+                let mut walker = TlsRecords::default();
+                for part in stream.chunks(chunk) {
+                    walker.feed(part);
+                }
+                assert_eq!((walker.done, walker.lost), (done, false), "{records:?} par {chunk}");
+            }
+        }
+
+        let mut walker = TlsRecords::default();
+        walker.feed(b"GET / HTTP/1.1\r\n");
+        walker.feed(&record(23, 10));
+        assert!(walker.lost && !walker.done);
+    }
+}
@@ -4,10 +4,156 @@ pub mod core {
     pub mod classifier;
     pub mod enrichment;
     pub mod alerts;
+    pub mod arpwatch;
+    pub mod beacon;
+    pub mod credentials;
+    pub mod scan;
+    pub mod volume;
+    pub mod rules;
+    pub mod triage;
+    pub mod suricata;
     pub mod models;
+    pub mod tls;
+    pub mod dns;
+    pub mod dnsanomaly;
+    pub mod dhcp;
+    pub mod netbios;
+    pub mod inventory;
+    pub mod filter;
+    pub mod fingerprint;
+    pub mod handshake;
+    pub mod x509;
+    pub mod stream;
+    pub mod tcpanalysis;
+    pub mod follow;
+    pub mod flow;
+    pub mod netflow;
+    pub mod zeek;
+    pub mod http;
+    pub mod hpack;
+    pub mod http2;
+    pub mod protobuf;
+    pub mod grpc;
+    pub mod objects;
 }
 
 /// Public API surface for embedders (e.g., Tauri)
 pub fn devices() -> Vec<String> {
     core::capture::list_devices()
 }
+
+/// Builds the LAN device inventory (mDNS, LLMNR and NetBIOS names, DNS-SD
+/// services) from already parsed packets.
+pub fn name_inventory<'a>(packets: impl IntoIterator<Item = &'a core::models::Packet>) -> Vec<core::models::Device> {
+    let mut inventory = core::inventory::NameInventory::new();
+    for packet in packets {
+        inventory.observe(packet);
+    }
+    inventory.devices()
+}
+
+/// Groups already parsed packets into conversations (bidirectional 5-tuple
+/// per VLAN) with per-direction counters, TCP state and performance, and
+/// application label.
+pub fn conversations<'a>(packets: impl IntoIterator<Item = &'a core::models::Packet>) -> Vec<core::models::Flow> {
+    let mut table = core::flow::FlowTable::new();
+    let mut analyzer = core::tcpanalysis::TcpAnalyzer::new();
+    for packet in packets {
+        let mut packet = packet.clone();
+        analyzer.observe(&mut packet);
+        table.observe(&packet, None, core::classifier::classify(&packet));
+    }
+    table.flows()
+}
+
+/// Builds the tables of DHCP servers and leases from already parsed packets.
+/// Servers in `trusted` (addresses or identifiers) are expected; without
+/// any, the first server seen is.
+pub fn dhcp_table<'a>(
+    packets: impl IntoIterator<Item = &'a core::models::Packet>,
+    trusted: Vec<String>,
+) -> (Vec<core::models::DhcpServer>, Vec<core::models::DhcpLease>) {
+    let mut monitor = core::dhcp::DhcpMonitor::new(trusted);
+    for (i, packet) in packets.into_iter().enumerate() {
+        monitor.observe(packet, i as u64 + 1);
+    }
+    (monitor.servers(), monitor.leases())
+}
+
+/// Learns per-host, per-protocol and half-open connection baselines from
+/// already parsed packets, starting from `known` (e.g. the
+/// `wirefish_baselines.json` kept by the CLI), for the dashboard.
+pub fn baselines<'a>(
+    packets: impl IntoIterator<Item = &'a core::models::Packet>,
+    known: Vec<core::models::Baseline>,
+) -> Vec<core::models::Baseline> {
+    let mut detector = core::volume::VolumeDetector::with_baselines(core::volume::VolumeConfig::default(), known);
+    for packet in packets {
+        detector.observe(packet, core::classifier::classify(packet));
+    }
+    detector.baselines()
+}
+
+/// Runs detection rules (see `core::rules::load_rules`) and the built-in
+/// detectors over already parsed packets, numbered from 1, with TCP
+/// reassembly for stream rules and application decoders. Streams still
+/// open after the last packet are flushed and inspected too.
+pub fn alerts<'a>(
+    packets: impl IntoIterator<Item = &'a core::models::Packet>,
+    rules: Vec<core::rules::Rule>,
+) -> Vec<core::models::Alert> {
+    let mut engine = core::rules::RuleEngine::new(rules);
+    let mut streams = core::stream::TcpReassembler::new();
+    let mut hellos = core::handshake::HelloReassembler::new();
+    let mut http = core::http::HttpTracker::new();
+    let mut dns_tcp = core::dns::DnsTracker::new();
+    let mut credentials = core::credentials::CredentialDetector::new();
+    let mut scans = core::scan::ScanDetector::new(core::scan::ScanConfig::default());
+    let mut arp = core::arpwatch::ArpWatch::new();
+    let mut dhcp = core::dhcp::DhcpMonitor::new(Vec::new());
+    let mut dns = core::dnsanomaly::DnsAnomalyDetector::new();
+    let mut beacons = core::beacon::BeaconDetector::new();
+    let mut volume = core::volume::VolumeDetector::new(core::volume::VolumeConfig::default());
+    let mut alerts = Vec::new();
+    // Number and timestamp of the last packet.
+    let mut last = (0, 0);
+    for (i, packet) in packets.into_iter().enumerate() {
+        let id = i as u64 + 1;
+        let mut packet = packet.clone();
+        let events = streams.observe(&packet);
+        hellos.observe(&mut packet, &events);
+        http.observe(&mut packet, &events);
+        dns_tcp.observe(&mut packet, &events);
+        let application = core::classifier::classify(&packet);
+        alerts.extend(engine.observe(&packet, id, application, &events));
+        alerts.extend(credentials.observe(&packet, id, &events));
+        alerts.extend(scans.observe(&packet, id));
+        alerts.extend(arp.observe(&packet, id));
+        alerts.extend(dhcp.observe(&packet, id));
+        alerts.extend(dns.observe(&packet, id));
+        alerts.extend(beacons.observe(&packet, id));
+        alerts.extend(volume.observe(&packet, application));
+        last = (id, packet.timestamp);
+    }
+    for (mut packet, events) in core::stream::end_of_capture(streams.finish(), last.1) {
+        http.observe(&mut packet, &events);
+        dns_tcp.observe(&mut packet, &events);
+        alerts.extend(engine.finish(&packet, last.0, &events));
+        alerts.extend(credentials.observe(&packet, last.0, &events));
+    }
+    alerts
+}
+
+/// Merges alerts with the same rule, source and destination addresses into
+/// groups with a count and first/last seen, marking those acknowledged in
+/// `acks` (e.g. the `wirefish_alert_acks.json` kept by the CLI).
+pub fn alert_groups(
+    alerts: &[core::models::Alert],
+    acks: &[core::models::AlertAck],
+) -> Vec<core::models::AlertGroup> {
+    let mut triage = core::triage::AlertTriage::new(Default::default(), acks);
+    for alert in alerts {
+        triage.admit(alert);
+    }
+    triage.groups()
+}
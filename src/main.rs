@@ -2,14 +2,44 @@ mod core;
 
 use crossbeam::channel::{unbounded, Receiver};
 use std::io::{self, Write};
+use std::path::PathBuf;
 use std::thread;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
+use crate::core::alerts::{self, FingerprintList};
 use crate::core::capture;
 use crate::core::classifier;
-use crate::core::models::Packet;
+use crate::core::arpwatch::{self, ArpWatch};
+use crate::core::beacon::BeaconDetector;
+use crate::core::credentials::CredentialDetector;
+use crate::core::dhcp::{self, DhcpMonitor};
+use crate::core::dns::{self, DnsTracker};
+use crate::core::dnsanomaly::DnsAnomalyDetector;
+use crate::core::filter::Filter;
+use crate::core::fingerprint::FingerprintTracker;
+use crate::core::flow::{self, FlowTable};
+use crate::core::follow::{self, FollowFormat};
+use crate::core::grpc::{self, GrpcDecoder};
+use crate::core::handshake::{HandshakeReassembler, HelloReassembler};
+use crate::core::http::{self, HttpTracker};
+use crate::core::http2::{self, Http2Tracker};
+use crate::core::inventory::{self, NameInventory};
+use crate::core::models::{Alert, AlertAck, AlertGroup, Packet, Severity, TlsInfo};
+use crate::core::netbios;
+use crate::core::netflow::{ExportFormat, FlowExporter};
+use crate::core::objects;
+use crate::core::protobuf::DescriptorPool;
+use crate::core::rules::{self, RuleEngine};
+use crate::core::scan::{ScanConfig, ScanDetector};
+use crate::core::volume::{VolumeConfig, VolumeDetector};
 use crate::core::storage;
+use crate::core::suricata::{self, RuleVars};
+use crate::core::stream::{self, CloseReason, Direction, OverlapPolicy, ReassemblyConfig, StreamEvent, TcpReassembler};
+use crate::core::tcpanalysis::{self, TcpAnalyzer};
+use crate::core::tls;
+use crate::core::triage::{self, AlertTriage};
+use crate::core::zeek::ZeekLogger;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum OutputMode {
@@ -18,17 +48,52 @@ enum OutputMode {
     Both,
 }
 
+struct Options {
+    mode: OutputMode,
+    iface_index: Option<usize>,
+    filter: Filter,
+    fingerprint_list: Option<PathBuf>,
+    descriptor_set: Option<PathBuf>,
+    reassembly: ReassemblyConfig,
+    export_flows: Option<String>,
+    export_format: ExportFormat,
+    flow_idle_timeout_ms: u128,
+    flow_active_timeout_ms: u128,
+    zeek_logs: Option<PathBuf>,
+    zeek_json: bool,
+    rules: Vec<PathBuf>,
+    rule_vars: RuleVars,
+    alert_filters: Option<PathBuf>,
+    scan: ScanConfig,
+    volume: VolumeConfig,
+    dhcp_servers: Vec<String>,
+}
+
+/// Per-capture state handed to the packet loop.
+struct Session {
+    known_fps: Option<FingerprintList>,
+    grpc_decoder: GrpcDecoder,
+    streams: TcpReassembler,
+    flow_export: Option<(FlowTable, FlowExporter)>,
+    zeek: Option<ZeekLogger>,
+    rules: Option<RuleEngine>,
+    triage: AlertTriage,
+    scans: ScanDetector,
+    arp: ArpWatch,
+    dhcp: DhcpMonitor,
+    volume: VolumeDetector,
+}
+
 static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
 
 fn fit_cell(s: &str, width: usize) -> String {
-    if s.len() <= width {
+    if s.chars().count() <= width {
         return s.to_string();
     }
     if width <= 1 {
         return "…".to_string();
     }
-    let mut tmp = s.to_string();
-    tmp.truncate(width - 1);
+    let mut tmp: String = s.chars().take(width - 1).collect();
     tmp.push('…');
     tmp
 }
@@ -65,56 +130,608 @@ fn parse_args_for_check() -> Option<u64> {
     }
 }
 
-fn parse_args() -> (OutputMode, Option<usize>) {
-    let mut mode = OutputMode::PacketsOnly;
-    let mut iface_index: Option<usize> = None;
+/// `wirefish export-objects http --out <dir> <capture.pcap>`
+fn run_export_objects() -> Option<Result<(), String>> {
+    let mut args = std::env::args().skip(1);
+    if args.next()? != "export-objects" {
+        return None;
+    }
+    Some(export_objects(args))
+}
+
+/// `wirefish follow <id> [--hex] [--raw <fichier>] [--side client|server]`
+fn run_follow() -> Option<Result<(), String>> {
+    let mut args = std::env::args().skip(1);
+    if args.next()? != "follow" {
+        return None;
+    }
+    Some(follow(args))
+}
+
+fn follow(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let usage = "Usage: wirefish follow <id> [--hex] [--raw <fichier>] [--side client|server]";
+    let mut id = None;
+    let mut format = FollowFormat::Ascii;
+    let mut raw = None;
+    let mut side = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--hex" => format = FollowFormat::Hex,
+            "--raw" => raw = Some(PathBuf::from(args.next().ok_or(usage)?)),
+            "--side" => {
+                side = match args.next().as_deref() {
+                    Some("client") => Some(Direction::ToServer),
+                    Some("server") => Some(Direction::ToClient),
+                    _ => return Err(usage.to_string()),
+                }
+            }
+            _ => id = Some(arg.parse::<u64>().map_err(|_| format!("ID invalide : {arg}"))?),
+        }
+    }
+    let id = id.ok_or(usage)?;
+
+    let packets: Vec<(u64, Packet)> = storage::stored_packets()?
+        .into_iter()
+        .map(|(rec, packet)| (rec.id, packet))
+        .collect();
+    let stream = follow::follow_stream(&packets, id)?;
+    match raw {
+        Some(path) => {
+            let data = stream.raw(side);
+            std::fs::write(&path, &data).map_err(|e| format!("{}: {e}", path.display()))?;
+            println!("{} octets écrits dans {}", data.len(), path.display());
+        }
+        None => follow::print_stream(&stream, format),
+    }
+    Ok(())
+}
+
+/// `wirefish conversations [--sort bytes|packets|duration] [--json]`
+fn run_conversations() -> Option<Result<(), String>> {
+    let mut args = std::env::args().skip(1);
+    if args.next()? != "conversations" {
+        return None;
+    }
+    Some(conversations(args))
+}
+
+fn conversations(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let usage = "Usage: wirefish conversations [--sort bytes|packets|duration] [--json]";
+    let mut sort = None;
+    let mut json = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--sort" => sort = Some(args.next().ok_or(usage)?),
+            _ => return Err(usage.to_string()),
+        }
+    }
+
+    let mut table = FlowTable::new();
+    let mut analyzer = TcpAnalyzer::new();
+    for (rec, mut packet) in storage::stored_packets()? {
+        analyzer.observe(&mut packet);
+        table.observe(&packet, Some(&rec.iface), &rec.proto);
+    }
+    let mut flows = table.flows();
+    match sort.as_deref() {
+        None => {}
+        Some("bytes") => flows.sort_by_key(|f| std::cmp::Reverse(f.bytes())),
+        Some("packets") => flows.sort_by_key(|f| std::cmp::Reverse(f.packets())),
+        Some("duration") => flows.sort_by_key(|f| std::cmp::Reverse(f.duration_ms())),
+        Some(_) => return Err(usage.to_string()),
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&flows).map_err(|e| e.to_string())?);
+    } else {
+        flow::print_report(&flows);
+    }
+    Ok(())
+}
+
+/// `wirefish alerts [--min-severity <niveau>] [--all] [--unacked] [--json]`,
+/// `wirefish alerts ack|unack <groupe>...`
+fn run_alerts() -> Option<Result<(), String>> {
+    let mut args = std::env::args().skip(1).peekable();
+    if args.next()? != "alerts" {
+        return None;
+    }
+    match args.peek().map(String::as_str) {
+        Some("ack") => Some(acknowledge_alerts(args.skip(1), true)),
+        Some("unack") => Some(acknowledge_alerts(args.skip(1), false)),
+        _ => Some(list_alerts(args)),
+    }
+}
+
+fn list_alerts(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let usage = "Usage: wirefish alerts [--min-severity info|low|medium|high|critical] [--all] [--unacked] [--json]";
+    let mut min_severity = Severity::Info;
+    let mut all = false;
+    let mut unacked = false;
+    let mut json = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--all" => all = true,
+            "--unacked" => unacked = true,
+            "--min-severity" => {
+                min_severity = args
+                    .next()
+                    .as_deref()
+                    .and_then(alerts::parse_severity)
+                    .ok_or(usage)?;
+            }
+            _ => return Err(usage.to_string()),
+        }
+    }
+
+    let acked = triage::ack_keys(&storage::stored_alert_acks()?);
+    if all {
+        let alerts: Vec<Alert> = storage::stored_alerts()?
+            .into_iter()
+            .filter(|a| a.severity >= min_severity)
+            .filter(|a| !unacked || !acked.contains(&triage::group_key(&a.rule_id, a.src_ip.as_deref(), a.dst_ip.as_deref())))
+            .collect();
+        if json {
+            println!("{}", serde_json::to_string_pretty(&alerts).map_err(|e| e.to_string())?);
+        } else {
+            alerts::print_report(&alerts);
+        }
+        return Ok(());
+    }
+
+    // Acknowledgements made since the capture apply too.
+    let groups: Vec<AlertGroup> = storage::stored_alert_groups()?
+        .into_iter()
+        .map(|g| AlertGroup {
+            acknowledged: acked.contains(&triage::group_key(&g.rule_id, g.src_ip.as_deref(), g.dst_ip.as_deref())),
+            ..g
+        })
+        .filter(|g| g.severity >= min_severity && !(unacked && g.acknowledged))
+        .collect();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&groups).map_err(|e| e.to_string())?);
+    } else {
+        triage::print_groups(&groups);
+    }
+    Ok(())
+}
+
+/// Acknowledges (or no longer) alert groups of the last capture, for the
+/// following captures too.
+fn acknowledge_alerts(args: impl Iterator<Item = String>, ack: bool) -> Result<(), String> {
+    let ids: Vec<String> = args.collect();
+    if ids.is_empty() || ids.iter().any(|id| id.starts_with('-')) {
+        return Err("Usage: wirefish alerts ack|unack <groupe>... (voir wirefish alerts)".to_string());
+    }
+    let mut acks = storage::stored_alert_acks()?;
+    if ack {
+        let groups = storage::stored_alert_groups()?;
+        let now = chrono::Utc::now().timestamp_millis() as u128;
+        if let Some(id) = ids.iter().find(|id| !groups.iter().any(|g| g.id == **id)) {
+            return Err(format!("Groupe d’alertes {id} introuvable dans la dernière capture"));
+        }
+        let acked = triage::ack_keys(&acks);
+        for group in groups.iter().filter(|g| ids.contains(&g.id)) {
+            let id = &group.id;
+            if acked.contains(&triage::group_key(&group.rule_id, group.src_ip.as_deref(), group.dst_ip.as_deref())) {
+                continue;
+            }
+            acks.push(AlertAck {
+                id: id.clone(),
+                rule_id: group.rule_id.clone(),
+                src_ip: group.src_ip.clone(),
+                dst_ip: group.dst_ip.clone(),
+                acknowledged_at: now,
+            });
+            println!("✓ {id} acquitté ({})", group.rule_id);
+        }
+    } else {
+        for id in &ids {
+            let before = acks.len();
+            // Acknowledgements recorded with a shorter identifier match too.
+            acks.retain(|a| triage::group_id(&a.rule_id, a.src_ip.as_deref(), a.dst_ip.as_deref()) != *id);
+            if acks.len() == before {
+                return Err(format!("Groupe d’alertes {id} non acquitté"));
+            }
+            println!("{id} n’est plus acquitté");
+        }
+    }
+    storage::save_alert_acks(&acks)
+}
 
-    for arg in std::env::args().skip(1) {
+fn run_arp() -> Option<Result<(), String>> {
+    let mut args = std::env::args().skip(1);
+    if args.next()? != "arp" {
+        return None;
+    }
+    Some(list_arp(args))
+}
+
+fn list_arp(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut json = false;
+    for arg in args {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ => return Err("Usage: wirefish arp [--json]".to_string()),
+        }
+    }
+
+    let bindings = ArpWatch::with_bindings(storage::stored_arp_bindings()?).bindings();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&bindings).map_err(|e| e.to_string())?);
+    } else {
+        arpwatch::print_report(&bindings);
+    }
+    Ok(())
+}
+
+fn run_dhcp() -> Option<Result<(), String>> {
+    let mut args = std::env::args().skip(1);
+    if args.next()? != "dhcp" {
+        return None;
+    }
+    Some(list_dhcp(args))
+}
+
+fn list_dhcp(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let usage = "Usage: wirefish dhcp [--dhcp-server <ip|id>]... [--json]
+  --dhcp-server : serveur DHCP légitime ; sans cette option, le premier serveur vu (par version d'IP) est considéré comme légitime";
+    let mut trusted = Vec::new();
+    let mut json = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--dhcp-server" => trusted.push(args.next().ok_or(usage)?),
+            _ => return Err(usage.to_string()),
+        }
+    }
+
+    let mut monitor = DhcpMonitor::new(trusted);
+    for (rec, packet) in storage::stored_packets()? {
+        monitor.observe(&packet, rec.id);
+    }
+    let (servers, leases) = (monitor.servers(), monitor.leases());
+    if json {
+        let table = serde_json::json!({ "servers": servers, "leases": leases });
+        println!("{}", serde_json::to_string_pretty(&table).map_err(|e| e.to_string())?);
+    } else {
+        dhcp::print_report(&servers, &leases);
+    }
+    Ok(())
+}
+
+fn export_objects(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let usage = "Usage: wirefish export-objects http --out <dossier> <capture.pcap>";
+    if args.next().as_deref() != Some("http") {
+        return Err(usage.to_string());
+    }
+    let mut out = None;
+    let mut input = None;
+    while let Some(arg) = args.next() {
+        if arg == "--out" {
+            out = Some(PathBuf::from(args.next().ok_or(usage)?));
+        } else {
+            input = Some(PathBuf::from(arg));
+        }
+    }
+    let (Some(out), Some(input)) = (out, input) else {
+        return Err(usage.to_string());
+    };
+
+    let objects = objects::export_http(&input, &out)?;
+    for obj in &objects {
+        println!(
+            "{:>6}  {:<40} {:>10} o  {}{}",
+            obj.frame,
+            obj.file,
+            obj.size,
+            obj.host.as_deref().unwrap_or("?"),
+            obj.uri.as_deref().unwrap_or("")
+        );
+        if let Some(e) = &obj.decode_error {
+            eprintln!("⚠️ {} : {e} (corps enregistré tel quel)", obj.file);
+        }
+    }
+    println!("{} objet(s) HTTP exporté(s) dans {}", objects.len(), out.display());
+    Ok(())
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut opts = Options {
+        mode: OutputMode::PacketsOnly,
+        iface_index: None,
+        filter: Filter::default(),
+        fingerprint_list: None,
+        descriptor_set: None,
+        reassembly: ReassemblyConfig::default(),
+        export_flows: None,
+        export_format: ExportFormat::Ipfix,
+        flow_idle_timeout_ms: 15_000,
+        flow_active_timeout_ms: 60_000,
+        zeek_logs: None,
+        zeek_json: false,
+        rules: Vec::new(),
+        rule_vars: RuleVars::default(),
+        alert_filters: None,
+        scan: ScanConfig::default(),
+        volume: VolumeConfig::default(),
+        dhcp_servers: Vec::new(),
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
         if arg.starts_with('-') {
             match arg.as_str() {
-                "--debug-only" => mode = OutputMode::DebugOnly,
-                "--debug" | "--both" => mode = OutputMode::Both,
-                "--packets-only" => mode = OutputMode::PacketsOnly,
+                "--debug-only" => opts.mode = OutputMode::DebugOnly,
+                "--debug" | "--both" => opts.mode = OutputMode::Both,
+                "--packets-only" => opts.mode = OutputMode::PacketsOnly,
+                "--filter" => {
+                    let expr = args
+                        .next()
+                        .ok_or("--filter attend une expression (ex: tls.sni~example.com)")?;
+                    opts.filter.add(&expr)?;
+                }
+                "--fingerprints" => {
+                    let path = args
+                        .next()
+                        .ok_or("--fingerprints attend un fichier de JA3/JA4 connus")?;
+                    opts.fingerprint_list = Some(PathBuf::from(path));
+                }
+                "--proto-descriptors" => {
+                    let path = args
+                        .next()
+                        .ok_or("--proto-descriptors attend un FileDescriptorSet (protoc --descriptor_set_out)")?;
+                    opts.descriptor_set = Some(PathBuf::from(path));
+                }
+                "--tcp-overlap" => {
+                    opts.reassembly.overlap = match args.next().as_deref() {
+                        Some("first") => OverlapPolicy::First,
+                        Some("last") => OverlapPolicy::Last,
+                        _ => return Err("--tcp-overlap attend first ou last".to_string()),
+                    };
+                }
+                "--export-flows" => {
+                    let addr = args
+                        .next()
+                        .ok_or("--export-flows attend l'adresse du collecteur (ex: 127.0.0.1:4739)")?;
+                    opts.export_flows = Some(addr);
+                }
+                "--export-format" => {
+                    opts.export_format = args
+                        .next()
+                        .as_deref()
+                        .and_then(ExportFormat::parse)
+                        .ok_or("--export-format attend ipfix, v9 ou v5")?;
+                }
+                "--zeek-logs" => {
+                    let dir = args
+                        .next()
+                        .ok_or("--zeek-logs attend un dossier (conn.log, dns.log, http.log, ssl.log)")?;
+                    opts.zeek_logs = Some(PathBuf::from(dir));
+                }
+                "--zeek-json" => opts.zeek_json = true,
+                "--rules" => {
+                    let path = args
+                        .next()
+                        .ok_or("--rules attend un fichier de règles (JSON ou Suricata)")?;
+                    opts.rules.push(PathBuf::from(path));
+                }
+                "--rule-var" => {
+                    let var = args.next().unwrap_or_default();
+                    let (name, value) = var
+                        .split_once('=')
+                        .ok_or("--rule-var attend NOM=valeur (ex: HOME_NET=[10.0.0.0/8])")?;
+                    opts.rule_vars.set(name.trim(), value);
+                }
+                "--alert-filters" => {
+                    let path = args
+                        .next()
+                        .ok_or("--alert-filters attend un fichier JSON (suppress, rate_limit)")?;
+                    opts.alert_filters = Some(PathBuf::from(path));
+                }
+                "--scan-window" => {
+                    let secs: u64 = args
+                        .next()
+                        .and_then(|v| v.parse().ok())
+                        .filter(|&secs| secs > 0)
+                        .ok_or("--scan-window attend un nombre de secondes")?;
+                    opts.scan.window_ms = secs as u128 * 1000;
+                }
+                "--scan-ports" => {
+                    opts.scan.port_threshold = args
+                        .next()
+                        .and_then(|v| v.parse().ok())
+                        .ok_or("--scan-ports attend un nombre de ports distincts")?;
+                }
+                "--scan-hosts" => {
+                    opts.scan.host_threshold = args
+                        .next()
+                        .and_then(|v| v.parse().ok())
+                        .ok_or("--scan-hosts attend un nombre d'hôtes distincts")?;
+                }
+                "--rate-factor" | "--syn-factor" => {
+                    let factor: f64 = args
+                        .next()
+                        .and_then(|v| v.parse().ok())
+                        .filter(|&f: &f64| f > 1.0)
+                        .ok_or(format!("{arg} attend un facteur supérieur à 1 (ex: 3)"))?;
+                    if arg == "--rate-factor" {
+                        opts.volume.rate_factor = factor;
+                    } else {
+                        opts.volume.half_open_factor = factor;
+                    }
+                }
+                "--syn-flood-min" => {
+                    opts.volume.half_open_min = args
+                        .next()
+                        .and_then(|v| v.parse().ok())
+                        .ok_or("--syn-flood-min attend un nombre de connexions semi-ouvertes")?;
+                }
+                "--dhcp-server" => {
+                    let server = args
+                        .next()
+                        .ok_or(
+                            "--dhcp-server attend l'adresse ou l'identifiant d'un serveur DHCP légitime \
+                             (sans cette option, le premier serveur vu est considéré comme légitime)",
+                        )?;
+                    opts.dhcp_servers.push(server);
+                }
+                "--flow-idle-timeout" => {
+                    let secs: u64 = args
+                        .next()
+                        .and_then(|v| v.parse().ok())
+                        .ok_or("--flow-idle-timeout attend un nombre de secondes")?;
+                    opts.flow_idle_timeout_ms = secs as u128 * 1000;
+                }
+                "--flow-active-timeout" => {
+                    let secs: u64 = args
+                        .next()
+                        .and_then(|v| v.parse().ok())
+                        .ok_or("--flow-active-timeout attend un nombre de secondes")?;
+                    opts.flow_active_timeout_ms = secs as u128 * 1000;
+                }
                 _ => {}
             }
-        } else if iface_index.is_none() {
+        } else if opts.iface_index.is_none() {
             if let Ok(idx) = arg.parse::<usize>() {
-                iface_index = Some(idx);
+                opts.iface_index = Some(idx);
             }
         }
     }
 
-    (mode, iface_index)
+    Ok(opts)
 }
 
-fn listen_to_packets(rx: Receiver<Packet>, iface_name: String, mode: OutputMode) {
-    if mode == OutputMode::DebugOnly {
-        for _ in rx.iter() {}
-        return;
+fn proto_label(packet: &Packet, proto: &str) -> String {
+    if let Some(summary) = packet.tcp_analysis.as_ref().and_then(tcpanalysis::summary) {
+        return format!("{proto} {summary}");
+    }
+    if let Some(info) = &packet.tls {
+        return format!("{proto} {}", tls::summary(info));
+    }
+    if let Some(msg) = packet.dns.first() {
+        return format!("{proto} {}", dns::summary(msg));
+    }
+    if let Some(msg) = &packet.nbns {
+        return format!("{proto} {}", netbios::summary(msg));
+    }
+    if let Some(msg) = &packet.dhcp {
+        return format!("{proto} {}", dhcp::summary(msg));
+    }
+    if let Some(call) = packet.grpc.last() {
+        return format!("{proto} {}", grpc::summary(call));
+    }
+    if let Some(info) = &packet.http2 {
+        return format!("{proto} {}", http2::summary(info));
+    }
+    if let Some(summary) = http::summary(&packet.http) {
+        return format!("{proto} {summary}");
+    }
+    proto.to_string()
+}
+
+fn log_stream_events(events: &[StreamEvent]) {
+    for event in events {
+        match event {
+            StreamEvent::Gap { key, direction, missing } => {
+                println!(
+                    "[DEBUG] TCP {} ↔ {} : {missing} octets manquants ({direction:?})",
+                    key.client, key.server
+                );
+            }
+            StreamEvent::Closed { key, reason } if *reason != CloseReason::Fin => {
+                println!("[DEBUG] TCP {} ↔ {} fermée ({reason:?})", key.client, key.server);
+            }
+            _ => {}
+        }
     }
+}
+
+fn listen_to_packets(
+    rx: Receiver<Packet>,
+    iface_name: String,
+    mode: OutputMode,
+    filter: Filter,
+    session: Session,
+) {
+    let Session {
+        known_fps,
+        grpc_decoder,
+        mut streams,
+        mut flow_export,
+        mut zeek,
+        rules: mut rule_engine,
+        mut triage,
+        mut scans,
+        mut arp,
+        mut dhcp,
+        mut volume,
+    } = session;
+    // Debug-only mode runs the same analysis, without the table.
+    let show_table = mode != OutputMode::DebugOnly;
 
-    const INNER_WIDTH: usize = 98;
+    const INNER_WIDTH: usize = 116;
     const COL_IP_WIDTH: usize = 30;
+    const COL_PROTO_WIDTH: usize = 24;
 
-    println!();
-    println!("┌{}┐", "─".repeat(INNER_WIDTH));
-    println!("│{:<width$}│", " WireFish - Live Capture", width = INNER_WIDTH);
-
-    let iface_line = format!(" Interface : {iface_name}");
-    let iface_trimmed = if iface_line.len() > INNER_WIDTH {
-        let mut s: String = iface_line.chars().take(INNER_WIDTH - 3).collect();
-        s.push_str("..");
-        s
-    } else {
-        iface_line
-    };
-    println!("│{:<width$}│", iface_trimmed, width = INNER_WIDTH);
+    if show_table {
+        println!();
+        println!("┌{}┐", "─".repeat(INNER_WIDTH));
+        println!("│{:<width$}│", " WireFish - Live Capture", width = INNER_WIDTH);
 
-    println!("├───────┬────────────────────────────────┬────────────────────────────────┬────────┬───────────────┤");
-    println!("│ #     │ Source IP                      │ Destination IP                 │ Proto  │ Size          │");
-    println!("├───────┼────────────────────────────────┼────────────────────────────────┼────────┼───────────────┤");
+        let iface_line = format!(" Interface : {iface_name}");
+        let iface_trimmed = if iface_line.len() > INNER_WIDTH {
+            let mut s: String = iface_line.chars().take(INNER_WIDTH - 3).collect();
+            s.push_str("..");
+            s
+        } else {
+            iface_line
+        };
+        println!("│{:<width$}│", iface_trimmed, width = INNER_WIDTH);
+
+        println!("├───────┬────────────────────────────────┬────────────────────────────────┬──────────────────────────┬───────────────┤");
+        println!("│ #     │ Source IP                      │ Destination IP                 │ Proto                    │ Size          │");
+        println!("├───────┼────────────────────────────────┼────────────────────────────────┼──────────────────────────┼───────────────┤");
+    }
 
     let mut count: usize = 0;
+    let mut fp_tracker = FingerprintTracker::new();
+    let mut hellos = HelloReassembler::new();
+    let mut handshakes = HandshakeReassembler::new();
+    let mut dns_tracker = DnsTracker::new();
+    let mut dns_anomalies = DnsAnomalyDetector::new();
+    let mut beacons = BeaconDetector::new();
+    let mut tcp_analyzer = TcpAnalyzer::new();
+    let mut names = NameInventory::new();
+    let mut http_tracker = HttpTracker::new();
+    let mut http2_tracker = Http2Tracker::new();
+    let mut credentials = CredentialDetector::new();
+    let print_row = |line: String| {
+        if show_table {
+            println!("│{:<width$}│", fit_cell(&line, INNER_WIDTH), width = INNER_WIDTH);
+        }
+    };
+    let report_alerts = |raised: &[Alert]| {
+        for alert in raised {
+            print_row(alerts::alert_line(alert));
+        }
+    };
+    let mut alert_count = 0usize;
+    let mut last_timestamp = 0;
+    let mut export_failed = false;
+    let mut export_tick = |flow_export: &mut Option<(FlowTable, FlowExporter)>| {
+        if let Some((table, exporter)) = flow_export {
+            let now = chrono::Utc::now().timestamp_millis() as u128;
+            if let Err(e) = exporter.tick(table, now) {
+                if !export_failed {
+                    print_row(format!(" ⚠ Export des flux vers {} : {e}", exporter.collector()));
+                    export_failed = true;
+                }
+            }
+        }
+    };
 
     loop {
         if STOP_REQUESTED.load(Ordering::SeqCst) {
@@ -122,11 +739,77 @@ fn listen_to_packets(rx: Receiver<Packet>, iface_name: String, mode: OutputMode)
         }
 
         match rx.recv_timeout(Duration::from_millis(100)) {
-            Ok(packet) => {
+            Ok(mut packet) => {
                 count += 1;
+                last_timestamp = packet.timestamp;
+                tcp_analyzer.observe(&mut packet);
+                let events = streams.observe(&packet);
+                hellos.observe(&mut packet, &events);
+                if mode != OutputMode::PacketsOnly {
+                    log_stream_events(&events);
+                }
+                dns_tracker.observe(&mut packet, &events);
+                names.observe(&packet);
+                http_tracker.observe(&mut packet, &events);
+                http2_tracker.observe(&mut packet, &events);
+                grpc_decoder.observe(&mut packet);
+
+                let mut cert_issues = Vec::new();
+                if let Some(chain) = handshakes.observe(&packet, &events) {
+                    let now = chrono::Utc::now().timestamp();
+                    cert_issues = alerts::check_certificates(&chain.certificates, chain.sni.as_deref(), now);
+                    let info = packet.tls.get_or_insert_with(|| TlsInfo {
+                        record_version: chain.version,
+                        ..Default::default()
+                    });
+                    info.certificates = chain.certificates;
+                }
+                fp_tracker.observe(&mut packet);
 
                 let proto = classifier::classify(&packet);
                 let size = packet.payload.len();
+                if let Some((table, _)) = &mut flow_export {
+                    table.observe(&packet, Some(&iface_name), proto);
+                }
+                export_tick(&mut flow_export);
+                if let Some(logger) = &mut zeek {
+                    logger.observe(&packet, &iface_name, proto, &events);
+                }
+
+                let id = count as u64;
+                let mut raised = match &mut rule_engine {
+                    Some(engine) => engine.observe(&packet, id, proto, &events),
+                    None => Vec::new(),
+                };
+                raised.extend(credentials.observe(&packet, id, &events));
+                raised.extend(scans.observe(&packet, id));
+                raised.extend(arp.observe(&packet, id));
+                raised.extend(dhcp.observe(&packet, id));
+                raised.extend(dns_anomalies.observe(&packet, id));
+                raised.extend(beacons.observe(&packet, id));
+                raised.extend(volume.observe(&packet, proto));
+                if let (Some(list), Some(info)) = (&known_fps, &packet.tls) {
+                    for m in list.check(&info.fingerprints) {
+                        let message = format!("{} connu : {} ({})", m.kind, m.label, m.fingerprint);
+                        raised.push(alerts::packet_alert(&packet, id, "tls-fingerprint", Severity::High, message));
+                    }
+                }
+                for issue in cert_issues {
+                    let rule_id = format!("tls-cert-{}", issue.kind);
+                    let message = format!("Certificat : {}", issue.detail);
+                    raised.push(alerts::packet_alert(&packet, id, &rule_id, Severity::Medium, message));
+                }
+                let mut shown = Vec::new();
+                for alert in raised {
+                    let admission = triage.admit(&alert);
+                    if admission.stored() {
+                        storage::save_alert(&alert);
+                        alert_count += 1;
+                    }
+                    if admission.shown() {
+                        shown.push(alert);
+                    }
+                }
 
                 let (src_raw, dst_raw, has_ip) = if let Some(ip) = &packet.ip {
                     (ip.src_ip.clone(), ip.dst_ip.clone(), true)
@@ -135,22 +818,31 @@ fn listen_to_packets(rx: Receiver<Packet>, iface_name: String, mode: OutputMode)
                 };
 
                 if !has_ip && proto == "OTHER" {
+                    report_alerts(&shown);
+                    continue;
+                }
+                // Stored whatever the display filter: follow, conversations
+                // and dhcp rebuild their state from every packet.
+                storage::save_packet_for_inspect(id, &iface_name, proto, &packet);
+                if !show_table || (!filter.is_empty() && !filter.matches(&packet)) {
+                    report_alerts(&shown);
                     continue;
                 }
 
                 let src = fit_cell(&src_raw, COL_IP_WIDTH);
                 let dst = fit_cell(&dst_raw, COL_IP_WIDTH);
+                let label = fit_cell(&proto_label(&packet, proto), COL_PROTO_WIDTH);
 
                 println!(
-                    "│ {:<5} │ {:<30} │ {:<30} │ {:<6} │ {:>4} B        │",
+                    "│ {:<5} │ {:<30} │ {:<30} │ {:<24} │ {:>4} B        │",
                     count,
                     src,
                     dst,
-                    proto,
+                    label,
                     size,
                 );
 
-                storage::save_packet_for_inspect(count as u64, &iface_name, &proto, &packet);
+                report_alerts(&shown);
 
                 thread::sleep(Duration::from_millis(5));
             }
@@ -158,13 +850,135 @@ fn listen_to_packets(rx: Receiver<Packet>, iface_name: String, mode: OutputMode)
                 if STOP_REQUESTED.load(Ordering::SeqCst) {
                     break;
                 }
+                export_tick(&mut flow_export);
+                if let Some(logger) = &mut zeek {
+                    logger.tick(chrono::Utc::now().timestamp_millis() as u128);
+                }
+            }
+        }
+    }
+
+    // Bytes still buffered behind a hole and connections left open.
+    for (mut packet, events) in stream::end_of_capture(streams.finish(), last_timestamp) {
+        if mode != OutputMode::PacketsOnly {
+            log_stream_events(&events);
+        }
+        dns_tracker.observe(&mut packet, &events);
+        names.observe(&packet);
+        http_tracker.observe(&mut packet, &events);
+        http2_tracker.observe(&mut packet, &events);
+        grpc_decoder.observe(&mut packet);
+        if let Some(logger) = &mut zeek {
+            logger.observe_end(&packet, &events);
+        }
+
+        let id = count as u64;
+        let mut raised = match &mut rule_engine {
+            Some(engine) => engine.finish(&packet, id, &events),
+            None => Vec::new(),
+        };
+        raised.extend(credentials.observe(&packet, id, &events));
+        for alert in raised {
+            let admission = triage.admit(&alert);
+            if admission.stored() {
+                storage::save_alert(&alert);
+                alert_count += 1;
+            }
+            if admission.shown() {
+                report_alerts(&[alert]);
+            }
+        }
+    }
+
+    if show_table {
+        println!("├───────┴────────────────────────────────┴────────────────────────────────┴──────────────────────────┴───────────────┤");
+    }
+    print_row(" Capture terminée (Ctrl+C)".to_string());
+
+    let stats = dns_tracker.stats();
+    if stats.queries > 0 || stats.responses > 0 {
+        let line = format!(
+            " DNS : {} requêtes, {} réponses, {} sans réponse, NXDOMAIN {:.1}%, latence moy. {}",
+            stats.queries,
+            stats.responses,
+            dns_tracker.unanswered(),
+            stats.nxdomain_rate() * 100.0,
+            stats
+                .avg_latency_ms()
+                .map(|ms| format!("{ms:.1} ms"))
+                .unwrap_or_else(|| "-".to_string()),
+        );
+        print_row(line);
+    }
+    let tcp = streams.stats();
+    if tcp.connections > 0 {
+        let line = format!(
+            " TCP : {} connexions, {} octets réassemblés, {} retransmis, {} manquants, {} chevauchements divergents",
+            tcp.connections, tcp.delivered_bytes, tcp.duplicate_bytes, tcp.missing_bytes, tcp.overlap_conflicts,
+        );
+        print_row(line);
+    }
+    if let Some(perf) = tcpanalysis::perf_line(tcp_analyzer.stats()) {
+        let line = format!(" TCP (performances) : {perf}");
+        print_row(line);
+    }
+    if let Some((table, exporter)) = &mut flow_export {
+        let now = chrono::Utc::now().timestamp_millis() as u128;
+        let line = match exporter.flush(table, now) {
+            Ok(()) => format!(" Flux exportés : {} vers {}", exporter.exported(), exporter.collector()),
+            Err(e) => format!(" ⚠ Export des flux vers {} : {e}", exporter.collector()),
+        };
+        print_row(line);
+    }
+    if let Some(logger) = zeek {
+        let dir = logger.dir().display().to_string();
+        let line = match logger.finish() {
+            Ok(counts) => {
+                let counts: Vec<String> = counts.iter().map(|(log, n)| format!("{log} {n}")).collect();
+                format!(" Journaux Zeek ({}) : {dir}", counts.join(", "))
             }
+            Err(e) => format!(" ⚠ Journaux Zeek dans {dir} : {e}"),
+        };
+        print_row(line);
+    }
+    let groups = triage.groups();
+    storage::save_alert_groups(&groups);
+    if alert_count > 0 || rule_engine.is_some() {
+        let rule_count = rule_engine.as_ref().map_or(0, |e| e.rules().len());
+        let mut line = format!(" Alertes : {alert_count} en {} groupes ({rule_count} règles", groups.len());
+        if triage.suppressed() > 0 {
+            line.push_str(&format!(", {} supprimées", triage.suppressed()));
+        }
+        if triage.rate_limited() > 0 {
+            line.push_str(&format!(", {} limitées", triage.rate_limited()));
         }
+        line.push_str(") — wirefish alerts pour la liste");
+        print_row(line);
+    }
+    storage::save_baselines(&volume.baselines());
+    let bindings = arp.bindings();
+    storage::save_arp_bindings(&bindings);
+    if !bindings.is_empty() {
+        let line = format!(" Table ARP : {} associations — wirefish arp pour la liste", bindings.len());
+        print_row(line);
+    }
+    let dhcp_servers = dhcp.servers();
+    if !dhcp_servers.is_empty() {
+        let line = format!(
+            " DHCP : {} serveurs, {} baux — wirefish dhcp pour le détail",
+            dhcp_servers.len(),
+            dhcp.leases().len()
+        );
+        print_row(line);
+    }
+    if show_table {
+        println!("└{}┘", "─".repeat(INNER_WIDTH));
     }
 
-    println!("├───────┴────────────────────────────────┴────────────────────────────────┴────────┴───────────────┤");
-    println!("│{:<width$}│", " Capture terminée (Ctrl+C)", width = INNER_WIDTH);
-    println!("└{}┘", "─".repeat(INNER_WIDTH));
+    let devices = names.devices();
+    if show_table && !devices.is_empty() {
+        inventory::print_report(&devices);
+    }
 }
 
 fn choose_device(devices: &[String]) -> Option<String> {
@@ -228,7 +1042,7 @@ fn choose_device(devices: &[String]) -> Option<String> {
 
         if input.is_empty() {
             println!("(Entrée vide → on prend 0 par défaut)");
-            return devices.get(0).cloned();
+            return devices.first().cloned();
         }
 
         match input.parse::<usize>() {
@@ -247,6 +1061,48 @@ fn choose_device(devices: &[String]) -> Option<String> {
 
 
 fn main() {
+    if let Some(result) = run_export_objects() {
+        if let Err(e) = result {
+            eprintln!("❌ {e}");
+        }
+        return;
+    }
+
+    if let Some(result) = run_conversations() {
+        if let Err(e) = result {
+            eprintln!("❌ {e}");
+        }
+        return;
+    }
+
+    if let Some(result) = run_alerts() {
+        if let Err(e) = result {
+            eprintln!("❌ {e}");
+        }
+        return;
+    }
+
+    if let Some(result) = run_arp() {
+        if let Err(e) = result {
+            eprintln!("❌ {e}");
+        }
+        return;
+    }
+
+    if let Some(result) = run_dhcp() {
+        if let Err(e) = result {
+            eprintln!("❌ {e}");
+        }
+        return;
+    }
+
+    if let Some(result) = run_follow() {
+        if let Err(e) = result {
+            eprintln!("❌ {e}");
+        }
+        return;
+    }
+
     if let Some(id_or_zero) = parse_args_for_check() {
         if id_or_zero != 0 {
             storage::inspect_packet(id_or_zero);
@@ -254,9 +1110,154 @@ fn main() {
         return;
     }
 
-    storage::reset_storage();
+    let opts = match parse_args() {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("❌ {e}");
+            return;
+        }
+    };
+    let mode = opts.mode;
+    let iface_index_arg = opts.iface_index;
 
-    let (mode, iface_index_arg) = parse_args();
+    let known_fps = match &opts.fingerprint_list {
+        Some(path) => match FingerprintList::load(path) {
+            Ok(list) if list.is_empty() => {
+                eprintln!("⚠️ Aucune empreinte TLS dans {}", path.display());
+                None
+            }
+            Ok(list) => {
+                println!("{} empreintes TLS chargées depuis {}", list.len(), path.display());
+                Some(list)
+            }
+            Err(e) => {
+                eprintln!("❌ Impossible de lire {} : {e}", path.display());
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let descriptors = match &opts.descriptor_set {
+        Some(path) => match DescriptorPool::load(path) {
+            Ok(pool) => {
+                println!(
+                    "{} messages et {} méthodes gRPC chargés depuis {}",
+                    pool.message_count(),
+                    pool.method_count(),
+                    path.display()
+                );
+                Some(pool)
+            }
+            Err(e) => {
+                eprintln!("❌ {e}");
+                return;
+            }
+        },
+        None => None,
+    };
+    let grpc_decoder = GrpcDecoder::new(descriptors);
+
+    let flow_export = match &opts.export_flows {
+        Some(collector) => {
+            let now = chrono::Utc::now().timestamp_millis() as u128;
+            match FlowExporter::new(
+                collector,
+                opts.export_format,
+                opts.flow_idle_timeout_ms,
+                opts.flow_active_timeout_ms,
+                now,
+            ) {
+                Ok(exporter) => {
+                    println!("Export des flux ({:?}) vers {}", opts.export_format, exporter.collector());
+                    Some((FlowTable::new(), exporter))
+                }
+                Err(e) => {
+                    eprintln!("❌ Collecteur invalide : {e}");
+                    return;
+                }
+            }
+        }
+        None => None,
+    };
+
+    let zeek = match &opts.zeek_logs {
+        Some(dir) => match ZeekLogger::create(dir, opts.zeek_json) {
+            Ok(logger) => Some(logger),
+            Err(e) => {
+                eprintln!("❌ Impossible de créer les journaux Zeek dans {} : {e}", dir.display());
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let rule_engine = if opts.rules.is_empty() {
+        None
+    } else {
+        let mut loaded = Vec::new();
+        for path in &opts.rules {
+            let json = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+            let result = if json {
+                rules::load_rules(path)
+            } else {
+                suricata::load_rules(path, &opts.rule_vars).map(|import| {
+                    for skipped in &import.skipped {
+                        eprintln!("⚠️ {} {}", path.display(), skipped.describe());
+                    }
+                    if !import.skipped.is_empty() {
+                        eprintln!("⚠️ {} règles ignorées dans {}", import.skipped.len(), path.display());
+                    }
+                    import.rules
+                })
+            };
+            match result {
+                Ok(list) => {
+                    println!("{} règles chargées depuis {}", list.len(), path.display());
+                    loaded.extend(list);
+                }
+                Err(e) => {
+                    eprintln!("❌ {e}");
+                    return;
+                }
+            }
+        }
+        Some(RuleEngine::new(loaded))
+    };
+
+    let filters = match &opts.alert_filters {
+        Some(path) => match triage::load_filters(path) {
+            Ok(filters) => filters,
+            Err(e) => {
+                eprintln!("❌ {e}");
+                return;
+            }
+        },
+        None => Default::default(),
+    };
+    let acks = storage::stored_alert_acks().unwrap_or_else(|e| {
+        eprintln!("⚠️ Acquittements d’alertes ignorés : {e}");
+        Vec::new()
+    });
+    let triage = AlertTriage::new(filters, &acks);
+
+    let arp_watch = match storage::stored_arp_bindings() {
+        Ok(bindings) => ArpWatch::with_bindings(bindings),
+        Err(e) => {
+            eprintln!("⚠️ Table ARP ignorée : {e}");
+            ArpWatch::new()
+        }
+    };
+
+    let volume = match storage::stored_baselines() {
+        Ok(baselines) => VolumeDetector::with_baselines(opts.volume, baselines),
+        Err(e) => {
+            eprintln!("⚠️ Références de trafic ignorées : {e}");
+            VolumeDetector::new(opts.volume)
+        }
+    };
+
+    storage::reset_storage();
 
     ctrlc::set_handler(|| {
         STOP_REQUESTED.store(true, Ordering::SeqCst);
@@ -304,5 +1305,18 @@ fn main() {
         capture::capture_on(&device_clone, tx, debug_enabled);
     });
 
-    listen_to_packets(rx, device, mode);
+    let session = Session {
+        known_fps,
+        grpc_decoder,
+        streams: TcpReassembler::with_config(opts.reassembly),
+        flow_export,
+        zeek,
+        rules: rule_engine,
+        triage,
+        scans: ScanDetector::new(opts.scan),
+        arp: arp_watch,
+        dhcp: DhcpMonitor::new(opts.dhcp_servers),
+        volume,
+    };
+    listen_to_packets(rx, device, mode, opts.filter, session);
 }
@@ -1,10 +1,59 @@
-export default function Alerts({ alerts }) {
+const SEVERITY_COLORS = {
+  info: "gray",
+  low: "steelblue",
+  medium: "orange",
+  high: "red",
+  critical: "darkred",
+};
+
+export default function Alerts({ alerts, groups = [] }) {
+  const endpoint = (ip, port) => (ip == null ? "?" : port == null ? ip : `${ip}:${port}`);
+  const time = (ms) => new Date(ms).toLocaleTimeString();
   return (
     <div>
+      {groups.length > 0 && (
+        <>
+          <h2>Alert groups</h2>
+          <table>
+            <thead>
+              <tr>
+                <th>Group</th>
+                <th>Count</th>
+                <th>First seen</th>
+                <th>Last seen</th>
+                <th>Rule</th>
+                <th>Source</th>
+                <th>Destination</th>
+                <th>Message</th>
+              </tr>
+            </thead>
+            <tbody>
+              {groups.map((g) => (
+                <tr key={g.id} style={{ color: SEVERITY_COLORS[g.severity] ?? "red", opacity: g.acknowledged ? 0.5 : 1 }}>
+                  <td>{g.id}</td>
+                  <td>{g.count}</td>
+                  <td>{time(g.first_seen)}</td>
+                  <td>{time(g.last_seen)}</td>
+                  <td>{g.rule_id}</td>
+                  <td>{g.src_ip ?? "?"}</td>
+                  <td>{g.dst_ip ?? "?"}</td>
+                  <td>
+                    {g.acknowledged && "✓ "}
+                    {g.message}
+                  </td>
+                </tr>
+              ))}
+            </tbody>
+          </table>
+        </>
+      )}
       <h2>Alerts</h2>
       {alerts.map((a, i) => (
-        <div key={i} style={{ color: "red" }}>
-          {a.message}
+        <div key={i} style={{ color: SEVERITY_COLORS[a.severity] ?? "red" }}>
+          {a.packet_id != null && <span>#{a.packet_id} </span>}
+          [{a.severity}] {a.message} ({endpoint(a.src_ip, a.src_port)} → {endpoint(a.dst_ip, a.dst_port)},{" "}
+          {a.rule_id})
+          {a.related_packets?.length > 0 && <span> — {a.related_packets.map((id) => `#${id}`).join(" ")}</span>}
         </div>
       ))}
     </div>
@@ -0,0 +1,41 @@
+export default function ConversationsView({ flows }) {
+  const endpoint = (ip, port) => (port == null ? ip : `${ip}:${port}`);
+  const rtt = (perf) => (perf?.handshake_rtt_ms == null ? '-' : `${perf.handshake_rtt_ms} ms`);
+  const retrans = (perf) =>
+    perf ? perf.retransmissions + perf.fast_retransmissions + perf.spurious_retransmissions : 0;
+  return (
+    <div>
+      <h2>Conversations</h2>
+      <table>
+        <thead>
+          <tr>
+            <th>Client</th>
+            <th>Server</th>
+            <th>App</th>
+            <th>Packets</th>
+            <th>Bytes →</th>
+            <th>Bytes ←</th>
+            <th>State</th>
+            <th>RTT</th>
+            <th>Retrans.</th>
+          </tr>
+        </thead>
+        <tbody>
+          {flows.map((f) => (
+            <tr key={f.id}>
+              <td>{endpoint(f.client_ip, f.client_port)}</td>
+              <td>{endpoint(f.server_ip, f.server_port)}</td>
+              <td>{f.application}</td>
+              <td>{f.packets_to_server + f.packets_to_client}</td>
+              <td>{f.bytes_to_server}</td>
+              <td>{f.bytes_to_client}</td>
+              <td>{f.state}</td>
+              <td>{rtt(f.tcp_perf)}</td>
+              <td>{retrans(f.tcp_perf)}</td>
+            </tr>
+          ))}
+        </tbody>
+      </table>
+    </div>
+  );
+}
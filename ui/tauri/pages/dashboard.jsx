@@ -1,8 +1,35 @@
-export default function Dashboard() {
+export default function Dashboard({ baselines = [] }) {
+  const perSecond = (b, v) => (v * 1000) / b.interval_ms;
+  const rate = (b, v) => (b.metric === "half_open" ? v.toFixed(0) : `${perSecond(b, v).toFixed(1)}/s`);
   return (
     <div>
       <h1>WireFish Dashboard</h1>
       <p>Live traffic, stats, alerts, etc.</p>
+      <h2>Baselines</h2>
+      <table>
+        <thead>
+          <tr>
+            <th>Metric</th>
+            <th>Key</th>
+            <th>Current</th>
+            <th>Mean</th>
+            <th>Deviation</th>
+            <th>Samples</th>
+          </tr>
+        </thead>
+        <tbody>
+          {baselines.map((b) => (
+            <tr key={`${b.metric}/${b.key}`}>
+              <td>{b.metric}</td>
+              <td>{b.key}</td>
+              <td>{rate(b, b.current)}</td>
+              <td>{rate(b, b.overall.mean)}</td>
+              <td>{rate(b, Math.sqrt(b.overall.variance))}</td>
+              <td>{b.overall.samples}</td>
+            </tr>
+          ))}
+        </tbody>
+      </table>
     </div>
   );
 }